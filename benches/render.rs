@@ -0,0 +1,86 @@
+// Criterion benchmarks for the renderer's passes and the scaler's filters, so changes to
+// them can be measured: `cargo bench`, or `cargo bench -- scaler` for one group.
+//
+// The wall and flat passes are internal to the renderer, so they're measured through
+// whole frames of synthetic worlds picked to lean on one or the other.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use two_halfD_engine::physics::STAND_EYE_HEIGHT;
+use two_halfD_engine::scaler::{
+    SharpenScratch, blit_bilinear_stretch, build_scale_lut, sharpen3x3_cross_inplace,
+};
+use two_halfD_engine::{Camera, Renderer, World, procgen};
+
+// Internal framebuffer sizes, from the default 240 lines up
+const RENDER_SIZES: [(usize, usize); 3] = [(320, 240), (640, 480), (1280, 960)];
+// Window sizes the 320x240 frame is scaled up to
+const WINDOW_SIZES: [(usize, usize); 3] = [(1280, 960), (1920, 1080), (2560, 1440)];
+
+// (name, world) pairs for the frame benchmarks
+fn scenes() -> Vec<(&'static str, World)> {
+    vec![
+        ("demo", World::demo()),
+        // One room: mostly floor and ceiling spans
+        ("room", procgen::generate(1, [1, 1])),
+        // Rooms and corridors through portal after portal: mostly wall columns
+        ("maze", procgen::generate(1, [16, 16])),
+    ]
+}
+
+fn start_camera(world: &World, width: usize, height: usize) -> Camera {
+    let start = &world.player_start;
+    let floor_z = world
+        .sector_at(start.pos)
+        .map_or(0.0, |s| world.sectors[s].floor_z);
+    let mut camera = Camera {
+        pos: start.pos,
+        yaw: start.yaw,
+        eye_z: floor_z + STAND_EYE_HEIGHT,
+        fx: 0.0,
+        fy: 0.0,
+    };
+    camera.set_fov_hor_plus(width as f32, height as f32, 90.0);
+    camera
+}
+
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    for (name, world) in scenes() {
+        for (width, height) in RENDER_SIZES {
+            let camera = start_camera(&world, width, height);
+            let mut renderer = Renderer::new();
+            let mut buf = vec![0u32; width * height];
+            let id = BenchmarkId::new(name, format!("{width}x{height}"));
+            group.bench_function(id, |b| {
+                b.iter(|| renderer.render(&mut buf, width, height, &world, black_box(&camera)));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn scaler(c: &mut Criterion) {
+    let (src_w, src_h) = RENDER_SIZES[0];
+    let camera = start_camera(&World::demo(), src_w, src_h);
+    let src = Renderer::new().render_to_buffer(&World::demo(), &camera, src_w, src_h);
+
+    let mut group = c.benchmark_group("scaler");
+    for (width, height) in WINDOW_SIZES {
+        let size = format!("{width}x{height}");
+        let lut = build_scale_lut(width, height, src_w, src_h);
+        let mut dst = vec![0u32; width * height];
+        group.bench_function(BenchmarkId::new("bilinear", &size), |b| {
+            b.iter(|| blit_bilinear_stretch(&mut dst, width, black_box(&src), src_w, &lut));
+        });
+        let mut scratch = SharpenScratch::default();
+        group.bench_function(BenchmarkId::new("sharpen", &size), |b| {
+            b.iter(|| sharpen3x3_cross_inplace(black_box(&mut dst), width, height, &mut scratch));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, frames, scaler);
+criterion_main!(benches);
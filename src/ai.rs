@@ -0,0 +1,258 @@
+// Hunting entities: idle until they see the target, then chase it and attack in range.
+// Chasing walks straight at a visible target and otherwise follows an A* route through
+// the sector portal graph to where it is.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collision::MAX_STEP;
+use crate::entity::{Behavior, Entities, EntityId};
+use crate::raycast;
+use crate::world::World;
+
+// Seconds between attacks
+const ATTACK_COOLDOWN: f32 = 1.0;
+/// Health each attack takes from the target
+pub const ATTACK_DAMAGE: f32 = 10.0;
+// Seconds without sight of the target before giving up the chase
+const GIVE_UP_TIME: f32 = 8.0;
+// Seconds between route searches while the target is out of sight
+const REPATH_INTERVAL: f32 = 0.5;
+// Close enough to a waypoint to head for the next one
+const WAYPOINT_REACHED: f32 = 0.3;
+// Eyes sit this far up an entity's height
+const EYE_FRACTION: f32 = 0.8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AiMode {
+    #[default]
+    Idle,
+    Chase,
+    Attack,
+}
+
+/// Per-entity AI state, for entities with `Behavior::Hunt`
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct AiState {
+    pub mode: AiMode,
+    cooldown: f32, // until the next attack may start
+    unseen: f32,   // seconds since the target was last in sight
+    repath: f32,   // until the route is searched again
+    waypoint: Option<[f32; 2]>,
+}
+
+/// What the entities are hunting, usually the player
+#[derive(Clone, Copy)]
+pub struct Target {
+    pub pos: [f32; 2],
+    pub eye_z: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AiEvent {
+    Sighted { entity: EntityId }, // went from idle to chasing
+    Attack { entity: EntityId },
+}
+
+/// Think for every hunting entity, setting velocities for the movement system to apply.
+/// Run before `entity::update`.
+pub fn update(world: &mut World, target: Target, dt: f32) -> Vec<AiEvent> {
+    let mut entities = std::mem::take(&mut world.entities);
+    let events = think(&mut entities, world, target, dt);
+    world.entities = entities;
+    events
+}
+
+fn think(entities: &mut Entities, world: &World, target: Target, dt: f32) -> Vec<AiEvent> {
+    let mut events = Vec::new();
+    let ids: Vec<EntityId> = entities.ids().collect();
+    for id in ids {
+        let Behavior::Hunt {
+            speed,
+            sight_range,
+            attack_range,
+        } = entities.behaviors[id]
+        else {
+            continue;
+        };
+        let pos = entities.transforms[id].pos;
+        let height = entity_height(entities, id);
+        let eye_z = entities.transforms[id].z + height * EYE_FRACTION;
+        let to_target = [target.pos[0] - pos[0], target.pos[1] - pos[1]];
+        let distance = (to_target[0] * to_target[0] + to_target[1] * to_target[1]).sqrt();
+        let sees = distance <= sight_range
+            && raycast::line_of_sight(world, pos, eye_z, target.pos, target.eye_z);
+
+        let ai = &mut entities.ai[id];
+        ai.cooldown = (ai.cooldown - dt).max(0.0);
+        ai.repath -= dt;
+        ai.unseen = if sees { 0.0 } else { ai.unseen + dt };
+
+        ai.mode = match ai.mode {
+            AiMode::Idle if sees => {
+                events.push(AiEvent::Sighted { entity: id });
+                AiMode::Chase
+            }
+            AiMode::Idle => AiMode::Idle,
+            _ if ai.unseen > GIVE_UP_TIME => AiMode::Idle,
+            _ if sees && distance <= attack_range => AiMode::Attack,
+            _ => AiMode::Chase,
+        };
+
+        let heading = match ai.mode {
+            AiMode::Idle => None,
+            AiMode::Attack => {
+                entities.transforms[id].yaw = to_target[0].atan2(to_target[1]);
+                if ai.cooldown == 0.0 {
+                    ai.cooldown = ATTACK_COOLDOWN;
+                    events.push(AiEvent::Attack { entity: id });
+                }
+                None // stand still while attacking
+            }
+            AiMode::Chase if sees => {
+                ai.waypoint = None;
+                Some(target.pos)
+            }
+            AiMode::Chase => {
+                let reached = ai.waypoint.is_some_and(|w| dist(w, pos) < WAYPOINT_REACHED);
+                if ai.repath <= 0.0 || reached || ai.waypoint.is_none() {
+                    ai.repath = REPATH_INTERVAL;
+                    ai.waypoint = find_path(world, pos, target.pos, height).and_then(|path| {
+                        path.into_iter().find(|&w| dist(w, pos) >= WAYPOINT_REACHED)
+                    });
+                }
+                ai.waypoint
+            }
+        };
+
+        entities.velocities[id] = match heading {
+            Some(goal) => {
+                let d = [goal[0] - pos[0], goal[1] - pos[1]];
+                let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+                if len > f32::EPSILON {
+                    [d[0] / len * speed, d[1] / len * speed]
+                } else {
+                    [0.0, 0.0]
+                }
+            }
+            None => [0.0, 0.0],
+        };
+    }
+    events
+}
+
+#[inline]
+fn entity_height(entities: &Entities, id: EntityId) -> f32 {
+    let radius = entities.colliders[id].unwrap_or(0.0);
+    entities.sprites[id].map_or(radius * 2.0, |s| s.world_height())
+}
+
+#[inline]
+fn dist(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    (dx * dx + dy * dy).sqrt()
+}
+
+// Open A* entry, ordered so the heap pops the lowest estimate first
+struct Open {
+    estimate: f32,
+    sector: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Waypoints from `from` to `to` for something `height` tall: the middle of each portal
+/// crossed, then `to` itself. None if `to` can't be reached through the portals' current
+/// openings, e.g. behind a closed door or up a step too tall.
+pub fn find_path(
+    world: &World,
+    from: [f32; 2],
+    to: [f32; 2],
+    height: f32,
+) -> Option<Vec<[f32; 2]>> {
+    let start = world.sector_at(from)?;
+    let goal = world.sector_at(to)?;
+    if start == goal {
+        return Some(vec![to]);
+    }
+
+    // Portals out of each sector: (wall, sector on the other side)
+    let mut portals = vec![Vec::new(); world.sectors.len()];
+    for (i, wall) in world.walls.iter().enumerate() {
+        if let Some(back) = wall.back_sector {
+            portals[wall.front_sector].push((i, back));
+            portals[back].push((i, wall.front_sector));
+        }
+    }
+    let passable = |a: usize, b: usize| {
+        let (a, b) = (&world.sectors[a], &world.sectors[b]);
+        let opening = a.ceiling_z.min(b.ceiling_z) - a.floor_z.max(b.floor_z);
+        b.floor_z - a.floor_z <= MAX_STEP && opening >= height
+    };
+    let midpoint = |wall: usize| {
+        let w = &world.walls[wall];
+        [0.5 * (w.start[0] + w.end[0]), 0.5 * (w.start[1] + w.end[1])]
+    };
+
+    // Each sector is entered at a portal midpoint, which is where costs are measured from
+    let mut cost = vec![f32::INFINITY; world.sectors.len()];
+    let mut entry = vec![from; world.sectors.len()];
+    let mut came_from: Vec<Option<(usize, usize)>> = vec![None; world.sectors.len()]; // (sector, wall)
+    let mut open = BinaryHeap::new();
+    cost[start] = 0.0;
+    open.push(Open {
+        estimate: dist(from, to),
+        sector: start,
+    });
+
+    while let Some(Open { sector, .. }) = open.pop() {
+        if sector == goal {
+            let mut path = vec![to];
+            let mut s = goal;
+            while let Some((prev, wall)) = came_from[s] {
+                path.push(midpoint(wall));
+                s = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &(wall, next) in &portals[sector] {
+            if !passable(sector, next) {
+                continue;
+            }
+            let point = midpoint(wall);
+            let g = cost[sector] + dist(entry[sector], point);
+            if g < cost[next] {
+                cost[next] = g;
+                entry[next] = point;
+                came_from[next] = Some((sector, wall));
+                open.push(Open {
+                    estimate: g + dist(point, to),
+                    sector: next,
+                });
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,165 @@
+// Image textures loaded from an assets directory and shared by name, so walls, things and
+// the sky that use the same image get the same `TextureId`
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::renderer::pack_rgb;
+use crate::texture::{Rotations, TRANSPARENT, Texture, TextureId};
+
+/// Where maps look for images unless told otherwise
+pub const DEFAULT_ASSET_DIR: &str = "assets";
+
+// Tried in order for a name without an extension
+const EXTENSIONS: [&str; 2] = ["png", "tga"];
+
+// Texels with less alpha than this become `TRANSPARENT`
+const ALPHA_CUTOFF: u8 = 128;
+
+const MISSING_SIZE: usize = 64;
+const MISSING_CELL: usize = 8;
+
+pub struct TextureManager {
+    root: PathBuf,
+    textures: Vec<Texture>,
+    by_name: HashMap<String, TextureId>,
+}
+
+impl TextureManager {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            textures: Vec::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The texture for image `name`, loading `<root>/<name>.png` (or `.tga`) the first time.
+    /// An image that can't be loaded gets a magenta/black checkerboard so it stands out.
+    pub fn load(&mut self, name: &str) -> TextureId {
+        if let Some(&id) = self.by_name.get(name) {
+            return id;
+        }
+        let texture = self.read_image(name).unwrap_or_else(|err| {
+            warn!(target: "renderer", "texture {name:?}: {err}; using a placeholder");
+            missing_texture()
+        });
+        self.insert(Some(name), texture)
+    }
+
+    /// The rotation frames of sprite `name`, images `<name>1` (its front) to `<name>8`, each
+    /// loaded as by `load`
+    pub fn load_rotations(&mut self, name: &str) -> Rotations {
+        std::array::from_fn(|i| self.load(&format!("{name}{}", i + 1)))
+    }
+
+    /// Register a texture that doesn't come from disk, e.g. a procedural one. A named texture
+    /// replaces nothing: if `name` is already known its existing id is returned.
+    pub fn add(&mut self, name: Option<&str>, texture: Texture) -> TextureId {
+        if let Some(&id) = name.and_then(|n| self.by_name.get(n)) {
+            return id;
+        }
+        self.insert(name, texture)
+    }
+
+    pub fn id(&self, name: &str) -> Option<TextureId> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn get(&self, id: TextureId) -> Option<&Texture> {
+        self.textures.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// The textures in id order, ready for `World::new`
+    pub fn into_textures(self) -> Vec<Texture> {
+        self.textures
+    }
+
+    fn insert(&mut self, name: Option<&str>, texture: Texture) -> TextureId {
+        let id = self.textures.len();
+        self.textures.push(texture);
+        if let Some(name) = name {
+            self.by_name.insert(name.to_string(), id);
+        }
+        id
+    }
+
+    fn read_image(&self, name: &str) -> Result<Texture, String> {
+        load_image(&self.root, name)
+    }
+}
+
+/// Image `<root>/<name>.png` (or `.tga`) as a texture of its own, without registering it
+/// with a `TextureManager`, e.g. for HUD graphics that never go into a world
+pub fn load_image(root: &Path, name: &str) -> Result<Texture, String> {
+    let candidates: Vec<PathBuf> = if Path::new(name).extension().is_some() {
+        vec![root.join(name)]
+    } else {
+        EXTENSIONS
+            .iter()
+            .map(|ext| root.join(format!("{name}.{ext}")))
+            .collect()
+    };
+    let Some(path) = candidates.iter().find(|p| p.is_file()) else {
+        return Err(format!("not found in {}", root.display()));
+    };
+    let image = image::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    texture_from_rgba(image.to_rgba8()).ok_or_else(|| format!("{} is empty", path.display()))
+}
+
+// RGBA8 to the packed framebuffer layout, with the alpha channel reduced to the color key
+fn texture_from_rgba(image: image::RgbaImage) -> Option<Texture> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let pixels = image
+        .into_raw()
+        .chunks_exact(4)
+        .map(|p| {
+            if p[3] < ALPHA_CUTOFF {
+                TRANSPARENT
+            } else {
+                opaque(pack_rgb(p[0], p[1], p[2]))
+            }
+        })
+        .collect();
+    Some(Texture::from_pixels(
+        width as usize,
+        height as usize,
+        pixels,
+    ))
+}
+
+// An opaque texel that happens to be pure magenta would read as transparent
+#[inline]
+fn opaque(color: u32) -> u32 {
+    if color == TRANSPARENT {
+        color ^ 0x0000_0100
+    } else {
+        color
+    }
+}
+
+fn missing_texture() -> Texture {
+    Texture::checkerboard(
+        MISSING_SIZE,
+        MISSING_CELL,
+        opaque(pack_rgb(255, 0, 255)),
+        pack_rgb(0, 0, 0),
+    )
+}
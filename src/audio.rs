@@ -0,0 +1,288 @@
+// Sound effects played at world positions: quieter with distance, panned by where the
+// source sits relative to the camera's facing, and muffled by the walls in between. Plus
+// one looping music track per map, crossfaded when the map changes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rodio::buffer::SamplesBuffer;
+use rodio::source::ChannelVolume;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use tracing::warn;
+
+use crate::camera::Camera;
+use crate::raycast;
+use crate::world::World;
+
+// Tried in order for a name without an extension
+const EFFECT_EXTENSIONS: [&str; 2] = ["wav", "ogg"];
+const MUSIC_EXTENSIONS: [&str; 2] = ["ogg", "mp3"];
+
+// Seconds for one track to fade out while the next fades in
+const CROSSFADE: f32 = 2.0;
+
+// Distance at which a sound plays at half volume
+const HALF_VOLUME_DISTANCE: f32 = 4.0;
+// Farther than this a sound isn't started at all
+const MAX_DISTANCE: f32 = 40.0;
+// Each wall between a sound and the listener keeps this share of its volume...
+const WALL_GAIN: f32 = 0.7;
+// ...and muffles it: the low-pass cutoff in Hz behind one wall, halved for each one after
+const WALL_CUTOFF: u32 = 2000;
+const MIN_CUTOFF: u32 = 250;
+// Quieter than this in both ears, a sound isn't started
+const MIN_GAIN: f32 = 0.01;
+
+/// Left and right ear gains for a sound at `pos` heard from `camera`, each 0..=1
+pub fn spatialize(camera: &Camera, pos: [f32; 2]) -> [f32; 2] {
+    let [cx, cy] = camera.world_to_camera(pos);
+    let distance = (cx * cx + cy * cy).sqrt();
+    if distance > MAX_DISTANCE {
+        return [0.0, 0.0];
+    }
+    let gain = 1.0 / (1.0 + distance / HALF_VOLUME_DISTANCE);
+
+    // -1 hard left ..= 1 hard right; a sound on top of the listener is centered
+    let pan = if distance > f32::EPSILON {
+        cx / distance
+    } else {
+        0.0
+    };
+    // Constant power, so a sound keeps its loudness as it moves across
+    let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+    [gain * angle.cos(), gain * angle.sin()]
+}
+
+/// Gain and low-pass cutoff (None for unfiltered) for a sound heard through `walls` walls
+fn occlusion(walls: usize) -> (f32, Option<u32>) {
+    if walls == 0 {
+        return (1.0, None);
+    }
+    let gain = WALL_GAIN.powi(walls as i32);
+    let cutoff = (WALL_CUTOFF >> (walls - 1).min(31)).max(MIN_CUTOFF);
+    (gain, Some(cutoff))
+}
+
+// A decoded effect, kept in memory so it can be started any number of times
+struct Sound {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+// A music track playing in its own sink, `level` of the way faded in
+struct Track {
+    name: String,
+    sink: Arc<Sink>, // shared with the thread that opens and decodes the file
+    level: f32,
+}
+
+pub struct Audio {
+    _stream: OutputStream, // playback stops when this is dropped
+    handle: OutputStreamHandle,
+    root: PathBuf, // effects are in `root/sounds`, music in `root/music`
+    sounds: HashMap<String, Option<Sound>>, // None for sounds that failed to load
+    pub volume: f32, // 0..=1, applied to every effect
+    music_volume: f32,
+    music: Option<Track>,
+    fading_out: Vec<Track>,
+}
+
+impl Audio {
+    /// Open the default output device; sounds are looked up under the asset directory `root`
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, AudioError> {
+        let (stream, handle) = OutputStream::try_default().map_err(AudioError::Device)?;
+        Ok(Self {
+            _stream: stream,
+            handle,
+            root: root.into(),
+            sounds: HashMap::new(),
+            volume: 1.0,
+            music_volume: 1.0,
+            music: None,
+            fading_out: Vec::new(),
+        })
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    /// 0..=1; takes effect on the playing track straight away
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume.clamp(0.0, 1.0);
+        for track in self.music.iter().chain(&self.fading_out) {
+            track.sink.set_volume(track.level * self.music_volume);
+        }
+    }
+
+    /// Crossfade to looping track `name`, or fade out with None. Asking for the track
+    /// that's already playing changes nothing.
+    pub fn play_music(&mut self, name: Option<&str>) {
+        if self.music.as_ref().map(|t| t.name.as_str()) == name {
+            return;
+        }
+        self.fading_out.extend(self.music.take());
+        let Some(name) = name else {
+            return;
+        };
+
+        let sink = match Sink::try_new(&self.handle) {
+            Ok(sink) => Arc::new(sink),
+            Err(err) => {
+                warn!(target: "audio", "music {name:?}: {err}");
+                return;
+            }
+        };
+        sink.set_volume(0.0);
+
+        // Opening and probing the file can stall on a slow disk, so it happens off the
+        // render thread; the sink stays silent until the decoder is appended
+        let dir = self.root.join("music");
+        let thread_sink = Arc::clone(&sink);
+        let thread_name = name.to_string();
+        std::thread::spawn(move || {
+            let decoder = find_file(&dir, &thread_name, &MUSIC_EXTENSIONS).and_then(|path| {
+                let file = File::open(path).map_err(AudioError::Io)?;
+                Decoder::new(BufReader::new(file)).map_err(AudioError::Decode)
+            });
+            match decoder {
+                Ok(decoder) => thread_sink.append(decoder.repeat_infinite()),
+                Err(err) => warn!(target: "audio", "music {thread_name:?}: {err}"),
+            }
+        });
+
+        self.music = Some(Track {
+            name: name.to_string(),
+            sink,
+            level: 0.0,
+        });
+    }
+
+    /// Advance music fades by `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        let step = dt / CROSSFADE;
+        if let Some(track) = &mut self.music {
+            track.level = (track.level + step).min(1.0);
+            track.sink.set_volume(track.level * self.music_volume);
+        }
+        self.fading_out.retain_mut(|track| {
+            track.level -= step;
+            if track.level <= 0.0 {
+                track.sink.stop();
+                return false;
+            }
+            track.sink.set_volume(track.level * self.music_volume);
+            true
+        });
+    }
+
+    /// Play effect `name` at full volume in both ears, e.g. for the player's own sounds
+    pub fn play(&mut self, name: &str) {
+        self.start(name, [1.0, 1.0]);
+    }
+
+    /// Play effect `name` in both ears at `volume`, 0 to 1
+    pub fn play_scaled(&mut self, name: &str, volume: f32) {
+        self.start(name, [volume, volume]);
+    }
+
+    /// Play effect `name` as heard from `camera`, coming from `pos` in `world`
+    pub fn play_at(&mut self, name: &str, pos: [f32; 2], camera: &Camera, world: &World) {
+        let [left, right] = spatialize(camera, pos);
+        if left.max(right) < MIN_GAIN {
+            return;
+        }
+        let walls = raycast::walls_between(world, camera.pos, pos);
+        let (gain, cutoff) = occlusion(walls);
+        if gain * left.max(right) < MIN_GAIN {
+            return;
+        }
+        self.start_filtered(name, [left * gain, right * gain], cutoff);
+    }
+
+    fn start(&mut self, name: &str, gains: [f32; 2]) {
+        self.start_filtered(name, gains, None);
+    }
+
+    // As `start`, through a low-pass filter at `cutoff` Hz if given
+    fn start_filtered(&mut self, name: &str, [left, right]: [f32; 2], cutoff: Option<u32>) {
+        if !self.sounds.contains_key(name) {
+            let sound = load_sound(&self.root.join("sounds"), name)
+                .inspect_err(|err| warn!(target: "audio", "sound {name:?}: {err}"))
+                .ok();
+            self.sounds.insert(name.to_string(), sound);
+        }
+        let Some(Some(sound)) = self.sounds.get(name) else {
+            return;
+        };
+
+        let sink = match Sink::try_new(&self.handle) {
+            Ok(sink) => sink,
+            Err(err) => {
+                warn!(target: "audio", "sound {name:?}: {err}");
+                return;
+            }
+        };
+        let source = SamplesBuffer::new(sound.channels, sound.sample_rate, sound.samples.clone());
+        // Mixes down to mono, then out to each ear at its own gain
+        let gains = vec![left * self.volume, right * self.volume];
+        match cutoff {
+            Some(cutoff) => sink.append(ChannelVolume::new(source.low_pass(cutoff), gains)),
+            None => sink.append(ChannelVolume::new(source, gains)),
+        }
+        sink.detach();
+    }
+}
+
+// `dir/name`, trying each extension in turn if `name` has none
+fn find_file(dir: &Path, name: &str, extensions: &[&str]) -> Result<PathBuf, AudioError> {
+    let candidates: Vec<PathBuf> = if Path::new(name).extension().is_some() {
+        vec![dir.join(name)]
+    } else {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{name}.{ext}")))
+            .collect()
+    };
+    candidates
+        .into_iter()
+        .find(|p| p.is_file())
+        .ok_or_else(|| AudioError::NotFound(dir.to_path_buf()))
+}
+
+fn load_sound(dir: &Path, name: &str) -> Result<Sound, AudioError> {
+    let path = find_file(dir, name, &EFFECT_EXTENSIONS)?;
+    let file = File::open(&path).map_err(AudioError::Io)?;
+    let decoder = Decoder::new(BufReader::new(file)).map_err(AudioError::Decode)?;
+    Ok(Sound {
+        channels: decoder.channels(),
+        sample_rate: decoder.sample_rate(),
+        samples: decoder.convert_samples().collect(),
+    })
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    Device(rodio::StreamError),
+    NotFound(PathBuf), // the directory that was searched
+    Io(std::io::Error),
+    Decode(rodio::decoder::DecoderError),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::Device(err) => write!(f, "no audio output: {err}"),
+            AudioError::NotFound(dir) => write!(f, "not found in {}", dir.display()),
+            AudioError::Io(err) => write!(f, "could not read sound: {err}"),
+            AudioError::Decode(err) => write!(f, "could not decode sound: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
@@ -0,0 +1,150 @@
+use crate::{camera::Camera, renderer::pack_rgb, world::World};
+
+// Screen pixels per world unit
+const MIN_ZOOM: f32 = 2.0;
+const MAX_ZOOM: f32 = 128.0;
+// Player arrow length in screen pixels, independent of zoom
+const ARROW_PX: f32 = 12.0;
+const THING_PX: f32 = 2.0;
+
+/// Top-down line view of the world, north (+Y) up
+pub struct Automap {
+    pub zoom: f32,        // screen pixels per world unit
+    pub follow: bool,     // keep the player centered
+    pub center: [f32; 2], // world point at the screen center
+}
+
+impl Default for Automap {
+    fn default() -> Self {
+        Self {
+            zoom: 16.0,
+            follow: true,
+            center: [0.0, 0.0],
+        }
+    }
+}
+
+impl Automap {
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Move the view by `delta` world units; only meaningful while not following
+    pub fn pan(&mut self, delta: [f32; 2]) {
+        self.center = [self.center[0] + delta[0], self.center[1] + delta[1]];
+    }
+
+    /// Replace the frame with the map; an unfollowed map stays where the player last left it
+    pub fn draw(
+        &mut self,
+        buf: &mut [u32],
+        width: usize,
+        height: usize,
+        world: &World,
+        camera: &Camera,
+    ) {
+        if self.follow {
+            self.center = camera.pos;
+        }
+        buf[..width * height].fill(pack_rgb(0, 0, 0));
+
+        let solid = pack_rgb(220, 60, 60);
+        let portal = pack_rgb(110, 90, 60);
+        for wall in &world.walls {
+            let color = if wall.back_sector.is_some() {
+                portal
+            } else {
+                solid
+            };
+            let (a, b) = (
+                self.to_screen(wall.start, width, height),
+                self.to_screen(wall.end, width, height),
+            );
+            draw_line(buf, width, height, a, b, color);
+        }
+
+        let thing_color = pack_rgb(80, 200, 80);
+        for id in world.entities.ids() {
+            let pos = world.entities.transforms[id].pos;
+            let [x, y] = self.to_screen(pos, width, height);
+            let (h0, h1) = ([x - THING_PX, y], [x + THING_PX, y]);
+            let (v0, v1) = ([x, y - THING_PX], [x, y + THING_PX]);
+            draw_line(buf, width, height, h0, h1, thing_color);
+            draw_line(buf, width, height, v0, v1, thing_color);
+        }
+
+        // Player as an arrow along the facing direction (yaw 0 faces +Y)
+        let len = ARROW_PX / self.zoom;
+        let fwd = [camera.yaw.sin() * len, camera.yaw.cos() * len];
+        let side = [fwd[1] * 0.5, -fwd[0] * 0.5];
+        let at = |f: f32, s: f32| {
+            let p = [
+                camera.pos[0] + fwd[0] * f + side[0] * s,
+                camera.pos[1] + fwd[1] * f + side[1] * s,
+            ];
+            self.to_screen(p, width, height)
+        };
+        let (tip, tail) = (at(0.5, 0.0), at(-0.5, 0.0));
+        let player = pack_rgb(255, 255, 255);
+        draw_line(buf, width, height, tail, tip, player);
+        draw_line(buf, width, height, at(0.0, -1.0), tip, player);
+        draw_line(buf, width, height, at(0.0, 1.0), tip, player);
+    }
+
+    #[inline]
+    fn to_screen(&self, p: [f32; 2], width: usize, height: usize) -> [f32; 2] {
+        [
+            0.5 * width as f32 + (p[0] - self.center[0]) * self.zoom,
+            0.5 * height as f32 - (p[1] - self.center[1]) * self.zoom,
+        ]
+    }
+}
+
+// DDA line, clipped to the screen first so zoomed-in walls don't walk millions of off-screen pixels
+fn draw_line(buf: &mut [u32], width: usize, height: usize, a: [f32; 2], b: [f32; 2], color: u32) {
+    let Some((a, b)) = clip_line(a, b, (width - 1) as f32, (height - 1) as f32) else {
+        return;
+    };
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0);
+    let (sx, sy) = (dx / steps, dy / steps);
+    let (mut x, mut y) = (a[0], a[1]);
+    for _ in 0..=steps as usize {
+        let (px, py) = (x.round() as usize, y.round() as usize);
+        if px < width && py < height {
+            buf[py * width + px] = color;
+        }
+        x += sx;
+        y += sy;
+    }
+}
+
+// Liang-Barsky against [0, max_x] x [0, max_y]
+fn clip_line(a: [f32; 2], b: [f32; 2], max_x: f32, max_y: f32) -> Option<([f32; 2], [f32; 2])> {
+    let d = [b[0] - a[0], b[1] - a[1]];
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in [
+        (-d[0], a[0]),
+        (d[0], max_x - a[0]),
+        (-d[1], a[1]),
+        (d[1], max_y - a[1]),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None; // parallel to and outside this edge
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let at = |t: f32| [a[0] + d[0] * t, a[1] + d[1] * t];
+    Some((at(t0), at(t1)))
+}
@@ -0,0 +1,137 @@
+// --bench: a fixed camera path drawn for a set number of frames as fast as possible,
+// timed per stage so renderer changes can be compared run to run
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use two_halfD_engine::collision::{self, PLAYER_RADIUS};
+use two_halfD_engine::physics::STAND_EYE_HEIGHT;
+use two_halfD_engine::scaler::PresentTimings;
+use two_halfD_engine::world::World;
+
+pub const DEFAULT_BENCH_FRAMES: usize = 1000;
+
+// The world steps this much per frame, so doors and things move the same in every run
+pub const BENCH_DT: f32 = 1.0 / 60.0;
+
+// The path circles the player start this far out while turning once
+const PATH_RADIUS: f32 = 1.0;
+
+/// Timings of one benchmarked frame
+#[derive(Clone, Copy, Default)]
+pub struct FrameSample {
+    pub frame: Duration, // since the previous frame started, event loop included
+    pub render: Duration,
+    pub present: PresentTimings,
+    pub surface: Duration, // handing the buffer to the window system
+}
+
+pub struct Bench {
+    frames: usize,
+    samples: Vec<FrameSample>,
+    last_frame: Option<Instant>,
+}
+
+impl Bench {
+    pub fn new(frames: usize) -> Self {
+        Self {
+            frames: frames.max(1),
+            samples: Vec::with_capacity(frames),
+            last_frame: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.samples.len() >= self.frames
+    }
+
+    /// Camera position and yaw for the next frame: one full turn at the player start
+    /// while circling it, slid against walls so it never leaves the map
+    pub fn camera_at(&self, world: &World) -> ([f32; 2], f32) {
+        let t = self.samples.len() as f32 / self.frames as f32;
+        let angle = t * std::f32::consts::TAU;
+        let start = world.player_start.pos;
+        let floor_z = world
+            .sector_at(start)
+            .map_or(0.0, |s| world.sectors[s].floor_z);
+        let offset = [PATH_RADIUS * angle.sin(), PATH_RADIUS * (1.0 - angle.cos())];
+        let pos = collision::slide_move(
+            world,
+            start,
+            offset,
+            PLAYER_RADIUS,
+            floor_z,
+            STAND_EYE_HEIGHT,
+        );
+        (pos, world.player_start.yaw + angle)
+    }
+
+    /// Call at the start of each frame; the first frame only sets the clock
+    pub fn frame_started(&mut self, now: Instant) -> Option<Duration> {
+        let since = self.last_frame.map(|last| now - last);
+        self.last_frame = Some(now);
+        since
+    }
+
+    pub fn record(&mut self, sample: FrameSample) {
+        self.samples.push(sample);
+    }
+
+    /// min/avg/p99 frame time and the average of each stage
+    pub fn report(&self) -> String {
+        let mut frames: Vec<Duration> = self.samples.iter().map(|s| s.frame).collect();
+        frames.sort_unstable();
+        let avg = |f: fn(&FrameSample) -> Duration| {
+            let total: Duration = self.samples.iter().map(f).sum();
+            ms(total / self.samples.len().max(1) as u32)
+        };
+        let p99 = frames
+            .get((frames.len() * 99).div_ceil(100).saturating_sub(1))
+            .copied()
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "Bench: {} frames", self.samples.len());
+        let _ = writeln!(
+            out,
+            "  frame    min {:.3} ms  avg {:.3} ms  p99 {:.3} ms",
+            ms(frames.first().copied().unwrap_or_default()),
+            avg(|s| s.frame),
+            ms(p99),
+        );
+        let _ = writeln!(out, "  render   avg {:.3} ms", avg(|s| s.render));
+        let _ = writeln!(out, "  blit     avg {:.3} ms", avg(|s| s.present.blit));
+        let _ = writeln!(out, "  sharpen  avg {:.3} ms", avg(|s| s.present.sharpen));
+        let _ = writeln!(out, "  crt      avg {:.3} ms", avg(|s| s.present.crt));
+        let _ = writeln!(out, "  color    avg {:.3} ms", avg(|s| s.present.color));
+        let _ = write!(out, "  present  avg {:.3} ms", avg(|s| s.surface));
+        out
+    }
+
+    /// One row per frame, times in milliseconds
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut out = String::from(
+            "frame,frame_ms,render_ms,blit_ms,sharpen_ms,crt_ms,color_ms,present_ms\n",
+        );
+        for (i, s) in self.samples.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{i},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4}",
+                ms(s.frame),
+                ms(s.render),
+                ms(s.present.blit),
+                ms(s.present.sharpen),
+                ms(s.present.crt),
+                ms(s.present.color),
+                ms(s.surface),
+            );
+        }
+        std::fs::write(path, out)
+    }
+}
+
+#[inline]
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
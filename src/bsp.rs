@@ -0,0 +1,256 @@
+use crate::world::Wall;
+
+// Distance below which a point counts as lying on a splitter line
+const ON_LINE_EPS: f32 = 1e-4;
+// Splitter candidates evaluated per node; large maps sample instead of trying every seg
+const MAX_CANDIDATES: usize = 64;
+
+/// A piece of a wall; walls crossing a splitter are cut into several segs
+pub struct Seg {
+    pub wall: usize,
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    pub u_offset: f32, // distance from the wall's start to this seg's start, for texturing
+}
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Aabb {
+    fn empty() -> Self {
+        Self {
+            min: [f32::INFINITY; 2],
+            max: [f32::NEG_INFINITY; 2],
+        }
+    }
+
+    fn add(&mut self, p: [f32; 2]) {
+        self.min = [self.min[0].min(p[0]), self.min[1].min(p[1])];
+        self.max = [self.max[0].max(p[0]), self.max[1].max(p[1])];
+    }
+
+    fn merge(&mut self, other: &Aabb) {
+        self.add(other.min);
+        self.add(other.max);
+    }
+
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        [
+            self.min,
+            [self.max[0], self.min[1]],
+            self.max,
+            [self.min[0], self.max[1]],
+        ]
+    }
+}
+
+// Segs lying on the splitter are stored on the node itself; they can't occlude each other
+struct Node {
+    origin: [f32; 2],
+    dir: [f32; 2],
+    segs: std::ops::Range<usize>,
+    front: Option<usize>, // left of the splitter
+    back: Option<usize>,
+    bbox: Aabb, // everything in this subtree
+}
+
+/// Receives segs front-to-back from `Bsp::walk_front_to_back`
+pub trait BspVisitor {
+    /// Return true to skip a subtree whose bounds can't be visible
+    fn cull(&mut self, bbox: &Aabb) -> bool;
+    /// Return false to stop the traversal (e.g. the screen is full)
+    fn visit(&mut self, seg: &Seg) -> bool;
+}
+
+pub struct Bsp {
+    nodes: Vec<Node>,
+    segs: Vec<Seg>,
+    root: Option<usize>,
+}
+
+impl Bsp {
+    pub fn build(walls: &[Wall]) -> Self {
+        let segs = walls
+            .iter()
+            .enumerate()
+            .map(|(i, w)| Seg {
+                wall: i,
+                start: w.start,
+                end: w.end,
+                u_offset: 0.0,
+            })
+            .collect();
+
+        let mut bsp = Bsp {
+            nodes: Vec::new(),
+            segs: Vec::new(),
+            root: None,
+        };
+        bsp.root = bsp.build_node(segs);
+        bsp
+    }
+
+    fn build_node(&mut self, segs: Vec<Seg>) -> Option<usize> {
+        if segs.is_empty() {
+            return None;
+        }
+
+        let split = choose_splitter(&segs);
+        let origin = segs[split].start;
+        let dir = sub(segs[split].end, segs[split].start);
+
+        let mut on = Vec::new();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        for seg in segs {
+            let d0 = side(origin, dir, seg.start);
+            let d1 = side(origin, dir, seg.end);
+            if d0.abs() < ON_LINE_EPS && d1.abs() < ON_LINE_EPS {
+                on.push(seg);
+            } else if d0 >= -ON_LINE_EPS && d1 >= -ON_LINE_EPS {
+                front.push(seg);
+            } else if d0 <= ON_LINE_EPS && d1 <= ON_LINE_EPS {
+                back.push(seg);
+            } else {
+                // Straddles the splitter: cut at the crossing point
+                let (a, b) = split_seg(seg, d0 / (d0 - d1));
+                if d0 > 0.0 {
+                    front.push(a);
+                    back.push(b);
+                } else {
+                    back.push(a);
+                    front.push(b);
+                }
+            }
+        }
+
+        let mut bbox = Aabb::empty();
+        for seg in &on {
+            bbox.add(seg.start);
+            bbox.add(seg.end);
+        }
+        let first = self.segs.len();
+        self.segs.extend(on);
+        let segs = first..self.segs.len();
+
+        let front = self.build_node(front);
+        let back = self.build_node(back);
+        for child in [front, back].into_iter().flatten() {
+            let child_bbox = self.nodes[child].bbox;
+            bbox.merge(&child_bbox);
+        }
+
+        self.nodes.push(Node {
+            origin,
+            dir,
+            segs,
+            front,
+            back,
+            bbox,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Visit segs nearest-first as seen from `pos`
+    pub fn walk_front_to_back(&self, pos: [f32; 2], visitor: &mut impl BspVisitor) {
+        if let Some(root) = self.root {
+            self.walk_node(root, pos, visitor);
+        }
+    }
+
+    // Returns false once the visitor asked to stop
+    fn walk_node(&self, idx: usize, pos: [f32; 2], visitor: &mut impl BspVisitor) -> bool {
+        let node = &self.nodes[idx];
+        if visitor.cull(&node.bbox) {
+            return true;
+        }
+
+        let (near, far) = if side(node.origin, node.dir, pos) >= 0.0 {
+            (node.front, node.back)
+        } else {
+            (node.back, node.front)
+        };
+
+        if let Some(near) = near
+            && !self.walk_node(near, pos, visitor)
+        {
+            return false;
+        }
+        for seg in &self.segs[node.segs.clone()] {
+            if !visitor.visit(seg) {
+                return false;
+            }
+        }
+        match far {
+            Some(far) => self.walk_node(far, pos, visitor),
+            None => true,
+        }
+    }
+}
+
+// Fewest splits first, then the most even front/back balance
+fn choose_splitter(segs: &[Seg]) -> usize {
+    let stride = segs.len().div_ceil(MAX_CANDIDATES);
+    let mut best = 0;
+    let mut best_score = usize::MAX;
+    for cand in (0..segs.len()).step_by(stride) {
+        let origin = segs[cand].start;
+        let dir = sub(segs[cand].end, segs[cand].start);
+        let (mut splits, mut front, mut back) = (0usize, 0usize, 0usize);
+        for seg in segs {
+            let d0 = side(origin, dir, seg.start);
+            let d1 = side(origin, dir, seg.end);
+            if d0.abs() < ON_LINE_EPS && d1.abs() < ON_LINE_EPS {
+                continue;
+            } else if d0 >= -ON_LINE_EPS && d1 >= -ON_LINE_EPS {
+                front += 1;
+            } else if d0 <= ON_LINE_EPS && d1 <= ON_LINE_EPS {
+                back += 1;
+            } else {
+                splits += 1;
+            }
+        }
+        let score = splits * 8 + front.abs_diff(back);
+        if score < best_score {
+            best = cand;
+            best_score = score;
+        }
+    }
+    best
+}
+
+fn split_seg(seg: Seg, t: f32) -> (Seg, Seg) {
+    let mid = [
+        seg.start[0] + t * (seg.end[0] - seg.start[0]),
+        seg.start[1] + t * (seg.end[1] - seg.start[1]),
+    ];
+    let d = sub(mid, seg.start);
+    let a = Seg {
+        wall: seg.wall,
+        start: seg.start,
+        end: mid,
+        u_offset: seg.u_offset,
+    };
+    let b = Seg {
+        wall: seg.wall,
+        start: mid,
+        end: seg.end,
+        u_offset: seg.u_offset + (d[0] * d[0] + d[1] * d[1]).sqrt(),
+    };
+    (a, b)
+}
+
+// Signed distance-like value: positive on the left of the line (the front side)
+#[inline]
+fn side(origin: [f32; 2], dir: [f32; 2], p: [f32; 2]) -> f32 {
+    let len = (dir[0] * dir[0] + dir[1] * dir[1]).sqrt().max(f32::EPSILON);
+    (dir[0] * (p[1] - origin[1]) - dir[1] * (p[0] - origin[0])) / len
+}
+
+#[inline]
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
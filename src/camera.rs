@@ -1,3 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Horizontal field of view in degrees on a 4:3 screen, the usual way to state one
+pub const DEFAULT_FOV: f32 = 90.0;
+/// Widest horizontal field of view a screen is given, however wide it is
+pub const MAX_FOV_X: f32 = 130.0;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Camera {
     pub pos: [f32; 2], // (x, y) position in world space
     pub yaw: f32,      // radians, camera facing direction in the X-Y plane
@@ -35,6 +43,16 @@ impl Camera {
         self.fy = self.fx / aspect;
     }
 
+    /// Hor+ field of view: `fov_deg` across a 4:3 screen, with square pixels, so wider
+    /// screens see further to the sides rather than stretching the same view, up to
+    /// `MAX_FOV_X`, past which the view is cropped top and bottom instead
+    pub fn set_fov_hor_plus(&mut self, width: f32, height: f32, fov_deg: f32) {
+        let tan_half_y = (0.5 * fov_deg.to_radians()).tan() * 3.0 / 4.0;
+        let tan_half_x = (tan_half_y * width / height).min((0.5 * MAX_FOV_X.to_radians()).tan());
+        self.fx = 0.5 * width / tan_half_x;
+        self.fy = self.fx;
+    }
+
     #[inline]
     pub fn screen_center_y(&self, screen_h: f32) -> f32 {
         0.5 * screen_h
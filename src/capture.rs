@@ -0,0 +1,280 @@
+// Frame capture (F10): the frames handed to presentation, at the internal resolution so it
+// works the same on either backend, are sampled at a fixed rate and encoded to an animated
+// GIF, a Y4M video or a numbered PNG sequence. Encoding runs on its own thread behind a
+// short queue; when it falls behind, frames are dropped rather than stalling the game.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, ExtendedColorType, Frame, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+// Frames per second written; 25 is a whole number of GIF centiseconds per frame
+pub const CAPTURE_FPS: u32 = 25;
+// Frames waiting for the encoder before new ones are dropped
+const QUEUE_LENGTH: usize = 8;
+// GIF quantizer effort, 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureFormat {
+    #[default]
+    Gif,
+    Y4m, // uncompressed 4:4:4 video, for ffmpeg and friends
+    Png, // a directory of numbered frames
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    Encode(image::ImageError),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Io(err) => write!(f, "{err}"),
+            CaptureError::Encode(err) => write!(f, "could not encode a frame: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(err: io::Error) -> Self {
+        CaptureError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for CaptureError {
+    fn from(err: image::ImageError) -> Self {
+        CaptureError::Encode(err)
+    }
+}
+
+/// A capture in progress, feeding its encoder thread
+pub struct Capture {
+    path: PathBuf,
+    size: [usize; 2],
+    sender: SyncSender<Vec<u32>>,
+    worker: JoinHandle<Result<usize, CaptureError>>, // frames written
+    next_frame: Instant,
+    end: Instant,
+    dropped: usize,
+}
+
+impl Capture {
+    /// Start capturing `width` x `height` frames for `duration`, to a new file (or, for
+    /// PNGs, directory) in the working directory named after the time
+    pub fn start(
+        format: CaptureFormat,
+        width: usize,
+        height: usize,
+        duration: Duration,
+        now: Instant,
+    ) -> Result<Self, CaptureError> {
+        let stamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = PathBuf::from(match format {
+            CaptureFormat::Gif => format!("capture-{stamp}.gif"),
+            CaptureFormat::Y4m => format!("capture-{stamp}.y4m"),
+            CaptureFormat::Png => format!("capture-{stamp}"),
+        });
+        let encoder = Encoder::create(format, &path, width, height)?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let worker = thread::spawn(move || encode_frames(encoder, receiver));
+        Ok(Self {
+            path,
+            size: [width, height],
+            sender,
+            worker,
+            next_frame: now,
+            end: now + duration,
+            dropped: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Offer the frame presented at `now`; false once the capture is over, either because
+    /// its time is up or because the frame size changed
+    pub fn frame(&mut self, frame: &[u32], width: usize, height: usize, now: Instant) -> bool {
+        if now >= self.end || [width, height] != self.size {
+            return false;
+        }
+        if now < self.next_frame {
+            return true;
+        }
+        // Keep to the frame rate on average even when frames arrive late
+        self.next_frame += Duration::from_secs(1) / CAPTURE_FPS;
+        if self.next_frame < now {
+            self.next_frame = now;
+        }
+        match self.sender.try_send(frame.to_vec()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false, // the encoder failed
+        }
+    }
+
+    /// Stop taking frames. The encoder finishes the queue in the background and reports
+    /// on stdout; join the returned handle to wait for it.
+    pub fn stop(self) -> JoinHandle<()> {
+        let Capture {
+            path,
+            sender,
+            worker,
+            dropped,
+            ..
+        } = self;
+        drop(sender);
+        thread::spawn(move || match worker.join() {
+            Ok(Ok(frames)) => {
+                info!(target: "renderer", frames, "captured to {}", path.display());
+                if dropped > 0 {
+                    warn!(
+                        target: "renderer",
+                        dropped,
+                        "frames dropped while the encoder caught up"
+                    );
+                }
+            }
+            Ok(Err(err)) => error!(target: "renderer", "{}: {err}", path.display()),
+            Err(_) => error!(target: "renderer", "{}: the encoder panicked", path.display()),
+        })
+    }
+}
+
+fn encode_frames(
+    mut encoder: Encoder,
+    receiver: Receiver<Vec<u32>>,
+) -> Result<usize, CaptureError> {
+    let mut frames = 0;
+    for frame in receiver {
+        encoder.write(&frame, frames)?;
+        frames += 1;
+    }
+    encoder.finish()?;
+    Ok(frames)
+}
+
+enum Encoder {
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+        size: [u32; 2],
+    },
+    Y4m {
+        out: BufWriter<File>,
+        planes: Vec<u8>, // Y, Cb and Cr, reused from frame to frame
+    },
+    Png {
+        dir: PathBuf,
+        size: [u32; 2],
+    },
+}
+
+impl Encoder {
+    fn create(
+        format: CaptureFormat,
+        path: &Path,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, CaptureError> {
+        let size = [width as u32, height as u32];
+        Ok(match format {
+            CaptureFormat::Gif => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+                encoder.set_repeat(Repeat::Infinite)?;
+                Encoder::Gif { encoder, size }
+            }
+            CaptureFormat::Y4m => {
+                let mut out = BufWriter::new(File::create(path)?);
+                writeln!(
+                    out,
+                    "YUV4MPEG2 W{width} H{height} F{CAPTURE_FPS}:1 Ip A1:1 C444"
+                )?;
+                Encoder::Y4m {
+                    out,
+                    planes: vec![0; width * height * 3],
+                }
+            }
+            CaptureFormat::Png => {
+                fs::create_dir(path)?;
+                Encoder::Png {
+                    dir: path.to_path_buf(),
+                    size,
+                }
+            }
+        })
+    }
+
+    fn write(&mut self, frame: &[u32], index: usize) -> Result<(), CaptureError> {
+        match self {
+            Encoder::Gif { encoder, size } => {
+                let rgba = frame
+                    .iter()
+                    .flat_map(|&p| {
+                        let [r, g, b] = unpack(p);
+                        [r, g, b, 255]
+                    })
+                    .collect();
+                let image = RgbaImage::from_raw(size[0], size[1], rgba)
+                    .expect("frame matches the capture size");
+                let delay = Delay::from_numer_denom_ms(1000, CAPTURE_FPS);
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+            }
+            Encoder::Y4m { out, planes } => {
+                let (y, chroma) = planes.split_at_mut(frame.len());
+                let (cb, cr) = chroma.split_at_mut(frame.len());
+                for (i, &p) in frame.iter().enumerate() {
+                    [y[i], cb[i], cr[i]] = ycbcr(unpack(p));
+                }
+                out.write_all(b"FRAME\n")?;
+                out.write_all(planes)?;
+            }
+            Encoder::Png { dir, size } => {
+                let rgb: Vec<u8> = frame.iter().flat_map(|&p| unpack(p)).collect();
+                let path = dir.join(format!("frame-{index:05}.png"));
+                image::save_buffer(path, &rgb, size[0], size[1], ExtendedColorType::Rgb8)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), CaptureError> {
+        match self {
+            // The GIF trailer is written when the encoder drops
+            Encoder::Gif { .. } | Encoder::Png { .. } => Ok(()),
+            Encoder::Y4m { mut out, .. } => Ok(out.flush()?),
+        }
+    }
+}
+
+fn unpack(p: u32) -> [u8; 3] {
+    [(p >> 16) as u8, (p >> 8) as u8, p as u8]
+}
+
+// Studio-range BT.601, what players assume of Y4M without a colorspace tag
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let cb = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let cr = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+    [y.round() as u8, cb.round() as u8, cr.round() as u8]
+}
@@ -0,0 +1,124 @@
+// Third-person chase view: a camera held behind and above the player, pulled in short of
+// whatever is in the way, and the player's figure for it to look at
+
+use crate::camera::Camera;
+use crate::entity::{EntityId, Sprite, Transform};
+use crate::raycast::{self, Ray};
+use crate::renderer::pack_rgb;
+use crate::texture::{TRANSPARENT, Texture, TextureId};
+use crate::world::{Blend, World};
+
+/// How far behind the player's eye the camera sits, and how far above it
+pub const DISTANCE: f32 = 2.5;
+pub const RISE: f32 = 0.6;
+// Room kept between the camera and a wall it was pulled in by, and from floor and ceiling
+const WALL_GAP: f32 = 0.2;
+const FLAT_GAP: f32 = 0.1;
+// Pulled in closer than this, the figure would fill the view, so the eye is used instead
+const MIN_DISTANCE: f32 = 0.5;
+
+pub struct Chase {
+    figure: Texture,
+    texture: Option<TextureId>, // `figure`'s id in the attached world
+}
+
+impl Default for Chase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chase {
+    pub fn new() -> Self {
+        Self {
+            figure: figure_texture(pack_rgb(60, 170, 60)),
+            texture: None,
+        }
+    }
+
+    /// Give `world` the player's figure; call whenever the world is replaced
+    pub fn attach(&mut self, world: &mut World) {
+        world.textures.push(Texture::from_pixels(
+            self.figure.width,
+            self.figure.height,
+            self.figure.pixels.clone(),
+        ));
+        self.texture = Some(world.textures.len() - 1);
+    }
+
+    /// Put the player's figure into `world`, standing at `feet_z` and `height` tall; despawn
+    /// it again once drawn, so shots and monsters never find it
+    pub fn place_figure(
+        &self,
+        world: &mut World,
+        pos: [f32; 2],
+        feet_z: f32,
+        height: f32,
+        yaw: f32,
+    ) -> Option<EntityId> {
+        let texture = self.texture?;
+        let id = world.entities.spawn(Transform {
+            pos,
+            z: feet_z,
+            yaw,
+        });
+        world.entities.sprites[id] = Some(Sprite {
+            texture,
+            height,
+            scale: 1.0,
+            rotations: None,
+            model: None,
+            blend: Blend::Masked,
+        });
+        Some(id)
+    }
+}
+
+/// The view from behind and above `eye`, looking the same way; brought in front of any
+/// wall between the two and kept between floor and ceiling, or `eye` itself where there's
+/// no room behind
+pub fn camera(world: &World, eye: &Camera) -> Camera {
+    let (s, c) = eye.yaw.sin_cos();
+    let back = [eye.pos[0] - s * DISTANCE, eye.pos[1] - c * DISTANCE];
+    let (ray, dist) = Ray::between(eye.pos, eye.eye_z, back, eye.eye_z + RISE);
+    let dist = raycast::cast(world, &ray, dist)
+        .wall
+        .map_or(dist, |hit| hit.distance - WALL_GAP);
+    if dist < MIN_DISTANCE {
+        return *eye;
+    }
+    let pos = ray.point_at(dist);
+    let Some(sector) = world.sector_at(pos) else {
+        return *eye;
+    };
+    let (floor_z, ceiling_z) = world.floor_ceiling_at(sector, ray.z_at(dist));
+    Camera {
+        pos,
+        eye_z: ray
+            .z_at(dist)
+            .min(ceiling_z - FLAT_GAP)
+            .max(floor_z + FLAT_GAP),
+        ..*eye
+    }
+}
+
+/// A figure in `shirt`: head, body and legs, transparent around them
+pub fn figure_texture(shirt: u32) -> Texture {
+    const W: usize = 16;
+    const H: usize = 32;
+    let skin = pack_rgb(220, 180, 140);
+    let legs = pack_rgb(50, 50, 60);
+    let mut pixels = vec![TRANSPARENT; W * H];
+    for y in 0..H {
+        for x in 0..W {
+            let (cx, cy) = (x as f32 + 0.5 - W as f32 / 2.0, y as f32 + 0.5);
+            pixels[y * W + x] = match y {
+                0..8 if cx * cx + (cy - 4.0) * (cy - 4.0) <= 12.0 => skin,
+                8..20 if cx.abs() <= 6.0 => shirt,
+                20.. if (1.0..=5.0).contains(&cx.abs()) => legs,
+                _ => continue,
+            };
+        }
+    }
+    Texture::from_pixels(W, H, pixels)
+}
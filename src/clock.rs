@@ -0,0 +1,77 @@
+// Game time: how far each frame moves the simulation, as opposed to the wall clock that paces
+// drawing. It can be paused, stepped a tick at a time while paused, and run slower or faster
+// than real time; frames keep being drawn throughout.
+
+use std::time::{Duration, Instant};
+
+// Most game time a tick covers, however long the frame or fast the scale, so neither a
+// stall nor a high speed flings things through walls
+const MAX_FRAME: Duration = Duration::from_millis(100);
+// Seconds of game time in each single step
+pub const STEP_DT: f32 = 1.0 / 60.0;
+// Slowest and fastest the game can be set to run, as a multiple of real time
+pub const SCALE_RANGE: (f32, f32) = (0.05, 8.0);
+
+pub struct GameClock {
+    last: Instant, // when game time last caught up with the wall clock
+    paused: bool,
+    steps: u32, // single steps still to run while paused
+    scale: f32, // game seconds per real second
+}
+
+impl GameClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last: now,
+            paused: false,
+            steps: 0,
+            scale: 1.0,
+        }
+    }
+
+    /// Game seconds for a tick this frame, or None if the game shouldn't tick: paused with
+    /// no step asked for
+    pub fn advance(&mut self, now: Instant) -> Option<f32> {
+        let real = now.duration_since(self.last);
+        self.last = now;
+        if !self.paused {
+            return Some(real.mul_f32(self.scale).min(MAX_FRAME).as_secs_f32());
+        }
+        let step = self.steps.checked_sub(1)?;
+        self.steps = step;
+        Some(STEP_DT)
+    }
+
+    /// Let real time pass without any of it reaching the game, as while a menu is open
+    pub fn hold(&mut self, now: Instant) {
+        self.last = now;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause or unpause; unpausing drops any steps not yet run
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            self.steps = 0;
+        }
+    }
+
+    /// Run `ticks` more ticks of `STEP_DT` each, one a frame; pauses first if running
+    pub fn step(&mut self, ticks: u32) {
+        self.paused = true;
+        self.steps = self.steps.saturating_add(ticks);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Game seconds per real second, within `SCALE_RANGE`
+    pub fn set_scale(&mut self, scale: f32) {
+        let (lo, hi) = SCALE_RANGE;
+        self.scale = scale.clamp(lo, hi);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::entity::{Behavior, Entities, EntityId};
+use crate::world::{Wall, World};
+
+pub const PLAYER_RADIUS: f32 = 0.25;
+// Tallest ledge walked up (or down) without jumping
+pub const MAX_STEP: f32 = 0.4;
+
+// Push-out passes per sub-step; corners need more than one
+const RESOLVE_ITERATIONS: usize = 4;
+// How far a circle has to reach past a portal before the room beyond counts as over it;
+// push-outs leave circles just touching the walls they stopped at
+const OVERLAP_SLOP: f32 = 1e-3;
+
+/// An entity standing in the way, such as an intact solid prop
+#[derive(Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub entity: EntityId,
+    pub pos: [f32; 2],
+    pub radius: f32,
+    pub bottom_z: f32,
+    pub top_z: f32,
+}
+
+/// Entities in `entities` that block movement: intact solid props and platforms
+pub fn obstacles(entities: &Entities) -> impl Iterator<Item = Obstacle> + Clone + '_ {
+    entities
+        .ids()
+        .filter(|&id| {
+            entities.props[id].is_some_and(|p| p.solid && !p.is_destroyed())
+                || matches!(entities.behaviors[id], Behavior::Platform { .. })
+        })
+        .filter_map(|id| {
+            let radius = entities.colliders[id]?;
+            let t = entities.transforms[id];
+            let height = entities.sprites[id].map_or(radius * 2.0, |s| s.world_height());
+            Some(Obstacle {
+                entity: id,
+                pos: t.pos,
+                radius,
+                bottom_z: t.z,
+                top_z: t.z + height,
+            })
+        })
+}
+
+/// The highest of `obstacles` under a circle of `radius` at `pos` with its top no more
+/// than `MAX_STEP` above `feet_z`: something to land or stand on, raising the floor there
+pub fn standing_on(
+    obstacles: impl IntoIterator<Item = Obstacle>,
+    pos: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+) -> Option<Obstacle> {
+    obstacles
+        .into_iter()
+        .filter(|o| o.top_z <= feet_z + MAX_STEP)
+        .filter(|o| {
+            let (dx, dy) = (pos[0] - o.pos[0], pos[1] - o.pos[1]);
+            let reach = radius + o.radius - OVERLAP_SLOP;
+            dx * dx + dy * dy < reach * reach
+        })
+        .max_by(|a, b| a.top_z.total_cmp(&b.top_z))
+}
+
+/// Move a circle of `radius` from `pos` by `delta`, sliding along walls and around the
+/// world's solid entities
+///
+/// `feet_z` and `height` decide which portals are passable: a floor more than `MAX_STEP`
+/// above the feet, or an opening lower than `height`, blocks like a solid wall.
+pub fn slide_move(
+    world: &World,
+    pos: [f32; 2],
+    delta: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+    height: f32,
+) -> [f32; 2] {
+    let obstacles = obstacles(&world.entities);
+    slide_move_among(world, obstacles, pos, delta, radius, feet_z, height)
+}
+
+/// As `slide_move`, against `obstacles` instead of the world's entities
+pub fn slide_move_among(
+    world: &World,
+    obstacles: impl Iterator<Item = Obstacle> + Clone,
+    pos: [f32; 2],
+    delta: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+    height: f32,
+) -> [f32; 2] {
+    // Sub-step so a fast move can't tunnel through a wall in one frame
+    let len = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+    let steps = ((len / (radius * 0.5)).ceil() as usize).max(1);
+    let step = [delta[0] / steps as f32, delta[1] / steps as f32];
+    // The sector the move starts in, which never holds it in
+    let here = world.sector_at(pos);
+
+    let mut p = pos;
+    for _ in 0..steps {
+        let prev = p;
+        p = [p[0] + step[0], p[1] + step[1]];
+        for _ in 0..RESOLVE_ITERATIONS {
+            let walls = push_out_of_walls(world, here, &mut p, prev, radius, feet_z, height);
+            let others = push_out_of_obstacles(obstacles.clone(), &mut p, radius, feet_z, height);
+            if !walls && !others {
+                break;
+            }
+        }
+    }
+    p
+}
+
+// Returns true if any wall moved the circle
+fn push_out_of_walls(
+    world: &World,
+    here: Option<usize>,
+    p: &mut [f32; 2],
+    prev: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+    height: f32,
+) -> bool {
+    let mut moved = false;
+    // Twice the radius, for walls a push from a nearer one moves the circle into
+    let reach = 2.0 * radius;
+    let near = world
+        .grid
+        .walls_in([p[0] - reach, p[1] - reach], [p[0] + reach, p[1] + reach]);
+    for wall in near
+        .map(|i| &world.walls[i])
+        .filter(|w| blocks(world, w, here, feet_z, height))
+    {
+        let c = closest_point_on_segment(*p, wall.start, wall.end);
+        let dx = p[0] - c[0];
+        let dy = p[1] - c[1];
+        let d2 = dx * dx + dy * dy;
+        if d2 >= radius * radius {
+            continue;
+        }
+
+        // Push along the contact normal; removing only the penetrating component
+        // leaves the tangential part of the move intact, which is what slides
+        let d = d2.sqrt();
+        let n = if d > 1e-6 {
+            [dx / d, dy / d]
+        } else {
+            // Center exactly on the wall: push back toward the side we came from
+            wall_normal_towards(wall, prev)
+        };
+        let push = radius - d;
+        p[0] += n[0] * push;
+        p[1] += n[1] * push;
+        moved = true;
+    }
+    moved
+}
+
+// As `push_out_of_walls`, for obstacles overlapping the mover's height
+fn push_out_of_obstacles(
+    obstacles: impl Iterator<Item = Obstacle>,
+    p: &mut [f32; 2],
+    radius: f32,
+    feet_z: f32,
+    height: f32,
+) -> bool {
+    let mut moved = false;
+    for o in obstacles {
+        if o.top_z <= feet_z || o.bottom_z >= feet_z + height {
+            continue;
+        }
+        let dx = p[0] - o.pos[0];
+        let dy = p[1] - o.pos[1];
+        let d2 = dx * dx + dy * dy;
+        let reach = radius + o.radius;
+        if d2 >= reach * reach {
+            continue;
+        }
+        let d = d2.sqrt();
+        // Dead center gives no direction to push in; pick one
+        let n = if d > 1e-6 {
+            [dx / d, dy / d]
+        } else {
+            [1.0, 0.0]
+        };
+        let push = reach - d;
+        p[0] += n[0] * push;
+        p[1] += n[1] * push;
+        moved = true;
+    }
+    moved
+}
+
+/// Headroom in `sector` for something with its feet at `feet_z`: from the floor, or the
+/// feet if they're above it, up to the ceiling, on or under a slab as `floor_ceiling_at`
+pub fn clearance(world: &World, sector: usize, feet_z: f32) -> f32 {
+    let (floor_z, ceiling_z) = world.floor_ceiling_at(sector, feet_z);
+    ceiling_z - floor_z.max(feet_z)
+}
+
+/// Lowest ceiling over a circle of `radius` at `pos`, counting every sector across a
+/// portal the circle reaches into, so a body half into a crawlspace can't stand up in it;
+/// None outside the map
+pub fn ceiling_over(world: &World, pos: [f32; 2], radius: f32, feet_z: f32) -> Option<f32> {
+    sectors_under(world, pos, radius)
+        .into_iter()
+        .map(|s| world.floor_ceiling_at(s, feet_z).1)
+        .min_by(f32::total_cmp)
+}
+
+/// Sectors a circle of `radius` at `pos` is in: the one under its center and every one
+/// across a portal it reaches into, each once; empty outside the map
+pub fn sectors_under(world: &World, pos: [f32; 2], radius: f32) -> Vec<usize> {
+    let Some(here) = world.sector_at(pos) else {
+        return Vec::new();
+    };
+    let mut sectors = vec![here];
+    let reach = radius - OVERLAP_SLOP;
+    let near = world.grid.walls_in(
+        [pos[0] - radius, pos[1] - radius],
+        [pos[0] + radius, pos[1] + radius],
+    );
+    for wall in near.map(|i| &world.walls[i]) {
+        let Some(back) = wall.back_sector else {
+            continue;
+        };
+        let c = closest_point_on_segment(pos, wall.start, wall.end);
+        let (dx, dy) = (pos[0] - c[0], pos[1] - c[1]);
+        if dx * dx + dy * dy >= reach * reach {
+            continue;
+        }
+        for s in [wall.front_sector, back] {
+            if !sectors.contains(&s) {
+                sectors.push(s);
+            }
+        }
+    }
+    sectors
+}
+
+/// Floor and ceiling for a body of `radius` at `pos` in sector `s` with its feet at
+/// `feet_z`: the top of a platform or crate underfoot if that's above the floor, and the
+/// lowest ceiling over any of the body
+pub fn floor_ceiling_under(
+    world: &World,
+    s: usize,
+    pos: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+) -> (f32, f32) {
+    let (floor_z, ceiling_z) = world.floor_ceiling_at(s, feet_z);
+    let floor_z = standing_on(obstacles(&world.entities), pos, radius, feet_z)
+        .map_or(floor_z, |o| o.top_z.max(floor_z));
+    let ceiling_z = ceiling_over(world, pos, radius, feet_z).unwrap_or(ceiling_z);
+    (floor_z, ceiling_z)
+}
+
+/// The platform a body on the ground at `pos` stands on, and where it is now, to `ride`
+/// along once it has moved; None if it's standing on the floor
+pub fn riding(
+    world: &World,
+    pos: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+) -> Option<(EntityId, [f32; 2])> {
+    let s = world.sector_at(pos)?;
+    let floor_z = world.floor_ceiling_at(s, feet_z).0;
+    let under = standing_on(obstacles(&world.entities), pos, radius, feet_z)?;
+    (under.top_z >= floor_z).then_some((under.entity, under.pos))
+}
+
+/// Where a body of `radius` at `pos` ends up carried as far as what it was `riding` has
+/// moved since, and shoved aside by anything solid that ran into it; walls stop both as
+/// they stop walking
+pub fn ride(
+    world: &World,
+    riding: Option<(EntityId, [f32; 2])>,
+    pos: [f32; 2],
+    radius: f32,
+    feet_z: f32,
+    height: f32,
+) -> [f32; 2] {
+    let entities = &world.entities;
+    let carry = riding
+        .filter(|&(id, _)| entities.is_alive(id))
+        .map_or([0.0, 0.0], |(id, from)| {
+            let to = entities.transforms[id].pos;
+            [to[0] - from[0], to[1] - from[1]]
+        });
+    slide_move(world, pos, carry, radius, feet_z, height)
+}
+
+// One-sided walls are solid; a portal is solid if either side can't be stepped into, over
+// or under a slab depending on which side of it the feet are, or is too low to fit under.
+// `here`, the sector the mover set out from, is skipped: the mover is already in it, and a
+// ceiling come down on them there mustn't wall them in.
+fn blocks(world: &World, wall: &Wall, here: Option<usize>, feet_z: f32, height: f32) -> bool {
+    let Some(back) = wall.back_sector else {
+        return true;
+    };
+    [wall.front_sector, back].into_iter().any(|s| {
+        if Some(s) == here {
+            return false;
+        }
+        let floor_z = world.floor_ceiling_at(s, feet_z).0;
+        floor_z > feet_z + MAX_STEP || clearance(world, s, feet_z) < height
+    })
+}
+
+#[inline]
+pub fn closest_point_on_segment(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    let abx = b[0] - a[0];
+    let aby = b[1] - a[1];
+    let len2 = abx * abx + aby * aby;
+    if len2 <= f32::EPSILON {
+        return a;
+    }
+    let t = (((p[0] - a[0]) * abx + (p[1] - a[1]) * aby) / len2).clamp(0.0, 1.0);
+    [a[0] + t * abx, a[1] + t * aby]
+}
+
+fn wall_normal_towards(wall: &Wall, p: [f32; 2]) -> [f32; 2] {
+    let ex = wall.end[0] - wall.start[0];
+    let ey = wall.end[1] - wall.start[1];
+    let len = (ex * ex + ey * ey).sqrt().max(f32::EPSILON);
+    // Left-hand normal, flipped if p is on the right side
+    let (nx, ny) = (-ey / len, ex / len);
+    let side = (p[0] - wall.start[0]) * nx + (p[1] - wall.start[1]) * ny;
+    if side >= 0.0 { [nx, ny] } else { [-nx, -ny] }
+}
@@ -0,0 +1,156 @@
+// Player settings read from `config.toml` in the working directory. Every field is
+// optional; a missing or broken file falls back to the defaults. The settings menu and the
+// hotkeys write the whole file back when something changes, and with `watch` set, edits
+// made to it on disk are picked up while the game runs.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use two_halfD_engine::camera::DEFAULT_FOV;
+use two_halfD_engine::crosshair::CrosshairStyle;
+use two_halfD_engine::renderer::Mipmaps;
+use two_halfD_engine::scaler::{RenderScale, ScaleMode};
+use two_halfD_engine::screen_flash;
+use two_halfD_engine::status_bar::HudStyle;
+use winit::keyboard::KeyCode;
+
+use crate::capture::CaptureFormat;
+use crate::input::{Bindings, key_name, parse_key};
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub move_speed: f32,        // m/s
+    pub turn_speed: f32,        // degrees per second, for turning keys and the stick
+    pub mouse_sensitivity: f32, // radians per mouse count
+    pub fov: f32,               // degrees across a 4:3 screen, widened for wider ones
+    pub render_scale: RenderScale,
+    pub scale_mode: ScaleMode,
+    pub crt: bool,
+    pub dither: bool,
+    pub mipmaps: Mipmaps,
+    pub view_effects: bool, // head-bob, leaning into strafes and flinching when hurt
+    pub flash_intensity: f32, // 0 (off) ..= 1, of the damage, pickup and underwater flashes
+    pub flash_decay: f32,   // of a full flash worn off per second
+    pub gamma: f32,         // display correction, see `ColorAdjust`
+    pub brightness: f32,
+    pub contrast: f32,
+    pub effects_volume: f32, // 0..=1
+    pub music_volume: f32,   // 0..=1
+    pub hud: HudStyle,
+    pub crosshair: CrosshairStyle,
+    pub crosshair_color: [u8; 3],
+    pub crosshair_size: usize, // pixels at 240 lines, scaled up with the render height
+    pub capture_format: CaptureFormat, // what F10 records
+    pub capture_seconds: f32,  // how long it records for, unless stopped early
+    pub watch: bool,           // reload this file when it's edited on disk
+    pub keys: KeyConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            move_speed: 3.0,
+            turn_speed: 180.0,
+            mouse_sensitivity: 0.0025,
+            fov: DEFAULT_FOV,
+            render_scale: RenderScale::default(),
+            scale_mode: ScaleMode::Bilinear,
+            crt: false,
+            dither: false,
+            mipmaps: Mipmaps::default(),
+            view_effects: true,
+            flash_intensity: 1.0,
+            flash_decay: screen_flash::DEFAULT_DECAY,
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            effects_volume: 1.0,
+            music_volume: 0.7,
+            hud: HudStyle::default(),
+            crosshair: CrosshairStyle::default(),
+            crosshair_color: [255, 255, 255],
+            crosshair_size: 3,
+            capture_format: CaptureFormat::default(),
+            capture_seconds: 10.0,
+            watch: false,
+            keys: KeyConfig::default(),
+        }
+    }
+}
+
+/// Movement keys by name, as `input::parse_key` reads them
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyConfig {
+    pub forward: String,
+    pub back: String,
+    pub strafe_left: String,
+    pub strafe_right: String,
+    pub turn_left: String,
+    pub turn_right: String,
+    pub jump: String,
+    pub crouch: [String; 2],
+    pub activate: String,
+    pub fire: String,
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        let bindings = Bindings::default();
+        Self {
+            forward: key_name(bindings.forward),
+            back: key_name(bindings.back),
+            strafe_left: key_name(bindings.strafe_left),
+            strafe_right: key_name(bindings.strafe_right),
+            turn_left: key_name(bindings.turn_left),
+            turn_right: key_name(bindings.turn_right),
+            jump: key_name(bindings.jump),
+            crouch: bindings.crouch.map(key_name),
+            activate: key_name(bindings.activate),
+            fire: key_name(bindings.fire),
+        }
+    }
+}
+
+impl KeyConfig {
+    /// Set the keyboard half of `bindings`; a name that isn't a key leaves its binding as
+    /// it was
+    pub fn apply(&self, bindings: &mut Bindings) {
+        let bind = |slot: &mut KeyCode, name: &str| match parse_key(name) {
+            Some(key) => *slot = key,
+            None => warn!(target: "input", "{CONFIG_PATH}: unknown key {name:?}"),
+        };
+        bind(&mut bindings.forward, &self.forward);
+        bind(&mut bindings.back, &self.back);
+        bind(&mut bindings.strafe_left, &self.strafe_left);
+        bind(&mut bindings.strafe_right, &self.strafe_right);
+        bind(&mut bindings.turn_left, &self.turn_left);
+        bind(&mut bindings.turn_right, &self.turn_right);
+        bind(&mut bindings.jump, &self.jump);
+        for (slot, name) in bindings.crouch.iter_mut().zip(&self.crouch) {
+            bind(slot, name);
+        }
+        bind(&mut bindings.activate, &self.activate);
+        bind(&mut bindings.fire, &self.fire);
+    }
+}
+
+pub fn load_config(path: impl AsRef<Path>) -> Config {
+    let path = path.as_ref();
+    let Ok(source) = std::fs::read_to_string(path) else {
+        return Config::default();
+    };
+    toml::from_str(&source).unwrap_or_else(|err| {
+        warn!(target: "app", "{}: {err}; using default settings", path.display());
+        Config::default()
+    })
+}
+
+pub fn save_config(path: impl AsRef<Path>, config: &Config) -> std::io::Result<()> {
+    let source = toml::to_string(config).map_err(std::io::Error::other)?;
+    std::fs::write(path, source)
+}
@@ -0,0 +1,106 @@
+// Developer console: a command line across the top of the frame with the last few lines of
+// output above it. It only collects and shows text; what a command does is up to whoever
+// reads the submitted lines.
+
+use std::collections::VecDeque;
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::renderer::{mix, pack_rgb};
+
+// Lines of output kept and shown above the prompt
+const OUTPUT_LINES: usize = 5;
+// Longest command line accepted
+const MAX_INPUT: usize = 60;
+// How far toward black the band behind the text is, 0..=256
+const DIM: u32 = 176;
+
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    output: VecDeque<String>, // oldest first
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Open or close, keeping the output but starting a fresh line
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.input.clear();
+    }
+
+    /// Add typed `text` to the command line; control characters are left out
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if self.input.chars().count() < MAX_INPUT {
+                self.input.push(c);
+            }
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// The command line as entered, echoed to the output and cleared; None if it's blank
+    pub fn submit(&mut self) -> Option<String> {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        self.print(format!("> {line}"));
+        Some(line.to_string())
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == OUTPUT_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+
+    /// Across the top of `buf` over a darkened band, sized like the rest of the HUD
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize) {
+        if !self.open {
+            return;
+        }
+        let scale = (height / 240).max(1);
+        let line = (GLYPH_HEIGHT + 2) * scale;
+        let margin = 2 * scale;
+        let bottom = (2 * margin + (OUTPUT_LINES + 1) * line).min(height);
+        for pixel in &mut buf[..bottom * width] {
+            *pixel = mix(*pixel, 0, DIM);
+        }
+        for (i, text) in self.output.iter().enumerate() {
+            let y = margin + i * line;
+            font::draw_text(
+                buf,
+                width,
+                height,
+                [margin, y],
+                text,
+                pack_rgb(190, 190, 190),
+                scale,
+            );
+        }
+        let prompt = format!("> {}_", self.input);
+        let y = margin + OUTPUT_LINES * line;
+        font::draw_text(
+            buf,
+            width,
+            height,
+            [margin, y],
+            &prompt,
+            pack_rgb(255, 255, 255),
+            scale,
+        );
+    }
+}
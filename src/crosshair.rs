@@ -0,0 +1,123 @@
+// Crosshair at the middle of the player's view, drawn with the HUD over the finished
+// frame, and a hit marker flashing around it for a moment when a shot hits something
+
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{WHITE, mix};
+use crate::viewport::Viewport;
+
+// Seconds a hit marker takes to fade out
+const HIT_MARKER_TIME: f32 = 0.25;
+// Pixels (at 240 lines) between the center and the crosshair's arms
+const GAP: usize = 2;
+// Length of each of the hit marker's four strokes, likewise
+const HIT_MARKER_LENGTH: usize = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrosshairStyle {
+    None, // hidden, though hit markers still show
+    #[default]
+    Cross, // four arms around a gap
+    Dot,
+    Circle,
+}
+
+impl CrosshairStyle {
+    pub fn next(self) -> Self {
+        match self {
+            CrosshairStyle::None => CrosshairStyle::Cross,
+            CrosshairStyle::Cross => CrosshairStyle::Dot,
+            CrosshairStyle::Dot => CrosshairStyle::Circle,
+            CrosshairStyle::Circle => CrosshairStyle::None,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next().next()
+    }
+}
+
+#[derive(Default)]
+pub struct Crosshair {
+    pub style: CrosshairStyle,
+    pub color: u32,
+    pub size: usize, // arm length, dot width or ring radius in pixels at 240 lines
+    hit: f32,        // seconds of hit marker left
+}
+
+impl Crosshair {
+    /// Flash the hit marker, for a shot that hit something
+    pub fn hit(&mut self) {
+        self.hit = HIT_MARKER_TIME;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.hit = (self.hit - dt).max(0.0);
+    }
+
+    /// Centered in `view` of `buf`, a frame `width` pixels wide, scaled with its height
+    /// like the rest of the HUD
+    pub fn draw(&self, buf: &mut [u32], width: usize, view: Viewport) {
+        let scale = (view.height / 240).max(1) as isize;
+        let (cx, cy) = (
+            (view.x + view.width / 2) as isize,
+            (view.y + view.height / 2) as isize,
+        );
+        let mut plot = |x: isize, y: isize, color: u32, amount: u32| {
+            let inside_x = (view.x as isize..(view.x + view.width) as isize).contains(&x);
+            let inside_y = (view.y as isize..(view.y + view.height) as isize).contains(&y);
+            if inside_x && inside_y {
+                let idx = y as usize * width + x as usize;
+                buf[idx] = mix(buf[idx], color, amount);
+            }
+        };
+
+        let size = self.size.max(1) as isize * scale;
+        let gap = GAP as isize * scale;
+        match self.style {
+            CrosshairStyle::None => {}
+            CrosshairStyle::Cross => {
+                for along in gap..gap + size {
+                    for across in 0..scale {
+                        plot(cx + along, cy + across, self.color, 256);
+                        plot(cx - 1 - along, cy + across, self.color, 256);
+                        plot(cx + across, cy + along, self.color, 256);
+                        plot(cx + across, cy - 1 - along, self.color, 256);
+                    }
+                }
+            }
+            CrosshairStyle::Dot => {
+                let half = size / 2;
+                for y in cy - half..=cy + half {
+                    for x in cx - half..=cx + half {
+                        plot(x, y, self.color, 256);
+                    }
+                }
+            }
+            CrosshairStyle::Circle => {
+                // Pixels whose centers lie within half a stroke of the ring
+                let (radius, half_stroke) = (size as f32, 0.5 * scale as f32);
+                for y in cy - size - scale..=cy + size + scale {
+                    for x in cx - size - scale..=cx + size + scale {
+                        let (dx, dy) = ((x - cx) as f32 + 0.5, (y - cy) as f32 + 0.5);
+                        if ((dx * dx + dy * dy).sqrt() - radius).abs() <= half_stroke {
+                            plot(x, y, self.color, 256);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Four diagonal strokes just outside the crosshair, fading out
+        if self.hit > 0.0 {
+            let amount = (self.hit / HIT_MARKER_TIME * 256.0) as u32;
+            let start = gap + size / 2;
+            for along in start..start + HIT_MARKER_LENGTH as isize * scale {
+                for [dx, dy] in [[1, 1], [1, -1], [-1, 1], [-1, -1]] {
+                    plot(cx + dx * along, cy + dy * along, WHITE, amount);
+                }
+            }
+        }
+    }
+}
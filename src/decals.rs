@@ -0,0 +1,136 @@
+// Decals: small textures stuck flat onto wall surfaces, such as bullet holes. Each is
+// kept with the wall it's on and drawn over that wall's columns by the renderer. Only so
+// many are kept, and the least recently placed ones make way for new ones.
+
+use crate::texture::TextureId;
+use crate::world::{Blend, Wall};
+
+pub const DEFAULT_MAX_DECALS: usize = 128;
+
+// Placing a decal this close to an existing one with the same texture, as a fraction of
+// its size, refreshes that one instead of stacking another on top
+const MERGE_DISTANCE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decal {
+    pub u: f32,    // center, in world units along the wall from its start
+    pub z: f32,    // center height
+    pub size: f32, // world units across, square
+    pub texture: TextureId,
+    pub blend: Blend,
+    pub front: bool, // on the wall's front side, else its back (portals have two faces)
+}
+
+pub struct Decals {
+    by_wall: Vec<Vec<Decal>>,
+    stamps: Vec<Vec<u64>>, // when each decal was last placed, parallel to `by_wall`
+    next_stamp: u64,
+    len: usize,
+    max: usize,
+}
+
+impl Decals {
+    /// No decals yet on any of `walls` walls
+    pub fn new(walls: usize) -> Self {
+        Self {
+            by_wall: vec![Vec::new(); walls],
+            stamps: vec![Vec::new(); walls],
+            next_stamp: 0,
+            len: 0,
+            max: DEFAULT_MAX_DECALS,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Keep at most `max` decals, evicting the oldest if there are more already
+    pub fn set_max(&mut self, max: usize) {
+        self.max = max;
+        while self.len > self.max {
+            self.evict_oldest();
+        }
+    }
+
+    /// Decals on wall `wall`, on both of its sides
+    pub fn on_wall(&self, wall: usize) -> &[Decal] {
+        self.by_wall.get(wall).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn clear(&mut self) {
+        for (decals, stamps) in self.by_wall.iter_mut().zip(&mut self.stamps) {
+            decals.clear();
+            stamps.clear();
+        }
+        self.len = 0;
+    }
+
+    /// Stick `decal` onto wall `wall`, making room by removing the least recently placed
+    /// decal if already at the limit
+    pub fn place(&mut self, wall: usize, decal: Decal) {
+        if self.max == 0 {
+            return;
+        }
+        if wall >= self.by_wall.len() {
+            self.by_wall.resize_with(wall + 1, Vec::new);
+            self.stamps.resize_with(wall + 1, Vec::new);
+        }
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+
+        let near = self.by_wall[wall].iter().position(|d| {
+            let reach = MERGE_DISTANCE * d.size.max(decal.size);
+            d.texture == decal.texture
+                && d.front == decal.front
+                && (d.u - decal.u).abs() < reach
+                && (d.z - decal.z).abs() < reach
+        });
+        if let Some(i) = near {
+            self.by_wall[wall][i] = decal;
+            self.stamps[wall][i] = stamp;
+            return;
+        }
+
+        if self.len >= self.max {
+            self.evict_oldest();
+        }
+        self.by_wall[wall].push(decal);
+        self.stamps[wall].push(stamp);
+        self.len += 1;
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .stamps
+            .iter()
+            .enumerate()
+            .flat_map(|(w, stamps)| stamps.iter().enumerate().map(move |(i, &s)| (s, w, i)))
+            .min();
+        if let Some((_, wall, i)) = oldest {
+            self.by_wall[wall].swap_remove(i);
+            self.stamps[wall].swap_remove(i);
+            self.len -= 1;
+        }
+    }
+}
+
+/// Where along `wall` the point `p` lies, in world units from its start, and whether it's
+/// on the front side; for placing a decal where a ray from `from` struck the wall at `p`
+pub fn wall_position(wall: &Wall, from: [f32; 2], p: [f32; 2]) -> (f32, bool) {
+    let ex = wall.end[0] - wall.start[0];
+    let ey = wall.end[1] - wall.start[1];
+    let len = (ex * ex + ey * ey).sqrt().max(f32::EPSILON);
+    let u = ((p[0] - wall.start[0]) * ex + (p[1] - wall.start[1]) * ey) / len;
+    // Front sector is on the left of start->end
+    let front = ex * (from[1] - wall.start[1]) - ey * (from[0] - wall.start[0]) >= 0.0;
+    (u, front)
+}
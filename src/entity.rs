@@ -0,0 +1,429 @@
+// Game objects as entities with optional components, stored column-wise and indexed by
+// `EntityId`. Systems are plain functions that walk the columns each tick.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::AiState;
+use crate::collision;
+use crate::events::Event;
+use crate::inventory::Pickup;
+use crate::rng::Rng;
+use crate::texture::{Rotations, TextureId};
+use crate::voxel::ModelId;
+use crate::world::{Blend, World};
+
+pub type EntityId = usize;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Transform {
+    pub pos: [f32; 2],
+    pub z: f32, // world z of the bottom
+    #[serde(default)] // absent from saves made before entities faced anywhere
+    pub yaw: f32, // radians, which way it faces, measured like the camera's
+}
+
+/// Drawn as a camera-facing billboard, showing the side of the entity that faces the camera
+/// if it has `rotations`, or as a voxel model `height` tall if it has a `model`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Sprite {
+    pub texture: TextureId, // sizes the sprite, and drawn if there are no rotations
+    pub height: f32,        // world units tall at scale 1.0, width follows the texture aspect
+    pub scale: f32,
+    #[serde(default)]
+    pub rotations: Option<Rotations>,
+    #[serde(default)]
+    pub model: Option<ModelId>, // into `World::models`
+    #[serde(default)]
+    pub blend: Blend, // e.g. for ghosts and fire, drawn back to front with everything see-through
+}
+
+impl Sprite {
+    #[inline]
+    pub fn world_height(&self) -> f32 {
+        self.height * self.scale
+    }
+
+    /// The texture for an entity at `transform` seen from `viewer`: the rotation frame
+    /// nearest the side it shows, or `texture` without rotations
+    pub fn texture_from(&self, transform: &Transform, viewer: [f32; 2]) -> TextureId {
+        let Some(rotations) = self.rotations else {
+            return self.texture;
+        };
+        let view = (viewer[0] - transform.pos[0]).atan2(viewer[1] - transform.pos[1]);
+        let step = std::f32::consts::TAU / rotations.len() as f32;
+        let frame = ((transform.yaw - view) / step).round() as i32;
+        rotations[frame.rem_euclid(rotations.len() as i32) as usize]
+    }
+}
+
+/// Something that can be shot to pieces, like a barrel
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Prop {
+    pub health: u32,               // 0 once destroyed
+    pub solid: bool,               // blocks movement within its collider radius while intact
+    pub broken: Option<TextureId>, // sprite left behind when destroyed; None removes it
+}
+
+impl Prop {
+    #[inline]
+    pub fn is_destroyed(&self) -> bool {
+        self.health == 0
+    }
+}
+
+/// What an entity does on its own each tick
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Behavior {
+    #[default]
+    None,
+    Bob {
+        amplitude: f32, // world units above and below the spawn height
+        speed: f32,     // radians per second
+    },
+    Wander {
+        speed: f32, // world units per second, picking a new heading every few seconds
+    },
+    /// Chase and attack the player once seen; driven by `ai::update`
+    Hunt {
+        speed: f32,
+        sight_range: f32,
+        attack_range: f32, // from the entity's center
+    },
+    /// Despawn after this long, for short-lived effects like bullet puffs
+    Expire { seconds: f32 },
+    /// Shuttle between where it spawned and `to`, waiting `wait` seconds at each end. Solid
+    /// whether or not it's a prop, and carries whatever stands on its top.
+    Platform {
+        to: [f32; 2],
+        speed: f32, // world units per second
+        wait: f32,
+    },
+}
+
+// Per-entity runtime state for behaviors
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+struct Brain {
+    home_z: f32,
+    phase: f32,
+    timer: f32,
+    #[serde(default)] // absent from saves made before there were platforms
+    home: [f32; 2],
+}
+
+#[derive(Default)]
+pub struct Entities {
+    alive: Vec<bool>,
+    free: Vec<EntityId>,
+    brains: Vec<Brain>,
+    pub transforms: Vec<Transform>,
+    pub velocities: Vec<[f32; 2]>,
+    pub sprites: Vec<Option<Sprite>>,
+    pub colliders: Vec<Option<f32>>, // radius against walls
+    pub behaviors: Vec<Behavior>,
+    pub ai: Vec<AiState>, // used by `Behavior::Hunt`
+    pub props: Vec<Option<Prop>>,
+    pub pickups: Vec<Option<Pickup>>, // taken by touching, see `inventory::collect`
+}
+
+impl Entities {
+    /// New entity at `transform` with no other components; slots of despawned entities are reused
+    pub fn spawn(&mut self, transform: Transform) -> EntityId {
+        let brain = Brain {
+            home_z: transform.z,
+            home: transform.pos,
+            ..Brain::default()
+        };
+        if let Some(id) = self.free.pop() {
+            self.alive[id] = true;
+            self.brains[id] = brain;
+            self.transforms[id] = transform;
+            self.velocities[id] = [0.0, 0.0];
+            self.sprites[id] = None;
+            self.colliders[id] = None;
+            self.behaviors[id] = Behavior::None;
+            self.ai[id] = AiState::default();
+            self.props[id] = None;
+            self.pickups[id] = None;
+            return id;
+        }
+
+        let id = self.alive.len();
+        self.alive.push(true);
+        self.brains.push(brain);
+        self.transforms.push(transform);
+        self.velocities.push([0.0, 0.0]);
+        self.sprites.push(None);
+        self.colliders.push(None);
+        self.behaviors.push(Behavior::None);
+        self.ai.push(AiState::default());
+        self.props.push(None);
+        self.pickups.push(None);
+        id
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        if self.alive[id] {
+            self.alive[id] = false;
+            self.free.push(id);
+        }
+    }
+
+    #[inline]
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.alive.get(id).copied().unwrap_or(false)
+    }
+
+    /// Live entity ids
+    pub fn ids(&self) -> impl Iterator<Item = EntityId> + Clone + '_ {
+        (0..self.alive.len()).filter(|&id| self.alive[id])
+    }
+
+    /// Live entities that have a sprite, with their transform
+    pub fn visible(&self) -> impl Iterator<Item = (EntityId, &Transform, &Sprite)> + '_ {
+        self.ids()
+            .filter_map(|id| Some((id, &self.transforms[id], self.sprites[id].as_ref()?)))
+    }
+}
+
+/// One live entity with all its components, as stored in a save game
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityRecord {
+    id: EntityId,
+    transform: Transform,
+    velocity: [f32; 2],
+    sprite: Option<Sprite>,
+    collider: Option<f32>,
+    behavior: Behavior,
+    brain: Brain,
+    ai: AiState,
+    #[serde(default)] // absent from saves made before there were props
+    prop: Option<Prop>,
+    #[serde(default)] // absent from saves made before there were pickups
+    pickup: Option<Pickup>,
+}
+
+impl Entities {
+    /// Every live entity, ids included so they mean the same after `from_records`
+    pub fn records(&self) -> Vec<EntityRecord> {
+        self.ids()
+            .map(|id| EntityRecord {
+                id,
+                transform: self.transforms[id],
+                velocity: self.velocities[id],
+                sprite: self.sprites[id],
+                collider: self.colliders[id],
+                behavior: self.behaviors[id],
+                brain: self.brains[id],
+                ai: self.ai[id],
+                prop: self.props[id],
+                pickup: self.pickups[id],
+            })
+            .collect()
+    }
+
+    /// Rebuild from `records`; ids between them become free slots
+    pub fn from_records(records: Vec<EntityRecord>) -> Self {
+        let len = records.iter().map(|r| r.id + 1).max().unwrap_or(0);
+        let mut entities = Entities {
+            alive: vec![false; len],
+            free: Vec::new(),
+            brains: vec![Brain::default(); len],
+            transforms: vec![
+                Transform {
+                    pos: [0.0, 0.0],
+                    z: 0.0,
+                    yaw: 0.0,
+                };
+                len
+            ],
+            velocities: vec![[0.0, 0.0]; len],
+            sprites: vec![None; len],
+            colliders: vec![None; len],
+            behaviors: vec![Behavior::None; len],
+            ai: vec![AiState::default(); len],
+            props: vec![None; len],
+            pickups: vec![None; len],
+        };
+        for r in records {
+            entities.alive[r.id] = true;
+            entities.brains[r.id] = r.brain;
+            entities.transforms[r.id] = r.transform;
+            entities.velocities[r.id] = r.velocity;
+            entities.sprites[r.id] = r.sprite;
+            entities.colliders[r.id] = r.collider;
+            entities.behaviors[r.id] = r.behavior;
+            entities.ai[r.id] = r.ai;
+            entities.props[r.id] = r.prop;
+            entities.pickups[r.id] = r.pickup;
+        }
+        entities.free = (0..len).rev().filter(|&id| !entities.alive[id]).collect();
+        entities
+    }
+}
+
+/// Take `amount` of damage on entity `id` if it's an intact prop, publishing
+/// `Event::EntityDamaged`; returns true if that destroyed it, leaving its broken sprite
+/// (if any) and a burst of debris
+pub fn damage(world: &mut World, id: EntityId, amount: u32) -> bool {
+    let entities = &mut world.entities;
+    let Some(prop) = entities.props.get_mut(id).and_then(Option::as_mut) else {
+        return false;
+    };
+    if prop.is_destroyed() {
+        return false;
+    }
+    prop.health = prop.health.saturating_sub(amount);
+    let destroyed = prop.is_destroyed();
+    let broken = prop.broken;
+    let transform = entities.transforms[id];
+    world.events.publish(Event::EntityDamaged {
+        entity: id,
+        pos: transform.pos,
+        amount,
+        destroyed,
+    });
+    if !destroyed {
+        return false;
+    }
+
+    debris(world, id);
+    let entities = &mut world.entities;
+    match (broken, entities.sprites[id].as_mut()) {
+        (Some(texture), Some(sprite)) => {
+            sprite.texture = texture;
+            sprite.rotations = None;
+            sprite.model = None;
+        }
+        _ => entities.despawn(id),
+    }
+    true
+}
+
+/// Despawn entity `id` in a burst of debris, as when a crusher comes down on it
+pub fn squash(world: &mut World, id: EntityId) {
+    debris(world, id);
+    world.entities.despawn(id);
+}
+
+// Pieces of entity `id` flying from its middle, colored like its sprite
+fn debris(world: &mut World, id: EntityId) {
+    let transform = world.entities.transforms[id];
+    let sprite = world.entities.sprites[id];
+    let height = sprite.map_or(0.5, |s| s.world_height());
+    let color = sprite.map_or(0x808080, |s| world.textures[s.texture].average());
+    let (floor_z, light) = world
+        .sector_at(transform.pos)
+        .map_or((transform.z, 1.0), |s| {
+            (world.sectors[s].floor_z, world.sectors[s].light_level)
+        });
+    world.particles.debris(
+        transform.pos,
+        transform.z + 0.5 * height,
+        color,
+        floor_z,
+        light,
+    );
+}
+
+/// Run behaviors, then move entities by their velocity, sliding along walls if they collide
+pub fn update(world: &mut World, dt: f32) {
+    // Systems read the world's walls while writing entities
+    let mut entities = std::mem::take(&mut world.entities);
+    behavior_system(&mut entities, &mut world.rng, dt);
+    movement_system(&mut entities, world, dt);
+    world.entities = entities;
+}
+
+fn behavior_system(entities: &mut Entities, rng: &mut Rng, dt: f32) {
+    for id in 0..entities.alive.len() {
+        if !entities.alive[id] {
+            continue;
+        }
+        let brain = &mut entities.brains[id];
+        match entities.behaviors[id] {
+            Behavior::None | Behavior::Hunt { .. } => {}
+            Behavior::Bob { amplitude, speed } => {
+                brain.phase += speed * dt;
+                entities.transforms[id].z = brain.home_z + amplitude * brain.phase.sin();
+            }
+            Behavior::Wander { speed } => {
+                brain.timer -= dt;
+                if brain.timer <= 0.0 {
+                    let heading = rng.unit() * std::f32::consts::TAU;
+                    entities.velocities[id] = [heading.sin() * speed, heading.cos() * speed];
+                    brain.timer = rng.range(1.5, 3.5);
+                }
+            }
+            Behavior::Expire { seconds } => {
+                brain.timer += dt;
+                if brain.timer >= seconds {
+                    entities.despawn(id);
+                }
+            }
+            Behavior::Platform { to, speed, wait } => {
+                // `phase` runs 0..1 out to `to` and 1..2 back home
+                if brain.timer > 0.0 {
+                    brain.timer -= dt;
+                    continue;
+                }
+                let (dx, dy) = (to[0] - brain.home[0], to[1] - brain.home[1]);
+                let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+                let before = brain.phase;
+                brain.phase += speed * dt / length;
+                if before < 1.0 && brain.phase >= 1.0 {
+                    brain.phase = 1.0;
+                    brain.timer = wait;
+                } else if brain.phase >= 2.0 {
+                    brain.phase = 0.0;
+                    brain.timer = wait;
+                }
+                let along = 1.0 - (1.0 - brain.phase).abs();
+                entities.transforms[id].pos =
+                    [brain.home[0] + dx * along, brain.home[1] + dy * along];
+            }
+        }
+    }
+}
+
+fn movement_system(entities: &mut Entities, world: &mut World, dt: f32) {
+    // As they stood at the start of the tick, since moving mutates `entities`
+    let obstacles: Vec<_> = collision::obstacles(entities).collect();
+    for id in 0..entities.alive.len() {
+        let v = entities.velocities[id];
+        if !entities.alive[id] || v == [0.0, 0.0] {
+            continue;
+        }
+        let t = &mut entities.transforms[id];
+        t.yaw = v[0].atan2(v[1]); // facing the way it's going
+        let delta = [v[0] * dt, v[1] * dt];
+        let from = t.pos;
+        t.pos = match entities.colliders[id] {
+            Some(radius) => {
+                let height = entities.sprites[id].map_or(radius * 2.0, |s| s.world_height());
+                let others = obstacles.iter().filter(|o| o.entity != id).copied();
+                collision::slide_move_among(world, others, t.pos, delta, radius, t.z, height)
+            }
+            None => [t.pos[0] + delta[0], t.pos[1] + delta[1]],
+        };
+
+        // Teleporters send things on just as they do the player, as high above the floor
+        let floor_at = |world: &World, pos| world.sector_at(pos).map(|s| world.sectors[s].floor_z);
+        let entered = world
+            .sector_at(t.pos)
+            .filter(|&s| world.sector_at(from) != Some(s));
+        let teleport = world
+            .crossed_teleport(from, t.pos)
+            .or_else(|| entered.and_then(|s| world.sectors[s].teleport()));
+        if let Some(teleport) = teleport
+            && let Some(to) = world.teleport(teleport, t.pos, Some(id))
+        {
+            let rise =
+                floor_at(world, to.pos).unwrap_or(0.0) - floor_at(world, t.pos).unwrap_or(0.0);
+            t.pos = to.pos;
+            t.z += rise;
+            entities.brains[id].home_z += rise;
+            entities.velocities[id] = teleport.arrival_velocity(v, t.yaw, to.yaw);
+            t.yaw = to.yaw;
+        }
+    }
+}
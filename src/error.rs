@@ -0,0 +1,51 @@
+// Failures from the platform the engine runs on: the window, its surfaces and the event
+// loop. Most are worked around where they happen; only those at startup end the process.
+
+use std::fmt;
+
+use crate::gpu::GpuError;
+
+#[derive(Debug)]
+pub enum EngineError {
+    Window(winit::error::OsError),
+    EventLoop(winit::error::EventLoopError),
+    Surface(softbuffer::SoftBufferError), // from the software backend's window surface
+    Gpu(GpuError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Window(err) => write!(f, "could not open a window: {err}"),
+            EngineError::EventLoop(err) => write!(f, "event loop failed: {err}"),
+            EngineError::Surface(err) => write!(f, "window surface failed: {err}"),
+            EngineError::Gpu(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<winit::error::OsError> for EngineError {
+    fn from(err: winit::error::OsError) -> Self {
+        EngineError::Window(err)
+    }
+}
+
+impl From<winit::error::EventLoopError> for EngineError {
+    fn from(err: winit::error::EventLoopError) -> Self {
+        EngineError::EventLoop(err)
+    }
+}
+
+impl From<softbuffer::SoftBufferError> for EngineError {
+    fn from(err: softbuffer::SoftBufferError) -> Self {
+        EngineError::Surface(err)
+    }
+}
+
+impl From<GpuError> for EngineError {
+    fn from(err: GpuError) -> Self {
+        EngineError::Gpu(err)
+    }
+}
@@ -0,0 +1,68 @@
+// Events between subsystems: whatever happens during a tick is published to the world's
+// queue, and the game hands the lot to each subsystem that cares at the start of the next
+// one, so the publishers don't need to know who listens.
+
+use crate::entity::EntityId;
+use crate::inventory::{Key, Pickup};
+use crate::world::Special;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    /// A door, switch or exit was used
+    WallUsed {
+        wall: usize,
+        tag: u32,
+        special: Special,
+    },
+    /// The player walked across a walk-over line
+    LineCrossed {
+        wall: usize,
+        tag: u32,
+        special: Special,
+    },
+    /// A locked door was used without its key
+    DoorLocked { wall: usize, key: Key },
+    /// The player took a pickup, which is gone from where it was at `pos`
+    PickedUp {
+        entity: EntityId,
+        pos: [f32; 2],
+        pickup: Pickup,
+    },
+    /// The player moved into another sector, or appeared in one
+    SectorEntered { sector: usize },
+    /// A prop was shot, `destroyed` if that broke it; `pos` is where it stood
+    EntityDamaged {
+        entity: EntityId,
+        pos: [f32; 2],
+        amount: u32,
+        destroyed: bool,
+    },
+    /// The player was hurt, `killed` if that was the end of them
+    PlayerDamaged { amount: f32, killed: bool },
+    /// The player (`entity` None) or an entity was teleported, with a `flash` if the
+    /// teleporter makes one
+    Teleported {
+        entity: Option<EntityId>,
+        from: [f32; 2],
+        to: [f32; 2],
+        flash: bool,
+    },
+}
+
+/// Events published this tick, waiting to be taken at the start of the next
+#[derive(Default)]
+pub struct EventQueue {
+    pending: Vec<Event>,
+}
+
+impl EventQueue {
+    pub fn publish(&mut self, event: Event) {
+        self.pending.push(event);
+    }
+
+    /// Everything published since the last take, oldest first; what's published from here
+    /// on waits for the next
+    pub fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.pending)
+    }
+}
@@ -0,0 +1,17 @@
+// Importers for files made by other engines' editors: maps, each producing a `World`, and
+// voxel models
+
+use crate::renderer::pack_rgb;
+
+pub mod build;
+pub mod udmf;
+pub mod vox;
+
+// Two colors standing in for a texture the importer has no image for, picked by `key`
+// (a tile number or a hashed name) so they're the same every load
+fn stand_in_colors(key: u32) -> (u32, u32) {
+    let h = key.wrapping_mul(0x9E37_79B9);
+    let channel = |shift: u32| 80 + ((h >> shift) & 0x7F) as u8;
+    let (r, g, b) = (channel(0), channel(8), channel(16));
+    (pack_rgb(r, g, b), pack_rgb(r - 30, g - 30, b - 30))
+}
@@ -0,0 +1,528 @@
+// Build engine (Duke Nukem 3D) `.MAP` files, version 7: sectors, walls and sprites mapped
+// onto the engine's own types.
+//
+// Build measures x/y in units of which `UNITS_PER_WORLD` make one world unit, z sixteen
+// times finer and pointing down, and y pointing south; all three are converted here. The
+// engine has no slopes, so a sloped floor or ceiling is flattened to its average height
+// over the sector's corners. Without the game's ART files every tile number gets its own
+// checkerboard, and sprites become discs.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use super::stand_in_colors;
+use crate::entity::Behavior;
+use crate::renderer::WHITE;
+use crate::texture::{Texture, TextureId};
+use crate::world::{Blend, Material, PlayerStart, Sector, Special, Thing, Wall, World};
+
+const VERSION: i32 = 7;
+
+// Build x/y units per world unit; puts Duke's eyes near the engine's standing eye height
+const UNITS_PER_WORLD: f32 = 384.0;
+// Build z units per x/y unit
+const Z_SCALE: f32 = 16.0;
+
+// Record sizes in bytes
+const SECTOR_SIZE: usize = 40;
+const WALL_SIZE: usize = 32;
+const SPRITE_SIZE: usize = 44;
+
+// Sector stat bits
+const STAT_SLOPED: i16 = 1 << 1;
+// Wall cstat bit for textures aligned to the floor instead of the ceiling
+const CSTAT_ALIGN_BOTTOM: i16 = 1 << 2;
+// Sprite cstat bit for sprites the game never draws (effectors and other markers)
+const CSTAT_INVISIBLE: i16 = 1 << 15;
+// Shade at which a surface is drawn black
+const FULL_SHADE: f32 = 32.0;
+// Tile height assumed for sprites, in texels; the real one lives in the ART files
+const TILE_HEIGHT: f32 = 64.0;
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io(std::io::Error),
+    Truncated,    // ended inside a record
+    Version(i32), // not a version 7 map
+    Invalid(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io(err) => write!(f, "could not read Build map: {err}"),
+            BuildError::Truncated => write!(f, "Build map ends early"),
+            BuildError::Version(v) => write!(f, "Build map version {v}, only {VERSION} is read"),
+            BuildError::Invalid(message) => write!(f, "invalid Build map: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<std::io::Error> for BuildError {
+    fn from(err: std::io::Error) -> Self {
+        BuildError::Io(err)
+    }
+}
+
+struct BuildSector {
+    wallptr: usize,
+    wallnum: usize,
+    ceilingz: i32,
+    floorz: i32,
+    ceilingstat: i16,
+    floorstat: i16,
+    ceilingpicnum: i16,
+    ceilingheinum: i16,
+    ceilingshade: i8,
+    floorpicnum: i16,
+    floorheinum: i16,
+    floorshade: i8,
+}
+
+struct BuildWall {
+    x: i32,
+    y: i32,
+    point2: usize,
+    nextwall: i16,
+    nextsector: i16,
+    cstat: i16,
+    picnum: i16,
+}
+
+struct BuildSprite {
+    x: i32,
+    y: i32,
+    z: i32,
+    cstat: i16,
+    picnum: i16,
+    yrepeat: u8,
+    ang: i16,
+}
+
+pub fn load_map(path: impl AsRef<Path>) -> Result<World, BuildError> {
+    let bytes = std::fs::read(path)?;
+    parse_map(&bytes)
+}
+
+pub fn parse_map(bytes: &[u8]) -> Result<World, BuildError> {
+    let mut r = Reader { bytes, at: 0 };
+    let version = r.i32()?;
+    if version != VERSION {
+        return Err(BuildError::Version(version));
+    }
+    let start = [r.i32()?, r.i32()?];
+    let _start_z = r.i32()?;
+    let start_angle = r.i16()?;
+    let _start_sector = r.i16()?;
+
+    let sector_count = r.u16()? as usize;
+    r.need(sector_count * SECTOR_SIZE)?;
+    let build_sectors: Vec<BuildSector> = (0..sector_count)
+        .map(|_| r.sector())
+        .collect::<Result<_, _>>()?;
+    let wall_count = r.u16()? as usize;
+    r.need(wall_count * WALL_SIZE)?;
+    let build_walls: Vec<BuildWall> = (0..wall_count)
+        .map(|_| r.wall())
+        .collect::<Result<_, _>>()?;
+    let sprite_count = r.u16()? as usize;
+    r.need(sprite_count * SPRITE_SIZE)?;
+    let build_sprites: Vec<BuildSprite> = (0..sprite_count)
+        .map(|_| r.sprite())
+        .collect::<Result<_, _>>()?;
+
+    if build_sectors.is_empty() {
+        return Err(BuildError::Invalid("no sectors".to_string()));
+    }
+    for (i, s) in build_sectors.iter().enumerate() {
+        if s.wallnum < 3 || s.wallptr + s.wallnum > wall_count {
+            return Err(BuildError::Invalid(format!(
+                "sector {i} has walls {}..{}, but the map has {wall_count}",
+                s.wallptr,
+                s.wallptr + s.wallnum
+            )));
+        }
+    }
+    for (i, w) in build_walls.iter().enumerate() {
+        if w.point2 >= wall_count || w.nextsector as isize >= sector_count as isize {
+            return Err(BuildError::Invalid(format!(
+                "wall {i} links past the end of the map"
+            )));
+        }
+    }
+
+    // One checkerboard or disc per tile number
+    let mut textures = Vec::new();
+    let mut wall_tiles = HashMap::new();
+    let mut wall_texture = |picnum: i16, textures: &mut Vec<Texture>| -> TextureId {
+        *wall_tiles.entry(picnum).or_insert_with(|| {
+            let (a, b) = stand_in_colors(picnum as u32);
+            textures.push(Texture::checkerboard(64, 16, a, b));
+            textures.len() - 1
+        })
+    };
+
+    let mut sectors = Vec::with_capacity(sector_count);
+    let mut walls = Vec::with_capacity(wall_count);
+    for (s, bs) in build_sectors.iter().enumerate() {
+        let corners = &build_walls[bs.wallptr..bs.wallptr + bs.wallnum];
+        let floor = flat_height(bs.floorz, bs.floorstat, bs.floorheinum, bs, &build_walls);
+        let ceiling = flat_height(
+            bs.ceilingz,
+            bs.ceilingstat,
+            bs.ceilingheinum,
+            bs,
+            &build_walls,
+        );
+        let shade = 0.5 * (bs.floorshade as f32 + bs.ceilingshade as f32);
+        sectors.push(Sector {
+            floor_z: floor,
+            ceiling_z: ceiling.max(floor),
+            floor_color: stand_in_colors(bs.floorpicnum as u32).0,
+            ceiling_color: stand_in_colors(bs.ceilingpicnum as u32).1,
+            floor_texture: None,
+            ceiling_texture: None,
+            flat_offset: [0.0, 0.0],
+            flat_angle: 0.0,
+            flat_scroll: [0.0, 0.0],
+            slab: None,
+            light_level: (1.0 - shade / FULL_SHADE).clamp(0.1, 1.0),
+            light_color: WHITE,
+            liquid: None,
+            material: Material::default(),
+            specials: Vec::new(),
+        });
+
+        for (i, w) in corners.iter().enumerate() {
+            let index = bs.wallptr + i;
+            // A two-sided line is stored once from each side; keep one of the pair
+            if w.nextwall >= 0 && (w.nextwall as usize) < index {
+                continue;
+            }
+            let next = &build_walls[w.point2];
+            // Build loops run clockwise around their sector as seen from above, leaving it
+            // on the right; reversed, it's on the left where the engine wants the front
+            walls.push(Wall {
+                start: to_world(next.x, next.y),
+                end: to_world(w.x, w.y),
+                front_sector: s,
+                back_sector: (w.nextsector >= 0).then_some(w.nextsector as usize),
+                texture: wall_texture(w.picnum, &mut textures),
+                tag: 0,
+                special: Special::None,
+                mid: None,
+                scroll: [0.0, 0.0],
+                offset: [0.0, 0.0],
+                peg_upper: false,
+                peg_lower: w.cstat & CSTAT_ALIGN_BOTTOM != 0,
+                teleport: None,
+                walkover: None,
+            });
+        }
+    }
+
+    let mut sprite_tiles: HashMap<i16, TextureId> = HashMap::new();
+    let things = build_sprites
+        .iter()
+        .filter(|sp| sp.cstat & CSTAT_INVISIBLE == 0 && sp.yrepeat > 0)
+        .map(|sp| {
+            let texture = *sprite_tiles.entry(sp.picnum).or_insert_with(|| {
+                let (a, b) = stand_in_colors(sp.picnum as u32);
+                textures.push(Texture::disc(32, a, b));
+                textures.len() - 1
+            });
+            Thing {
+                pos: to_world(sp.x, sp.y),
+                z: to_world_z(sp.z),
+                // Build sprites are tile height * yrepeat / 4 units tall
+                height: TILE_HEIGHT * sp.yrepeat as f32 / 4.0 / UNITS_PER_WORLD,
+                texture,
+                scale: 1.0,
+                radius: 0.0,
+                behavior: Behavior::None,
+                prop: None,
+                pickup: None,
+                // As the player start's angle
+                yaw: std::f32::consts::FRAC_PI_2 + sp.ang as f32 * std::f32::consts::TAU / 2048.0,
+                rotations: None,
+                model: None,
+                blend: Blend::Masked,
+            }
+        })
+        .collect();
+
+    // Angle 0 faces east and turns clockwise on the map, in 2048ths of a turn
+    let angle = start_angle as f32 * std::f32::consts::TAU / 2048.0;
+    let player_start = PlayerStart {
+        pos: to_world(start[0], start[1]),
+        yaw: std::f32::consts::FRAC_PI_2 + angle,
+    };
+
+    Ok(World::new(
+        sectors,
+        walls,
+        textures,
+        things,
+        player_start,
+        Vec::new(),
+    ))
+}
+
+#[inline]
+fn to_world(x: i32, y: i32) -> [f32; 2] {
+    [x as f32 / UNITS_PER_WORLD, -y as f32 / UNITS_PER_WORLD]
+}
+
+#[inline]
+fn to_world_z(z: i32) -> f32 {
+    -z as f32 / (Z_SCALE * UNITS_PER_WORLD)
+}
+
+// World height of a floor or ceiling, averaged over the sector's corners if it's sloped.
+// Build slopes rise `heinum / 256` z units per x/y unit away from the sector's first wall.
+fn flat_height(z: i32, stat: i16, heinum: i16, sector: &BuildSector, walls: &[BuildWall]) -> f32 {
+    if stat & STAT_SLOPED == 0 || heinum == 0 {
+        return to_world_z(z);
+    }
+    let first = &walls[sector.wallptr];
+    let second = &walls[first.point2];
+    let (dx, dy) = ((second.x - first.x) as f32, (second.y - first.y) as f32);
+    let len = (dx * dx + dy * dy).sqrt().max(1.0);
+    let corners = &walls[sector.wallptr..sector.wallptr + sector.wallnum];
+    let sum: f32 = corners
+        .iter()
+        .map(|w| {
+            let dist = (dx * (w.y - first.y) as f32 - dy * (w.x - first.x) as f32) / len;
+            z as f32 + heinum as f32 * dist / 256.0
+        })
+        .sum();
+    -sum / corners.len() as f32 / (Z_SCALE * UNITS_PER_WORLD)
+}
+
+// Little-endian reads over the file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    // Fail early on a count that runs past the end, before allocating for it
+    fn need(&self, len: usize) -> Result<(), BuildError> {
+        if self.at + len > self.bytes.len() {
+            return Err(BuildError::Truncated);
+        }
+        Ok(())
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], BuildError> {
+        self.need(N)?;
+        let mut out = [0; N];
+        out.copy_from_slice(&self.bytes[self.at..self.at + N]);
+        self.at += N;
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8, BuildError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, BuildError> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> Result<u16, BuildError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, BuildError> {
+        Ok(i16::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, BuildError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), BuildError> {
+        self.need(len)?;
+        self.at += len;
+        Ok(())
+    }
+
+    fn sector(&mut self) -> Result<BuildSector, BuildError> {
+        let wallptr = self.i16()?.max(0) as usize;
+        let wallnum = self.i16()?.max(0) as usize;
+        let ceilingz = self.i32()?;
+        let floorz = self.i32()?;
+        let ceilingstat = self.i16()?;
+        let floorstat = self.i16()?;
+        let ceilingpicnum = self.i16()?;
+        let ceilingheinum = self.i16()?;
+        let ceilingshade = self.i8()?;
+        self.skip(3)?; // palette, x and y panning
+        let floorpicnum = self.i16()?;
+        let floorheinum = self.i16()?;
+        let floorshade = self.i8()?;
+        self.skip(3 + 2 + 6)?; // palette and panning, visibility and filler, tags and extra
+        Ok(BuildSector {
+            wallptr,
+            wallnum,
+            ceilingz,
+            floorz,
+            ceilingstat,
+            floorstat,
+            ceilingpicnum,
+            ceilingheinum,
+            ceilingshade,
+            floorpicnum,
+            floorheinum,
+            floorshade,
+        })
+    }
+
+    fn wall(&mut self) -> Result<BuildWall, BuildError> {
+        let x = self.i32()?;
+        let y = self.i32()?;
+        let point2 = self.i16()?.max(0) as usize;
+        let nextwall = self.i16()?;
+        let nextsector = self.i16()?;
+        let cstat = self.i16()?;
+        let picnum = self.i16()?;
+        self.skip(2 + 6 + 6)?; // overpicnum, shade to panning, tags and extra
+        Ok(BuildWall {
+            x,
+            y,
+            point2,
+            nextwall,
+            nextsector,
+            cstat,
+            picnum,
+        })
+    }
+
+    fn sprite(&mut self) -> Result<BuildSprite, BuildError> {
+        let x = self.i32()?;
+        let y = self.i32()?;
+        let z = self.i32()?;
+        let cstat = self.i16()?;
+        let picnum = self.i16()?;
+        self.skip(4)?; // shade, palette, clip distance, filler
+        let _xrepeat = self.u8()?;
+        let yrepeat = self.u8()?;
+        self.skip(2 + 4)?; // offsets, sector and status
+        let ang = self.i16()?;
+        self.skip(14)?; // owner through extra
+        Ok(BuildSprite {
+            x,
+            y,
+            z,
+            cstat,
+            picnum,
+            yrepeat,
+            ang,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Build x/y units per world unit and z units per world unit
+    const U: i32 = UNITS_PER_WORLD as i32;
+    const UZ: i32 = U * Z_SCALE as i32;
+
+    fn sector(out: &mut Vec<u8>, wallptr: i16, floorz: i32, ceilingz: i32) {
+        out.extend(wallptr.to_le_bytes());
+        out.extend(4i16.to_le_bytes()); // wallnum
+        out.extend(ceilingz.to_le_bytes());
+        out.extend(floorz.to_le_bytes());
+        out.resize(out.len() + SECTOR_SIZE - 12, 0);
+    }
+
+    fn wall(out: &mut Vec<u8>, [x, y]: [i32; 2], point2: i16, next: Option<(i16, i16)>) {
+        let (nextwall, nextsector) = next.unwrap_or((-1, -1));
+        out.extend(x.to_le_bytes());
+        out.extend(y.to_le_bytes());
+        out.extend(point2.to_le_bytes());
+        out.extend(nextwall.to_le_bytes());
+        out.extend(nextsector.to_le_bytes());
+        out.resize(out.len() + WALL_SIZE - 14, 0);
+    }
+
+    // Two square rooms side by side, the east one's floor a step up, sharing one line
+    fn two_rooms() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(VERSION.to_le_bytes());
+        out.extend([U / 2, U / 2, 0].into_iter().flat_map(i32::to_le_bytes));
+        out.extend(512i16.to_le_bytes()); // facing south
+        out.extend(0i16.to_le_bytes());
+
+        out.extend(2u16.to_le_bytes());
+        sector(&mut out, 0, 0, -3 * UZ);
+        sector(&mut out, 4, -UZ / 2, -3 * UZ);
+
+        // Clockwise as seen from above, with y pointing south
+        out.extend(8u16.to_le_bytes());
+        wall(&mut out, [0, 0], 1, None);
+        wall(&mut out, [U, 0], 2, Some((7, 1)));
+        wall(&mut out, [U, U], 3, None);
+        wall(&mut out, [0, U], 0, None);
+        wall(&mut out, [U, 0], 5, None);
+        wall(&mut out, [2 * U, 0], 6, None);
+        wall(&mut out, [2 * U, U], 7, None);
+        wall(&mut out, [U, U], 4, Some((1, 0)));
+
+        out.extend(0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn reads_sectors_and_walls() {
+        let world = parse_map(&two_rooms()).unwrap();
+        assert_eq!(world.validate(), Vec::new());
+
+        let heights: Vec<_> = world
+            .sectors
+            .iter()
+            .map(|s| (s.floor_z, s.ceiling_z))
+            .collect();
+        assert_eq!(heights, [(0.0, 3.0), (0.5, 3.0)]);
+
+        // The shared line is kept once, from the west room's side
+        assert_eq!(world.walls.len(), 7);
+        let portals: Vec<_> = world
+            .walls
+            .iter()
+            .filter(|w| w.back_sector.is_some())
+            .collect();
+        assert_eq!(portals.len(), 1);
+        let portal = portals[0];
+        assert_eq!((portal.front_sector, portal.back_sector), (0, Some(1)));
+        assert_eq!((portal.start, portal.end), ([1.0, -1.0], [1.0, 0.0]));
+
+        assert_eq!(world.player_start.pos, [0.5, -0.5]);
+        assert_eq!(world.sector_at(world.player_start.pos), Some(0));
+        assert_eq!(world.sector_at([1.5, -0.5]), Some(1));
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        let bytes = two_rooms();
+        for len in [0, 10, 30, bytes.len() - WALL_SIZE, bytes.len() - 1] {
+            assert!(
+                matches!(parse_map(&bytes[..len]), Err(BuildError::Truncated)),
+                "{len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let mut bytes = two_rooms();
+        bytes[..4].copy_from_slice(&8i32.to_le_bytes());
+        assert!(matches!(parse_map(&bytes), Err(BuildError::Version(8))));
+    }
+}
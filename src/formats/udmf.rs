@@ -0,0 +1,663 @@
+// UDMF `TEXTMAP` files, as saved by Ultimate Doom Builder and SLADE: vertices, linedefs,
+// sidedefs, sectors and things mapped onto the engine's own types.
+//
+// Doom measures in units of which `UNITS_PER_WORLD` make one world unit, and puts a line's
+// front side on its right where the engine puts it on the left, so lines are reversed.
+// Texture names get stand-in checkerboards (walls) and colors (flats), things other than
+// the player start and teleport destinations become discs, and line specials other than
+// teleporters are left out.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use super::stand_in_colors;
+use crate::entity::Behavior;
+use crate::renderer::WHITE;
+use crate::texture::{Texture, TextureId};
+use crate::world::{
+    self, Blend, Destination, Material, PlayerStart, Sector, Special, Teleport, Thing, Wall, World,
+};
+
+// Doom units per world unit, the usual "32 units to a meter"
+const UNITS_PER_WORLD: f32 = 32.0;
+// Doom light level for full bright
+const FULL_LIGHT: f32 = 255.0;
+// UDMF's default sector light level
+const DEFAULT_LIGHT: f32 = 160.0;
+// Thing type of player 1's start
+const PLAYER_START: i64 = 1;
+// Thing type teleporters send things to, in the sector tagged like the line
+const TELEPORT_DESTINATION: i64 = 14;
+// Walk-over teleporter line specials, once and repeatable
+const TELEPORT_SPECIALS: [i64; 2] = [39, 97];
+// World height of the discs standing in for things
+const THING_HEIGHT: f32 = 1.0;
+
+#[derive(Debug)]
+pub enum UdmfError {
+    Io(std::io::Error),
+    Syntax { line: usize, message: String },
+    Invalid(String),
+}
+
+impl fmt::Display for UdmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdmfError::Io(err) => write!(f, "could not read TEXTMAP: {err}"),
+            UdmfError::Syntax { line, message } => write!(f, "TEXTMAP line {line}: {message}"),
+            UdmfError::Invalid(message) => write!(f, "invalid TEXTMAP: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for UdmfError {}
+
+impl From<std::io::Error> for UdmfError {
+    fn from(err: std::io::Error) -> Self {
+        UdmfError::Io(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+// One `kind { key = value; ... }` block
+struct Block {
+    kind: String,
+    fields: HashMap<String, Value>,
+    line: usize,
+}
+
+impl Block {
+    fn number(&self, key: &str) -> Option<f64> {
+        match self.fields.get(key)? {
+            Value::Int(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn int(&self, key: &str) -> Option<i64> {
+        match self.fields.get(key)? {
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    // Absent flags are false
+    fn flag(&self, key: &str) -> bool {
+        matches!(self.fields.get(key), Some(Value::Bool(true)))
+    }
+
+    fn string(&self, key: &str) -> Option<&str> {
+        match self.fields.get(key)? {
+            Value::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn required_number(&self, key: &str) -> Result<f64, UdmfError> {
+        self.number(key).ok_or_else(|| self.missing(key))
+    }
+
+    // An index into another block list, checked against its length
+    fn index(&self, key: &str, len: usize) -> Result<usize, UdmfError> {
+        let v = self.int(key).ok_or_else(|| self.missing(key))?;
+        usize::try_from(v)
+            .ok()
+            .filter(|&i| i < len)
+            .ok_or_else(|| UdmfError::Syntax {
+                line: self.line,
+                message: format!("{} {key} = {v} is out of range (0..{len})", self.kind),
+            })
+    }
+
+    fn missing(&self, key: &str) -> UdmfError {
+        UdmfError::Syntax {
+            line: self.line,
+            message: format!("{} needs {key}", self.kind),
+        }
+    }
+}
+
+pub fn load_map(path: impl AsRef<Path>) -> Result<World, UdmfError> {
+    let source = std::fs::read_to_string(path)?;
+    parse_map(&source)
+}
+
+pub fn parse_map(source: &str) -> Result<World, UdmfError> {
+    let (namespace, blocks) = parse_text(source)?;
+    if namespace.is_none() {
+        return Err(UdmfError::Invalid("no namespace".to_string()));
+    }
+    let of_kind = |kind: &str| -> Vec<&Block> {
+        blocks
+            .iter()
+            .filter(|b| b.kind.eq_ignore_ascii_case(kind))
+            .collect()
+    };
+    let (vertices, linedefs, sidedefs, sector_defs, thing_defs) = (
+        of_kind("vertex"),
+        of_kind("linedef"),
+        of_kind("sidedef"),
+        of_kind("sector"),
+        of_kind("thing"),
+    );
+    if sector_defs.is_empty() {
+        return Err(UdmfError::Invalid("no sectors".to_string()));
+    }
+
+    let points = vertices
+        .iter()
+        .map(|v| Ok(to_world(v.required_number("x")?, v.required_number("y")?)))
+        .collect::<Result<Vec<_>, UdmfError>>()?;
+
+    let mut textures = Vec::new();
+    let mut by_name: HashMap<String, TextureId> = HashMap::new();
+    let mut wall_texture = |name: &str, textures: &mut Vec<Texture>| -> TextureId {
+        *by_name.entry(name.to_ascii_uppercase()).or_insert_with(|| {
+            let (a, b) = stand_in_colors(name_key(name));
+            textures.push(Texture::checkerboard(64, 16, a, b));
+            textures.len() - 1
+        })
+    };
+
+    let sectors: Vec<Sector> = sector_defs
+        .iter()
+        .map(|s| {
+            let floor = s.number("heightfloor").unwrap_or(0.0) as f32 / UNITS_PER_WORLD;
+            let ceiling = s.number("heightceiling").unwrap_or(0.0) as f32 / UNITS_PER_WORLD;
+            let light = s.number("lightlevel").map_or(DEFAULT_LIGHT, |l| l as f32);
+            let flat = |key| stand_in_colors(name_key(s.string(key).unwrap_or("-")));
+            Sector {
+                floor_z: floor,
+                ceiling_z: ceiling.max(floor),
+                floor_color: flat("texturefloor").0,
+                ceiling_color: flat("textureceiling").1,
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: (light / FULL_LIGHT).clamp(0.0, 1.0),
+                light_color: WHITE,
+                liquid: None,
+                material: Material::default(),
+                specials: Vec::new(),
+            }
+        })
+        .collect();
+
+    let mut walls = Vec::with_capacity(linedefs.len());
+    for line in &linedefs {
+        let v1 = line.index("v1", points.len())?;
+        let v2 = line.index("v2", points.len())?;
+        let front = sidedefs[line.index("sidefront", sidedefs.len())?];
+        let back = match line.int("sideback") {
+            None | Some(-1) => None,
+            Some(_) => Some(sidedefs[line.index("sideback", sidedefs.len())?]),
+        };
+        let front_sector = front.index("sector", sectors.len())?;
+        let back_sector = back
+            .map(|side| side.index("sector", sectors.len()))
+            .transpose()?;
+
+        // One-sided lines show their middle texture; two-sided ones their upper or lower
+        let texture_name = if back.is_some() {
+            [front.string("texturetop"), front.string("texturebottom")]
+        } else {
+            [front.string("texturemiddle"), None]
+        }
+        .into_iter()
+        .flatten()
+        .find(|&name| name != "-")
+        .unwrap_or("-");
+        walls.push(Wall {
+            start: points[v2],
+            end: points[v1],
+            front_sector,
+            back_sector,
+            texture: wall_texture(texture_name, &mut textures),
+            tag: 0,
+            special: Special::None,
+            mid: None,
+            scroll: [0.0, 0.0],
+            // Reversed, the texture runs the other way along the line
+            offset: [
+                -front.number("offsetx").unwrap_or(0.0) as f32 / UNITS_PER_WORLD,
+                front.number("offsety").unwrap_or(0.0) as f32 / UNITS_PER_WORLD,
+            ],
+            // Doom pegs upper textures to the lower ceiling unless told not to
+            peg_upper: !line.flag("dontpegtop"),
+            peg_lower: line.flag("dontpegbottom"),
+            // Doom teleporters stop whoever they send and flash at both ends
+            teleport: line
+                .int("special")
+                .filter(|special| TELEPORT_SPECIALS.contains(special))
+                .map(|_| Teleport {
+                    tag: line.int("arg0").unwrap_or(0) as u32,
+                    keep_velocity: false,
+                    flash: true,
+                }),
+            walkover: None,
+        });
+    }
+
+    let player = thing_defs
+        .iter()
+        .find(|t| t.int("type") == Some(PLAYER_START))
+        .ok_or_else(|| UdmfError::Invalid("no player 1 start (thing type 1)".to_string()))?;
+    // Angles are degrees counterclockwise from east
+    let angle = (player.number("angle").unwrap_or(0.0) as f32).to_radians();
+    let player_start = PlayerStart {
+        pos: to_world(player.required_number("x")?, player.required_number("y")?),
+        yaw: std::f32::consts::FRAC_PI_2 - angle,
+    };
+
+    let mut by_type: HashMap<i64, TextureId> = HashMap::new();
+    let mut things = Vec::new();
+    let mut destinations = Vec::new();
+    for t in thing_defs
+        .iter()
+        .filter(|t| t.int("type") != Some(PLAYER_START))
+    {
+        let kind = t.int("type").ok_or_else(|| t.missing("type"))?;
+        let pos = to_world(t.required_number("x")?, t.required_number("y")?);
+        let sector = world::sector_containing(&walls, sectors.len(), pos);
+        // Doom angles turn counterclockwise from east, in degrees
+        let yaw =
+            std::f32::consts::FRAC_PI_2 - (t.number("angle").unwrap_or(0.0) as f32).to_radians();
+        if kind == TELEPORT_DESTINATION {
+            // Found by the tag of the sector it stands in
+            if let Some(s) = sector {
+                let tag = sector_defs[s].int("id").unwrap_or(0).max(0) as u32;
+                destinations.push(Destination { pos, yaw, tag });
+            }
+            continue;
+        }
+        let floor_z = sector.map_or(0.0, |s| sectors[s].floor_z);
+        let texture = *by_type.entry(kind).or_insert_with(|| {
+            let (a, b) = stand_in_colors(kind as u32);
+            textures.push(Texture::disc(32, a, b));
+            textures.len() - 1
+        });
+        things.push(Thing {
+            pos,
+            // `height` is above the floor
+            z: floor_z + t.number("height").unwrap_or(0.0) as f32 / UNITS_PER_WORLD,
+            height: THING_HEIGHT,
+            texture,
+            scale: 1.0,
+            radius: 0.0,
+            behavior: Behavior::None,
+            prop: None,
+            pickup: None,
+            yaw,
+            rotations: None,
+            model: None,
+            blend: Blend::Masked,
+        });
+    }
+
+    let mut world = World::new(sectors, walls, textures, things, player_start, Vec::new());
+    world.destinations = destinations;
+    Ok(world)
+}
+
+#[inline]
+fn to_world(x: f64, y: f64) -> [f32; 2] {
+    [x as f32 / UNITS_PER_WORLD, y as f32 / UNITS_PER_WORLD]
+}
+
+// FNV-1a of the upper-cased name; texture names are case-insensitive
+fn name_key(name: &str) -> u32 {
+    name.bytes().fold(0x811C_9DC5, |h, b| {
+        (h ^ u32::from(b.to_ascii_uppercase())).wrapping_mul(0x0100_0193)
+    })
+}
+
+// The namespace and every block, in file order. Top-level assignments other than the
+// namespace are allowed and ignored.
+fn parse_text(source: &str) -> Result<(Option<String>, Vec<Block>), UdmfError> {
+    let mut tokens = Tokens::new(source);
+    let mut namespace = None;
+    let mut blocks = Vec::new();
+    while let Some((line, token)) = tokens.next()? {
+        let Token::Ident(name) = token else {
+            return Err(syntax(
+                line,
+                format!("expected a block or assignment, found {token}"),
+            ));
+        };
+        match tokens.next()? {
+            Some((_, Token::Punct('='))) => {
+                let value = tokens.value()?;
+                tokens.expect(';')?;
+                if name.eq_ignore_ascii_case("namespace") {
+                    let Value::Str(ns) = value else {
+                        return Err(syntax(line, "namespace must be a string".to_string()));
+                    };
+                    namespace = Some(ns);
+                }
+            }
+            Some((_, Token::Punct('{'))) => {
+                let mut fields = HashMap::new();
+                loop {
+                    match tokens.next()? {
+                        Some((_, Token::Punct('}'))) => break,
+                        Some((_, Token::Ident(key))) => {
+                            tokens.expect('=')?;
+                            let value = tokens.value()?;
+                            tokens.expect(';')?;
+                            fields.insert(key.to_ascii_lowercase(), value);
+                        }
+                        Some((at, other)) => {
+                            return Err(syntax(at, format!("expected a field, found {other}")));
+                        }
+                        None => return Err(syntax(line, format!("{name} block is never closed"))),
+                    }
+                }
+                blocks.push(Block {
+                    kind: name,
+                    fields,
+                    line,
+                });
+            }
+            Some((at, other)) => {
+                return Err(syntax(at, format!("expected '=' or '{{', found {other}")));
+            }
+            None => return Err(syntax(line, format!("{name} is cut off"))),
+        }
+    }
+    Ok((namespace, blocks))
+}
+
+fn syntax(line: usize, message: String) -> UdmfError {
+    UdmfError::Syntax { line, message }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "{s:?}"),
+            Token::Str(s) => write!(f, "string {s:?}"),
+            Token::Punct(c) => write!(f, "'{c}'"),
+        }
+    }
+}
+
+struct Tokens<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    // Next token and the line it starts on, skipping whitespace and comments
+    fn next(&mut self) -> Result<Option<(usize, Token)>, UdmfError> {
+        self.skip_blank()?;
+        let line = self.line;
+        let Some(&c) = self.chars.peek() else {
+            return Ok(None);
+        };
+        let token = if c == '"' {
+            self.chars.next();
+            let mut s = String::new();
+            loop {
+                match self.chars.next() {
+                    Some('"') => break,
+                    Some('\\') => s.extend(self.chars.next()),
+                    Some(c) => {
+                        if c == '\n' {
+                            self.line += 1;
+                        }
+                        s.push(c);
+                    }
+                    None => return Err(syntax(line, "string is never closed".to_string())),
+                }
+            }
+            Token::Str(s)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            Token::Ident(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_'))
+        } else if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') {
+            Token::Number(self.take_while(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+        } else {
+            self.chars.next();
+            Token::Punct(c)
+        };
+        Ok(Some((line, token)))
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), UdmfError> {
+        match self.next()? {
+            Some((_, Token::Punct(c))) if c == punct => Ok(()),
+            Some((line, other)) => Err(syntax(line, format!("expected '{punct}', found {other}"))),
+            None => Err(syntax(self.line, format!("expected '{punct}' at the end"))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, UdmfError> {
+        let Some((line, token)) = self.next()? else {
+            return Err(syntax(self.line, "expected a value at the end".to_string()));
+        };
+        match token {
+            Token::Str(s) => Ok(Value::Str(s)),
+            Token::Ident(s) if s.eq_ignore_ascii_case("true") => Ok(Value::Bool(true)),
+            Token::Ident(s) if s.eq_ignore_ascii_case("false") => Ok(Value::Bool(false)),
+            Token::Number(s) => {
+                parse_number(&s).ok_or_else(|| syntax(line, format!("bad number {s:?}")))
+            }
+            other => Err(syntax(line, format!("expected a value, found {other}"))),
+        }
+    }
+
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek()
+            && keep(c)
+        {
+            s.push(c);
+            self.chars.next();
+        }
+        s
+    }
+
+    fn skip_blank(&mut self) -> Result<(), UdmfError> {
+        loop {
+            match self.chars.peek() {
+                Some('\n') => {
+                    self.line += 1;
+                    self.chars.next();
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+                    match ahead.peek() {
+                        Some('/') => while self.chars.next_if(|&c| c != '\n').is_some() {},
+                        Some('*') => {
+                            let line = self.line;
+                            self.chars.next();
+                            self.chars.next();
+                            let mut prev = ' ';
+                            loop {
+                                let Some(c) = self.chars.next() else {
+                                    return Err(syntax(
+                                        line,
+                                        "comment is never closed".to_string(),
+                                    ));
+                                };
+                                if c == '\n' {
+                                    self.line += 1;
+                                }
+                                if prev == '*' && c == '/' {
+                                    break;
+                                }
+                                prev = c;
+                            }
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+// Decimal, hex (0x) or octal (leading 0) integers, or floats
+fn parse_number(s: &str) -> Option<Value> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let sign = if negative { -1 } else { 1 };
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        return i64::from_str_radix(hex, 16)
+            .ok()
+            .map(|v| Value::Int(sign * v));
+    }
+    if digits.contains(['.', 'e', 'E']) {
+        return s.parse().ok().map(Value::Float);
+    }
+    if digits.len() > 1 && digits.starts_with('0') {
+        return i64::from_str_radix(digits, 8)
+            .ok()
+            .map(|v| Value::Int(sign * v));
+    }
+    digits.parse::<i64>().ok().map(|v| Value::Int(sign * v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 64-unit square room, lines clockwise so the room is on each front (right) side
+    const SQUARE: &str = r#"
+namespace = "zdoom";
+vertex { x = 0; y = 0; }
+vertex { x = 0; y = 64; }
+vertex { x = 64.0; y = 64.0; }
+vertex { x = 64; y = 0; }
+linedef { v1 = 0; v2 = 1; sidefront = 0; }
+linedef { v1 = 1; v2 = 2; sidefront = 0; }
+linedef { v1 = 2; v2 = 3; sidefront = 0; }
+linedef { v1 = 3; v2 = 0; sidefront = 0; }
+sidedef { sector = 0; texturemiddle = "STARTAN2"; }
+sector { heightfloor = 8; heightceiling = 104; texturefloor = "FLOOR4_8"; lightlevel = 255; }
+thing { type = 1; x = 32; y = 16; angle = 90; }
+"#;
+
+    #[test]
+    fn reads_a_minimal_map() {
+        let world = parse_map(SQUARE).unwrap();
+        assert_eq!(world.validate(), Vec::new());
+        assert_eq!(world.sectors.len(), 1);
+        assert_eq!(
+            (world.sectors[0].floor_z, world.sectors[0].ceiling_z),
+            (0.25, 3.25)
+        );
+        assert_eq!(world.sectors[0].light_level, 1.0);
+        // Reversed, so the room is on the left
+        assert_eq!(world.walls.len(), 4);
+        assert_eq!(
+            (world.walls[0].start, world.walls[0].end),
+            ([0.0, 2.0], [0.0, 0.0])
+        );
+        assert!(world.walls.iter().all(|w| w.back_sector.is_none()));
+        assert_eq!(world.player_start.pos, [1.0, 0.5]);
+        assert!(world.player_start.yaw.abs() < 1e-6);
+        assert_eq!(world.sector_at(world.player_start.pos), Some(0));
+    }
+
+    #[test]
+    fn skips_comments_and_unescapes_strings() {
+        let source = r#"
+// a line comment
+namespace /* inline */ = "doom";
+Sidedef // keys and kinds are case-insensitive
+{
+    TextureMiddle = "SAY \"HI\"";
+    /* a block comment
+       over two lines */
+    sector = 0x1;
+    offsetx = -010;
+    dontpegtop = TRUE;
+}
+"#;
+        let (namespace, blocks) = parse_text(source).unwrap();
+        assert_eq!(namespace.as_deref(), Some("doom"));
+        assert_eq!(blocks.len(), 1);
+        let side = &blocks[0];
+        assert_eq!(side.line, 4);
+        assert_eq!(side.string("texturemiddle"), Some(r#"SAY "HI""#));
+        assert_eq!(side.int("sector"), Some(1));
+        assert_eq!(side.int("offsetx"), Some(-8));
+        assert!(side.flag("dontpegtop"));
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let cases = [
+            ("vertex { x = 0; y = 0;", "never closed"),
+            ("namespace = \"doom", "never closed"),
+            ("/* namespace = \"doom\";", "never closed"),
+            ("namespace = \"doom\"", "expected ';'"),
+            ("vertex { x = 0 y = 0; }", "expected ';'"),
+            ("vertex { x = 1.2.3; }", "bad number"),
+            ("vertex { x = ; }", "expected a value"),
+            ("= 1;", "expected a block"),
+            ("namespace", "cut off"),
+            ("namespace = 3;", "must be a string"),
+        ];
+        for (source, expected) in cases {
+            match parse_map(source) {
+                Err(UdmfError::Syntax { message, .. }) => {
+                    assert!(message.contains(expected), "{source:?}: {message}")
+                }
+                other => panic!("{source:?}: {:?}", other.err()),
+            }
+        }
+    }
+
+    #[test]
+    fn broken_references_are_an_error() {
+        let cases = [
+            SQUARE.replace("namespace = \"zdoom\";", ""),
+            SQUARE.replace("v2 = 3;", "v2 = 9;"),
+            SQUARE.replace("sidefront = 0; }\nlinedef { v1 = 3", "}\nlinedef { v1 = 3"),
+            SQUARE.replace("sector = 0;", "sector = -1;"),
+            SQUARE.replace("type = 1;", "type = 3004;"),
+            SQUARE.replace("sector {", "nothing {"),
+        ];
+        for source in &cases {
+            assert!(parse_map(source).is_err(), "{source}");
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// MagicaVoxel `.vox` models: the first model's SIZE and XYZI chunks, colored by the file's
+// RGBA palette. Files without one get grays by palette index rather than MagicaVoxel's
+// default palette.
+//
+// MagicaVoxel's front looks toward -y with +x on the viewer's right, so models are turned
+// half a turn to face +y like the engine's.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::renderer::pack_rgb;
+use crate::voxel::VoxelModel;
+
+const MAGIC: &[u8; 4] = b"VOX ";
+// Voxel models are meant to be small props; this keeps a bad SIZE from allocating gigabytes
+const MAX_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    Truncated, // ended inside a chunk
+    NotVox,    // no "VOX " magic
+    Invalid(String),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::Io(err) => write!(f, "could not read .vox model: {err}"),
+            VoxError::Truncated => write!(f, ".vox model ends early"),
+            VoxError::NotVox => write!(f, "not a MagicaVoxel .vox model"),
+            VoxError::Invalid(message) => write!(f, "invalid .vox model: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<std::io::Error> for VoxError {
+    fn from(err: std::io::Error) -> Self {
+        VoxError::Io(err)
+    }
+}
+
+pub fn load_model(path: impl AsRef<Path>) -> Result<VoxelModel, VoxError> {
+    let bytes = std::fs::read(path)?;
+    parse_model(&bytes)
+}
+
+pub fn parse_model(bytes: &[u8]) -> Result<VoxelModel, VoxError> {
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return Err(VoxError::NotVox);
+    }
+    // The version after the magic changes nothing in the chunks read here
+    let mut size = None;
+    let mut voxels: Option<&[u8]> = None;
+    let mut palette: Option<&[u8]> = None;
+    let mut at = 8;
+    while at < bytes.len() {
+        let header = bytes.get(at..at + 12).ok_or(VoxError::Truncated)?;
+        let id = &header[..4];
+        let content_len = u32_at(header, 4) as usize;
+        let content = bytes
+            .get(at + 12..at + 12 + content_len)
+            .ok_or(VoxError::Truncated)?;
+        // MAIN's children are the chunks that follow, so step into them rather than over
+        at += 12 + content_len;
+        if id != b"MAIN" {
+            at += u32_at(header, 8) as usize;
+        }
+        match id {
+            b"SIZE" if size.is_none() => {
+                if content.len() < 12 {
+                    return Err(VoxError::Truncated);
+                }
+                size = Some([0, 4, 8].map(|o| u32_at(content, o) as usize));
+            }
+            b"XYZI" if voxels.is_none() => voxels = Some(content),
+            b"RGBA" => palette = Some(content),
+            _ => {}
+        }
+    }
+
+    let size = size.ok_or_else(|| VoxError::Invalid("no SIZE chunk".to_string()))?;
+    if size.iter().any(|&n| n == 0 || n > MAX_SIZE) {
+        return Err(VoxError::Invalid(format!(
+            "size {}x{}x{} is not within 1..={MAX_SIZE}",
+            size[0], size[1], size[2]
+        )));
+    }
+    let voxels = voxels.ok_or_else(|| VoxError::Invalid("no XYZI chunk".to_string()))?;
+    if voxels.len() < 4 {
+        return Err(VoxError::Truncated);
+    }
+    let count = u32_at(voxels, 0) as usize;
+    let records = voxels
+        .get(4..4 + count.saturating_mul(4))
+        .ok_or(VoxError::Truncated)?;
+
+    // Palette entry i colors index i + 1; index 0 is empty space
+    let color = |index: u8| match palette {
+        Some(rgba) if rgba.len() >= 4 * index as usize => {
+            let c = &rgba[4 * (index as usize - 1)..];
+            pack_rgb(c[0], c[1], c[2])
+        }
+        _ => pack_rgb(index, index, index),
+    };
+    let [w, d, h] = size;
+    let mut solid = vec![None; w * d * h];
+    for v in records.chunks_exact(4) {
+        let (x, y, z) = (v[0] as usize, v[1] as usize, v[2] as usize);
+        if x >= w || y >= d || z >= h || v[3] == 0 {
+            continue;
+        }
+        let (x, y) = (w - 1 - x, d - 1 - y);
+        solid[x + w * (y + d * z)] = Some(color(v[3]));
+    }
+    Ok(VoxelModel::from_voxels(size, &solid))
+}
+
+#[inline]
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::Voxel;
+
+    fn chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: u32) {
+        out.extend(id);
+        out.extend((content.len() as u32).to_le_bytes());
+        out.extend(children.to_le_bytes());
+        out.extend(content);
+    }
+
+    // A 3 wide, 2 deep, 4 tall model with a voxel in two opposite corners
+    fn two_corners(palette: bool) -> Vec<u8> {
+        let mut children = Vec::new();
+        let size: Vec<u8> = [3u32, 2, 4]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        chunk(&mut children, b"SIZE", &size, 0);
+        let mut xyzi = 2u32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1, 2, 1, 3, 2]);
+        chunk(&mut children, b"XYZI", &xyzi, 0);
+        if palette {
+            let mut rgba = vec![0; 256 * 4];
+            rgba[..8].copy_from_slice(&[255, 0, 0, 255, 0, 255, 0, 255]);
+            chunk(&mut children, b"RGBA", &rgba, 0);
+        }
+
+        let mut out = MAGIC.to_vec();
+        out.extend(150u32.to_le_bytes());
+        chunk(&mut out, b"MAIN", &[], children.len() as u32);
+        out.extend(children);
+        out
+    }
+
+    #[test]
+    fn reads_size_and_palette() {
+        let model = parse_model(&two_corners(true)).unwrap();
+        assert_eq!(model.size, [3, 2, 4]);
+        // Turned half a turn, so each corner lands in the opposite column
+        let red = Voxel {
+            z: 0,
+            color: pack_rgb(255, 0, 0),
+        };
+        let green = Voxel {
+            z: 3,
+            color: pack_rgb(0, 255, 0),
+        };
+        assert_eq!(model.column(2, 1), [red]);
+        assert_eq!(model.column(0, 0), [green]);
+        assert_eq!(model.column(1, 0), []);
+    }
+
+    #[test]
+    fn no_palette_means_grays() {
+        let model = parse_model(&two_corners(false)).unwrap();
+        assert_eq!(model.column(2, 1)[0].color, pack_rgb(1, 1, 1));
+        assert_eq!(model.column(0, 0)[0].color, pack_rgb(2, 2, 2));
+    }
+
+    #[test]
+    fn truncated_chunk_is_an_error() {
+        let bytes = two_corners(true);
+        // Inside the palette's content, then inside the XYZI chunk's header
+        for len in [bytes.len() - 1, 8 + 12 + 24 + 6] {
+            assert!(
+                matches!(parse_model(&bytes[..len]), Err(VoxError::Truncated)),
+                "{len} bytes"
+            );
+        }
+        // Never a panic, wherever it's cut; cut between chunks, what's left can still read
+        for len in 0..bytes.len() {
+            let _ = parse_model(&bytes[..len]);
+        }
+    }
+
+    #[test]
+    fn voxel_count_past_the_chunk_is_an_error() {
+        let mut bytes = two_corners(false);
+        // The XYZI count, after the file, MAIN and SIZE headers and SIZE's content
+        let count = 8 + 12 + 12 + 12 + 12;
+        bytes[count..count + 4].copy_from_slice(&3u32.to_le_bytes());
+        assert!(matches!(parse_model(&bytes), Err(VoxError::Truncated)));
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        assert!(matches!(
+            parse_model(b"PNG\0\0\0\0\0"),
+            Err(VoxError::NotVox)
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+// Frames in whatever pixel layout a target wants. The renderer and scaler work in the
+// engine's own packed pixels (`pack_rgb`); a `Framebuffer` holds a frame in a target's
+// format, converted as the last step of presenting it.
+
+/// How a target lays out one pixel in memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// The engine's own: a little-endian `u32` 0x00RRGGBB, bytes B, G, R then an unused
+    /// zero, as softbuffer takes it
+    #[default]
+    Xrgb8888,
+    Bgra8888, // bytes B, G, R, A, with A opaque
+    Rgba8888, // bytes R, G, B, A, with A opaque
+    Rgb565,   // a little-endian `u16`, 5 bits of red over 6 of green over 5 of blue
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Xrgb8888 | PixelFormat::Bgra8888 | PixelFormat::Rgba8888 => 4,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+
+    /// Packed `pixel` in this format; only the first `bytes_per_pixel` bytes are used
+    #[inline]
+    pub fn encode(self, pixel: u32) -> [u8; 4] {
+        let [b, g, r, _] = pixel.to_le_bytes();
+        match self {
+            PixelFormat::Xrgb8888 => [b, g, r, 0],
+            PixelFormat::Bgra8888 => [b, g, r, 0xFF],
+            PixelFormat::Rgba8888 => [r, g, b, 0xFF],
+            PixelFormat::Rgb565 => {
+                let packed =
+                    (u16::from(r) >> 3) << 11 | (u16::from(g) >> 2) << 5 | u16::from(b) >> 3;
+                let [lo, hi] = packed.to_le_bytes();
+                [lo, hi, 0, 0]
+            }
+        }
+    }
+
+    /// Back to a packed pixel from this format's bytes, e.g. to read a frame back; RGB565
+    /// widens each channel by repeating its top bits
+    #[inline]
+    pub fn decode(self, bytes: &[u8]) -> u32 {
+        let (r, g, b) = match self {
+            PixelFormat::Xrgb8888 | PixelFormat::Bgra8888 => (bytes[2], bytes[1], bytes[0]),
+            PixelFormat::Rgba8888 => (bytes[0], bytes[1], bytes[2]),
+            PixelFormat::Rgb565 => {
+                let packed = u16::from_le_bytes([bytes[0], bytes[1]]);
+                let (r, g, b) = (
+                    (packed >> 11) as u8,
+                    (packed >> 5 & 0x3F) as u8,
+                    (packed & 0x1F) as u8,
+                );
+                (r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2)
+            }
+        };
+        u32::from_le_bytes([b, g, r, 0])
+    }
+}
+
+/// Write packed pixels `src` into `dst` in `format`, `bytes_per_pixel` bytes each
+pub fn convert(src: &[u32], format: PixelFormat, dst: &mut [u8]) {
+    let size = format.bytes_per_pixel();
+    assert_eq!(dst.len(), src.len() * size, "framebuffer size mismatch");
+    for (&pixel, out) in src.iter().zip(dst.chunks_exact_mut(size)) {
+        out.copy_from_slice(&format.encode(pixel)[..size]);
+    }
+}
+
+/// A `width` x `height` frame stored row by row in `format`, with no padding between rows
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    data: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            data: vec![0; width * height * format.bytes_per_pixel()],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Bytes from one row to the next
+    pub fn stride(&self) -> usize {
+        self.width * self.format.bytes_per_pixel()
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        (self.width, self.height) = (width, height);
+        self.data
+            .resize(width * height * self.format.bytes_per_pixel(), 0);
+    }
+
+    /// The frame's bytes, ready to copy to its target
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Replace the frame with `src`, packed pixels of the same size
+    pub fn store(&mut self, src: &[u32]) {
+        convert(src, self.format, &mut self.data);
+    }
+
+    /// Pixel (`x`, `y`) as a packed pixel
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        let size = self.format.bytes_per_pixel();
+        let at = (y * self.width + x) * size;
+        self.format.decode(&self.data[at..at + size])
+    }
+}
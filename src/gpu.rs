@@ -0,0 +1,396 @@
+// GPU presentation (--backend gpu): the internal framebuffer is uploaded as a texture each
+// frame and a fragment shader does the scaling, sharpening and CRT filter the CPU scaler
+// would, which takes the per-pixel work off the CPU on large windows
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use two_halfD_engine::framebuffer::{Framebuffer, PixelFormat};
+use two_halfD_engine::scaler::{ColorAdjust, CrtParams, PresentTimings, ScaleMode};
+use winit::window::Window;
+
+// Sizes, mode, CRT strengths and color correction as laid out in the shader's `Params`
+const PARAMS_SIZE: usize = 64;
+// Layout of the framebuffer texture's `Bgra8Unorm` texels
+const FRAME_FORMAT: PixelFormat = PixelFormat::Bgra8888;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Software, // softbuffer, scaled on the CPU
+    Gpu,
+}
+
+impl Backend {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "software" => Some(Backend::Software),
+            "gpu" => Some(Backend::Gpu),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GpuError {
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+    Frame(wgpu::SurfaceError),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::Surface(err) => write!(f, "no GPU surface for the window: {err}"),
+            GpuError::NoAdapter => write!(f, "no suitable GPU adapter"),
+            GpuError::Device(err) => write!(f, "could not open the GPU: {err}"),
+            GpuError::Frame(err) => write!(f, "could not present a frame: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+impl From<wgpu::CreateSurfaceError> for GpuError {
+    fn from(err: wgpu::CreateSurfaceError) -> Self {
+        GpuError::Surface(err)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for GpuError {
+    fn from(err: wgpu::RequestDeviceError) -> Self {
+        GpuError::Device(err)
+    }
+}
+
+impl From<wgpu::SurfaceError> for GpuError {
+    fn from(err: wgpu::SurfaceError) -> Self {
+        GpuError::Frame(err)
+    }
+}
+
+pub struct GpuPresenter {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    params: wgpu::Buffer,
+    frame: wgpu::Texture, // the internal framebuffer
+    bind_group: wgpu::BindGroup,
+    src_size: [u32; 2],
+    upload: Framebuffer, // the framebuffer converted to `FRAME_FORMAT`, reused each frame
+}
+
+impl GpuPresenter {
+    /// Set up presentation to `window` at its current size from a `src_w` x `src_h`
+    /// framebuffer
+    pub fn new(window: Arc<Window>, src_w: usize, src_h: usize) -> Result<Self, GpuError> {
+        let size = window.inner_size();
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance.create_surface(window)?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        }))
+        .ok_or(GpuError::NoAdapter)?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("present"),
+                required_features: wgpu::Features::empty(),
+                required_limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))?;
+
+        // Colors go to the screen as they are, like softbuffer, so no sRGB conversion
+        let caps = surface.get_capabilities(&adapter);
+        let format = caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| !f.is_srgb())
+            .unwrap_or(caps.formats[0]);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            // The frame pacer decides when to draw
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: Vec::new(),
+        };
+        surface.configure(&device, &config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("present"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let fragment_only = wgpu::ShaderStages::FRAGMENT;
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("present"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: fragment_only,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: fragment_only,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("present"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("present"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("present params"),
+            size: PARAMS_SIZE as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let src_size = [src_w.max(1) as u32, src_h.max(1) as u32];
+        let frame = frame_texture(&device, src_size);
+        let bind_group = bind_group(&device, &layout, &frame, &params);
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            config,
+            pipeline,
+            layout,
+            params,
+            frame,
+            bind_group,
+            src_size,
+            upload: Framebuffer::new(src_size[0] as usize, src_size[1] as usize, FRAME_FORMAT),
+        })
+    }
+
+    /// Follow a window resize to `dst_w` x `dst_h` and a framebuffer of `src_w` x `src_h`
+    pub fn resize(&mut self, dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) {
+        if dst_w > 0 && dst_h > 0 {
+            self.config.width = dst_w as u32;
+            self.config.height = dst_h as u32;
+            self.surface.configure(&self.device, &self.config);
+        }
+        let src_size = [src_w.max(1) as u32, src_h.max(1) as u32];
+        if src_size != self.src_size {
+            self.src_size = src_size;
+            self.frame = frame_texture(&self.device, src_size);
+            self.bind_group = bind_group(&self.device, &self.layout, &self.frame, &self.params);
+            self.upload
+                .resize(src_size[0] as usize, src_size[1] as usize);
+        }
+    }
+
+    /// Upload `src` and draw it to the window in `mode`, then through the CRT filter if
+    /// given and corrected by `color`; `src` must match the framebuffer size last given. The GPU's own time doesn't
+    /// show in the timings, only the upload and submit, which count as the blit.
+    pub fn present(
+        &mut self,
+        src: &[u32],
+        mode: ScaleMode,
+        crt: Option<CrtParams>,
+        color: ColorAdjust,
+    ) -> Result<PresentTimings, GpuError> {
+        let start = Instant::now();
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            // Gone stale, e.g. mid-resize: set it up again and skip this frame
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface.configure(&self.device, &self.config);
+                return Ok(PresentTimings::default());
+            }
+            Err(wgpu::SurfaceError::Timeout) => return Ok(PresentTimings::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        self.upload.store(src);
+        let h = self.src_size[1];
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.frame,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            self.upload.as_bytes(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.upload.stride() as u32),
+                rows_per_image: Some(h),
+            },
+            self.frame.size(),
+        );
+        let params = params_bytes(
+            self.src_size,
+            [self.config.width, self.config.height],
+            mode,
+            crt,
+            color,
+        );
+        self.queue.write_buffer(&self.params, 0, &params);
+
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("present"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("present"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit([encoder.finish()]);
+        frame.present();
+        Ok(PresentTimings {
+            blit: start.elapsed(),
+            ..PresentTimings::default()
+        })
+    }
+}
+
+fn frame_texture(device: &wgpu::Device, [width, height]: [u32; 2]) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("framebuffer"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Bgra8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    frame: &wgpu::Texture,
+    params: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    let view = frame.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("present"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: params.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn params_bytes(
+    src_size: [u32; 2],
+    dst_size: [u32; 2],
+    mode: ScaleMode,
+    crt: Option<CrtParams>,
+    color: ColorAdjust,
+) -> [u8; PARAMS_SIZE] {
+    let mode = match mode {
+        ScaleMode::Bilinear => 0,
+        ScaleMode::Nearest => 1,
+        ScaleMode::Integer => 2,
+    };
+    let strengths = crt.map_or([0.0; 3], |c| [c.scanlines, c.vignette, c.mask]);
+    let apply_color: f32 = if color.is_identity() { 0.0 } else { 1.0 };
+    let words: [u32; PARAMS_SIZE / 4] = [
+        src_size[0],
+        src_size[1],
+        dst_size[0],
+        dst_size[1],
+        mode,
+        crt.is_some() as u32,
+        0, // padding up to the vec4
+        0,
+        strengths[0].to_bits(),
+        strengths[1].to_bits(),
+        strengths[2].to_bits(),
+        0,
+        color.gamma.to_bits(),
+        color.brightness.to_bits(),
+        color.contrast.to_bits(),
+        apply_color.to_bits(),
+    ];
+    let mut bytes = [0; PARAMS_SIZE];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
@@ -0,0 +1,102 @@
+// Presents the internal framebuffer on the GPU, matching the CPU scaler's modes: bilinear
+// stretch with a cross sharpen, nearest stretch, or integer scale with black bars, then the
+// optional CRT filter and color correction. Sizes are in pixels and positions sample at pixel centers.
+
+struct Params {
+    src_size: vec2<u32>,
+    dst_size: vec2<u32>,
+    mode: u32,    // 0 bilinear, 1 nearest, 2 integer
+    crt_on: u32,
+    crt: vec4<f32>, // scanlines, vignette, mask, unused
+    color: vec4<f32>, // gamma, brightness, contrast, 1 to apply them
+}
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+// One triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn texel(p: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(params.src_size);
+    return textureLoad(frame, clamp(p, vec2<i32>(0), size - 1), 0).rgb;
+}
+
+// As the CPU bilinear blit: destination pixel d starts at source d * src / dst
+fn bilinear(d: vec2<f32>) -> vec3<f32> {
+    let f = d * vec2<f32>(params.src_size) / vec2<f32>(params.dst_size);
+    let p = vec2<i32>(floor(f));
+    let w = f - floor(f);
+    let top = mix(texel(p), texel(p + vec2<i32>(1, 0)), w.x);
+    let bottom = mix(texel(p + vec2<i32>(0, 1)), texel(p + vec2<i32>(1, 1)), w.x);
+    return mix(top, bottom, w.y);
+}
+
+fn bilinear_sharpened(d: vec2<f32>) -> vec3<f32> {
+    let c = bilinear(d);
+    if any(d == vec2<f32>(0.0)) || any(d >= vec2<f32>(params.dst_size) - 1.0) {
+        return c; // borders are left unsharpened
+    }
+    let around = bilinear(d + vec2<f32>(1.0, 0.0)) + bilinear(d - vec2<f32>(1.0, 0.0))
+        + bilinear(d + vec2<f32>(0.0, 1.0)) + bilinear(d - vec2<f32>(0.0, 1.0));
+    return clamp(5.0 * c - around, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn nearest(d: vec2<f32>) -> vec3<f32> {
+    let step = vec2<f32>(params.src_size) / vec2<f32>(params.dst_size);
+    return texel(vec2<i32>((d + 0.5) * step));
+}
+
+fn integer(d: vec2<f32>) -> vec3<f32> {
+    let src = vec2<i32>(params.src_size);
+    let dst = vec2<i32>(params.dst_size);
+    let k = max(1, min(dst.x / max(src.x, 1), dst.y / max(src.y, 1)));
+    let s = vec2<i32>(d) - (dst - src * k) / 2;
+    if any(s < vec2<i32>(0)) || any(s >= src * k) {
+        return vec3<f32>(0.0);
+    }
+    return texel(s / k);
+}
+
+// 1 - v * t^4 with t in -1..1 across the screen
+fn vignette(i: f32, n: f32) -> f32 {
+    let t = (i + 0.5) / n * 2.0 - 1.0;
+    return 1.0 - params.crt.y * t * t * t * t;
+}
+
+fn crt(color: vec3<f32>, d: vec2<f32>) -> vec3<f32> {
+    let size = vec2<f32>(params.dst_size);
+    let phase = fract((d.y + 0.5) * f32(params.src_size.y) / size.y);
+    let scan = 1.0 - params.crt.x * phase * phase;
+    var mask = vec3<f32>(1.0 - params.crt.z);
+    mask[u32(d.x) % 3u] = 1.0;
+    return color * mask * scan * vignette(d.x, size.x) * vignette(d.y, size.y);
+}
+
+// As `ColorAdjust::apply`
+fn adjust(c: vec3<f32>) -> vec3<f32> {
+    let lifted = pow(c, vec3<f32>(1.0 / params.color.x));
+    return clamp((lifted - 0.5) * params.color.z + 0.5 + params.color.y, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let d = floor(pos.xy);
+    var color: vec3<f32>;
+    switch params.mode {
+        case 0u: { color = bilinear_sharpened(d); }
+        case 1u: { color = nearest(d); }
+        default: { color = integer(d); }
+    }
+    if params.crt_on != 0u {
+        color = crt(color, d);
+    }
+    if params.color.w != 0.0 {
+        color = adjust(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
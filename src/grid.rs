@@ -0,0 +1,410 @@
+// Uniform grid over the walls' bounding boxes, so point, box and line queries only look at
+// the walls near them instead of every wall in the map. The renderer doesn't need it: it
+// walks the BSP and skips whole subtrees outside the view.
+
+use crate::world::Wall;
+
+// Side of a cell in world units, unless the map is big enough to need coarser cells
+const CELL_SIZE: f32 = 4.0;
+// Keeps a map with a few far-flung walls from allocating a huge, mostly empty grid
+const MAX_CELLS: usize = 1 << 18;
+
+#[derive(Default)]
+pub struct WallGrid {
+    origin: [f32; 2], // min corner of the walls' bounds
+    cell: f32,
+    size: [usize; 2], // cells across x and y
+    // Walls overlapping cell i are walls[starts[i]..starts[i + 1]]
+    starts: Vec<u32>,
+    walls: Vec<u32>,
+    // Cells (x0, y0, x1, y1) each wall covers, by wall, to tell the first cell a query
+    // meets it in
+    ranges: Vec<CellRange>,
+}
+
+type CellRange = (usize, usize, usize, usize);
+
+impl WallGrid {
+    pub fn build(walls: &[Wall]) -> Self {
+        if walls.is_empty() {
+            return Self::default();
+        }
+        let mut min = [f32::INFINITY; 2];
+        let mut max = [f32::NEG_INFINITY; 2];
+        for wall in walls {
+            for p in [wall.start, wall.end] {
+                min = [min[0].min(p[0]), min[1].min(p[1])];
+                max = [max[0].max(p[0]), max[1].max(p[1])];
+            }
+        }
+        let extent = [max[0] - min[0], max[1] - min[1]];
+        let cell = CELL_SIZE.max(fitting_cell(extent));
+        let size = extent.map(|e| (e / cell) as usize + 1);
+        let mut grid = Self {
+            origin: min,
+            cell,
+            size,
+            starts: vec![0; size[0] * size[1] + 1],
+            walls: Vec::new(),
+            ranges: Vec::new(),
+        };
+
+        // Count each cell's walls, turn the counts into starts, then fill the cells in
+        grid.ranges = walls
+            .iter()
+            .map(|w| grid.cell_range(w.start, w.end))
+            .collect();
+        for &(x0, y0, x1, y1) in &grid.ranges {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    grid.starts[x + y * size[0] + 1] += 1;
+                }
+            }
+        }
+        for i in 1..grid.starts.len() {
+            grid.starts[i] += grid.starts[i - 1];
+        }
+        let mut next = grid.starts.clone();
+        grid.walls = vec![0; *grid.starts.last().unwrap() as usize];
+        for (i, &(x0, y0, x1, y1)) in grid.ranges.iter().enumerate() {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let at = &mut next[x + y * size[0]];
+                    grid.walls[*at as usize] = i as u32;
+                    *at += 1;
+                }
+            }
+        }
+        grid
+    }
+
+    /// Walls whose bounding boxes overlap the box from `min` to `max`, each once; walks
+    /// the cells without collecting them
+    pub fn walls_in(&self, min: [f32; 2], max: [f32; 2]) -> impl Iterator<Item = usize> + '_ {
+        let far = self.far();
+        let outside = self.walls.is_empty()
+            || max[0] < self.origin[0]
+            || max[1] < self.origin[1]
+            || min[0] > far[0]
+            || min[1] > far[1];
+        // An empty run of rows when the box misses the grid
+        let (x0, y0, x1, y1) = if outside {
+            (0, 1, 0, 0)
+        } else {
+            self.cell_range(min, max)
+        };
+        (y0..=y1).flat_map(move |y| {
+            (x0..=x1).flat_map(move |x| {
+                // A wall spanning several cells counts in the first of them inside the box
+                self.cell_walls(x, y).filter(move |&i| {
+                    let (wx0, wy0, _, _) = self.ranges[i];
+                    (wx0.max(x0), wy0.max(y0)) == (x, y)
+                })
+            })
+        })
+    }
+
+    /// Walls whose bounding boxes overlap the cells the segment `from`-`to` passes
+    /// through, each once, roughly nearest `from` first
+    pub fn walls_along(&self, from: [f32; 2], to: [f32; 2]) -> impl Iterator<Item = usize> + '_ {
+        // The cell before each, to tell which walls the segment has only just reached
+        self.cells_along(from, to)
+            .scan(None, |before, cell| Some((cell, before.replace(cell))))
+            .flat_map(move |(cell, before)| {
+                // The walk only ever steps one way along each axis, so it can't leave a
+                // wall's cells and come back to them
+                self.cell_walls(cell[0], cell[1]).filter(move |&i| {
+                    let (x0, y0, x1, y1) = self.ranges[i];
+                    before.is_none_or(|[x, y]| x < x0 || x > x1 || y < y0 || y > y1)
+                })
+            })
+    }
+
+    /// Walls whose bounding boxes overlap the grid row holding `p`, from its cell on
+    /// toward +x, each once; walks the cells without collecting them
+    pub fn walls_right_of(&self, p: [f32; 2]) -> impl Iterator<Item = usize> + '_ {
+        let far = self.far();
+        let outside =
+            self.walls.is_empty() || p[1] < self.origin[1] || p[1] > far[1] || p[0] > far[0];
+        // An empty run of cells when there's nothing to the right
+        let (y, first) = if outside {
+            (0, self.size[0])
+        } else {
+            (self.cell_of(p[1], 1), self.cell_of(p[0], 0))
+        };
+        (first..self.size[0]).flat_map(move |x| {
+            // A wall spanning several cells counts in the first of them the walk reaches
+            self.cell_walls(x, y)
+                .filter(move |&i| self.ranges[i].0.max(first) == x)
+        })
+    }
+
+    // Cells the segment `from`-`to` passes through, in order from `from`, stepping
+    // across whichever boundary it reaches first; none if it misses the grid
+    fn cells_along(&self, from: [f32; 2], to: [f32; 2]) -> CellWalk {
+        let mut walk = CellWalk {
+            cell: [0, 0],
+            step: [1, 1],
+            t_next: [f32::INFINITY; 2],
+            t_delta: [0.0; 2],
+            t_end: 0.0,
+            size: self.size,
+            done: true,
+        };
+        if self.walls.is_empty() {
+            return walk;
+        }
+        // Clip the segment to the grid, as t from 0 at `from` to 1 at `to`
+        let d = [to[0] - from[0], to[1] - from[1]];
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        for axis in 0..2 {
+            let lo = self.origin[axis];
+            let hi = lo + self.size[axis] as f32 * self.cell;
+            if d[axis].abs() < f32::EPSILON {
+                if from[axis] < lo || from[axis] > hi {
+                    return walk;
+                }
+                continue;
+            }
+            let (a, b) = ((lo - from[axis]) / d[axis], (hi - from[axis]) / d[axis]);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 {
+            return walk;
+        }
+
+        let start = [from[0] + d[0] * t0, from[1] + d[1] * t0];
+        walk.cell = [0, 1].map(|axis| self.cell_of(start[axis], axis) as isize);
+        walk.step = d.map(|d| if d < 0.0 { -1 } else { 1 });
+        walk.t_delta = d.map(|d| self.cell / d.abs());
+        walk.t_next = [0, 1].map(|axis| {
+            if d[axis].abs() < f32::EPSILON {
+                return f32::INFINITY; // never crosses this axis's boundaries
+            }
+            let offset = if walk.step[axis] > 0 { 1 } else { 0 };
+            let boundary = self.origin[axis] + (walk.cell[axis] + offset) as f32 * self.cell;
+            (boundary - from[axis]) / d[axis]
+        });
+        walk.t_end = t1;
+        walk.done = false;
+        walk
+    }
+
+    fn cell_walls(&self, x: usize, y: usize) -> impl Iterator<Item = usize> + '_ {
+        let i = x + y * self.size[0];
+        let range = self.starts[i] as usize..self.starts[i + 1] as usize;
+        self.walls[range].iter().map(|&w| w as usize)
+    }
+
+    // Max corner of the grid
+    fn far(&self) -> [f32; 2] {
+        [
+            self.origin[0] + self.size[0] as f32 * self.cell,
+            self.origin[1] + self.size[1] as f32 * self.cell,
+        ]
+    }
+
+    #[inline]
+    fn cell_of(&self, v: f32, axis: usize) -> usize {
+        let c = ((v - self.origin[axis]) / self.cell).max(0.0) as usize;
+        c.min(self.size[axis] - 1)
+    }
+
+    // Cells (x0, y0)..=(x1, y1) covering the box with corners `a` and `b`
+    fn cell_range(&self, a: [f32; 2], b: [f32; 2]) -> CellRange {
+        (
+            self.cell_of(a[0].min(b[0]), 0),
+            self.cell_of(a[1].min(b[1]), 1),
+            self.cell_of(a[0].max(b[0]), 0),
+            self.cell_of(a[1].max(b[1]), 1),
+        )
+    }
+}
+
+// Smallest cell side `c` for a grid over `extent` to have fewer than MAX_CELLS cells. There
+// are at most e / c + 1 cells across an axis of extent e, so with n = MAX_CELLS - 1 this is
+// the root of (e0 + c)(e1 + c) = n c², which holds on long thin maps as well as square ones.
+fn fitting_cell(extent: [f32; 2]) -> f32 {
+    let n = (MAX_CELLS - 1) as f64;
+    let (e0, e1) = (extent[0] as f64, extent[1] as f64);
+    let (sum, product) = (e0 + e1, e0 * e1);
+    ((sum + (sum * sum + 4.0 * (n - 1.0) * product).sqrt()) / (2.0 * (n - 1.0))) as f32
+}
+
+// Grid traversal of a segment, one cell at a time
+struct CellWalk {
+    cell: [isize; 2],
+    step: [isize; 2],  // -1 or 1 along each axis
+    t_next: [f32; 2],  // where the segment next crosses a cell boundary on each axis
+    t_delta: [f32; 2], // between boundaries on each axis
+    t_end: f32,        // where the segment leaves the grid or ends
+    size: [usize; 2],
+    done: bool,
+}
+
+impl Iterator for CellWalk {
+    type Item = [usize; 2];
+
+    fn next(&mut self) -> Option<[usize; 2]> {
+        if self.done {
+            return None;
+        }
+        let here = self.cell.map(|c| c as usize);
+        let axis = if self.t_next[0] < self.t_next[1] {
+            0
+        } else {
+            1
+        };
+        self.cell[axis] += self.step[axis];
+        self.done = self.t_next[axis] > self.t_end
+            || self.cell[axis] < 0
+            || self.cell[axis] >= self.size[axis] as isize;
+        self.t_next[axis] += self.t_delta[axis];
+        Some(here)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Special;
+
+    fn wall(start: [f32; 2], end: [f32; 2]) -> Wall {
+        Wall {
+            start,
+            end,
+            front_sector: 0,
+            back_sector: None,
+            texture: 0,
+            tag: 0,
+            special: Special::None,
+            mid: None,
+            scroll: [0.0, 0.0],
+            offset: [0.0, 0.0],
+            peg_upper: false,
+            peg_lower: false,
+            teleport: None,
+            walkover: None,
+        }
+    }
+
+    // A 40 x 40 box, 11 cells a side, with a short diagonal in the middle and a long
+    // horizontal wall across the upper half
+    fn grid() -> WallGrid {
+        WallGrid::build(&[
+            wall([0.0, 0.0], [40.0, 0.0]),
+            wall([0.0, 40.0], [40.0, 40.0]),
+            wall([0.0, 0.0], [0.0, 40.0]),
+            wall([40.0, 0.0], [40.0, 40.0]),
+            wall([18.0, 18.0], [22.0, 22.0]),
+            wall([10.0, 30.0], [30.0, 30.0]),
+        ])
+    }
+
+    fn sorted(walls: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut walls: Vec<_> = walls.collect();
+        walls.sort_unstable();
+        walls
+    }
+
+    #[test]
+    fn box_finds_each_wall_once() {
+        let grid = grid();
+        assert_eq!(sorted(grid.walls_in([17.0, 17.0], [23.0, 23.0])), [4]);
+        assert_eq!(sorted(grid.walls_in([5.0, 28.0], [35.0, 32.0])), [5]);
+        assert_eq!(
+            sorted(grid.walls_in([0.0, 0.0], [40.0, 40.0])),
+            [0, 1, 2, 3, 4, 5]
+        );
+        // Overhanging the grid on every side
+        assert_eq!(
+            sorted(grid.walls_in([-100.0, -100.0], [100.0, 100.0])),
+            [0, 1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn box_outside_finds_nothing() {
+        let grid = grid();
+        assert_eq!(grid.walls_in([-10.0, -10.0], [-1.0, -1.0]).count(), 0);
+        // Past the last cell, which reaches a little beyond the walls
+        assert_eq!(grid.walls_in([45.0, 0.0], [50.0, 40.0]).count(), 0);
+    }
+
+    #[test]
+    fn segment_along_x_from_outside() {
+        let grid = grid();
+        let found: Vec<_> = grid.walls_along([-5.0, 30.0], [45.0, 30.0]).collect();
+        assert_eq!(found, [2, 5, 3]);
+        // And back the other way
+        let found: Vec<_> = grid.walls_along([45.0, 30.0], [-5.0, 30.0]).collect();
+        assert_eq!(found, [3, 5, 2]);
+    }
+
+    #[test]
+    fn segment_along_y() {
+        let grid = grid();
+        let found: Vec<_> = grid.walls_along([20.0, 45.0], [20.0, -5.0]).collect();
+        assert_eq!(found, [1, 5, 4, 0]);
+    }
+
+    #[test]
+    fn segment_stops_at_its_end() {
+        let grid = grid();
+        let found: Vec<_> = grid.walls_along([20.0, 10.0], [20.0, 25.0]).collect();
+        assert_eq!(found, [4]);
+    }
+
+    #[test]
+    fn diagonal_segments() {
+        let grid = grid();
+        let found: Vec<_> = grid.walls_along([1.0, 2.0], [39.0, 38.0]).collect();
+        assert_eq!(found, [0, 2, 4, 5]);
+        // Through cell corners exactly
+        assert_eq!(
+            sorted(grid.walls_along([1.0, 1.0], [39.0, 39.0])),
+            [0, 2, 4, 5]
+        );
+        let found: Vec<_> = grid.walls_along([39.0, 38.0], [1.0, 2.0]).collect();
+        assert_eq!(found, [5, 4, 2, 0]);
+    }
+
+    #[test]
+    fn segment_outside_finds_nothing() {
+        let grid = grid();
+        assert_eq!(grid.walls_along([-5.0, -5.0], [-1.0, 50.0]).count(), 0);
+        assert_eq!(grid.walls_along([-5.0, 45.0], [45.0, 45.0]).count(), 0);
+        // Pointed at the grid but stopping short of it
+        assert_eq!(grid.walls_along([-20.0, 20.0], [-10.0, 20.0]).count(), 0);
+    }
+
+    #[test]
+    fn row_to_the_right() {
+        let grid = grid();
+        let found: Vec<_> = grid.walls_right_of([15.0, 30.0]).collect();
+        assert_eq!(found, [5, 3]);
+        assert_eq!(grid.walls_right_of([45.0, 30.0]).count(), 0);
+    }
+
+    #[test]
+    fn long_thin_map_stays_within_max_cells() {
+        for length in [1.0e6, 1.0e7] {
+            let grid = WallGrid::build(&[wall([0.0, 0.0], [length, 1.0])]);
+            assert!(grid.size[0] * grid.size[1] <= MAX_CELLS);
+            assert_eq!(sorted(grid.walls_in([5.0e5, 0.0], [5.0e5, 1.0])), [0]);
+        }
+
+        // And a square one, where the cell from area alone leaves a row and column over
+        let grid = WallGrid::build(&[wall([0.0, 0.0], [2048.0, 2048.0])]);
+        assert!(grid.size[0] * grid.size[1] <= MAX_CELLS);
+    }
+
+    #[test]
+    fn empty_map() {
+        let grid = WallGrid::build(&[]);
+        assert_eq!(grid.walls_in([-1.0, -1.0], [1.0, 1.0]).count(), 0);
+        assert_eq!(grid.walls_along([-1.0, -1.0], [1.0, 1.0]).count(), 0);
+        assert_eq!(grid.walls_right_of([0.0, 0.0]).count(), 0);
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use gilrs::{Axis, Button, Gamepad};
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+/// Movement requested for one tick, in camera space
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MoveIntent {
+    pub forward: f32, // +1 forward, -1 back
+    pub strafe: f32,  // +1 right, -1 left
+    pub turn: f32,    // +1 right, -1 left; scaled by the turn speed
+    pub jump: bool,
+    pub crouch: bool,
+    pub activate: bool, // "use": open doors and other tagged walls
+    #[serde(default)] // absent from demos recorded before there was a weapon
+    pub fire: bool,
+}
+
+impl MoveIntent {
+    /// Combine two sources, keeping the move vector no longer than 1 so nothing moves faster
+    pub fn merge(self, other: MoveIntent) -> MoveIntent {
+        let mut out = MoveIntent {
+            forward: self.forward + other.forward,
+            strafe: self.strafe + other.strafe,
+            turn: (self.turn + other.turn).clamp(-1.0, 1.0),
+            jump: self.jump || other.jump,
+            crouch: self.crouch || other.crouch,
+            activate: self.activate || other.activate,
+            fire: self.fire || other.fire,
+        };
+        let len = (out.forward * out.forward + out.strafe * out.strafe).sqrt();
+        if len > 1.0 {
+            out.forward /= len;
+            out.strafe /= len;
+        }
+        out
+    }
+}
+
+/// Keys and stick axes mapped onto movement
+pub struct Bindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub strafe_left: KeyCode,
+    pub strafe_right: KeyCode,
+    pub turn_left: KeyCode,
+    pub turn_right: KeyCode,
+    pub jump: KeyCode,
+    pub crouch: [KeyCode; 2], // either Ctrl key
+    pub activate: KeyCode,
+    pub fire: KeyCode, // as well as the left mouse button
+
+    pub move_x: Axis,
+    pub move_y: Axis,
+    pub turn_x: Axis,
+    pub dead_zone: f32, // stick deflection ignored around the center, 0..1
+    pub jump_button: Button,
+    pub crouch_button: Button,
+    pub activate_button: Button,
+    pub fire_button: Button,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            strafe_left: KeyCode::KeyA,
+            strafe_right: KeyCode::KeyD,
+            turn_left: KeyCode::KeyQ,
+            turn_right: KeyCode::KeyE,
+            jump: KeyCode::Space,
+            crouch: [KeyCode::ControlLeft, KeyCode::ControlRight],
+            activate: KeyCode::KeyR,
+            fire: KeyCode::KeyF,
+
+            move_x: Axis::LeftStickX,
+            move_y: Axis::LeftStickY,
+            turn_x: Axis::RightStickX,
+            dead_zone: 0.15,
+            jump_button: Button::South,
+            crouch_button: Button::East,
+            activate_button: Button::West,
+            fire_button: Button::RightTrigger2,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn keyboard(&self, keys_down: &HashSet<KeyCode>) -> MoveIntent {
+        let axis = |neg: KeyCode, pos: KeyCode| {
+            keys_down.contains(&pos) as i32 as f32 - keys_down.contains(&neg) as i32 as f32
+        };
+        // Merging with nothing normalizes diagonal speed
+        MoveIntent {
+            forward: axis(self.back, self.forward),
+            strafe: axis(self.strafe_left, self.strafe_right),
+            turn: axis(self.turn_left, self.turn_right),
+            jump: keys_down.contains(&self.jump),
+            crouch: self.crouch.iter().any(|k| keys_down.contains(k)),
+            activate: keys_down.contains(&self.activate),
+            fire: keys_down.contains(&self.fire),
+        }
+        .merge(MoveIntent::default())
+    }
+
+    pub fn gamepad(&self, pad: &Gamepad<'_>) -> MoveIntent {
+        // Stick Y is positive when pushed up, which is forward
+        let (strafe, forward) = radial_dead_zone(
+            pad.value(self.move_x),
+            pad.value(self.move_y),
+            self.dead_zone,
+        );
+        let (turn, _) = radial_dead_zone(pad.value(self.turn_x), 0.0, self.dead_zone);
+        MoveIntent {
+            forward,
+            strafe,
+            turn,
+            jump: pad.is_pressed(self.jump_button),
+            crouch: pad.is_pressed(self.crouch_button),
+            activate: pad.is_pressed(self.activate_button),
+            fire: pad.is_pressed(self.fire_button),
+        }
+    }
+}
+
+// Zero inside the dead zone and rescale the rest to 0..1, so motion starts smoothly at its edge
+fn radial_dead_zone(x: f32, y: f32, dead_zone: f32) -> (f32, f32) {
+    let len = (x * x + y * y).sqrt();
+    if len <= dead_zone {
+        return (0.0, 0.0);
+    }
+    let scaled = ((len - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    (x / len * scaled, y / len * scaled)
+}
+
+// Keys that can be bound by name in the config, besides letters and digits
+const NAMED_KEYS: [KeyCode; 24] = [
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+];
+
+const LETTER_KEYS: [KeyCode; 26] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+];
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// The config's name for a key: winit's, without the "Key" and "Digit" prefixes, so "W",
+/// "7", "Space", "ControlLeft"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    let short = name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"));
+    short.unwrap_or(&name).to_string()
+}
+
+/// A key from its config name, ignoring case; None for names that aren't bindable
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    LETTER_KEYS
+        .iter()
+        .chain(&DIGIT_KEYS)
+        .chain(&NAMED_KEYS)
+        .copied()
+        .find(|&key| key_name(key).eq_ignore_ascii_case(name.trim()))
+}
@@ -0,0 +1,171 @@
+// What the player carries: keys for locked doors and ammo for the weapon, topped up by
+// walking over pickup entities, along with the HUD icons that show it
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::EntityId;
+use crate::events::Event;
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::player::Player;
+use crate::renderer::pack_rgb;
+use crate::world::World;
+
+pub const START_AMMO: u32 = 50;
+pub const MAX_AMMO: u32 = 200;
+// How close the player's center has to come to a pickup's to take it
+pub const PICKUP_RADIUS: f32 = 0.6;
+
+/// Opens doors locked with the same color
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Key {
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl Key {
+    pub const ALL: [Key; 3] = [Key::Red, Key::Yellow, Key::Blue];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Key::Red => "red",
+            Key::Yellow => "yellow",
+            Key::Blue => "blue",
+        }
+    }
+
+    /// HUD icon color
+    pub fn color(self) -> u32 {
+        match self {
+            Key::Red => pack_rgb(220, 40, 40),
+            Key::Yellow => pack_rgb(230, 200, 40),
+            Key::Blue => pack_rgb(50, 90, 230),
+        }
+    }
+}
+
+/// What an entity gives the player who touches it, taken only if it's any use to them
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Pickup {
+    Health { amount: f32 }, // up to `player::MAX_HEALTH`
+    Ammo { amount: u32 },   // up to `MAX_AMMO`
+    Key(Key),
+}
+
+impl Pickup {
+    /// For the HUD message, e.g. "the red key"
+    pub fn describe(self) -> String {
+        match self {
+            Pickup::Health { amount } => format!("{} health", amount.ceil() as u32),
+            Pickup::Ammo { amount } => format!("{amount} ammo"),
+            Pickup::Key(key) => format!("the {} key", key.name()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    keys: [bool; Key::ALL.len()], // by `Key as usize`
+    pub ammo: u32,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self {
+            keys: [false; Key::ALL.len()],
+            ammo: START_AMMO,
+        }
+    }
+
+    pub fn has_key(&self, key: Key) -> bool {
+        self.keys[key as usize]
+    }
+
+    /// Add `key`; false if it was already held
+    pub fn give_key(&mut self, key: Key) -> bool {
+        !std::mem::replace(&mut self.keys[key as usize], true)
+    }
+
+    /// Add `amount` of ammo up to `MAX_AMMO`; false if already full
+    pub fn give_ammo(&mut self, amount: u32) -> bool {
+        if self.ammo >= MAX_AMMO {
+            return false;
+        }
+        self.ammo = (self.ammo + amount).min(MAX_AMMO);
+        true
+    }
+
+    /// Use up a round; false if there are none left
+    pub fn take_ammo(&mut self) -> bool {
+        let Some(left) = self.ammo.checked_sub(1) else {
+            return false;
+        };
+        self.ammo = left;
+        true
+    }
+
+    /// Ammo and the keys held in the bottom-right corner, keys as swatches of their color
+    pub fn draw_hud(&self, buf: &mut [u32], width: usize, height: usize) {
+        // Sized like the health readout across from it
+        let scale = (height / 240).max(1);
+        let margin = 2 * scale;
+        let y = height.saturating_sub(margin + GLYPH_HEIGHT * scale);
+        let label = format!("ammo {}", self.ammo);
+        let color = if self.ammo == 0 {
+            pack_rgb(255, 60, 60)
+        } else {
+            pack_rgb(255, 255, 255)
+        };
+        let mut x = width.saturating_sub(margin + font::text_width(&label, scale));
+        font::draw_text(buf, width, height, [x, y], &label, color, scale);
+
+        let size = GLYPH_HEIGHT * scale;
+        for key in Key::ALL.into_iter().rev().filter(|&k| self.has_key(k)) {
+            x = x.saturating_sub(size + 2 * margin);
+            for row in y..(y + size).min(height) {
+                let end = (x + size).min(width);
+                buf[row * width + x..row * width + end].fill(key.color());
+            }
+        }
+    }
+}
+
+/// Give `player` whatever pickups they're touching at `pos`, between `feet_z` and `top_z`,
+/// that they have a use for. Each one taken is despawned and published as
+/// `Event::PickedUp`.
+pub fn collect(world: &mut World, pos: [f32; 2], feet_z: f32, top_z: f32, player: &mut Player) {
+    if player.is_dead() {
+        return;
+    }
+    let touching: Vec<(EntityId, Pickup)> = world
+        .entities
+        .ids()
+        .filter_map(|id| {
+            let pickup = world.entities.pickups[id]?;
+            let t = &world.entities.transforms[id];
+            let (dx, dy) = (t.pos[0] - pos[0], t.pos[1] - pos[1]);
+            let height = world.entities.sprites[id].map_or(0.0, |s| s.world_height());
+            let near = dx * dx + dy * dy <= PICKUP_RADIUS * PICKUP_RADIUS;
+            (near && t.z <= top_z && t.z + height >= feet_z).then_some((id, pickup))
+        })
+        .collect();
+    for (id, pickup) in touching {
+        if !player.pick_up(pickup) {
+            continue;
+        }
+        let pos = world.entities.transforms[id].pos;
+        world.entities.despawn(id);
+        world.events.publish(Event::PickedUp {
+            entity: id,
+            pos,
+            pickup,
+        });
+    }
+}
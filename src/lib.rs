@@ -0,0 +1,63 @@
+//! Software 2.5D renderer: sector-and-portal worlds drawn column by column into a
+//! plain `u32` framebuffer, with no window or GPU dependency.
+//!
+//! Build or load a [`World`], place a [`Camera`], then call [`Renderer::render`] each
+//! frame. [`Scaler`] can stretch the (usually small) render target to the window, and
+//! [`Automap`] draws a top-down view of the same world.
+//!
+//! Warnings and status are logged with `tracing` under per-subsystem targets
+//! (`renderer`, `audio`, `world`, ...), for whatever subscriber the embedder installs.
+
+#![allow(non_snake_case)] // the package name predates the library
+
+pub mod ai;
+pub mod assets;
+pub mod audio;
+pub mod automap;
+pub mod bsp;
+pub mod camera;
+pub mod chase;
+pub mod collision;
+pub mod console;
+pub mod crosshair;
+pub mod decals;
+pub mod entity;
+pub mod events;
+pub mod font;
+pub mod formats;
+pub mod framebuffer;
+pub mod grid;
+pub mod inventory;
+pub mod light_effects;
+pub mod menu;
+pub mod messages;
+pub mod palette;
+pub mod particles;
+pub mod physics;
+pub mod player;
+pub mod procgen;
+pub mod profiler;
+pub mod pvs;
+pub mod raycast;
+pub mod renderer;
+pub mod rng;
+pub mod save;
+pub mod scaler;
+pub mod screen_flash;
+pub mod script;
+pub mod sector_effects;
+pub mod status_bar;
+pub mod texture;
+pub mod view_effects;
+pub mod viewport;
+pub mod voxel;
+pub mod weapon;
+pub mod world;
+
+pub use automap::Automap;
+pub use camera::Camera;
+pub use renderer::{Renderer, pack_rgb};
+pub use scaler::Scaler;
+pub use texture::{Texture, TextureId};
+pub use viewport::Viewport;
+pub use world::{PlayerStart, Sector, Thing, Wall, World};
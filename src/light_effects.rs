@@ -0,0 +1,74 @@
+// Sector lights that change by themselves: flickering like a failing lamp, glowing up and
+// down smoothly, or strobing on and off. Each effect owns its sector's light level and
+// rewrites it every tick, from the level the map gave the sector.
+
+use crate::rng::Rng;
+use crate::world::World;
+
+// Seconds a flickering light holds each level, at least and at most
+const FLICKER_MIN_HOLD: f32 = 0.05;
+const FLICKER_MAX_HOLD: f32 = 0.25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LightKind {
+    /// Jumps at random between `min` and the sector's level
+    Flicker { min: f32 },
+    /// Eases between `min` and `max` and back every `period` seconds
+    Glow { min: f32, max: f32, period: f32 },
+    /// The sector's level for `on` seconds out of every `period`, `min` the rest
+    Strobe { min: f32, period: f32, on: f32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct LightEffect {
+    pub sector: usize,
+    pub kind: LightKind,
+    level: f32, // the sector's own, what flicker and strobe light up to
+    time: f32,  // seconds into the cycle, or until the next flicker
+}
+
+impl LightEffect {
+    /// `level` is the sector's starting light level
+    pub fn new(kind: LightKind, sector: usize, level: f32) -> Self {
+        Self {
+            sector,
+            kind,
+            level,
+            time: 0.0,
+        }
+    }
+
+    fn update(&mut self, light_level: &mut f32, dt: f32, rng: &mut Rng) {
+        match self.kind {
+            LightKind::Flicker { min } => {
+                self.time -= dt;
+                if self.time <= 0.0 {
+                    self.time = rng.range(FLICKER_MIN_HOLD, FLICKER_MAX_HOLD);
+                    *light_level = rng.range(min, self.level);
+                }
+            }
+            LightKind::Glow { min, max, period } => {
+                self.time = (self.time + dt) % period;
+                let wave = 0.5 - 0.5 * (self.time / period * std::f32::consts::TAU).cos();
+                *light_level = min + (max - min) * wave;
+            }
+            LightKind::Strobe { min, period, on } => {
+                self.time = (self.time + dt) % period;
+                *light_level = if self.time < on { self.level } else { min };
+            }
+        }
+    }
+}
+
+/// Advance every light effect by `dt`, setting its sector's light level
+pub fn update(world: &mut World, dt: f32) {
+    let World {
+        sectors,
+        lights,
+        rng,
+        ..
+    } = world;
+    for light in lights.iter_mut() {
+        light.update(&mut sectors[light.sector].light_level, dt, rng);
+    }
+}
@@ -1,201 +1,623 @@
 use std::collections::HashSet;
 use std::num::NonZeroU32;
-use std::rc::Rc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
+use tracing::{error, info, trace_span, warn};
+use tracing_subscriber::EnvFilter;
 use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
-use winit::event::{KeyEvent, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Window, WindowId};
 
-use crate::camera::Camera;
-use crate::scaler::{ScaleLut, blit_bilinear_stretch, build_scale_lut, sharpen3x3_cross_inplace};
-use crate::world::{Sector, Wall, World};
+use two_halfD_engine::ai::{self, AiEvent};
+use two_halfD_engine::assets::DEFAULT_ASSET_DIR;
+use two_halfD_engine::audio::Audio;
+use two_halfD_engine::camera::{DEFAULT_FOV, MAX_FOV_X};
+use two_halfD_engine::chase::{self, Chase};
+use two_halfD_engine::collision::{self, PLAYER_RADIUS};
+use two_halfD_engine::console::Console;
+use two_halfD_engine::crosshair::Crosshair;
+use two_halfD_engine::entity::EntityId;
+use two_halfD_engine::events::Event;
+use two_halfD_engine::inventory;
+use two_halfD_engine::menu::MenuKey;
+use two_halfD_engine::messages::Messages;
+use two_halfD_engine::palette::Palette;
+use two_halfD_engine::physics::{
+    self, CROUCH_EYE_HEIGHT, HEAD_ABOVE_EYE, STAND_EYE_HEIGHT, VerticalBody,
+};
+use two_halfD_engine::player::Player;
+use two_halfD_engine::procgen;
+use two_halfD_engine::profiler::{Profiler, Stage};
+use two_halfD_engine::renderer::ViewOffset;
+use two_halfD_engine::save::{self, SaveGame, WorldState};
+use two_halfD_engine::scaler::{ColorAdjust, CrtParams, PresentTimings, RenderScale, ScaleMode};
+use two_halfD_engine::screen_flash::ScreenFlash;
+use two_halfD_engine::script::Script;
+use two_halfD_engine::status_bar::{HudStyle, StatusBar};
+use two_halfD_engine::view_effects::ViewEffects;
+use two_halfD_engine::viewport::SplitLayout;
+use two_halfD_engine::weapon::{Shot, Weapon};
+use two_halfD_engine::world::{Material, Special, Teleport};
+use two_halfD_engine::{Automap, Camera, Renderer, Scaler, World, pack_rgb, world};
+use two_halfD_engine::{entity, light_effects, sector_effects};
 
-mod camera;
-mod renderer;
-mod scaler;
-mod world;
+use crate::bench::{BENCH_DT, Bench, DEFAULT_BENCH_FRAMES, FrameSample};
+use crate::capture::Capture;
+use crate::clock::GameClock;
+use crate::config::{CONFIG_PATH, Config, load_config, save_config};
+use crate::error::EngineError;
+use crate::gpu::{Backend, GpuPresenter};
+use crate::input::{Bindings, MoveIntent};
+use crate::net::client::NetStatus;
+use crate::net::{DEFAULT_PORT, NetClient, NetInput, Server};
+use crate::pacing::{DEFAULT_TARGET_FPS, FramePacer};
+use crate::pause::{PauseMenu, PauseOutcome};
+use crate::recording::{Demo, DemoPlayer, DemoTick};
+use crate::resize::ResizeDebouncer;
+use crate::session::GameSession;
+use crate::watch::FileWatcher;
+
+mod bench;
+mod capture;
+mod clock;
+mod config;
+mod error;
+mod gpu;
+mod input;
+mod net;
+mod pacing;
+mod pause;
+mod recording;
+mod resize;
+mod session;
+mod watch;
+
+// F5 writes and F9 reads this, in the working directory
+const QUICKSAVE_PATH: &str = "quicksave.toml";
+
+// Presents failing this many times in a row, despite setting presenting up again after
+// each, end the process
+const MAX_PRESENT_FAILURES: u32 = 3;
+
+// What gets logged when RUST_LOG doesn't say, e.g. RUST_LOG=info,net=debug,renderer=warn
+const DEFAULT_LOG_FILTER: &str = "info";
+
+// Narrowest field of view the config may ask for
+const MIN_FOV: f32 = 60.0;
+
+// Distance walked between footstep sounds
+const STEP_LENGTH: f32 = 0.8;
+// Landing at least this fast (m/s) kicks up dust
+const DUST_FALL_SPEED: f32 = 4.0;
+// Landing at least this fast (m/s) is heard, loudest from `DUST_FALL_SPEED * 2.0` up
+const LAND_FALL_SPEED: f32 = 2.5;
+// Horizontal speed while swimming, as a fraction of walking speed
+const SWIM_MOVE_SCALE: f32 = 0.6;
+// Flying speed with Shift held in noclip, as a multiple of walking speed
+const FLY_FAST_SCALE: f32 = 4.0;
+// Drifting further than this from where the server has us snaps back to it
+const NET_CORRECTION: f32 = 0.75;
+
+// Demo recording (--record) or playback (--playdemo); the input source for every tick
+enum DemoMode {
+    Off,
+    Recording { path: String, demo: Demo },
+    Playing(DemoPlayer),
+}
+
+type SoftwareSurface = softbuffer::Surface<Arc<Window>, Arc<Window>>;
 
 struct App {
-    window: Option<Rc<Window>>,
-    surface: Option<softbuffer::Surface<Rc<Window>, Rc<Window>>>,
+    window: Option<Arc<Window>>,
+    backend: Backend,
+    surface: Option<SoftwareSurface>, // software backend
+    gpu: Option<GpuPresenter>,        // gpu backend
+    present_failures: u32,            // in a row, reset by a frame that makes it out
     world: World,
+    map_path: Option<String>,         // None for the built-in demo
+    map_watcher: Option<FileWatcher>, // reloads `map_path` when it changes, outside demos
+    generated: Option<u64>,           // --gen-map seed; such a map has no file for saves to name
+    session: Option<GameSession>,     // --campaign: the maps exits lead through
+    script: Option<Script>,           // the current map's, if it names one
+    net: Option<NetClient>,           // --connect or --host: other players come from here
+    net_frags: u16,                   // ours, as of the last snapshot
     camera: Camera,
+    fov: f32,       // degrees across a 4:3 screen, widened for wider ones
+    config: Config, // player settings, as last loaded or changed in the menu
+    config_watcher: Option<FileWatcher>, // reloads `config` when it's edited, if it says to
+    pause: Option<PauseMenu>, // open menu; the game doesn't tick under it
+    layout: SplitLayout, // how the frame is shared with `second_camera`
+    second_camera: Camera, // a fixed viewpoint, shown in split and picture-in-picture
+    body: VerticalBody, // drives camera.eye_z
+    noclip: Option<Camera>, // (N) flying free while set, from this camera
+    velocity: [f32; 2], // horizontal, m/s, lagging behind the input on slippery floors
+    last_sector: Option<usize>, // the player was in last tick, to notice entering another
+    player: Player, // health and armor, kept from map to map
+    renderer: Renderer,
+    time: f32, // seconds of game time, for animated effects
 
     // HUD
+    messages: Messages,
+    crosshair: Crosshair,  // styled by `config`
+    status_bar: StatusBar, // shown when `config.hud` is `HudStyle::Full`
     frame_counter: u32,
     last_fps_print: Instant,
+    pacer: FramePacer,
+    profiler: Profiler,
+    profiler_open: bool,  // F3
+    console: Console,     // (`) developer commands
+    bench: Option<Bench>, // --bench: scripted camera, timed frames
+    bench_csv: Option<String>,
+    capture: Option<Capture>,             // F10
+    capture_writers: Vec<JoinHandle<()>>, // stopped captures still encoding
 
-    // Internal 640x480 buffer
+    // Internal buffer, its height set by the render scale and its width by the window aspect
     fb_small: Vec<u32>,
+    present_size: [usize; 2], // window size the framebuffer and scaler are set up for
+    resize: ResizeDebouncer,
+    view_fb: Vec<u32>, // the player's view when it's only part of the frame
     fb_w: usize,
     fb_h: usize,
+    render_scale: RenderScale,
 
-    scale_lut: ScaleLut,
+    scaler: Scaler,
 
     // Input and movement
+    bindings: Bindings,
+    activate_held: bool, // Use was down last tick
+    dry_fired: bool,     // the empty click has played for this pull of the trigger
     keys_down: HashSet<KeyCode>,
-    last_tick: Instant,
+    gilrs: Option<gilrs::Gilrs>, // None when no gamepad backend is available
+    clock: GameClock,            // game time: paused, stepped or scaled from the console
+    demo: DemoMode,
     move_speed: f32,
     turn_speed: f32,
+
+    // Mouse look
+    mouse_captured: bool,
+    mouse_dx: f32,          // accumulated raw horizontal motion since last tick
+    mouse_sensitivity: f32, // radians per mouse count
+    mouse_fire: bool,       // left button held
+
+    weapon: Weapon,
+    chase: Chase,
+    third_person: bool,        // (T) seen from behind through `chase`
+    view_effects: ViewEffects, // shown when `config.view_effects` is set
+    screen_flash: ScreenFlash, // red when hurt, yellow on pickups, green underwater
+
+    // Sound effects; None without an output device
+    audio: Option<Audio>,
+    stride: f32, // walked since the last footstep
+
+    // Automap (Tab)
+    automap: Automap,
+    automap_open: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
-        let sector = Sector {
-            floor_z: 0.0,
-            ceiling_z: 3.0,
-        };
-        let walls = vec![
-            Wall {
-                start: [-1.0, 8.0],
-                end: [1.0, 8.0],
-                front_sector: 0,
-                back_sector: None,
-            },
-            Wall {
-                start: [1.0, 8.0],
-                end: [1.0, 10.0],
-                front_sector: 0,
-                back_sector: None,
-            },
-            Wall {
-                start: [1.0, 10.0],
-                end: [-1.0, 10.0],
-                front_sector: 0,
-                back_sector: None,
-            },
-            Wall {
-                start: [-1.0, 10.0],
-                end: [-1.0, 8.0],
-                front_sector: 0,
-                back_sector: None,
-            },
-        ];
-
+        let mut world = World::demo();
+        let mut weapon = Weapon::new();
+        weapon.attach(&mut world);
+        let mut chase = Chase::new();
+        chase.attach(&mut world);
         Self {
             window: None,
+            backend: Backend::Software,
             surface: None,
-            world: World {
-                sectors: vec![sector],
-                walls,
-            },
+            gpu: None,
+            present_failures: 0,
+            world,
+            map_path: None,
+            map_watcher: None,
+            generated: None,
+            session: None,
+            script: None,
+            net: None,
+            net_frags: 0,
             camera: Camera {
                 pos: [0.0, 0.0],
-                yaw: 0.0,   // facing along +Y axis
-                eye_z: 1.7, // eye height
+                yaw: 0.0,                // facing along +Y axis
+                eye_z: STAND_EYE_HEIGHT, // eye height
+                fx: 0.0,
+                fy: 0.0,
+            },
+            fov: DEFAULT_FOV,
+            config: Config::default(),
+            config_watcher: None,
+            pause: None,
+            layout: SplitLayout::Single,
+            second_camera: Camera {
+                pos: [0.0, 0.0],
+                yaw: 0.0,
+                eye_z: STAND_EYE_HEIGHT,
                 fx: 0.0,
                 fy: 0.0,
             },
+            body: VerticalBody::new(0.0),
+            noclip: None,
+            velocity: [0.0, 0.0],
+            last_sector: None,
+            player: Player::new(),
+            renderer: Renderer::new(),
+            time: 0.0,
 
+            messages: Messages::default(),
+            crosshair: Crosshair::default(),
+            status_bar: StatusBar::load(DEFAULT_ASSET_DIR),
             frame_counter: 0,
             last_fps_print: Instant::now(),
+            pacer: FramePacer::new(Some(DEFAULT_TARGET_FPS)),
+            profiler: Profiler::new(),
+            profiler_open: false,
+            console: Console::new(),
+            bench: None,
+            bench_csv: None,
+            capture: None,
+            capture_writers: Vec::new(),
 
             fb_small: vec![0; 640 * 480],
+            present_size: [0, 0],
+            resize: ResizeDebouncer::default(),
+            view_fb: Vec::new(),
             fb_w: 640,
             fb_h: 480,
+            render_scale: RenderScale::default(),
 
-            scale_lut: ScaleLut::empty(),
+            scaler: Scaler::new(0, 0, 640, 480, ScaleMode::Bilinear),
 
+            bindings: Bindings::default(),
+            activate_held: false,
+            dry_fired: false,
             keys_down: HashSet::new(),
-            last_tick: Instant::now(),
+            gilrs: gilrs::Gilrs::new()
+                .inspect_err(|err| warn!(target: "input", "Gamepad support unavailable: {err}"))
+                .ok(),
+            clock: GameClock::new(Instant::now()),
+            demo: DemoMode::Off,
             move_speed: 3.0,                  // m/s
             turn_speed: std::f32::consts::PI, // rad/s
+
+            mouse_captured: false,
+            mouse_dx: 0.0,
+            mouse_sensitivity: 0.0025,
+            mouse_fire: false,
+
+            weapon,
+            chase,
+            third_person: false,
+            view_effects: ViewEffects::new(),
+            screen_flash: ScreenFlash::new(),
+
+            audio: Audio::new(DEFAULT_ASSET_DIR)
+                .inspect_err(|err| warn!(target: "audio", "Sound unavailable: {err}"))
+                .ok(),
+            stride: 0.0,
+
+            automap: Automap::default(),
+            automap_open: false,
         }
     }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let attributes = Window::default_attributes()
-            .with_title("2.5D Engine")
-            .with_inner_size(LogicalSize::new(800.0, 600.0));
-
-        let window = Rc::new(event_loop.create_window(attributes).expect("create window"));
-
-        let context = softbuffer::Context::new(window.clone()).expect("softbuffer context");
-        let surface =
-            softbuffer::Surface::new(&context, window.clone()).expect("softbuffer surface");
+        let window = match self.open_window(event_loop) {
+            Ok(window) => window,
+            Err(err) => {
+                error!(target: "renderer", "{err}");
+                event_loop.exit();
+                return;
+            }
+        };
 
         // Update camera focal factors
         let size = window.inner_size();
         self.rebuild_internal_fb_and_lut(size.width as usize, size.height as usize);
 
-        self.surface = Some(surface);
-        self.window = Some(window);
-
-        self.last_tick = Instant::now();
-        self.window.as_ref().unwrap().request_redraw();
+        self.clock.hold(Instant::now());
+        window.request_redraw();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                info!(target: "app", "The close button was pressed; stopping");
                 event_loop.exit();
             }
 
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
-                        physical_key,
+                        physical_key: PhysicalKey::Code(code),
                         state,
+                        repeat,
+                        text,
                         ..
                     },
                 ..
             } => {
-                if let PhysicalKey::Code(code) = physical_key {
-                    use winit::event::ElementState;
-                    match state {
-                        ElementState::Pressed => {
-                            self.keys_down.insert(code);
-                        }
-                        ElementState::Released => {
-                            self.keys_down.remove(&code);
+                use winit::event::ElementState;
+                if state.is_pressed()
+                    && self.pause.is_none()
+                    && (code == KeyCode::Backquote || self.console.is_open())
+                {
+                    self.console_key(code, text.as_deref());
+                    return;
+                }
+                if state.is_pressed() && (code == KeyCode::Escape || self.pause.is_some()) {
+                    self.pause_menu_key(event_loop, code);
+                    return;
+                }
+                match state {
+                    ElementState::Pressed => {
+                        if !repeat {
+                            match code {
+                                KeyCode::KeyM => self.set_mouse_capture(!self.mouse_captured),
+                                KeyCode::KeyV => {
+                                    self.config.scale_mode = self.config.scale_mode.next();
+                                    self.config_changed();
+                                    info!(
+                                        target: "renderer",
+                                        "Scale mode: {:?}",
+                                        self.scaler.mode()
+                                    );
+                                }
+                                KeyCode::KeyC => {
+                                    self.config.crt = !self.config.crt;
+                                    self.config_changed();
+                                }
+                                KeyCode::BracketLeft | KeyCode::BracketRight => {
+                                    let scale = self.config.render_scale;
+                                    self.config.render_scale = if code == KeyCode::BracketLeft {
+                                        scale.prev()
+                                    } else {
+                                        scale.next()
+                                    };
+                                    self.config_changed();
+                                    info!(
+                                        target: "renderer",
+                                        "Render scale: {:?}",
+                                        self.render_scale
+                                    );
+                                }
+                                // A load mid-demo would desync it from its recording
+                                KeyCode::F5 | KeyCode::F9
+                                    if !matches!(self.demo, DemoMode::Off) =>
+                                {
+                                    info!(
+                                        target: "app",
+                                        "Quick save and load are off during demos"
+                                    );
+                                }
+                                KeyCode::F5 if self.generated.is_some() => {
+                                    info!(target: "app", "Quick save is off on generated maps");
+                                }
+                                KeyCode::F5 => self.quick_save(),
+                                KeyCode::F9 => self.quick_load(),
+                                KeyCode::KeyP => {
+                                    let palette = match self.renderer.palette() {
+                                        Some(_) => None,
+                                        None => Some(Palette::default()),
+                                    };
+                                    info!(
+                                        target: "renderer",
+                                        "Palette rendering: {}",
+                                        palette.is_some()
+                                    );
+                                    self.renderer.set_palette(palette);
+                                }
+                                // Gamma down and up, for displays that crush dark sectors; the
+                                // automap zooms with the same keys
+                                KeyCode::Minus | KeyCode::Equal if !self.automap_open => {
+                                    let step = if code == KeyCode::Minus { -0.1 } else { 0.1 };
+                                    let gamma = self.config.gamma + step;
+                                    let (lo, hi) = ColorAdjust::GAMMA_RANGE;
+                                    self.config.gamma =
+                                        ((gamma * 10.0).round() / 10.0).clamp(lo, hi);
+                                    self.config_changed();
+                                    info!(
+                                        target: "renderer",
+                                        "Gamma: {:.1}",
+                                        self.config.gamma
+                                    );
+                                }
+                                KeyCode::KeyB => {
+                                    self.config.dither = !self.config.dither;
+                                    self.config_changed();
+                                    info!(
+                                        target: "renderer",
+                                        "Dithering: {}",
+                                        self.config.dither
+                                    );
+                                }
+                                KeyCode::F3 => self.profiler_open = !self.profiler_open,
+                                KeyCode::F4 => {
+                                    let view = self.renderer.debug_view().next();
+                                    self.renderer.set_debug_view(view);
+                                    info!(target: "renderer", "Debug view: {view:?}");
+                                }
+                                KeyCode::F10 => self.toggle_capture(),
+                                KeyCode::KeyL => {
+                                    // The second camera stays where the player stood
+                                    if self.layout == SplitLayout::Single {
+                                        self.second_camera = self.camera;
+                                    }
+                                    self.layout = self.layout.next();
+                                    info!(target: "renderer", "View layout: {:?}", self.layout);
+                                }
+                                KeyCode::Tab => self.automap_open = !self.automap_open,
+                                KeyCode::KeyH => {
+                                    self.config.hud = self.config.hud.next();
+                                    self.config_changed();
+                                    info!(target: "renderer", "HUD: {:?}", self.config.hud);
+                                }
+                                KeyCode::KeyN => self.toggle_noclip(),
+                                KeyCode::KeyT => {
+                                    self.third_person = !self.third_person;
+                                    info!(target: "renderer", "Third person: {}", self.third_person);
+                                }
+                                KeyCode::KeyF if self.automap_open => {
+                                    self.automap.follow = !self.automap.follow;
+                                }
+                                _ => (),
+                            }
                         }
+                        self.keys_down.insert(code);
+                    }
+                    ElementState::Released => {
+                        self.keys_down.remove(&code);
                     }
                 }
             }
 
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => self.mouse_fire = state.is_pressed(),
+
+            WindowEvent::Focused(false) => {
+                // Give the pointer back when alt-tabbing away
+                self.set_mouse_capture(false);
+                self.keys_down.clear();
+                self.mouse_fire = false;
+            }
+
             WindowEvent::RedrawRequested => {
-                self.tick();
+                let _frame = trace_span!(target: "renderer", "frame").entered();
+                let frame_start = Instant::now();
+                let since_last_frame = self
+                    .bench
+                    .as_mut()
+                    .and_then(|bench| bench.frame_started(frame_start));
+                if self.pause.is_some() {
+                    // The world holds still behind the menu, though a server keeps going
+                    self.clock.hold(frame_start);
+                    self.sync_net(MoveIntent::default());
+                } else if let Some(dt) = self.clock.advance(frame_start) {
+                    let Some(input) = self.bench_input().or_else(|| self.next_input(dt)) else {
+                        if let DemoMode::Playing(player) = &self.demo {
+                            // Where the run ended, to compare against the recording
+                            info!(
+                                target: "app",
+                                "Demo finished after {} ticks at {:?}, yaw {}",
+                                player.played(),
+                                self.camera.pos,
+                                self.camera.yaw
+                            );
+                        }
+                        event_loop.exit();
+                        return;
+                    };
+                    let tick_start = Instant::now();
+                    self.tick(input);
+                    self.profiler.record(Stage::Tick, tick_start.elapsed());
+                } else {
+                    // Paused from the console: frames keep being drawn over a world that holds
+                    // still, and looking around waits for it to run again
+                    self.mouse_dx = 0.0;
+                }
 
-                let (window, surface) = match (&self.window, &mut self.surface) {
-                    (Some(w), Some(s)) if w.id() == id => (w, s),
-                    _ => return,
-                };
+                if self.window.as_ref().is_none_or(|w| w.id() != id) {
+                    return;
+                }
 
-                let size = window.inner_size();
-                let (dw, dh) = (size.width as usize, size.height as usize);
+                // The size last taken up, which lags the window's while it's being dragged
+                let [dw, dh] = self.present_size;
                 if dw == 0 || dh == 0 {
                     return; // Minimized window, skip drawing
                 }
 
-                // Set softbuffer to window size
-                surface
-                    .resize(
-                        NonZeroU32::new(dw as u32).unwrap(),
-                        NonZeroU32::new(dh as u32).unwrap(),
-                    )
-                    .unwrap();
-
-                renderer::render_frame(
-                    &mut self.fb_small,
-                    self.fb_w,
-                    self.fb_h,
-                    &self.world,
-                    &self.camera,
-                );
-
-                let mut buf = surface.buffer_mut().expect("buffer_mut");
-                blit_bilinear_stretch(&mut buf, dw, &self.fb_small, self.fb_w, &self.scale_lut);
+                let render_start = Instant::now();
+                if self.automap_open {
+                    self.automap.draw(
+                        &mut self.fb_small,
+                        self.fb_w,
+                        self.fb_h,
+                        &self.world,
+                        &self.camera,
+                    );
+                } else {
+                    self.draw_views();
+                    self.player
+                        .draw_death(&mut self.fb_small, self.fb_w, self.fb_h);
+                    match self.config.hud {
+                        HudStyle::Full => self.status_bar.draw(
+                            &mut self.fb_small,
+                            self.fb_w,
+                            self.fb_h,
+                            &self.player,
+                        ),
+                        HudStyle::Minimal => {
+                            self.player
+                                .draw_hud(&mut self.fb_small, self.fb_w, self.fb_h)
+                        }
+                        HudStyle::None => {}
+                    }
+                    if !self.player.is_dead() && !self.third_person {
+                        let view = self.layout.viewports(self.fb_w, self.view_height())[0];
+                        self.crosshair.draw(&mut self.fb_small, self.fb_w, view);
+                    }
+                    self.messages.draw(&mut self.fb_small, self.fb_w, self.fb_h);
+                }
+                self.console.draw(&mut self.fb_small, self.fb_w, self.fb_h);
+                if let Some(pause) = &self.pause {
+                    pause.draw(&mut self.fb_small, self.fb_w, self.fb_h);
+                }
+                let render = render_start.elapsed();
+                if self.profiler_open {
+                    self.profiler
+                        .draw_overlay(&mut self.fb_small, self.fb_w, self.fb_h);
+                }
+                if let Some(capture) = &mut self.capture
+                    && !capture.frame(&self.fb_small, self.fb_w, self.fb_h, frame_start)
+                {
+                    self.stop_capture();
+                }
 
-                sharpen3x3_cross_inplace(&mut buf, dw, dh);
+                let presented = if self.gpu.is_some() {
+                    self.present_gpu()
+                } else if self.surface.is_some() {
+                    self.present_software(dw, dh)
+                } else {
+                    return;
+                };
+                let (present, surface_time) = match presented {
+                    Ok(timings) => {
+                        self.present_failures = 0;
+                        timings
+                    }
+                    Err(err) => {
+                        self.recover_presenting(err, event_loop);
+                        return;
+                    }
+                };
+                self.profiler.record_present(present);
+                self.profiler.record(Stage::Present, surface_time);
+                self.profiler.end_frame();
 
-                buf.present().unwrap();
+                if let Some(bench) = &mut self.bench {
+                    // The first frame has nothing to be timed against
+                    if let Some(frame) = since_last_frame {
+                        bench.record(FrameSample {
+                            frame,
+                            render,
+                            present,
+                            surface: surface_time,
+                        });
+                    }
+                    if bench.is_done() {
+                        self.finish_bench();
+                        event_loop.exit();
+                        return;
+                    }
+                }
 
                 // Print FPS
                 self.frame_counter += 1;
@@ -203,76 +625,184 @@ impl ApplicationHandler for App {
                 if now.duration_since(self.last_fps_print).as_secs_f32() >= 1.0 {
                     let fps = self.frame_counter as f32
                         / now.duration_since(self.last_fps_print).as_secs_f32();
-                    println!("FPS: {:.1}", fps);
+                    info!(target: "renderer", fps = (fps * 10.0).round() / 10.0, "frame rate");
                     self.frame_counter = 0;
                     self.last_fps_print = now;
                 }
-
-                self.window.as_ref().unwrap().request_redraw();
             }
 
             WindowEvent::Resized(new_size) => {
-                let (dw, dh) = (new_size.width as usize, new_size.height as usize);
-                // Update internal window
-                self.rebuild_internal_fb_and_lut(dw, dh);
+                let size = [new_size.width as usize, new_size.height as usize];
+                let now = Instant::now();
+                if let Some([dw, dh]) = self.resize.resized(self.present_size, size, now) {
+                    // Update internal window
+                    self.rebuild_internal_fb_and_lut(dw, dh);
+                }
             }
             _ => (),
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let Some(window) = &self.window {
-            window.request_redraw();
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        // Raw motion keeps working when the cursor is locked at the window edge
+        if let DeviceEvent::MouseMotion { delta } = event
+            && self.mouse_captured
+        {
+            self.mouse_dx += delta.0 as f32;
         }
     }
-}
 
-impl App {
-    fn tick(&mut self) {
-        // Compute dt with cap to avoid huge jumps if the app was paused
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         let now = Instant::now();
-        let mut dt = now.duration_since(self.last_tick);
-        self.last_tick = now;
-        if dt > Duration::from_millis(100) {
-            dt = Duration::from_millis(100);
+        if self.map_watcher.as_mut().is_some_and(|w| w.poll(now)) {
+            self.reload_map();
         }
-        let dt_s = dt.as_secs_f32();
+        if self.config_watcher.as_mut().is_some_and(|w| w.poll(now)) {
+            self.config = load_config(CONFIG_PATH);
+            self.apply_config();
+            info!(target: "app", "Reloaded {CONFIG_PATH}");
+        }
+        if let Some([dw, dh]) = self.resize.poll(now) {
+            self.rebuild_internal_fb_and_lut(dw, dh);
+        }
+        let Some(window) = &self.window else {
+            return;
+        };
+        let (redraw, control_flow) = self.pacer.poll(now);
+        if redraw {
+            window.request_redraw();
+        }
+        event_loop.set_control_flow(control_flow);
+    }
 
-        // Build movement vector in camera space
-        let mut fwd = 0.0;
-        let mut strafe = 0.0;
-        if self.keys_down.contains(&KeyCode::KeyW) {
-            fwd += 1.0;
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(net) = &self.net {
+            net.leave();
         }
-        if self.keys_down.contains(&KeyCode::KeyS) {
-            fwd -= 1.0;
+        // Let captures finish writing rather than leave a truncated file
+        self.stop_capture();
+        for writer in self.capture_writers.drain(..) {
+            let _ = writer.join();
         }
-        if self.keys_down.contains(&KeyCode::KeyD) {
-            strafe += 1.0;
+        if let DemoMode::Recording { path, demo } = &self.demo {
+            match recording::write_demo(path, demo) {
+                Ok(()) => info!(
+                    target: "app",
+                    "Recorded {} ticks to {path}, ending at {:?}, yaw {}",
+                    demo.ticks.len(),
+                    self.camera.pos,
+                    self.camera.yaw
+                ),
+                Err(err) => error!(target: "app", "{path}: {err}"),
+            }
         }
-        if self.keys_down.contains(&KeyCode::KeyA) {
-            strafe -= 1.0;
+    }
+}
+
+impl App {
+    // This tick's input: live, recorded as it's read when recording, or the demo's next tick.
+    // None when a demo has run out.
+    // `dt` is the game clock's; a demo plays back with the one it was recorded with.
+    fn next_input(&mut self, dt: f32) -> Option<DemoTick> {
+        // Keyboard and every connected gamepad feed the same movement vector
+        let mut intent = self.bindings.keyboard(&self.keys_down);
+        intent.fire |= self.mouse_fire;
+        if let Some(gilrs) = &mut self.gilrs {
+            // Drain events so gilrs keeps its cached axis state current
+            while gilrs.next_event().is_some() {}
+            for (_, pad) in gilrs.gamepads() {
+                intent = intent.merge(self.bindings.gamepad(&pad));
+            }
         }
+        let live = DemoTick {
+            dt,
+            mouse_dx: std::mem::take(&mut self.mouse_dx),
+            intent,
+        };
+
+        match &mut self.demo {
+            DemoMode::Off => Some(live),
+            DemoMode::Recording { demo, .. } => {
+                demo.ticks.push(live);
+                Some(live)
+            }
+            DemoMode::Playing(player) => player.next_tick(),
+        }
+    }
+
+    // Under --bench the camera follows the bench path and the world steps at a fixed rate
+    fn bench_input(&mut self) -> Option<DemoTick> {
+        let bench = self.bench.as_ref()?;
+        (self.camera.pos, self.camera.yaw) = bench.camera_at(&self.world);
+        Some(DemoTick {
+            dt: BENCH_DT,
+            mouse_dx: 0.0,
+            intent: MoveIntent::default(),
+        })
+    }
 
-        // Normalize diagonal speed
-        if fwd != 0.0 || strafe != 0.0 {
-            let len = ((fwd * fwd + strafe * strafe) as f32).sqrt();
-            let inv = 1.0 / len;
-            fwd *= inv;
-            strafe *= inv;
+    fn finish_bench(&self) {
+        let Some(bench) = &self.bench else {
+            return;
+        };
+        println!("{}", bench.report());
+        if let Some(path) = &self.bench_csv {
+            match bench.write_csv(path) {
+                Ok(()) => info!(target: "app", "Wrote frame times to {path}"),
+                Err(err) => error!(target: "app", "{path}: {err}"),
+            }
         }
+    }
+
+    fn tick(&mut self, input: DemoTick) {
+        let DemoTick {
+            dt: dt_s,
+            mut mouse_dx,
+            mut intent,
+        } = input;
+        self.time += dt_s;
+        self.world.time += dt_s;
 
-        // Turn with Q/E
-        let mut yaw_delta = 0.0;
-        if self.keys_down.contains(&KeyCode::KeyQ) {
-            yaw_delta -= 1.0;
+        // Last tick's events, for each subsystem that reacts to them
+        let events = self.world.events.take();
+        sector_effects::handle(&mut self.world, &events);
+        if let Some(script) = &mut self.script {
+            for message in script.handle(&mut self.world, &events) {
+                self.messages.push(message);
+            }
         }
-        if self.keys_down.contains(&KeyCode::KeyE) {
-            yaw_delta += 1.0;
+        self.play_event_sounds(&events);
+        let exited = events.iter().any(|event| {
+            matches!(
+                event,
+                Event::WallUsed {
+                    special: Special::Exit,
+                    ..
+                } | Event::LineCrossed {
+                    special: Special::Exit,
+                    ..
+                }
+            )
+        });
+
+        // The dead only look on, until Use gets them back up
+        self.player.update(dt_s);
+        if self.player.is_dead() {
+            if intent.activate && !self.activate_held && self.player.can_respawn() {
+                self.respawn();
+            }
+            // Held, so the press that respawned doesn't also use a wall
+            self.activate_held = intent.activate;
+            intent = MoveIntent {
+                activate: intent.activate,
+                ..MoveIntent::default()
+            };
+            mouse_dx = 0.0;
         }
 
-        // Apply yaw
-        self.camera.yaw += yaw_delta * self.turn_speed * dt_s;
+        // Apply yaw from keys, sticks and mouse
+        self.camera.yaw += intent.turn * self.turn_speed * dt_s;
+        self.camera.yaw += mouse_dx * self.mouse_sensitivity;
         // Keep yaw in [-pi, pi] to avoid float drift
         if self.camera.yaw > std::f32::consts::PI {
             self.camera.yaw -= 2.0 * std::f32::consts::PI;
@@ -280,27 +810,1135 @@ impl App {
         if self.camera.yaw < -std::f32::consts::PI {
             self.camera.yaw += 2.0 * std::f32::consts::PI;
         }
+        self.sync_net(intent);
+
+        // Move in world space based on yaw, or free of the world while flying
+        let walked = if self.noclip.is_some() {
+            self.fly(intent, dt_s);
+            0.0
+        } else {
+            self.walk(intent, dt_s)
+        };
+
+        // Doors and lifts move before the player lands on them
+        let mut occupied = self.world.sector_at(self.camera.pos);
+        if occupied != self.last_sector {
+            self.last_sector = occupied;
+            if let Some(sector) = occupied {
+                self.world.events.publish(Event::SectorEntered { sector });
+                // A teleporter sector sends us on at once, and arriving in another doesn't
+                if self.noclip.is_none()
+                    && let Some(teleport) = self.world.sectors[sector].teleport()
+                    && self.teleport(teleport)
+                {
+                    occupied = self.world.sector_at(self.camera.pos);
+                    self.last_sector = occupied;
+                    if let Some(sector) = occupied {
+                        self.world.events.publish(Event::SectorEntered { sector });
+                    }
+                }
+            }
+        }
+        // Use fires once per press, so a held key doesn't flip switches every frame
+        if intent.activate && !self.activate_held {
+            self.world
+                .use_line(self.camera.pos, self.camera.yaw, &self.player.inventory);
+        }
+        self.activate_held = intent.activate;
+        sector_effects::update(&mut self.world, dt_s, occupied.as_slice());
+        self.world.update_switches(dt_s);
+        light_effects::update(&mut self.world, dt_s);
+        if let Some(script) = &mut self.script {
+            for message in script.update(&mut self.world, dt_s) {
+                self.messages.push(message);
+            }
+        }
+        self.messages.update(dt_s);
+        self.crosshair.update(dt_s);
+        self.status_bar.update(dt_s);
+        if let Some(audio) = &mut self.audio {
+            audio.update(dt_s);
+        }
+        let target = ai::Target {
+            pos: self.camera.pos,
+            eye_z: self.camera.eye_z,
+        };
+        for event in ai::update(&mut self.world, target, dt_s) {
+            let (sound, entity) = match event {
+                AiEvent::Sighted { entity } => ("sight", entity),
+                AiEvent::Attack { entity } => ("attack", entity),
+            };
+            if let Some(audio) = &mut self.audio {
+                let pos = self.world.entities.transforms[entity].pos;
+                audio.play_at(sound, pos, &self.camera, &self.world);
+            }
+            if let AiEvent::Attack { .. } = event {
+                self.hurt(ai::ATTACK_DAMAGE);
+            }
+        }
+        let pace = if dt_s > 0.0 {
+            walked / (self.move_speed * dt_s)
+        } else {
+            0.0
+        };
+        self.fire_weapon(intent.fire && !self.automap_open, walked, pace, dt_s);
+        self.view_effects.update(dt_s, walked, pace, intent.strafe);
+        let riding = self.riding();
+        entity::update(&mut self.world, dt_s);
+        self.world.particles.update(dt_s);
+        if self.noclip.is_none() {
+            self.ride(riding);
+            occupied = self.world.sector_at(self.camera.pos);
+        }
+
+        // Fall, jump and crouch against the sector we're standing in, on or under its slab
+        if self.noclip.is_none()
+            && let Some(s) = occupied
+        {
+            let sector = &self.world.sectors[s];
+            // Platforms and crates underfoot are floor too, and standing up and head bumps
+            // go by the lowest ceiling over any of the body
+            let (floor_z, ceiling_z) = collision::floor_ceiling_under(
+                &self.world,
+                s,
+                self.camera.pos,
+                PLAYER_RADIUS,
+                self.body.feet_z,
+            );
+            let (airborne, fall_speed) = (!self.body.on_ground, -self.body.vz);
+            if let Some(liquid) = sector.liquid {
+                // Jump swims up and crouch dives
+                let swim = intent.jump as i32 as f32 - intent.crouch as i32 as f32;
+                if intent.jump && !self.body.swimming {
+                    self.body.jump();
+                }
+                self.body.update_in_liquid(
+                    dt_s,
+                    floor_z,
+                    ceiling_z,
+                    liquid.surface_z,
+                    swim,
+                    intent.crouch,
+                );
+            } else {
+                if intent.jump {
+                    self.body.jump();
+                }
+                self.body.update(dt_s, floor_z, ceiling_z, intent.crouch);
+            }
+            if airborne
+                && self.body.on_ground
+                && fall_speed >= DUST_FALL_SPEED
+                && sector.liquid.is_none()
+            {
+                let light = sector.light_level;
+                self.world.particles.dust(self.camera.pos, floor_z, light);
+            }
+            if airborne && self.body.on_ground && fall_speed >= LAND_FALL_SPEED {
+                let material = self.underfoot();
+                let volume = (fall_speed / (DUST_FALL_SPEED * 2.0)).min(1.0);
+                if let Some(audio) = &mut self.audio {
+                    audio.play_scaled(material.landing_sound(), volume);
+                }
+            }
+
+            // Damaging floors hurt underfoot, and their liquid hurts all the way up; a crusher
+            // come down lower than we can crouch under hurts wherever we are
+            let in_liquid = sector
+                .liquid
+                .is_some_and(|l| self.body.feet_z < l.surface_z);
+            let mut per_second = if self.body.on_ground || in_liquid {
+                sector.damage_per_second()
+            } else {
+                0.0
+            };
+            let body = collision::sectors_under(&self.world, self.camera.pos, PLAYER_RADIUS);
+            let crouched_top_z = self.body.feet_z + CROUCH_EYE_HEIGHT + HEAD_ABOVE_EYE;
+            if sector_effects::crusher_over(&self.world, &body, crouched_top_z).is_some() {
+                per_second += sector_effects::CRUSH_DAMAGE;
+            }
+            if per_second > 0.0 {
+                let amount = self.player.hazard(dt_s, per_second);
+                self.hurt(amount);
+            }
+        }
+        if self.noclip.is_none() {
+            let eye_height = self.player.eye_height(self.body.eye_height);
+            self.camera.eye_z = self.body.feet_z + eye_height;
+            let top_z = self.body.feet_z + self.body.eye_height + HEAD_ABOVE_EYE;
+            inventory::collect(
+                &mut self.world,
+                self.camera.pos,
+                self.body.feet_z,
+                top_z,
+                &mut self.player,
+            );
+        }
+        let submerged = self
+            .world
+            .sector_at(self.camera.pos)
+            .and_then(|s| self.world.sectors[s].liquid)
+            .is_some_and(|l| self.camera.eye_z < l.surface_z);
+        self.screen_flash.update(dt_s, submerged);
+
+        if self.automap_open {
+            self.tick_automap(dt_s);
+        }
+        // Last, as it replaces the world the rest of the tick worked on
+        if exited {
+            self.exit_map();
+        }
+    }
+
+    // Walk, slide along walls and step to the sound of the floor; the distance covered
+    fn walk(&mut self, intent: MoveIntent, dt_s: f32) -> f32 {
+        let mut walked = 0.0;
+        let (fwd, strafe) = (intent.forward, intent.strafe);
+        let c = self.camera.yaw.cos();
+        let s = self.camera.yaw.sin();
+        // forward vector (0, +1) rotated by yaw = (s, c) in +Y forward convention
+        let dir_fwd = [s, c];
+        let dir_right = [c, -s]; // perpendicular (right-hand)
+        let speed = if self.body.swimming {
+            self.move_speed * SWIM_MOVE_SCALE
+        } else {
+            self.move_speed
+        };
+        let wanted = [
+            (dir_fwd[0] * fwd + dir_right[0] * strafe) * speed,
+            (dir_fwd[1] * fwd + dir_right[1] * strafe) * speed,
+        ];
+
+        // Slippery floors only slide what stands on them; sector pushes come on top
+        let sector = self.world.sector_at(self.camera.pos);
+        let sector = sector.map(|s| &self.world.sectors[s]);
+        let on_floor = self.body.on_ground && !self.body.swimming;
+        let friction = sector.filter(|_| on_floor).map_or(1.0, |s| s.friction());
+        self.velocity = physics::walk_velocity(self.velocity, wanted, friction, dt_s);
+        let push = sector.map_or([0.0, 0.0], |s| s.push(on_floor));
+        let dx = (self.velocity[0] + push[0]) * dt_s;
+        let dy = (self.velocity[1] + push[1]) * dt_s;
+
+        if dx != 0.0 || dy != 0.0 {
+            let from = self.camera.pos;
+            self.camera.pos = collision::slide_move(
+                &self.world,
+                from,
+                [dx, dy],
+                PLAYER_RADIUS,
+                self.body.feet_z,
+                self.body.eye_height + HEAD_ABOVE_EYE,
+            );
+            self.world.cross_lines(from, self.camera.pos);
+            if let Some(teleport) = self.world.crossed_teleport(from, self.camera.pos) {
+                self.teleport(teleport);
+            }
+
+            // Footsteps by distance actually covered, so walking into a wall is silent; being
+            // carried along isn't walking
+            if on_floor && (fwd != 0.0 || strafe != 0.0) {
+                let (mx, my) = (self.camera.pos[0] - from[0], self.camera.pos[1] - from[1]);
+                walked = (mx * mx + my * my).sqrt();
+                self.stride += walked;
+                if self.stride >= STEP_LENGTH {
+                    self.stride -= STEP_LENGTH;
+                    // Steps come quicker and louder the faster we go
+                    let pace = (walked / (self.move_speed * dt_s)).min(1.0);
+                    let material = self.underfoot();
+                    if let Some(audio) = &mut self.audio {
+                        audio.play_scaled(material.footstep_sound(), 0.4 + 0.6 * pace);
+                    }
+                }
+            }
+        }
+        walked
+    }
+
+    // The platform we're standing on and where it is, to go along when it moves
+    fn riding(&self) -> Option<(EntityId, [f32; 2])> {
+        if self.noclip.is_some() || !self.body.on_ground {
+            return None;
+        }
+        collision::riding(
+            &self.world,
+            self.camera.pos,
+            PLAYER_RADIUS,
+            self.body.feet_z,
+        )
+    }
+
+    // Carried along by the platform we stood on, crossing lines on the way
+    fn ride(&mut self, riding: Option<(EntityId, [f32; 2])>) {
+        let from = self.camera.pos;
+        self.camera.pos = collision::ride(
+            &self.world,
+            riding,
+            from,
+            PLAYER_RADIUS,
+            self.body.feet_z,
+            self.body.eye_height + HEAD_ABOVE_EYE,
+        );
+        self.world.cross_lines(from, self.camera.pos);
+    }
+
+    // Noclip: straight along the view and up or down with jump and crouch, through
+    // anything, quicker with Shift held
+    fn fly(&mut self, intent: MoveIntent, dt_s: f32) {
+        let shift = self.keys_down.contains(&KeyCode::ShiftLeft)
+            || self.keys_down.contains(&KeyCode::ShiftRight);
+        let speed = self.move_speed * if shift { FLY_FAST_SCALE } else { 1.0 } * dt_s;
+        let (s, c) = self.camera.yaw.sin_cos();
+        let (fwd, strafe) = (intent.forward, intent.strafe);
+        self.camera.pos[0] += (s * fwd + c * strafe) * speed;
+        self.camera.pos[1] += (c * fwd - s * strafe) * speed;
+        let rise = intent.jump as i32 as f32 - intent.crouch as i32 as f32;
+        self.camera.eye_z += rise * speed;
+    }
+
+    // Noclip on, or off: back on foot falling from where the flight ended if that's in the
+    // map, or where it began if not
+    fn toggle_noclip(&mut self) {
+        if !matches!(self.demo, DemoMode::Off) || self.bench.is_some() || self.net.is_some() {
+            info!(target: "app", "Noclip is off during demos and in multiplayer");
+            return;
+        }
+        let Some(start) = self.noclip.take() else {
+            self.noclip = Some(self.camera);
+            self.velocity = [0.0, 0.0];
+            info!(target: "app", "Noclip: on");
+            return;
+        };
+        match self.world.sector_at(self.camera.pos) {
+            Some(s) => {
+                let feet_z = self.camera.eye_z - self.body.eye_height;
+                let (floor_z, ceiling_z) = self.world.floor_ceiling_at(s, feet_z);
+                let top_z = ceiling_z - self.body.eye_height - HEAD_ABOVE_EYE;
+                self.body.feet_z = feet_z.min(top_z).max(floor_z);
+                self.body.vz = 0.0;
+                self.body.on_ground = false;
+                self.camera.eye_z = self.body.eye_z();
+            }
+            None => {
+                self.camera.pos = start.pos;
+                self.camera.eye_z = start.eye_z;
+            }
+        }
+        info!(target: "app", "Noclip: off");
+    }
+
+    // Move on to the campaign's next map, starting over at its player start. Demos stay on
+    // the map they were recorded on.
+    fn exit_map(&mut self) {
+        if !matches!(self.demo, DemoMode::Off) {
+            info!(target: "app", "Exit reached");
+            return;
+        }
+        // The server stays on its map
+        if self.net.is_some() {
+            info!(target: "app", "Exit reached; exits are off in multiplayer");
+            return;
+        }
+        let Some(session) = &mut self.session else {
+            info!(target: "app", "Exit reached; start with --campaign to go on to another map");
+            return;
+        };
+        let Some(path) = session.advance().map(str::to_string) else {
+            info!(target: "app", "Campaign complete");
+            return;
+        };
+        let (number, count) = session.progress();
+        match world::loader::load_map(&path) {
+            Ok(world) => {
+                self.set_world(world);
+                self.map_path = Some(path.clone());
+                self.watch_map();
+                info!(target: "world", "Map {number} of {count}: {path}");
+            }
+            Err(err) => error!(target: "world", "{path}: {err}"),
+        }
+    }
+
+    // What the floor we're standing on sounds like; wading through liquid sounds like water
+    fn underfoot(&self) -> Material {
+        let Some(s) = self.world.sector_at(self.camera.pos) else {
+            return Material::default();
+        };
+        let sector = &self.world.sectors[s];
+        match sector.liquid {
+            Some(l) if self.body.feet_z < l.surface_z => Material::Water,
+            _ => sector.material,
+        }
+    }
+
+    // Damage the player; the cry of pain or of death follows with the event
+    fn hurt(&mut self, amount: f32) {
+        if amount <= 0.0 || self.player.is_dead() || self.noclip.is_some() {
+            return;
+        }
+        let killed = self.player.damage(amount);
+        self.view_effects.flinch(amount);
+        self.screen_flash.damage(amount);
+        self.status_bar.pain();
+        self.world
+            .events
+            .publish(Event::PlayerDamaged { amount, killed });
+    }
+
+    // Sounds for used walls, pickups, teleports and the player getting hurt
+    fn play_event_sounds(&mut self, events: &[Event]) {
+        let wall_middle = |wall: usize| {
+            let wall = &self.world.walls[wall];
+            [
+                0.5 * (wall.start[0] + wall.end[0]),
+                0.5 * (wall.start[1] + wall.end[1]),
+            ]
+        };
+        for event in events {
+            match *event {
+                Event::WallUsed { wall, special, .. } => {
+                    let sound = match special {
+                        Special::Door | Special::LockedDoor { .. } => "door",
+                        Special::Switch { .. } | Special::Exit => "switch",
+                        Special::None => continue,
+                    };
+                    let middle = wall_middle(wall);
+                    if let Some(audio) = &mut self.audio {
+                        audio.play_at(sound, middle, &self.camera, &self.world);
+                    }
+                }
+                Event::DoorLocked { wall, key } => {
+                    self.messages
+                        .push(format!("You need the {} key for this door", key.name()));
+                    let middle = wall_middle(wall);
+                    if let Some(audio) = &mut self.audio {
+                        audio.play_at("locked", middle, &self.camera, &self.world);
+                    }
+                }
+                Event::PickedUp { pickup, .. } => {
+                    self.messages
+                        .push(format!("Picked up {}", pickup.describe()));
+                    self.screen_flash.pickup();
+                    if let Some(audio) = &mut self.audio {
+                        audio.play("pickup");
+                    }
+                }
+                Event::Teleported {
+                    to, flash: true, ..
+                } => {
+                    if let Some(audio) = &mut self.audio {
+                        audio.play_at("teleport", to, &self.camera, &self.world);
+                    }
+                }
+                Event::PlayerDamaged { killed, .. } => {
+                    if killed {
+                        info!(target: "app", "You died; press Use to respawn");
+                    }
+                    if let Some(audio) = &mut self.audio {
+                        audio.play(if killed { "death" } else { "pain" });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Back on your feet at the player start, the map as it was left
+    fn respawn(&mut self) {
+        self.player.respawn();
+        self.move_to_start();
+    }
+
+    // Send the server this tick's input and take its word on where we are and how we're
+    // doing; the other players show up as sprites
+    fn sync_net(&mut self, intent: MoveIntent) {
+        let Some(net) = &mut self.net else {
+            return;
+        };
+        let now = Instant::now();
+        net.send_input(
+            NetInput {
+                seq: 0, // numbered by the client
+                forward: intent.forward,
+                strafe: intent.strafe,
+                yaw: self.camera.yaw,
+                jump: intent.jump,
+                crouch: intent.crouch,
+                fire: intent.fire && !self.automap_open,
+                activate: intent.activate,
+            },
+            now,
+        );
+        let status = net.status();
+        let fresh = net.poll(now);
+        if net.status() != status {
+            self.messages.push(match net.status() {
+                NetStatus::Joining => "Joining".to_string(),
+                NetStatus::Joined { id } => format!("Joined as player {id}"),
+                NetStatus::Full => "The server is full".to_string(),
+                NetStatus::Lost => "Lost the connection to the server".to_string(),
+            });
+        }
+        if !fresh {
+            return;
+        }
+        net.place_players(&mut self.world);
+        let Some(own) = net.own_state() else {
+            return;
+        };
+
+        // Moving ahead of the server is expected; being somewhere else entirely isn't
+        let (dx, dy) = (
+            own.pos[0] - self.camera.pos[0],
+            own.pos[1] - self.camera.pos[1],
+        );
+        if dx * dx + dy * dy > NET_CORRECTION * NET_CORRECTION {
+            self.camera.pos = own.pos;
+            self.body.feet_z = own.feet_z;
+            self.velocity = [0.0, 0.0];
+        }
+        if own.frags != self.net_frags {
+            self.net_frags = own.frags;
+            self.messages.push(format!("Frags: {}", own.frags));
+        }
+        // Health is the server's alone; hurt here too so the pain and death sounds play
+        if self.player.is_dead() && !own.dead {
+            self.player.respawn();
+        } else if own.health < self.player.health {
+            self.hurt(self.player.health - own.health);
+        }
+        if !self.player.is_dead() {
+            self.player.health = own.health;
+        }
+    }
+
+    fn fire_weapon(&mut self, fire: bool, walked: f32, pace: f32, dt_s: f32) {
+        self.weapon.update(dt_s, walked, pace);
+        if !fire {
+            self.dry_fired = false;
+            return;
+        }
+        if self.player.inventory.ammo == 0 {
+            if self.weapon.ready() && !self.dry_fired {
+                self.dry_fired = true;
+                if let Some(audio) = &mut self.audio {
+                    audio.play("empty");
+                }
+            }
+            return;
+        }
+        let Some(shot) = self.weapon.fire(&mut self.world, &self.camera) else {
+            return;
+        };
+        self.player.inventory.take_ammo();
+        if let Shot::Entity { .. } = shot {
+            self.crosshair.hit();
+        }
+        if let Shot::Wall { point, z, .. } = shot {
+            // Sparks fly back toward the shooter from just in front of the wall
+            let (dx, dy) = (self.camera.pos[0] - point[0], self.camera.pos[1] - point[1]);
+            let len = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+            let toward = [dx / len, dy / len];
+            let pos = [point[0] + toward[0] * 0.05, point[1] + toward[1] * 0.05];
+            let (floor_z, light) =
+                self.world
+                    .sector_at(pos)
+                    .map_or((f32::NEG_INFINITY, 1.0), |s| {
+                        let sector = &self.world.sectors[s];
+                        (sector.floor_z, sector.light_level)
+                    });
+            self.world.particles.sparks(pos, z, toward, floor_z);
+            self.world.particles.smoke(pos, z, light);
+        }
+        let Some(audio) = &mut self.audio else {
+            return;
+        };
+        audio.play("shoot");
+        match shot {
+            Shot::Missed => {}
+            Shot::Wall { point, .. } => audio.play_at("ricochet", point, &self.camera, &self.world),
+            Shot::Entity {
+                point, destroyed, ..
+            } => {
+                let sound = if destroyed { "break" } else { "hit" };
+                audio.play_at(sound, point, &self.camera, &self.world);
+            }
+        }
+    }
+
+    // Zoom with +/-, pan with the arrow keys once follow is off
+    fn tick_automap(&mut self, dt_s: f32) {
+        let key = |code| self.keys_down.contains(&code) as i32 as f32;
+        let zoom_dir = key(KeyCode::Equal) - key(KeyCode::Minus);
+        let pan = [
+            key(KeyCode::ArrowRight) - key(KeyCode::ArrowLeft),
+            key(KeyCode::ArrowUp) - key(KeyCode::ArrowDown),
+        ];
+
+        // Doubles (or halves) the zoom per second held
+        self.automap
+            .zoom_by((zoom_dir * dt_s * std::f32::consts::LN_2).exp());
+        if !self.automap.follow {
+            // Constant speed on screen regardless of zoom
+            let speed = 300.0 / self.automap.zoom * dt_s;
+            self.automap.pan([pan[0] * speed, pan[1] * speed]);
+        }
+    }
+
+    fn quick_save(&self) {
+        let save = SaveGame {
+            map: self.map_path.clone(),
+            camera: self.camera,
+            body: self.body,
+            player: self.player,
+            world: WorldState::capture(&self.world),
+        };
+        match save::write_save(QUICKSAVE_PATH, &save) {
+            Ok(()) => info!(target: "app", "Saved to {QUICKSAVE_PATH}"),
+            Err(err) => error!(target: "app", "{QUICKSAVE_PATH}: {err}"),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        let loaded = save::read_save(QUICKSAVE_PATH).and_then(|save| Ok((save.world()?, save)));
+        match loaded {
+            Ok((world, save)) => {
+                self.enter_world(world);
+                self.map_path = save.map;
+                self.generated = None;
+                if let (Some(session), Some(map)) = (&mut self.session, &self.map_path)
+                    && !session.resume_at(map)
+                {
+                    warn!(
+                        target: "app",
+                        "{map} isn't part of the campaign; exits won't lead anywhere"
+                    );
+                }
+                // Focal factors belong to the current window, not the save
+                self.camera = Camera {
+                    fx: self.camera.fx,
+                    fy: self.camera.fy,
+                    ..save.camera
+                };
+                self.body = save.body;
+                self.noclip = None;
+                self.player = save.player;
+                self.watch_map();
+                info!(target: "app", "Loaded {QUICKSAVE_PATH}");
+            }
+            Err(err) => error!(target: "app", "{QUICKSAVE_PATH}: {err}"),
+        }
+    }
+
+    fn set_world(&mut self, world: World) {
+        self.enter_world(world);
+        self.move_to_start();
+    }
+
+    // Make `world` the current one, with the weapon's, chase figure's and other players'
+    // textures added to it, its music and script started over, and the sector we were last
+    // in forgotten: sector numbers don't carry over to another map, or an edit of this one
+    fn enter_world(&mut self, world: World) {
+        self.world = world;
+        self.last_sector = None;
+        self.weapon.attach(&mut self.world);
+        self.chase.attach(&mut self.world);
+        if let Some(net) = &mut self.net {
+            net.attach(&mut self.world);
+        }
+        self.start_map_music();
+        self.start_script();
+    }
+
+    // Rows of the internal framebuffer the views share, above the status bar when it's shown
+    fn view_height(&self) -> usize {
+        match self.config.hud {
+            HudStyle::Full => self.fb_h - StatusBar::height(self.fb_h),
+            HudStyle::Minimal | HudStyle::None => self.fb_h,
+        }
+    }
+
+    // Each view of the split layout into the internal framebuffer, the player's first
+    fn draw_views(&mut self) {
+        self.renderer.update_monitors(&mut self.world);
+        let views = self.layout.viewports(self.fb_w, self.view_height());
+        let mut fb = std::mem::take(&mut self.fb_small);
+        let mut view = std::mem::take(&mut self.view_fb);
+        for (i, viewport) in views.into_iter().enumerate() {
+            if i > 0 {
+                let mut camera = self.second_camera;
+                camera.set_fov_hor_plus(viewport.width as f32, viewport.height as f32, self.fov);
+                self.renderer
+                    .render_viewport(&mut fb, self.fb_w, viewport, &self.world, &camera);
+            } else if viewport.is_full(self.fb_w, self.fb_h) {
+                self.draw_player_view(&mut fb, self.fb_w, self.fb_h);
+            } else {
+                // Drawn aside first so the weapon and underwater sway stay inside it
+                view.resize(viewport.width * viewport.height, 0);
+                self.draw_player_view(&mut view, viewport.width, viewport.height);
+                viewport.copy_into(&mut fb, self.fb_w, &view);
+            }
+        }
+        self.fb_small = fb;
+        self.view_fb = view;
+    }
+
+    // The world from the player's eyes, with their weapon, or from behind them in third
+    // person, into a `width` x `height` buffer
+    fn draw_player_view(&mut self, buf: &mut [u32], width: usize, height: usize) {
+        let mut camera = self.camera;
+        if width != self.fb_w || height != self.fb_h {
+            camera.set_fov_hor_plus(width as f32, height as f32, self.fov);
+        }
+        let mut offset = ViewOffset::default();
+        let mut figure = None;
+        if self.config.view_effects && !self.third_person {
+            offset = self.view_effects.offset();
+        }
+        if self.third_person {
+            camera = chase::camera(&self.world, &camera);
+            // Flying leaves the body where it was, so the figure hangs from the eye instead
+            let feet_z = match self.noclip {
+                Some(_) => self.camera.eye_z - self.body.eye_height,
+                None => self.body.feet_z,
+            };
+            figure = self.chase.place_figure(
+                &mut self.world,
+                self.camera.pos,
+                feet_z,
+                self.body.eye_height + HEAD_ABOVE_EYE,
+                self.camera.yaw,
+            );
+        }
+        self.renderer
+            .render_offset(buf, width, height, &self.world, &camera, offset);
+        if let Some(id) = figure {
+            self.world.entities.despawn(id);
+        }
+        self.profiler.record_render(self.renderer.timings());
+        let sector = self.world.sector_at(camera.pos);
+        let sector = sector.map(|s| &self.world.sectors[s]);
+        if let Some(liquid) = sector.and_then(|s| s.liquid)
+            && camera.eye_z < liquid.surface_z
+        {
+            self.renderer
+                .underwater(buf, width, height, &liquid, self.time);
+        }
+        let light = sector.map_or(1.0, |s| s.light_level);
+        if !self.player.is_dead() && !self.third_person {
+            self.weapon.draw(buf, width, height, light);
+        }
+        if let Some(flash) = self.screen_flash.flash() {
+            self.renderer.flash(buf, width, height, flash);
+        }
+    }
+
+    // Send the player where `teleport` goes, landing on the floor there; false if its
+    // destination is missing
+    fn teleport(&mut self, teleport: Teleport) -> bool {
+        let Some(to) = self.world.teleport(teleport, self.camera.pos, None) else {
+            return false;
+        };
+        self.velocity = teleport.arrival_velocity(self.velocity, self.camera.yaw, to.yaw);
+        self.camera.pos = to.pos;
+        self.camera.yaw = to.yaw;
+        let floor_z = self
+            .world
+            .sector_at(to.pos)
+            .map_or(0.0, |s| self.world.sectors[s].floor_z);
+        self.body = VerticalBody::new(floor_z);
+        self.camera.eye_z = self.body.eye_z();
+        true
+    }
+
+    // Stand at the world's player start
+    fn move_to_start(&mut self) {
+        let start = &self.world.player_start;
+        self.camera.pos = start.pos;
+        self.camera.yaw = start.yaw;
+        let floor_z = self
+            .world
+            .sector_at(start.pos)
+            .map_or(0.0, |s| self.world.sectors[s].floor_z);
+        self.body = VerticalBody::new(floor_z);
+        self.velocity = [0.0, 0.0];
+        self.noclip = None;
+        self.view_effects.reset();
+        self.screen_flash.reset();
+        self.camera.eye_z = self.body.eye_z();
+        self.second_camera = self.camera;
+    }
+
+    // Watch the current map file for changes, unless a demo or benchmark needs the world
+    // to stay as it started
+    fn watch_map(&mut self) {
+        let fixed = !matches!(self.demo, DemoMode::Off) || self.bench.is_some();
+        self.map_watcher = match &self.map_path {
+            Some(path) if !fixed => Some(FileWatcher::new(path)),
+            _ => None,
+        };
+    }
+
+    // Load the changed map file over the current world, keeping the camera where it is if
+    // that's still inside the map; a map that fails to load leaves the world as it was
+    fn reload_map(&mut self) {
+        let Some(path) = self.map_path.clone() else {
+            return;
+        };
+        let world = match world::loader::load_map(&path) {
+            Ok(world) => world,
+            Err(err) => {
+                error!(target: "world", "{path}: {err}");
+                return;
+            }
+        };
+        let Some(s) = world.sector_at(self.camera.pos) else {
+            self.set_world(world);
+            info!(target: "world", "Reloaded {path}, back at the player start");
+            return;
+        };
+        // Stand on the new floor if it rose past the feet
+        let (floor_z, _) = world.floor_ceiling_at(s, self.body.feet_z);
+        if self.body.feet_z < floor_z {
+            self.body.feet_z = floor_z;
+            self.body.vz = 0.0;
+        }
+        self.camera.eye_z = self.body.eye_z();
+        self.enter_world(world);
+        info!(target: "world", "Reloaded {path}");
+    }
+
+    // Load and start the current map's script from the beginning, if it has one
+    fn start_script(&mut self) {
+        self.messages.clear();
+        self.script = self.world.script.as_deref().and_then(|name| {
+            Script::load(DEFAULT_ASSET_DIR, name)
+                .inspect_err(|err| warn!(target: "world", "script {name:?}: {err}"))
+                .ok()
+        });
+        if let Some(script) = &mut self.script {
+            for message in script.start(&mut self.world) {
+                self.messages.push(message);
+            }
+        }
+    }
+
+    // Crossfade to the current map's track, or out to silence if it has none
+    fn start_map_music(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            audio.play_music(self.world.music.as_deref());
+        }
+    }
+
+    // Start capturing presented frames, or stop early
+    fn toggle_capture(&mut self) {
+        if self.capture.is_some() {
+            self.stop_capture();
+            return;
+        }
+        let seconds = self.config.capture_seconds.max(0.0);
+        match Capture::start(
+            self.config.capture_format,
+            self.fb_w,
+            self.fb_h,
+            Duration::from_secs_f32(seconds),
+            Instant::now(),
+        ) {
+            Ok(capture) => {
+                info!(target: "renderer", "Capturing {seconds}s to {}", capture.path().display());
+                self.capture = Some(capture);
+            }
+            Err(err) => error!(target: "renderer", "Capture failed: {err}"),
+        }
+    }
+
+    fn stop_capture(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            self.capture_writers.retain(|writer| !writer.is_finished());
+            self.capture_writers.push(capture.stop());
+        }
+    }
+
+    // Esc opens the pause menu; while it's open every key goes to it
+    fn pause_menu_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode) {
+        let Some(pause) = &mut self.pause else {
+            self.pause = Some(PauseMenu::new(self.mouse_captured));
+            self.set_mouse_capture(false);
+            self.keys_down.clear();
+            self.mouse_fire = false;
+            return;
+        };
+        let key = match code {
+            KeyCode::Escape => MenuKey::Back,
+            KeyCode::ArrowUp | KeyCode::KeyW => MenuKey::Up,
+            KeyCode::ArrowDown | KeyCode::KeyS => MenuKey::Down,
+            KeyCode::ArrowLeft | KeyCode::KeyA => MenuKey::Left,
+            KeyCode::ArrowRight | KeyCode::KeyD => MenuKey::Right,
+            KeyCode::Enter | KeyCode::Space => MenuKey::Select,
+            _ => return,
+        };
+        match pause.key(key, &mut self.config) {
+            PauseOutcome::Stay => (),
+            PauseOutcome::Resume => {
+                let recapture = pause.recapture();
+                self.pause = None;
+                if recapture {
+                    self.set_mouse_capture(true);
+                }
+            }
+            PauseOutcome::Quit => event_loop.exit(),
+            PauseOutcome::SettingsChanged => self.config_changed(),
+        }
+    }
+
+    // ` opens the console; while it's open every key goes to it, and ` or Esc closes it
+    fn console_key(&mut self, code: KeyCode, text: Option<&str>) {
+        if !self.console.is_open() {
+            self.console.set_open(true);
+            self.keys_down.clear();
+            self.mouse_fire = false;
+            return;
+        }
+        match code {
+            KeyCode::Backquote | KeyCode::Escape => self.console.set_open(false),
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                if let Some(line) = self.console.submit() {
+                    self.console_command(&line);
+                }
+            }
+            KeyCode::Backspace => self.console.backspace(),
+            _ => {
+                if let Some(text) = text {
+                    self.console.type_text(text);
+                }
+            }
+        }
+    }
+
+    // Run one console line, answering in the console
+    fn console_command(&mut self, line: &str) {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_ascii_lowercase();
+        let arg = words.next();
+        let clock_command = matches!(command.as_str(), "pause" | "step" | "timescale");
+        if clock_command && self.net.is_some() {
+            self.console
+                .print("Game time is the server's in multiplayer");
+            return;
+        }
+        match (command.as_str(), arg) {
+            ("pause", _) => {
+                let paused = !self.clock.is_paused();
+                self.clock.set_paused(paused);
+                self.console
+                    .print(if paused { "Paused" } else { "Running" });
+            }
+            ("step", _) => match arg.map_or(Ok(1), str::parse::<u32>) {
+                Ok(ticks) if ticks > 0 => {
+                    self.clock.step(ticks);
+                    self.console.print(format!(
+                        "Stepping {ticks} tick(s) of {:.4}s",
+                        clock::STEP_DT
+                    ));
+                }
+                _ => self.console.print("Usage: step [ticks]"),
+            },
+            ("timescale", None) => self
+                .console
+                .print(format!("Time scale {}", self.clock.scale())),
+            ("timescale", Some(arg)) => match arg.parse::<f32>() {
+                Ok(scale) if scale.is_finite() => {
+                    self.clock.set_scale(scale);
+                    let (lo, hi) = clock::SCALE_RANGE;
+                    self.console
+                        .print(format!("Time scale {} ({lo} to {hi})", self.clock.scale()));
+                }
+                _ => self.console.print("Usage: timescale [multiple]"),
+            },
+            ("help", _) => {
+                self.console.print("pause: stop or resume game time");
+                self.console.print("step [ticks]: run ticks while paused");
+                self.console.print("timescale [multiple]: slow or speed up");
+            }
+            _ => self
+                .console
+                .print(format!("Unknown command {command:?}; try help")),
+        }
+        info!(target: "app", "Console: {line}");
+    }
+
+    // Put `config` into effect and write it back to its file
+    fn config_changed(&mut self) {
+        self.apply_config();
+        if let Err(err) = save_config(CONFIG_PATH, &self.config) {
+            warn!(target: "app", "{CONFIG_PATH}: {err}");
+        }
+        // Our own write isn't an edit to reload
+        if self.config_watcher.is_some() {
+            self.config_watcher = Some(FileWatcher::new(CONFIG_PATH));
+        }
+    }
+
+    // Put `config` into effect
+    fn apply_config(&mut self) {
+        let config = &self.config;
+        if let Some(audio) = &mut self.audio {
+            audio.volume = config.effects_volume.clamp(0.0, 1.0);
+            audio.set_music_volume(config.music_volume);
+        }
+        self.move_speed = config.move_speed;
+        self.turn_speed = config.turn_speed.to_radians();
+        self.mouse_sensitivity = config.mouse_sensitivity;
+        config.keys.apply(&mut self.bindings);
+        self.fov = config.fov.clamp(MIN_FOV, MAX_FOV_X);
+        self.render_scale = config.render_scale;
+        if self.scaler.mode() != config.scale_mode {
+            self.scaler.set_mode(config.scale_mode);
+        }
+        if self.scaler.crt.is_some() != config.crt {
+            self.scaler.crt = config.crt.then(CrtParams::default);
+        }
+        self.renderer.set_dither(config.dither);
+        self.renderer.set_mipmaps(config.mipmaps);
+        self.crosshair.style = config.crosshair;
+        self.crosshair.color = pack_rgb(
+            config.crosshair_color[0],
+            config.crosshair_color[1],
+            config.crosshair_color[2],
+        );
+        self.crosshair.size = config.crosshair_size;
+        self.screen_flash.intensity = config.flash_intensity.clamp(0.0, 1.0);
+        self.screen_flash.decay = config.flash_decay.max(0.0);
+        if config.watch != self.config_watcher.is_some() {
+            self.config_watcher = config.watch.then(|| FileWatcher::new(CONFIG_PATH));
+        }
+        self.scaler.set_color_adjust(ColorAdjust {
+            gamma: config.gamma,
+            brightness: config.brightness,
+            contrast: config.contrast,
+        });
+        // Before the window opens there's nothing to size yet
+        let [w, h] = self.present_size;
+        if w > 0 && h > 0 {
+            self.rebuild_internal_fb_and_lut(w, h);
+        }
+    }
+
+    fn set_mouse_capture(&mut self, captured: bool) {
+        let Some(window) = &self.window else {
+            return;
+        };
+
+        if captured {
+            // Locked isn't available everywhere (e.g. X11), fall back to confining the cursor
+            let grabbed = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(err) = grabbed {
+                warn!(target: "input", "Mouse capture unavailable: {err}");
+                return;
+            }
+        } else {
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+        }
+
+        window.set_cursor_visible(!captured);
+        self.mouse_captured = captured;
+        self.mouse_dx = 0.0;
+    }
+
+    // The window, with whichever backend presents to it set up
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) -> Result<Arc<Window>, EngineError> {
+        let attributes = Window::default_attributes()
+            .with_title("2.5D Engine")
+            .with_inner_size(LogicalSize::new(800.0, 600.0));
+        let window = Arc::new(event_loop.create_window(attributes)?);
+
+        if self.backend == Backend::Gpu {
+            match GpuPresenter::new(window.clone(), self.fb_w, self.fb_h) {
+                Ok(gpu) => self.gpu = Some(gpu),
+                Err(err) => warn!(target: "renderer", "{err}; presenting in software instead"),
+            }
+        }
+        if self.gpu.is_none() {
+            self.surface = Some(software_surface(&window)?);
+        }
+        self.window = Some(window.clone());
+        Ok(window)
+    }
 
-        // Move in world space based on yaw
-        if fwd != 0.0 || strafe != 0.0 {
-            let c = self.camera.yaw.cos();
-            let s = self.camera.yaw.sin();
-            // forward vector (0, +1) rotated by yaw = (s, c) in +Y forward convention
-            let dir_fwd = [s, c];
-            let dir_right = [c, -s]; // perpendicular (right-hand)
+    // Scaled on the GPU; uploading and submitting is the whole CPU cost
+    fn present_gpu(&mut self) -> Result<(PresentTimings, Duration), EngineError> {
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(Default::default());
+        };
+        let present = gpu.present(
+            &self.fb_small,
+            self.scaler.mode(),
+            self.scaler.crt,
+            self.scaler.color_adjust(),
+        )?;
+        Ok((present, Duration::ZERO))
+    }
 
-            let speed = self.move_speed;
-            let dx = (dir_fwd[0] * fwd + dir_right[0] * strafe) * speed * dt_s;
-            let dy = (dir_fwd[1] * fwd + dir_right[1] * strafe) * speed * dt_s;
+    // Scaled by the CPU into a window-sized surface, `dw` x `dh`
+    fn present_software(
+        &mut self,
+        dw: usize,
+        dh: usize,
+    ) -> Result<(PresentTimings, Duration), EngineError> {
+        let (Some(surface), Some(width), Some(height)) = (
+            &mut self.surface,
+            NonZeroU32::new(dw as u32),
+            NonZeroU32::new(dh as u32),
+        ) else {
+            return Ok(Default::default());
+        };
+        surface.resize(width, height)?;
+
+        let mut buf = surface.buffer_mut()?;
+        let present = self.scaler.present(&mut buf, &self.fb_small);
 
-            self.camera.pos[0] += dx;
-            self.camera.pos[1] += dy;
+        let surface_start = Instant::now();
+        buf.present()?;
+        Ok((present, surface_start.elapsed()))
+    }
+
+    // A frame failed to present and is lost. Set presenting up again for the next one: a
+    // fresh software surface, which also stands in for a GPU that has stopped working.
+    fn recover_presenting(&mut self, err: EngineError, event_loop: &ActiveEventLoop) {
+        self.present_failures += 1;
+        if self.present_failures >= MAX_PRESENT_FAILURES {
+            error!(target: "renderer", "{err}; giving up after {} tries", self.present_failures);
+            event_loop.exit();
+            return;
+        }
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        if matches!(err, EngineError::Gpu(_)) {
+            warn!(target: "renderer", "{err}; presenting in software instead");
+            self.gpu = None;
+        } else {
+            warn!(target: "renderer", "{err}; recreating the window surface");
+        }
+        // The old surface goes before the new one attaches to the same window
+        self.surface = None;
+        match software_surface(&window) {
+            Ok(surface) => {
+                self.surface = Some(surface);
+                window.request_redraw();
+            }
+            Err(err) => {
+                // Nothing left to present with
+                error!(target: "renderer", "{err}; giving up");
+                event_loop.exit();
+            }
         }
     }
 
     fn rebuild_internal_fb_and_lut(&mut self, dst_w: usize, dst_h: usize) {
-        // Keep internal height fixed (controls pixel size look)
-        let target_h = 480usize;
+        // Internal height comes from the render scale (controls pixel size look)
+        let target_h = self.render_scale.height_for(dst_h);
         let aspect = if dst_h > 0 {
             dst_w as f32 / dst_h as f32
         } else {
@@ -312,35 +1950,294 @@ impl App {
         if target_w < 160 {
             target_w = 160;
         }
-        if target_w % 2 != 0 {
+        if !target_w.is_multiple_of(2) {
             target_w += 1;
         }
 
-        // Reallocate internal FB if size changed
+        // Resize internal FB in place if size changed, keeping its allocation when shrinking
         if target_w != self.fb_w || target_h != self.fb_h {
             self.fb_w = target_w;
             self.fb_h = target_h;
-            self.fb_small = vec![0u32; self.fb_w * self.fb_h];
+            self.fb_small.resize(self.fb_w * self.fb_h, 0);
         }
+        self.present_size = [dst_w, dst_h];
 
         self.camera
-            .set_fov_from_horizontal(self.fb_w as f32, self.fb_h as f32, 90.0);
-        self.scale_lut = build_scale_lut(dst_w, dst_h, self.fb_w, self.fb_h);
+            .set_fov_hor_plus(self.fb_w as f32, self.fb_h as f32, self.fov);
+        self.scaler.resize(dst_w, dst_h, self.fb_w, self.fb_h);
+        if let Some(gpu) = &mut self.gpu {
+            gpu.resize(dst_w, dst_h, self.fb_w, self.fb_h);
+        }
     }
 }
 
 fn main() {
-    let event_loop = EventLoop::new().unwrap();
+    init_logging();
+
+    // Usage: [--fps N | --uncapped] [--render-scale LINES|PERCENT%] [--backend software|gpu]
+    //        [--record demo.toml | --playdemo demo.toml]
+    //        [--bench [FRAMES] [--bench-csv out.csv]]
+    //        [--server | --host] [--port N] | [--connect HOST[:PORT]]
+    //        [--campaign campaign.toml | --gen-map SEED | map.toml | build.map | TEXTMAP]
+    let mut map_path = None;
+    let mut session = None;
+    let mut gen_seed = None;
+    let mut bench = None;
+    let mut bench_csv = None;
+    let mut record_path = None;
+    let mut play_path = None;
+    let mut target_fps = Some(DEFAULT_TARGET_FPS);
+    let mut render_scale = None; // overrides the config's
+    let mut backend = Backend::default();
+    let mut host = false;
+    let mut dedicated = false;
+    let mut port = DEFAULT_PORT;
+    let mut connect = None;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--uncapped" => target_fps = None,
+            "--fps" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(fps) => target_fps = Some(fps),
+                None => {
+                    eprintln!("--fps needs a frame rate, e.g. --fps 35");
+                    std::process::exit(1);
+                }
+            },
+            "--render-scale" => match args.next().and_then(|v| RenderScale::parse(&v)) {
+                Some(scale) => render_scale = Some(scale),
+                None => {
+                    eprintln!("--render-scale needs a line count or a percentage, e.g. 360 or 50%");
+                    std::process::exit(1);
+                }
+            },
+            "--backend" => match args.next().and_then(|v| Backend::parse(&v)) {
+                Some(b) => backend = b,
+                None => {
+                    eprintln!("--backend needs software or gpu");
+                    std::process::exit(1);
+                }
+            },
+            "--campaign" => match args.next().map(GameSession::read) {
+                Some(Ok(campaign)) => {
+                    map_path = Some(campaign.current_map().to_string());
+                    session = Some(campaign);
+                }
+                Some(Err(err)) => {
+                    eprintln!("--campaign: {err}");
+                    std::process::exit(1);
+                }
+                None => {
+                    eprintln!("--campaign needs a campaign file");
+                    std::process::exit(1);
+                }
+            },
+            "--gen-map" => match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(seed) => gen_seed = Some(seed),
+                None => {
+                    eprintln!("--gen-map needs a numeric seed, e.g. --gen-map 42");
+                    std::process::exit(1);
+                }
+            },
+            "--bench" => {
+                // The frame count is optional
+                let frames = args
+                    .next_if(|v| v.parse::<usize>().is_ok())
+                    .and_then(|v| v.parse().ok());
+                bench = Some(Bench::new(frames.unwrap_or(DEFAULT_BENCH_FRAMES)));
+            }
+            "--bench-csv" => match args.next() {
+                Some(path) => bench_csv = Some(path),
+                None => {
+                    eprintln!("--bench-csv needs an output file");
+                    std::process::exit(1);
+                }
+            },
+            "--host" => host = true,
+            "--server" => dedicated = true,
+            "--port" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(p) => port = p,
+                None => {
+                    eprintln!("--port needs a port number, e.g. --port {DEFAULT_PORT}");
+                    std::process::exit(1);
+                }
+            },
+            "--connect" => match args.next() {
+                Some(addr) => connect = Some(addr),
+                None => {
+                    eprintln!("--connect needs a server address, e.g. --connect 10.0.0.2");
+                    std::process::exit(1);
+                }
+            },
+            "--record" | "--playdemo" => match args.next() {
+                Some(path) if arg == "--record" => record_path = Some(path),
+                Some(path) => play_path = Some(path),
+                None => {
+                    eprintln!("{arg} needs a demo file");
+                    std::process::exit(1);
+                }
+            },
+            _ => map_path = Some(arg),
+        }
+    }
+
+    // Demos name their map by file, which a generated one doesn't have
+    if gen_seed.is_some() && (record_path.is_some() || play_path.is_some()) {
+        eprintln!("--gen-map can't be combined with --record or --playdemo");
+        std::process::exit(1);
+    }
+
+    // Ticks depend on what other players do, so neither replays
+    let networked = dedicated || host || connect.is_some();
+    if networked && (record_path.is_some() || play_path.is_some() || bench.is_some()) {
+        eprintln!("--host and --connect can't be combined with demos or --bench");
+        std::process::exit(1);
+    }
+    if dedicated && (host || connect.is_some()) {
+        eprintln!("--server plays no part itself; leave out --host and --connect");
+        std::process::exit(1);
+    }
+    if host && connect.is_some() {
+        eprintln!("--host already connects to its own server; leave out --connect");
+        std::process::exit(1);
+    }
+
+    // A demo replays on the map it was recorded on
+    let mut demo = DemoMode::Off;
+    if let Some(path) = play_path {
+        match recording::read_demo(&path) {
+            Ok(recorded) => {
+                map_path = recorded.map.clone();
+                demo = DemoMode::Playing(DemoPlayer::new(recorded));
+            }
+            Err(err) => {
+                error!(target: "app", "{path}: {err}");
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(path) = record_path {
+        demo = DemoMode::Recording {
+            path,
+            demo: Demo {
+                map: map_path.clone(),
+                ticks: Vec::new(),
+            },
+        };
+    }
 
-    // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
-    // dispatched any events. This is ideal for games and similar applications.
-    // event_loop.set_control_flow(ControlFlow::Poll);
+    // A dedicated server never opens a window, a sound device or a gamepad
+    if dedicated {
+        if let Err(err) = serve(map_path.as_deref(), gen_seed, port) {
+            error!(target: "net", "Server: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // ControlFlow::Wait pauses the event loop if no events are available to process.
-    // This is ideal for non-game applications that only update in response to user
-    // input, and uses significantly less power/CPU time than ControlFlow::Poll.
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(err) => {
+            error!(target: "app", "{}", EngineError::from(err));
+            std::process::exit(1);
+        }
+    };
+
+    // Wait for input between frames; the pacer switches to WaitUntil for the next frame,
+    // or to Poll when uncapped
     event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App::default();
-    let _ = event_loop.run_app(&mut app);
+    // Benchmarks run as fast as frames can be drawn
+    if bench.is_some() {
+        target_fps = None;
+    }
+
+    let mut app = App {
+        pacer: FramePacer::new(target_fps),
+        bench,
+        bench_csv,
+        backend,
+        demo,
+        session,
+        ..App::default()
+    };
+    app.config = load_config(CONFIG_PATH);
+    if let Some(scale) = render_scale {
+        app.config.render_scale = scale;
+    }
+    app.apply_config();
+    // A listen server: the same map, simulated on its own thread for everyone
+    if host {
+        let map_path = map_path.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = serve(map_path.as_deref(), gen_seed, port) {
+                error!(target: "net", "Server: {err}");
+            }
+        });
+        connect = Some(format!("127.0.0.1:{port}"));
+    }
+    if let Some(addr) = connect {
+        match NetClient::connect(&addr) {
+            Ok(mut net) => {
+                info!(target: "net", "Joining {addr}");
+                net.attach(&mut app.world);
+                app.net = Some(net);
+            }
+            Err(err) => {
+                error!(target: "net", "{addr}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(seed) = gen_seed {
+        app.set_world(procgen::generate(seed, procgen::DEFAULT_GRID));
+        app.generated = Some(seed);
+        info!(target: "world", "Generated map from seed {seed}");
+    } else if let Some(path) = map_path {
+        match world::loader::load_map(&path) {
+            Ok(world) => {
+                app.set_world(world);
+                app.map_path = Some(path);
+                app.watch_map();
+            }
+            Err(err) => {
+                error!(target: "world", "{path}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(err) = event_loop.run_app(&mut app) {
+        error!(target: "app", "{}", EngineError::from(err));
+        std::process::exit(1);
+    }
+}
+
+// To stderr, leaving stdout to --bench reports; filtered by RUST_LOG, by level and by
+// target: `renderer`, `input`, `audio`, `net`, `world` or `app`
+fn init_logging() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+fn software_surface(window: &Arc<Window>) -> Result<SoftwareSurface, EngineError> {
+    let context = softbuffer::Context::new(window.clone())?;
+    Ok(softbuffer::Surface::new(&context, window.clone())?)
+}
+
+// Run a server for the map named on the command line until the process ends
+fn serve(map_path: Option<&str>, gen_seed: Option<u64>, port: u16) -> Result<(), String> {
+    let world = match (map_path, gen_seed) {
+        (_, Some(seed)) => procgen::generate(seed, procgen::DEFAULT_GRID),
+        (Some(path), None) => {
+            world::loader::load_map(path).map_err(|err| format!("{path}: {err}"))?
+        }
+        (None, None) => World::demo(),
+    };
+    let server = Server::bind(("0.0.0.0", port), world).map_err(|err| err.to_string())?;
+    let addr = server.local_addr().map_err(|err| err.to_string())?;
+    info!(target: "net", "Serving {} on {addr}", map_path.unwrap_or("the demo map"));
+    server.run().map_err(|err| err.to_string())
 }
@@ -0,0 +1,153 @@
+// Menus drawn over the game with the HUD font: a title and a column of items, one of them
+// selected. Items either do something when picked or hold a value stepped left and right;
+// what they mean is up to the owner, which hears back through `MenuAction`.
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::renderer::{mix, pack_rgb};
+
+// How far the game behind is darkened, 0..=256
+const DIM: u32 = 160;
+
+pub struct MenuItem {
+    pub label: String,
+    pub value: Option<String>, // shown after the label for items stepped left and right
+}
+
+impl MenuItem {
+    /// An item that's picked, like "resume"
+    pub fn action(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            value: None,
+        }
+    }
+
+    /// An item holding a value, like a volume
+    pub fn value(label: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            value: Some(value.into()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+/// What a key press asks the menu's owner to do
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuAction {
+    None,
+    Activate(usize),    // item picked
+    Adjust(usize, i32), // item's value stepped down (-1) or up (+1)
+    Back,
+}
+
+pub struct Menu {
+    pub title: String,
+    items: Vec<MenuItem>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: impl Into<String>, items: Vec<MenuItem>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_value(&mut self, item: usize, value: impl Into<String>) {
+        if let Some(item) = self.items.get_mut(item) {
+            item.value = Some(value.into());
+        }
+    }
+
+    /// Move the selection, or turn `key` into an action on the selected item
+    pub fn handle(&mut self, key: MenuKey) -> MenuAction {
+        let count = self.items.len();
+        if count == 0 {
+            return if key == MenuKey::Back {
+                MenuAction::Back
+            } else {
+                MenuAction::None
+            };
+        }
+        let has_value = self.items[self.selected].value.is_some();
+        match key {
+            MenuKey::Up => {
+                self.selected = (self.selected + count - 1) % count;
+                MenuAction::None
+            }
+            MenuKey::Down => {
+                self.selected = (self.selected + 1) % count;
+                MenuAction::None
+            }
+            MenuKey::Left if has_value => MenuAction::Adjust(self.selected, -1),
+            MenuKey::Right if has_value => MenuAction::Adjust(self.selected, 1),
+            MenuKey::Select if has_value => MenuAction::Adjust(self.selected, 1),
+            MenuKey::Select => MenuAction::Activate(self.selected),
+            MenuKey::Back => MenuAction::Back,
+            MenuKey::Left | MenuKey::Right => MenuAction::None,
+        }
+    }
+
+    /// Darken `buf` and draw the menu centered on it
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize) {
+        for pixel in buf.iter_mut() {
+            *pixel = mix(*pixel, 0, DIM);
+        }
+
+        let scale = (height / 240).max(1);
+        let line = (GLYPH_HEIGHT + 3) * scale;
+        let lines: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| match &item.value {
+                Some(value) => format!("{}  < {value} >", item.label),
+                None => item.label.clone(),
+            })
+            .collect();
+        let total = (lines.len() + 2) * line;
+        let top = height.saturating_sub(total) / 2;
+
+        let title_scale = 2 * scale;
+        let x = width.saturating_sub(font::text_width(&self.title, title_scale)) / 2;
+        font::draw_text(
+            buf,
+            width,
+            height,
+            [x, top],
+            &self.title,
+            pack_rgb(255, 220, 120),
+            title_scale,
+        );
+
+        for (i, text) in lines.iter().enumerate() {
+            let y = top + (i + 2) * line;
+            let x = width.saturating_sub(font::text_width(text, scale)) / 2;
+            let color = if i == self.selected {
+                pack_rgb(255, 255, 255)
+            } else {
+                pack_rgb(140, 140, 140)
+            };
+            font::draw_text(buf, width, height, [x, y], text, color, scale);
+            if i == self.selected {
+                let marker = x.saturating_sub(4 * scale + font::text_width(">", scale));
+                font::draw_text(buf, width, height, [marker, y], ">", color, scale);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// HUD messages: short lines of text across the top of the screen, each shown for a few
+// seconds with the newest at the bottom
+
+use std::collections::VecDeque;
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::renderer::pack_rgb;
+
+// Seconds each message stays up
+const SHOW_TIME: f32 = 4.0;
+// Older messages make way once there are this many
+const MAX_LINES: usize = 4;
+
+#[derive(Default)]
+pub struct Messages {
+    lines: VecDeque<(String, f32)>, // text and seconds left
+}
+
+impl Messages {
+    pub fn push(&mut self, text: impl Into<String>) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((text.into(), SHOW_TIME));
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for (_, left) in &mut self.lines {
+            *left -= dt;
+        }
+        self.lines.retain(|(_, left)| *left > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Each message centered near the top of `buf`
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize) {
+        let scale = (height / 240).max(1);
+        let line = (GLYPH_HEIGHT + 2) * scale;
+        for (i, (text, _)) in self.lines.iter().enumerate() {
+            let x = width.saturating_sub(font::text_width(text, scale)) / 2;
+            let y = 2 * scale + i * line;
+            font::draw_text(
+                buf,
+                width,
+                height,
+                [x, y],
+                text,
+                pack_rgb(255, 255, 255),
+                scale,
+            );
+        }
+    }
+}
@@ -0,0 +1,378 @@
+// Multiplayer over UDP: a server runs the authoritative simulation at a fixed tick rate,
+// clients send it their input every tick and get back snapshots of every player. Only the
+// players are shared so far; doors, monsters and the like still run on each client alone.
+//
+// Datagrams are small and self-contained: a magic tag and protocol version, a message
+// kind, then little-endian fields. Anything that doesn't decode is ignored.
+
+use std::time::Duration;
+
+pub mod client;
+pub mod server;
+
+pub use client::NetClient;
+pub use server::Server;
+
+pub const DEFAULT_PORT: u16 = 27960;
+pub const TICK_RATE: u32 = 30;
+pub const TICK_DT: f32 = 1.0 / TICK_RATE as f32;
+pub const MAX_PLAYERS: usize = 4;
+// A peer not heard from for this long has gone
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAGIC: [u8; 2] = *b"2d";
+const VERSION: u8 = 1;
+// Largest datagram either side sends, a snapshot of every player
+const MAX_DATAGRAM: usize = 512;
+
+/// One tick of a client's input, sent every tick; the newest `seq` wins
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetInput {
+    pub seq: u32,
+    pub forward: f32, // -1..=1, as in `MoveIntent`
+    pub strafe: f32,
+    pub yaw: f32, // the client turns itself; the server takes its word for where it looks
+    pub jump: bool,
+    pub crouch: bool,
+    pub fire: bool,
+    pub activate: bool, // gets the dead back up
+}
+
+/// A player as the server last simulated them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerState {
+    pub id: u8,
+    pub pos: [f32; 2],
+    pub feet_z: f32,
+    pub eye_height: f32,
+    pub yaw: f32,
+    pub health: f32,
+    pub dead: bool,
+    pub frags: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub players: Vec<PlayerState>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientMessage {
+    Join,
+    Input(NetInput),
+    Leave,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    Welcome { id: u8 },
+    Full, // every player slot is taken
+    Snapshot(Snapshot),
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        match self {
+            ClientMessage::Join => w.u8(0),
+            ClientMessage::Input(input) => {
+                w.u8(1);
+                w.u32(input.seq);
+                w.f32(input.forward);
+                w.f32(input.strafe);
+                w.f32(input.yaw);
+                let buttons = [input.jump, input.crouch, input.fire, input.activate];
+                w.u8(buttons
+                    .iter()
+                    .enumerate()
+                    .fold(0, |bits, (i, &down)| bits | (down as u8) << i));
+            }
+            ClientMessage::Leave => w.u8(2),
+        }
+        w.finish()
+    }
+
+    pub fn decode(datagram: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(datagram)?;
+        let message = match r.u8()? {
+            0 => ClientMessage::Join,
+            1 => {
+                let (seq, forward, strafe, yaw) = (r.u32()?, r.f32()?, r.f32()?, r.f32()?);
+                let bits = r.u8()?;
+                let down = |i: u8| bits & (1 << i) != 0;
+                ClientMessage::Input(NetInput {
+                    seq,
+                    // A bad client doesn't get to run faster than anyone else
+                    forward: finite(forward)?.clamp(-1.0, 1.0),
+                    strafe: finite(strafe)?.clamp(-1.0, 1.0),
+                    yaw: finite(yaw)?,
+                    jump: down(0),
+                    crouch: down(1),
+                    fire: down(2),
+                    activate: down(3),
+                })
+            }
+            2 => ClientMessage::Leave,
+            _ => return None,
+        };
+        r.end().then_some(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        match self {
+            ServerMessage::Welcome { id } => {
+                w.u8(0);
+                w.u8(*id);
+            }
+            ServerMessage::Full => w.u8(1),
+            ServerMessage::Snapshot(snapshot) => {
+                w.u8(2);
+                w.u32(snapshot.tick);
+                w.u8(snapshot.players.len() as u8);
+                for p in &snapshot.players {
+                    w.u8(p.id);
+                    w.f32(p.pos[0]);
+                    w.f32(p.pos[1]);
+                    w.f32(p.feet_z);
+                    w.f32(p.eye_height);
+                    w.f32(p.yaw);
+                    w.f32(p.health);
+                    w.u8(p.dead as u8);
+                    w.u16(p.frags);
+                }
+            }
+        }
+        w.finish()
+    }
+
+    pub fn decode(datagram: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(datagram)?;
+        let message = match r.u8()? {
+            0 => ServerMessage::Welcome { id: r.u8()? },
+            1 => ServerMessage::Full,
+            2 => {
+                let tick = r.u32()?;
+                let count = r.u8()? as usize;
+                let mut players = Vec::with_capacity(count.min(MAX_PLAYERS));
+                for _ in 0..count {
+                    players.push(PlayerState {
+                        id: r.u8()?,
+                        pos: [r.f32()?, r.f32()?],
+                        feet_z: r.f32()?,
+                        eye_height: r.f32()?,
+                        yaw: r.f32()?,
+                        health: r.f32()?,
+                        dead: r.u8()? != 0,
+                        frags: r.u16()?,
+                    });
+                }
+                ServerMessage::Snapshot(Snapshot { tick, players })
+            }
+            _ => return None,
+        };
+        r.end().then_some(message)
+    }
+}
+
+fn finite(x: f32) -> Option<f32> {
+    x.is_finite().then_some(x)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn new() -> Self {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&MAGIC);
+        buf.push(VERSION);
+        Self(buf)
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    // None unless the datagram starts with our tag and version
+    fn new(datagram: &'a [u8]) -> Option<Self> {
+        let rest = datagram.strip_prefix(&MAGIC)?;
+        let (&version, rest) = rest.split_first()?;
+        (version == VERSION).then_some(Self(rest))
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[b]| b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    // True once everything has been read
+    fn end(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> NetInput {
+        NetInput {
+            seq: 7,
+            forward: 1.0,
+            strafe: -0.5,
+            yaw: 1.25,
+            jump: true,
+            crouch: false,
+            fire: true,
+            activate: false,
+        }
+    }
+
+    fn snapshot() -> ServerMessage {
+        ServerMessage::Snapshot(Snapshot {
+            tick: 42,
+            players: vec![
+                PlayerState {
+                    id: 0,
+                    pos: [1.5, -2.0],
+                    feet_z: 0.25,
+                    eye_height: 1.7,
+                    yaw: 3.0,
+                    health: 80.0,
+                    dead: false,
+                    frags: 2,
+                },
+                PlayerState {
+                    id: 3,
+                    pos: [0.0, 9.0],
+                    feet_z: -1.0,
+                    eye_height: 0.2,
+                    yaw: -1.0,
+                    health: 0.0,
+                    dead: true,
+                    frags: 0,
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn client_messages_round_trip() {
+        for message in [
+            ClientMessage::Join,
+            ClientMessage::Input(input()),
+            ClientMessage::Leave,
+        ] {
+            assert_eq!(ClientMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn server_messages_round_trip() {
+        for message in [
+            ServerMessage::Welcome { id: 2 },
+            ServerMessage::Full,
+            snapshot(),
+        ] {
+            assert_eq!(ServerMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn out_of_range_input_is_clamped() {
+        let message = ClientMessage::Input(NetInput {
+            forward: 5.0,
+            strafe: -9.0,
+            ..input()
+        });
+        let Some(ClientMessage::Input(decoded)) = ClientMessage::decode(&message.encode()) else {
+            panic!("input didn't decode");
+        };
+        assert_eq!((decoded.forward, decoded.strafe), (1.0, -1.0));
+    }
+
+    #[test]
+    fn truncated_datagrams_are_ignored() {
+        let client = ClientMessage::Input(input()).encode();
+        for len in 0..client.len() {
+            assert_eq!(ClientMessage::decode(&client[..len]), None, "{len} bytes");
+        }
+        let server = snapshot().encode();
+        for len in 0..server.len() {
+            assert_eq!(ServerMessage::decode(&server[..len]), None, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut datagram = ClientMessage::Join.encode();
+        datagram.push(0);
+        assert_eq!(ClientMessage::decode(&datagram), None);
+    }
+
+    #[test]
+    fn foreign_datagrams_are_ignored() {
+        let mut wrong_version = ServerMessage::Full.encode();
+        wrong_version[2] = VERSION + 1;
+        let mut unknown_kind = ClientMessage::Join.encode();
+        unknown_kind[3] = 200;
+        for datagram in [
+            b"hello".to_vec(),
+            b"xx\x01\x00".to_vec(),
+            wrong_version,
+            unknown_kind,
+        ] {
+            assert_eq!(ClientMessage::decode(&datagram), None);
+            assert_eq!(ServerMessage::decode(&datagram), None);
+        }
+    }
+
+    #[test]
+    fn non_finite_input_is_rejected() {
+        let message = ClientMessage::Input(NetInput {
+            yaw: f32::NAN,
+            ..input()
+        });
+        assert_eq!(ClientMessage::decode(&message.encode()), None);
+    }
+}
@@ -0,0 +1,218 @@
+// The game's side of a connection: joins the server, sends each tick's input and keeps the
+// newest snapshot. Other players are put into the world as sprite entities so the renderer
+// and the weapon's raycast see them like anything else.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use two_halfD_engine::chase::figure_texture;
+use two_halfD_engine::entity::{EntityId, Sprite, Transform};
+use two_halfD_engine::pack_rgb;
+use two_halfD_engine::texture::TextureId;
+use two_halfD_engine::world::{Blend, World};
+
+use super::{
+    ClientMessage, DEFAULT_PORT, MAX_DATAGRAM, MAX_PLAYERS, NetInput, PlayerState, ServerMessage,
+    Snapshot, TIMEOUT,
+};
+
+// How often to ask again while the server hasn't answered a join, or has gone quiet
+const JOIN_RETRY: Duration = Duration::from_secs(1);
+// Sprite height of other players, world units
+const PLAYER_HEIGHT: f32 = 1.8;
+// Shirt colors by player id
+const COLORS: [[u8; 3]; MAX_PLAYERS] =
+    [[60, 170, 60], [200, 60, 50], [60, 90, 210], [210, 180, 40]];
+
+/// Where the connection stands, for the game to report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetStatus {
+    Joining,
+    Joined { id: u8 },
+    Full,
+    Lost, // nothing heard for `TIMEOUT`; asking to join again until the server answers
+}
+
+pub struct NetClient {
+    socket: UdpSocket,
+    status: NetStatus,
+    seq: u32,
+    last_heard: Instant,
+    last_join: Instant,
+    snapshot: Option<Snapshot>, // newest received
+    textures: Vec<TextureId>,   // per player id, once attached to a world
+    sprites: Vec<EntityId>,     // the other players, respawned each snapshot
+}
+
+impl NetClient {
+    /// Start joining the server at `addr`, a host name or address with an optional port
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        let target = if addr.contains(':') {
+            addr.to_socket_addrs()?
+        } else {
+            (addr, DEFAULT_PORT).to_socket_addrs()?
+        }
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for the server"))?;
+        let local = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+        let now = Instant::now();
+        let client = Self {
+            socket,
+            status: NetStatus::Joining,
+            seq: 0,
+            last_heard: now,
+            last_join: now,
+            snapshot: None,
+            textures: Vec::new(),
+            sprites: Vec::new(),
+        };
+        client.send(&ClientMessage::Join);
+        Ok(client)
+    }
+
+    pub fn status(&self) -> NetStatus {
+        self.status
+    }
+
+    /// This player's id, once the server has let them in
+    pub fn id(&self) -> Option<u8> {
+        match self.status {
+            NetStatus::Joined { id } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Send this tick's input; until joined, or once the server is lost, asks to join again
+    /// now and then instead
+    pub fn send_input(&mut self, input: NetInput, now: Instant) {
+        match self.status {
+            NetStatus::Joined { .. } => {
+                self.seq = self.seq.wrapping_add(1);
+                self.send(&ClientMessage::Input(NetInput {
+                    seq: self.seq,
+                    ..input
+                }));
+            }
+            NetStatus::Joining | NetStatus::Lost
+                if now.duration_since(self.last_join) >= JOIN_RETRY =>
+            {
+                self.last_join = now;
+                self.send(&ClientMessage::Join);
+            }
+            _ => {}
+        }
+    }
+
+    /// This player as the newest snapshot has them
+    pub fn own_state(&self) -> Option<PlayerState> {
+        let id = self.id()?;
+        let snapshot = self.snapshot.as_ref()?;
+        snapshot.players.iter().find(|p| p.id == id).copied()
+    }
+
+    /// Read everything that has arrived; true if it held a newer snapshot
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let mut buf = [0; MAX_DATAGRAM];
+        let mut fresh = false;
+        loop {
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                // Nobody listening yet; the next join will try again
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(_) => break,
+            };
+            let Some(message) = ServerMessage::decode(&buf[..len]) else {
+                continue;
+            };
+            self.last_heard = now;
+            match message {
+                ServerMessage::Welcome { id } => {
+                    // A server come back counts its ticks from the start again
+                    if self.status == NetStatus::Lost {
+                        self.snapshot = None;
+                    }
+                    self.status = NetStatus::Joined { id };
+                }
+                ServerMessage::Full => self.status = NetStatus::Full,
+                ServerMessage::Snapshot(snapshot) => {
+                    // Late datagrams are older than what we have
+                    let newer = self
+                        .snapshot
+                        .as_ref()
+                        .is_none_or(|s| snapshot.tick.wrapping_sub(s.tick) as i32 > 0);
+                    if newer {
+                        self.snapshot = Some(snapshot);
+                        fresh = true;
+                    }
+                }
+            }
+        }
+        if self.status != NetStatus::Full && now.duration_since(self.last_heard) > TIMEOUT {
+            self.status = NetStatus::Lost;
+        }
+        fresh
+    }
+
+    /// Give `world` the other players' textures; call whenever the world is replaced
+    pub fn attach(&mut self, world: &mut World) {
+        self.sprites.clear();
+        self.textures = COLORS
+            .iter()
+            .map(|&[r, g, b]| {
+                world.textures.push(figure_texture(pack_rgb(r, g, b)));
+                world.textures.len() - 1
+            })
+            .collect();
+    }
+
+    /// Put the other living players of the newest snapshot into `world`, replacing the
+    /// sprites from the one before
+    pub fn place_players(&mut self, world: &mut World) {
+        for id in self.sprites.drain(..) {
+            world.entities.despawn(id);
+        }
+        let (Some(snapshot), Some(own)) = (&self.snapshot, self.id()) else {
+            return;
+        };
+        for p in &snapshot.players {
+            if p.id == own || p.dead {
+                continue;
+            }
+            let Some(&texture) = self.textures.get(p.id as usize) else {
+                continue;
+            };
+            let id = world.entities.spawn(Transform {
+                pos: p.pos,
+                z: p.feet_z,
+                yaw: p.yaw,
+            });
+            world.entities.sprites[id] = Some(Sprite {
+                texture,
+                height: PLAYER_HEIGHT,
+                scale: 1.0,
+                rotations: None,
+                model: None,
+                blend: Blend::Masked,
+            });
+            self.sprites.push(id);
+        }
+    }
+
+    /// Tell the server we're going, so the slot frees up before the timeout
+    pub fn leave(&self) {
+        self.send(&ClientMessage::Leave);
+    }
+
+    fn send(&self, message: &ClientMessage) {
+        // Lost datagrams are covered by the next tick's
+        let _ = self.socket.send(&message.encode());
+    }
+}
@@ -0,0 +1,433 @@
+// The authoritative side: players join by sending `Join` from an address, move and use
+// doors and switches by the inputs they send, and shoot each other with the same hitscan
+// the weapon uses. Every tick each of them gets a snapshot of all players.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use tracing::{info, info_span};
+use two_halfD_engine::collision::{self, PLAYER_RADIUS};
+use two_halfD_engine::entity::{self, EntityId};
+use two_halfD_engine::physics::{self, CROUCH_EYE_HEIGHT, HEAD_ABOVE_EYE, VerticalBody};
+use two_halfD_engine::player::Player;
+use two_halfD_engine::raycast::{self, Ray};
+use two_halfD_engine::sector_effects;
+use two_halfD_engine::weapon;
+use two_halfD_engine::world::World;
+
+use super::{
+    ClientMessage, MAX_DATAGRAM, MAX_PLAYERS, NetInput, PlayerState, ServerMessage, Snapshot,
+    TICK_DT, TICK_RATE, TIMEOUT,
+};
+
+// Horizontal speed, as the single-player default
+const MOVE_SPEED: f32 = 3.0;
+// What a shot can hit of a player: their collision circle, feet to head
+const HIT_RADIUS: f32 = 0.3;
+
+struct Client {
+    addr: SocketAddr,
+    input: NetInput, // newest received, applied every tick until the next
+    last_heard: Instant,
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    body: VerticalBody,
+    player: Player,
+    cooldown: f32, // until the next shot
+    activate_held: bool,
+    frags: u16,
+}
+
+pub struct Server {
+    socket: UdpSocket,
+    world: World,
+    clients: [Option<Client>; MAX_PLAYERS], // by player id
+    tick: u32,
+}
+
+impl Server {
+    /// Listen on `addr` for players in `world`
+    pub fn bind(addr: impl ToSocketAddrs, world: World) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        Ok(Self {
+            socket,
+            world,
+            clients: Default::default(),
+            tick: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Serve forever, ticking at `TICK_RATE` and handling datagrams in between
+    pub fn run(mut self) -> io::Result<()> {
+        let _server =
+            info_span!(target: "net", "server", addr = ?self.socket.local_addr().ok()).entered();
+        let tick_time = Duration::from_secs(1) / TICK_RATE;
+        let mut next_tick = Instant::now() + tick_time;
+        let mut buf = [0; MAX_DATAGRAM];
+        loop {
+            let now = Instant::now();
+            let wait = next_tick.saturating_duration_since(now);
+            if wait.is_zero() {
+                self.step(now);
+                // Fall behind rather than burst to catch up after a stall
+                next_tick = (next_tick + tick_time).max(now);
+                continue;
+            }
+            self.socket.set_read_timeout(Some(wait))?;
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => self.receive(&buf[..len], from, now),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            // A client's port closing is reported here on some platforms
+                            | io::ErrorKind::ConnectionReset
+                    ) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn receive(&mut self, datagram: &[u8], from: SocketAddr, now: Instant) {
+        let Some(message) = ClientMessage::decode(datagram) else {
+            return;
+        };
+        let slot = self
+            .clients
+            .iter()
+            .position(|c| c.as_ref().is_some_and(|c| c.addr == from));
+        match (message, slot) {
+            // A repeated join, its welcome lost on the way
+            (ClientMessage::Join, Some(id)) => {
+                self.send(from, &ServerMessage::Welcome { id: id as u8 })
+            }
+            (ClientMessage::Join, None) => {
+                let Some(id) = self.clients.iter().position(Option::is_none) else {
+                    self.send(from, &ServerMessage::Full);
+                    return;
+                };
+                self.clients[id] = Some(self.spawn(from, now));
+                info!(target: "net", player = id, from = %from, "player joined");
+                self.send(from, &ServerMessage::Welcome { id: id as u8 });
+            }
+            (ClientMessage::Input(input), Some(id)) => {
+                let client = self.clients[id].as_mut().expect("slot of a known client");
+                client.last_heard = now;
+                // Datagrams can arrive out of order
+                if input.seq > client.input.seq {
+                    client.input = input;
+                }
+            }
+            (ClientMessage::Leave, Some(id)) => {
+                self.clients[id] = None;
+                info!(target: "net", player = id, "player left");
+            }
+            (ClientMessage::Input(_) | ClientMessage::Leave, None) => {}
+        }
+    }
+
+    fn spawn(&self, addr: SocketAddr, now: Instant) -> Client {
+        let start = &self.world.player_start;
+        let floor_z = self
+            .world
+            .sector_at(start.pos)
+            .map_or(0.0, |s| self.world.sectors[s].floor_z);
+        Client {
+            addr,
+            input: NetInput {
+                yaw: start.yaw,
+                ..NetInput::default()
+            },
+            last_heard: now,
+            pos: start.pos,
+            velocity: [0.0, 0.0],
+            body: VerticalBody::new(floor_z),
+            player: Player::new(),
+            cooldown: 0.0,
+            activate_held: false,
+            frags: 0,
+        }
+    }
+
+    /// Advance the game one tick and send everyone the result
+    pub fn step(&mut self, now: Instant) {
+        for (id, slot) in self.clients.iter_mut().enumerate() {
+            if slot
+                .as_ref()
+                .is_some_and(|c| now.duration_since(c.last_heard) > TIMEOUT)
+            {
+                *slot = None;
+                info!(target: "net", player = id, "player timed out");
+            }
+        }
+
+        // Last tick's uses, for the doors and floors they start
+        let events = self.world.events.take();
+        sector_effects::handle(&mut self.world, &events);
+
+        // In the single-player game's order: walk, use walls, move doors and lifts, then
+        // platforms move and carry whoever stood on them, then fall
+        let mut using = [false; MAX_PLAYERS];
+        for (id, used) in using.iter_mut().enumerate() {
+            if self.clients[id].is_some() {
+                *used = self.move_player(id);
+            }
+        }
+        for (c, used) in self.clients.iter().zip(using) {
+            if let Some(c) = c
+                && used
+            {
+                self.world.use_line(c.pos, c.input.yaw, &c.player.inventory);
+            }
+        }
+        // A door doesn't close on anyone standing in it
+        let occupied: Vec<_> = self
+            .clients
+            .iter()
+            .flatten()
+            .filter_map(|c| self.world.sector_at(c.pos))
+            .collect();
+        sector_effects::update(&mut self.world, TICK_DT, &occupied);
+        self.world.update_switches(TICK_DT);
+        let riding: Vec<_> = self
+            .clients
+            .iter()
+            .map(|c| {
+                let c = c.as_ref().filter(|c| c.body.on_ground)?;
+                collision::riding(&self.world, c.pos, PLAYER_RADIUS, c.body.feet_z)
+            })
+            .collect();
+        entity::update(&mut self.world, TICK_DT);
+        for (id, riding) in riding.into_iter().enumerate() {
+            if self.clients[id].is_some() {
+                self.fall_player(id, riding);
+            }
+        }
+        for id in 0..MAX_PLAYERS {
+            if self.clients[id].is_some() {
+                self.shoot(id);
+            }
+        }
+
+        self.tick = self.tick.wrapping_add(1);
+        let message = ServerMessage::Snapshot(self.snapshot());
+        for client in self.clients.iter().flatten() {
+            self.send(client.addr, &message);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let players = self
+            .clients
+            .iter()
+            .enumerate()
+            .filter_map(|(id, c)| {
+                let c = c.as_ref()?;
+                Some(PlayerState {
+                    id: id as u8,
+                    pos: c.pos,
+                    feet_z: c.body.feet_z,
+                    eye_height: c.player.eye_height(c.body.eye_height),
+                    yaw: c.input.yaw,
+                    health: c.player.health,
+                    dead: c.player.is_dead(),
+                    frags: c.frags,
+                })
+            })
+            .collect();
+        Snapshot {
+            tick: self.tick,
+            players,
+        }
+    }
+
+    // The same walking the single-player game does, less the parts that only make sound
+    // or particles; whether they pressed Use, to use what they face once everyone's moved
+    fn move_player(&mut self, id: usize) -> bool {
+        let world = &self.world;
+        let spawn_pos = world.player_start.pos;
+        let c = self.clients[id].as_mut().expect("moving a present player");
+        let dt = TICK_DT;
+        c.player.update(dt);
+        c.cooldown = (c.cooldown - dt).max(0.0);
+
+        let mut input = c.input;
+        if c.player.is_dead() {
+            if input.activate && !c.activate_held && c.player.can_respawn() {
+                c.player.respawn();
+                c.pos = spawn_pos;
+                c.velocity = [0.0, 0.0];
+                let floor_z = world
+                    .sector_at(spawn_pos)
+                    .map_or(0.0, |s| world.sectors[s].floor_z);
+                c.body = VerticalBody::new(floor_z);
+            }
+            input = NetInput {
+                yaw: input.yaw,
+                ..NetInput::default()
+            };
+        }
+        // Use fires once per press, and the press that respawned doesn't also use a wall
+        let used = input.activate && !c.activate_held;
+        c.activate_held = c.input.activate;
+
+        let (s, co) = input.yaw.sin_cos();
+        let speed = if c.body.swimming {
+            MOVE_SPEED * crate::SWIM_MOVE_SCALE
+        } else {
+            MOVE_SPEED
+        };
+        let wanted = [
+            (s * input.forward + co * input.strafe) * speed,
+            (co * input.forward - s * input.strafe) * speed,
+        ];
+        let sector = world.sector_at(c.pos).map(|s| &world.sectors[s]);
+        let on_floor = c.body.on_ground && !c.body.swimming;
+        let friction = sector.filter(|_| on_floor).map_or(1.0, |s| s.friction());
+        c.velocity = physics::walk_velocity(c.velocity, wanted, friction, dt);
+        let push = sector.map_or([0.0, 0.0], |s| s.push(on_floor));
+        let delta = [
+            (c.velocity[0] + push[0]) * dt,
+            (c.velocity[1] + push[1]) * dt,
+        ];
+        if delta != [0.0, 0.0] {
+            let from = c.pos;
+            c.pos = collision::slide_move(
+                world,
+                c.pos,
+                delta,
+                PLAYER_RADIUS,
+                c.body.feet_z,
+                c.body.eye_height + HEAD_ABOVE_EYE,
+            );
+            // Teleporters move the player but leave their facing to their client
+            let entered = world
+                .sector_at(c.pos)
+                .filter(|&s| world.sector_at(from) != Some(s));
+            let teleport = world
+                .crossed_teleport(from, c.pos)
+                .or_else(|| entered.and_then(|s| world.sectors[s].teleport()));
+            if let Some(teleport) = teleport
+                && let Some(to) = world.destination(teleport.tag)
+            {
+                c.velocity = teleport.arrival_velocity(c.velocity, input.yaw, to.yaw);
+                c.pos = to.pos;
+                let floor_z = world
+                    .sector_at(to.pos)
+                    .map_or(0.0, |s| world.sectors[s].floor_z);
+                c.body = VerticalBody::new(floor_z);
+            }
+        }
+        used
+    }
+
+    // Then the same riding, falling and swimming, by the input `move_player` went by
+    fn fall_player(&mut self, id: usize, riding: Option<(EntityId, [f32; 2])>) {
+        let world = &self.world;
+        let c = self.clients[id].as_mut().expect("moving a present player");
+        let dt = TICK_DT;
+        // The dead lie still
+        let input = if c.player.is_dead() {
+            NetInput::default()
+        } else {
+            c.input
+        };
+        c.pos = collision::ride(
+            world,
+            riding,
+            c.pos,
+            PLAYER_RADIUS,
+            c.body.feet_z,
+            c.body.eye_height + HEAD_ABOVE_EYE,
+        );
+        let Some(s) = world.sector_at(c.pos) else {
+            return;
+        };
+        let sector = &world.sectors[s];
+        let (floor_z, ceiling_z) =
+            collision::floor_ceiling_under(world, s, c.pos, PLAYER_RADIUS, c.body.feet_z);
+        if let Some(liquid) = sector.liquid {
+            let swim = input.jump as i32 as f32 - input.crouch as i32 as f32;
+            if input.jump && !c.body.swimming {
+                c.body.jump();
+            }
+            c.body
+                .update_in_liquid(dt, floor_z, ceiling_z, liquid.surface_z, swim, input.crouch);
+        } else {
+            if input.jump {
+                c.body.jump();
+            }
+            c.body.update(dt, floor_z, ceiling_z, input.crouch);
+        }
+        let in_liquid = sector.liquid.is_some_and(|l| c.body.feet_z < l.surface_z);
+        let mut per_second = if c.body.on_ground || in_liquid {
+            sector.damage_per_second()
+        } else {
+            0.0
+        };
+        let body = collision::sectors_under(world, c.pos, PLAYER_RADIUS);
+        let crouched_top_z = c.body.feet_z + CROUCH_EYE_HEIGHT + HEAD_ABOVE_EYE;
+        if sector_effects::crusher_over(world, &body, crouched_top_z).is_some() {
+            per_second += sector_effects::CRUSH_DAMAGE;
+        }
+        if per_second > 0.0 {
+            let amount = c.player.hazard(dt, per_second);
+            c.player.damage(amount);
+        }
+    }
+
+    // A hitscan shot from player `id` if they're firing, stopped by walls, hurting the
+    // first other player in its way
+    fn shoot(&mut self, id: usize) {
+        let c = self.clients[id]
+            .as_mut()
+            .expect("shooting a present player");
+        if !c.input.fire || c.cooldown > 0.0 || c.player.is_dead() {
+            return;
+        }
+        c.cooldown = weapon::COOLDOWN;
+        let ray = Ray {
+            origin: c.pos,
+            dir: [c.input.yaw.sin(), c.input.yaw.cos()],
+            z: c.body.feet_z + c.player.eye_height(c.body.eye_height),
+            slope: 0.0,
+        };
+
+        let limit = raycast::cast(&self.world, &ray, weapon::RANGE)
+            .wall
+            .map_or(weapon::RANGE, |hit| hit.distance);
+        let target = self
+            .clients
+            .iter()
+            .enumerate()
+            .filter(|&(other, _)| other != id)
+            .filter_map(|(other, c)| {
+                let c = c.as_ref().filter(|c| !c.player.is_dead())?;
+                let t = raycast::ray_circle(&ray, c.pos, HIT_RADIUS)?;
+                let z = ray.z_at(t);
+                let top = c.body.feet_z + c.body.eye_height + HEAD_ABOVE_EYE;
+                (t <= limit && z >= c.body.feet_z && z <= top).then_some((other, t))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((target, _)) = target else {
+            return;
+        };
+        let victim = self.clients[target].as_mut().expect("target is present");
+        if victim.player.damage(weapon::DAMAGE as f32) {
+            info!(target: "net", player = id, victim = target, "frag");
+            let shooter = self.clients[id].as_mut().expect("shooter is present");
+            shooter.frags = shooter.frags.saturating_add(1);
+        }
+    }
+
+    fn send(&self, to: SocketAddr, message: &ServerMessage) {
+        // Lost datagrams are covered by the next tick's
+        let _ = self.socket.send_to(&message.encode(), to);
+    }
+}
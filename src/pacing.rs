@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+pub const DEFAULT_TARGET_FPS: u32 = 60;
+
+/// Decides when the next frame is due: sleep until then with `WaitUntil`, or redraw as fast
+/// as possible when uncapped (benchmarking)
+pub struct FramePacer {
+    frame_time: Option<Duration>, // None when uncapped
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    /// `target_fps` of None or 0 means uncapped
+    pub fn new(target_fps: Option<u32>) -> Self {
+        Self {
+            frame_time: target_fps
+                .filter(|&fps| fps > 0)
+                .map(|fps| Duration::from_secs(1) / fps),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Called when the event loop is about to wait: whether a frame is due now, and how long
+    /// the loop should wait afterwards
+    pub fn poll(&mut self, now: Instant) -> (bool, ControlFlow) {
+        let Some(frame_time) = self.frame_time else {
+            return (true, ControlFlow::Poll);
+        };
+        if now < self.next_frame {
+            return (false, ControlFlow::WaitUntil(self.next_frame));
+        }
+
+        // Keep a steady cadence, but don't try to catch up on frames missed by a long stall
+        self.next_frame += frame_time;
+        if self.next_frame <= now {
+            self.next_frame = now + frame_time;
+        }
+        (true, ControlFlow::WaitUntil(self.next_frame))
+    }
+}
@@ -0,0 +1,209 @@
+// 256-color palette and the lookup tables for drawing in palette indices: colormaps
+// for shading, blend tables for translucency, and textures converted to indices
+
+use crate::renderer::{WHITE, add_saturating, mix, mix_half, pack_rgb, shade, tint};
+use crate::texture::{TRANSPARENT, Texture};
+
+/// Shading steps from black to full bright
+pub const LIGHT_LEVELS: usize = 32;
+
+/// Index kept for `TRANSPARENT` texels; never chosen as the nearest color
+pub const TRANSPARENT_INDEX: u8 = 255;
+
+#[derive(Clone)]
+pub struct Palette {
+    pub colors: [u32; 256], // packed BGRA8
+}
+
+impl Default for Palette {
+    /// Eight ramps of 32 steps from black to a full-bright hue, so shading keeps hues apart
+    fn default() -> Self {
+        const RAMPS: [(u32, u32, u32); 8] = [
+            (255, 255, 255), // gray
+            (255, 170, 170), // pink
+            (170, 255, 170), // mint
+            (180, 180, 255), // lavender
+            (255, 210, 150), // tan
+            (255, 140, 40),  // orange
+            (255, 60, 60),   // red
+            (60, 90, 255),   // blue
+        ];
+        let mut colors = [TRANSPARENT; 256];
+        for (i, color) in colors[..TRANSPARENT_INDEX as usize].iter_mut().enumerate() {
+            let (r, g, b) = RAMPS[i / 32];
+            let step = (i % 32) as u32 + 1;
+            *color = pack_rgb(
+                (r * step / 32) as u8,
+                (g * step / 32) as u8,
+                (b * step / 32) as u8,
+            );
+        }
+        Self { colors }
+    }
+}
+
+impl Palette {
+    /// Closest entry to `color` by squared RGB distance
+    pub fn nearest(&self, color: u32) -> u8 {
+        let ch = |c: u32, shift: u32| ((c >> shift) & 0xFF) as i32;
+        let dist = |c: u32| {
+            let (dr, dg, db) = (
+                ch(c, 16) - ch(color, 16),
+                ch(c, 8) - ch(color, 8),
+                ch(c, 0) - ch(color, 0),
+            );
+            dr * dr + dg * dg + db * db
+        };
+        (0..TRANSPARENT_INDEX)
+            .min_by_key(|&i| dist(self.colors[i as usize]))
+            .unwrap_or(0)
+    }
+}
+
+/// A texture as palette indices, same size and layout as the `Texture` it came from
+pub struct IndexedTexture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl IndexedTexture {
+    pub fn quantize(texture: &Texture, palette: &Palette) -> Self {
+        Self {
+            width: texture.width,
+            height: texture.height,
+            pixels: texture
+                .pixels
+                .iter()
+                .map(|&c| match c {
+                    TRANSPARENT => TRANSPARENT_INDEX,
+                    c => palette.nearest(c),
+                })
+                .collect(),
+        }
+    }
+
+    /// Fetch a texel with wrap-around addressing, like `Texture::texel`
+    #[inline]
+    pub fn texel(&self, tx: i32, ty: i32) -> u8 {
+        let x = tx.rem_euclid(self.width as i32) as usize;
+        let y = ty.rem_euclid(self.height as i32) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Lookup tables built once per palette, so drawing never searches for a color
+pub struct Colormap {
+    pub palette: Palette,
+    light: Vec<[u8; 256]>,              // [level][index] -> shaded index
+    translucent: Vec<u8>,               // [dst * 256 + src] -> 50% mix
+    additive: Vec<u8>,                  // [dst * 256 + src] -> saturating sum
+    fog: Option<(u32, Vec<[u8; 256]>)>, // fog color and [level][index] -> fogged index
+    tints: Vec<(u32, [u8; 256])>,       // light color and [index] -> tinted index
+}
+
+impl Colormap {
+    pub fn new(palette: Palette) -> Self {
+        let light = (0..LIGHT_LEVELS)
+            .map(|level| {
+                let scale = (level * 256 / (LIGHT_LEVELS - 1)) as u32;
+                std::array::from_fn(|i| match i as u8 {
+                    TRANSPARENT_INDEX => TRANSPARENT_INDEX,
+                    i => palette.nearest(shade(palette.colors[i as usize], scale)),
+                })
+            })
+            .collect();
+        let table = |op: fn(u32, u32) -> u32| -> Vec<u8> {
+            (0..256 * 256)
+                .map(|i| palette.nearest(op(palette.colors[i >> 8], palette.colors[i & 0xFF])))
+                .collect()
+        };
+        let translucent = table(mix_half);
+        let additive = table(add_saturating);
+        Self {
+            palette,
+            light,
+            translucent,
+            additive,
+            fog: None,
+            tints: Vec::new(),
+        }
+    }
+
+    /// Build the fog tables for fog of `color`, unless they're already for that color
+    pub fn set_fog_color(&mut self, color: u32) {
+        if self.fog.as_ref().is_some_and(|(c, _)| *c == color) {
+            return;
+        }
+        let palette = &self.palette;
+        let levels = (0..LIGHT_LEVELS)
+            .map(|level| {
+                let amount = (level * 256 / (LIGHT_LEVELS - 1)) as u32;
+                std::array::from_fn(|i| match i as u8 {
+                    TRANSPARENT_INDEX => TRANSPARENT_INDEX,
+                    i => palette.nearest(mix(palette.colors[i as usize], color, amount)),
+                })
+            })
+            .collect();
+        self.fog = Some((color, levels));
+    }
+
+    /// Build the tables for sector light `colors` that don't have one yet; white needs none
+    pub fn add_tints(&mut self, colors: impl IntoIterator<Item = u32>) {
+        for color in colors {
+            if color == WHITE || self.tints.iter().any(|(c, _)| *c == color) {
+                continue;
+            }
+            let palette = &self.palette;
+            let table = std::array::from_fn(|i| match i as u8 {
+                TRANSPARENT_INDEX => TRANSPARENT_INDEX,
+                i => palette.nearest(tint(palette.colors[i as usize], color)),
+            });
+            self.tints.push((color, table));
+        }
+    }
+
+    /// `index` multiplied by light `color`; unchanged if `add_tints` hasn't seen the color
+    #[inline]
+    pub fn tint(&self, index: u8, color: u32) -> u8 {
+        self.tints
+            .iter()
+            .find(|(c, _)| *c == color)
+            .map_or(index, |(_, table)| table[index as usize])
+    }
+
+    /// `index` lit by a 0..=256 light scale, as from the renderer's distance shading
+    #[inline]
+    pub fn shade(&self, index: u8, light: u32) -> u8 {
+        let level = (light as usize * (LIGHT_LEVELS - 1) + 128) >> 8;
+        self.light[level.min(LIGHT_LEVELS - 1)][index as usize]
+    }
+
+    /// `index` blended toward the fog color by `amount` in 0..=256; unchanged before
+    /// `set_fog_color`
+    #[inline]
+    pub fn fog(&self, index: u8, amount: u32) -> u8 {
+        let Some((_, levels)) = &self.fog else {
+            return index;
+        };
+        let level = (amount as usize * (LIGHT_LEVELS - 1) + 128) >> 8;
+        levels[level.min(LIGHT_LEVELS - 1)][index as usize]
+    }
+
+    #[inline]
+    pub fn translucent(&self, dst: u8, src: u8) -> u8 {
+        self.translucent[(dst as usize) << 8 | src as usize]
+    }
+
+    #[inline]
+    pub fn additive(&self, dst: u8, src: u8) -> u8 {
+        self.additive[(dst as usize) << 8 | src as usize]
+    }
+
+    /// Expand an indexed frame to packed BGRA8
+    pub fn expand(&self, src: &[u8], dst: &mut [u32]) {
+        for (d, &s) in dst.iter_mut().zip(src) {
+            *d = self.palette.colors[s as usize];
+        }
+    }
+}
@@ -0,0 +1,234 @@
+// CPU particles for small effects: sparks, dust and smoke. Each is a point with a velocity
+// and a lifetime, drawn by the renderer as a tiny square that nearer walls hide.
+
+use std::collections::VecDeque;
+
+use crate::physics::GRAVITY;
+use crate::renderer::pack_rgb;
+use crate::rng::Rng;
+use crate::world::Blend;
+
+// Oldest particles are dropped past this many
+const MAX_PARTICLES: usize = 2048;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub pos: [f32; 2],
+    pub z: f32,
+    pub vel: [f32; 3], // world units per second, z up
+    pub age: f32,
+    pub life: f32, // seconds until it's removed
+    pub color: u32,
+    pub blend: Blend,
+    pub size: f32,    // world units across; far-off particles still cover one pixel
+    pub growth: f32,  // size added per second, e.g. for spreading smoke
+    pub gravity: f32, // fraction of full gravity, negative to rise
+    pub drag: f32,    // fraction of velocity lost per second
+    pub floor_z: f32, // stops falling here
+    pub light: f32,   // sector light level it's lit by, 0..=1
+    pub glow: bool,   // ignores `light` and dims as it ages instead, for sparks
+}
+
+impl Particle {
+    /// 0 when new ..= 1 when it's about to be removed
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        (self.age / self.life).min(1.0)
+    }
+}
+
+pub struct Particles {
+    list: VecDeque<Particle>, // oldest first
+    rng: Rng,                 // not the world's, so effects never change how play goes
+}
+
+impl Particles {
+    /// Drawing from `rng`, a stream forked off the world's
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            list: VecDeque::new(),
+            rng,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.list.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    pub fn emit(&mut self, particle: Particle) {
+        if self.list.len() >= MAX_PARTICLES {
+            self.list.pop_front();
+        }
+        self.list.push_back(particle);
+    }
+
+    /// Bright sparks bursting from a wall hit at `pos`, thrown back along `toward`
+    pub fn sparks(&mut self, pos: [f32; 2], z: f32, toward: [f32; 2], floor_z: f32) {
+        for _ in 0..12 {
+            let speed = 1.5 + 2.5 * self.rng.unit();
+            let spread = (self.rng.unit() - 0.5) * 2.0;
+            // Rotate `toward` by up to about 60 degrees either way
+            let (s, c) = spread.sin_cos();
+            let dir = [toward[0] * c - toward[1] * s, toward[0] * s + toward[1] * c];
+            let vz = 2.0 * self.rng.unit();
+            let life = 0.2 + 0.3 * self.rng.unit();
+            self.emit(Particle {
+                pos,
+                z,
+                vel: [dir[0] * speed, dir[1] * speed, vz],
+                age: 0.0,
+                life,
+                color: pack_rgb(255, 220, 120),
+                blend: Blend::Additive,
+                size: 0.02,
+                growth: 0.0,
+                gravity: 1.0,
+                drag: 1.0,
+                floor_z,
+                light: 1.0,
+                glow: true,
+            });
+        }
+    }
+
+    /// A ring of dust kicked up around `pos` on the floor, e.g. when landing from a fall
+    pub fn dust(&mut self, pos: [f32; 2], floor_z: f32, light: f32) {
+        for i in 0..10 {
+            let angle = (i as f32 + self.rng.unit()) * std::f32::consts::TAU / 10.0;
+            let speed = 0.6 + 0.6 * self.rng.unit();
+            let vz = 0.4 * self.rng.unit();
+            let life = 0.5 + 0.3 * self.rng.unit();
+            self.emit(Particle {
+                pos,
+                z: floor_z + 0.02,
+                vel: [angle.sin() * speed, angle.cos() * speed, vz],
+                age: 0.0,
+                life,
+                color: pack_rgb(150, 135, 110),
+                blend: Blend::Translucent,
+                size: 0.06,
+                growth: 0.1,
+                gravity: 0.3,
+                drag: 3.0,
+                floor_z,
+                light,
+                glow: false,
+            });
+        }
+    }
+
+    /// A few puffs of smoke drifting up from `pos` and spreading as they go
+    pub fn smoke(&mut self, pos: [f32; 2], z: f32, light: f32) {
+        for _ in 0..4 {
+            let drift = [(self.rng.unit() - 0.5) * 0.3, (self.rng.unit() - 0.5) * 0.3];
+            let rise = 0.3 + 0.3 * self.rng.unit();
+            let life = 0.8 + 0.6 * self.rng.unit();
+            self.emit(Particle {
+                pos,
+                z,
+                vel: [drift[0], drift[1], rise],
+                age: 0.0,
+                life,
+                color: pack_rgb(180, 180, 175),
+                blend: Blend::Translucent,
+                size: 0.08,
+                growth: 0.25,
+                gravity: 0.0,
+                drag: 0.5,
+                floor_z: f32::NEG_INFINITY,
+                light,
+                glow: false,
+            });
+        }
+    }
+
+    /// A glowing column rising from the floor at `pos`, where something teleported from or to
+    pub fn flash(&mut self, pos: [f32; 2], floor_z: f32) {
+        for i in 0..24 {
+            let angle = (i as f32 + self.rng.unit()) * std::f32::consts::TAU / 24.0;
+            let spread = 0.2 + 0.2 * self.rng.unit();
+            let rise = 1.0 + 1.5 * self.rng.unit();
+            let z = floor_z + 0.1 + 0.8 * self.rng.unit();
+            let life = 0.4 + 0.4 * self.rng.unit();
+            self.emit(Particle {
+                pos: [pos[0] + angle.sin() * spread, pos[1] + angle.cos() * spread],
+                z,
+                vel: [0.0, 0.0, rise],
+                age: 0.0,
+                life,
+                color: pack_rgb(140, 255, 160),
+                blend: Blend::Additive,
+                size: 0.05,
+                growth: 0.0,
+                gravity: 0.0,
+                drag: 1.0,
+                floor_z,
+                light: 1.0,
+                glow: true,
+            });
+        }
+    }
+
+    /// Chunks of `color` flying out from `pos` at height `z`, as when a prop is destroyed
+    pub fn debris(&mut self, pos: [f32; 2], z: f32, color: u32, floor_z: f32, light: f32) {
+        for i in 0..16 {
+            let angle = (i as f32 + self.rng.unit()) * std::f32::consts::TAU / 16.0;
+            let speed = 1.0 + 2.0 * self.rng.unit();
+            let vz = 1.0 + 2.5 * self.rng.unit();
+            let life = 0.8 + 0.6 * self.rng.unit();
+            self.emit(Particle {
+                pos,
+                z,
+                vel: [angle.sin() * speed, angle.cos() * speed, vz],
+                age: 0.0,
+                life,
+                color,
+                blend: Blend::Masked,
+                size: 0.05,
+                growth: 0.0,
+                gravity: 1.0,
+                drag: 0.5,
+                floor_z,
+                light,
+                glow: false,
+            });
+        }
+    }
+
+    /// Move every particle on by `dt` seconds and remove those past their lifetime
+    pub fn update(&mut self, dt: f32) {
+        self.list.retain_mut(|p| {
+            p.age += dt;
+            if p.age >= p.life {
+                return false;
+            }
+            let keep = (1.0 - p.drag * dt).max(0.0);
+            p.vel[2] -= GRAVITY * p.gravity * dt;
+            for v in &mut p.vel {
+                *v *= keep;
+            }
+            p.pos[0] += p.vel[0] * dt;
+            p.pos[1] += p.vel[1] * dt;
+            p.z += p.vel[2] * dt;
+            if p.z < p.floor_z {
+                // Comes to rest on the floor
+                p.z = p.floor_z;
+                p.vel = [0.0, 0.0, 0.0];
+            }
+            p.size += p.growth * dt;
+            true
+        });
+    }
+}
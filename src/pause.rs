@@ -0,0 +1,225 @@
+// The Esc menu: resume, settings or quit, with the settings page editing the player's
+// config in place for the game to apply and save
+
+use two_halfD_engine::camera::MAX_FOV_X;
+use two_halfD_engine::menu::{Menu, MenuAction, MenuItem, MenuKey};
+
+use crate::config::Config;
+
+const FOV_RANGE: (f32, f32) = (60.0, MAX_FOV_X);
+const FOV_STEP: f32 = 5.0;
+const SENSITIVITY_RANGE: (f32, f32) = (0.0005, 0.01);
+const SENSITIVITY_STEP: f32 = 0.0005;
+const VOLUME_STEP: f32 = 0.1;
+const FLASH_STEP: f32 = 0.25;
+
+// Settings page items, in order
+const SETTINGS: [Setting; 11] = [
+    Setting::Fov,
+    Setting::RenderScale,
+    Setting::Sensitivity,
+    Setting::ScaleMode,
+    Setting::Mipmaps,
+    Setting::Hud,
+    Setting::Crosshair,
+    Setting::ViewEffects,
+    Setting::Flashes,
+    Setting::EffectsVolume,
+    Setting::MusicVolume,
+];
+
+#[derive(Clone, Copy)]
+enum Setting {
+    Fov,
+    RenderScale,
+    Sensitivity,
+    ScaleMode,
+    Mipmaps,
+    Hud,
+    Crosshair,
+    ViewEffects,
+    Flashes,
+    EffectsVolume,
+    MusicVolume,
+}
+
+impl Setting {
+    fn label(self) -> &'static str {
+        match self {
+            Setting::Fov => "field of view",
+            Setting::RenderScale => "render scale",
+            Setting::Sensitivity => "mouse sensitivity",
+            Setting::ScaleMode => "scaling",
+            Setting::Mipmaps => "mipmaps",
+            Setting::Hud => "hud",
+            Setting::Crosshair => "crosshair",
+            Setting::ViewEffects => "view motion",
+            Setting::Flashes => "screen flashes",
+            Setting::EffectsVolume => "effects volume",
+            Setting::MusicVolume => "music volume",
+        }
+    }
+
+    fn value(self, config: &Config) -> String {
+        match self {
+            Setting::Fov => format!("{:.0}", config.fov),
+            Setting::RenderScale => config.render_scale.to_string(),
+            Setting::Sensitivity => format!("{:.1}", config.mouse_sensitivity * 1000.0),
+            Setting::ScaleMode => format!("{:?}", config.scale_mode),
+            Setting::Mipmaps => format!("{:?}", config.mipmaps),
+            Setting::Hud => format!("{:?}", config.hud),
+            Setting::Crosshair => format!("{:?}", config.crosshair),
+            Setting::ViewEffects => if config.view_effects { "on" } else { "off" }.to_string(),
+            Setting::Flashes => format!("{:.0}%", config.flash_intensity * 100.0),
+            Setting::EffectsVolume => format!("{:.0}%", config.effects_volume * 100.0),
+            Setting::MusicVolume => format!("{:.0}%", config.music_volume * 100.0),
+        }
+    }
+
+    // Step `config` one notch up (+1) or down (-1)
+    fn adjust(self, config: &mut Config, dir: i32) {
+        let step = |value: f32, step: f32, (lo, hi): (f32, f32)| {
+            // Rounded to the step so repeated presses don't drift
+            (((value + step * dir as f32) / step).round() * step).clamp(lo, hi)
+        };
+        match self {
+            Setting::Fov => config.fov = step(config.fov, FOV_STEP, FOV_RANGE),
+            Setting::RenderScale => {
+                config.render_scale = if dir < 0 {
+                    config.render_scale.prev()
+                } else {
+                    config.render_scale.next()
+                };
+            }
+            Setting::Sensitivity => {
+                config.mouse_sensitivity = step(
+                    config.mouse_sensitivity,
+                    SENSITIVITY_STEP,
+                    SENSITIVITY_RANGE,
+                );
+            }
+            Setting::ScaleMode => {
+                config.scale_mode = if dir < 0 {
+                    config.scale_mode.prev()
+                } else {
+                    config.scale_mode.next()
+                };
+            }
+            Setting::Mipmaps => {
+                config.mipmaps = if dir < 0 {
+                    config.mipmaps.prev()
+                } else {
+                    config.mipmaps.next()
+                };
+            }
+            Setting::Hud => {
+                config.hud = if dir < 0 {
+                    config.hud.prev()
+                } else {
+                    config.hud.next()
+                };
+            }
+            Setting::Crosshair => {
+                config.crosshair = if dir < 0 {
+                    config.crosshair.prev()
+                } else {
+                    config.crosshair.next()
+                };
+            }
+            Setting::ViewEffects => config.view_effects = !config.view_effects,
+            Setting::Flashes => {
+                config.flash_intensity = step(config.flash_intensity, FLASH_STEP, (0.0, 1.0));
+            }
+            Setting::EffectsVolume => {
+                config.effects_volume = step(config.effects_volume, VOLUME_STEP, (0.0, 1.0));
+            }
+            Setting::MusicVolume => {
+                config.music_volume = step(config.music_volume, VOLUME_STEP, (0.0, 1.0));
+            }
+        }
+    }
+}
+
+/// What the game should do after a key in the menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PauseOutcome {
+    Stay,
+    Resume,
+    Quit,
+    SettingsChanged, // the config was edited: apply and save it
+}
+
+pub struct PauseMenu {
+    menu: Menu,
+    in_settings: bool,
+    recapture: bool, // the mouse was captured when the menu opened
+}
+
+impl PauseMenu {
+    pub fn new(recapture: bool) -> Self {
+        Self {
+            menu: main_page(),
+            in_settings: false,
+            recapture,
+        }
+    }
+
+    /// Whether to capture the mouse again on resuming
+    pub fn recapture(&self) -> bool {
+        self.recapture
+    }
+
+    pub fn key(&mut self, key: MenuKey, config: &mut Config) -> PauseOutcome {
+        let action = self.menu.handle(key);
+        if self.in_settings {
+            return match action {
+                MenuAction::Adjust(i, dir) => {
+                    SETTINGS[i].adjust(config, dir);
+                    self.menu.set_value(i, SETTINGS[i].value(config));
+                    PauseOutcome::SettingsChanged
+                }
+                // "back" is the item after the settings
+                MenuAction::Activate(_) | MenuAction::Back => {
+                    self.menu = main_page();
+                    self.in_settings = false;
+                    PauseOutcome::Stay
+                }
+                MenuAction::None => PauseOutcome::Stay,
+            };
+        }
+        match action {
+            MenuAction::Activate(0) | MenuAction::Back => PauseOutcome::Resume,
+            MenuAction::Activate(1) => {
+                self.menu = settings_page(config);
+                self.in_settings = true;
+                PauseOutcome::Stay
+            }
+            MenuAction::Activate(_) => PauseOutcome::Quit,
+            MenuAction::Adjust(..) | MenuAction::None => PauseOutcome::Stay,
+        }
+    }
+
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize) {
+        self.menu.draw(buf, width, height);
+    }
+}
+
+fn main_page() -> Menu {
+    Menu::new(
+        "paused",
+        vec![
+            MenuItem::action("resume"),
+            MenuItem::action("settings"),
+            MenuItem::action("quit"),
+        ],
+    )
+}
+
+fn settings_page(config: &Config) -> Menu {
+    let mut items: Vec<MenuItem> = SETTINGS
+        .iter()
+        .map(|s| MenuItem::value(s.label(), s.value(config)))
+        .collect();
+    items.push(MenuItem::action("back"));
+    Menu::new("settings", items)
+}
@@ -0,0 +1,164 @@
+// Vertical motion of the player: gravity, jumping, crouching and sector floor/ceiling contact
+
+use serde::{Deserialize, Serialize};
+
+use crate::collision::MAX_STEP;
+
+pub const GRAVITY: f32 = 12.0; // m/s^2, a little above real gravity so jumps feel snappy
+pub const JUMP_SPEED: f32 = 4.5; // m/s upward at takeoff
+pub const STAND_EYE_HEIGHT: f32 = 1.7;
+pub const CROUCH_EYE_HEIGHT: f32 = 1.0;
+// Top of the head above the eyes, for ceiling contact
+pub const HEAD_ABOVE_EYE: f32 = 0.1;
+// Eye height change per second when crouching or standing up
+const CROUCH_SPEED: f32 = 4.0;
+// Swimming once the feet are this far under a liquid's surface, and floating with them there
+pub const SWIM_DEPTH: f32 = 1.2;
+// m/s up or down when swimming
+const SWIM_SPEED: f32 = 2.0;
+// Slow sinking when not swimming either way, m/s
+const SINK_SPEED: f32 = 0.4;
+// How fast vertical speed eases toward the swim speed in water, per second
+const WATER_DRAG: f32 = 4.0;
+
+// How fast walking speed catches up with what's asked for on floors of friction 1, per
+// second; slippery floors scale this down
+const GRIP: f32 = 12.0;
+
+/// Horizontal velocity after `dt` seconds of trying to move at `wanted` on a floor of
+/// `friction`: at once with full grip, sliding toward it on anything less
+pub fn walk_velocity(velocity: [f32; 2], wanted: [f32; 2], friction: f32, dt: f32) -> [f32; 2] {
+    if friction >= 1.0 {
+        return wanted;
+    }
+    let t = (GRIP * friction.max(0.0) * dt).min(1.0);
+    [
+        velocity[0] + (wanted[0] - velocity[0]) * t,
+        velocity[1] + (wanted[1] - velocity[1]) * t,
+    ]
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VerticalBody {
+    pub feet_z: f32,
+    pub vz: f32,         // vertical velocity, positive up
+    pub eye_height: f32, // above the feet, eases between standing and crouched
+    pub on_ground: bool,
+    #[serde(default)] // absent from saves made before there was water
+    pub swimming: bool,
+}
+
+impl VerticalBody {
+    pub fn new(feet_z: f32) -> Self {
+        Self {
+            feet_z,
+            vz: 0.0,
+            eye_height: STAND_EYE_HEIGHT,
+            on_ground: true,
+            swimming: false,
+        }
+    }
+
+    #[inline]
+    pub fn eye_z(&self) -> f32 {
+        self.feet_z + self.eye_height
+    }
+
+    /// Start a jump; ignored in mid-air
+    pub fn jump(&mut self) {
+        if self.on_ground {
+            self.vz = JUMP_SPEED;
+            self.on_ground = false;
+        }
+    }
+
+    /// Advance by `dt` seconds inside a sector spanning `floor_z`..`ceiling_z`
+    pub fn update(&mut self, dt: f32, floor_z: f32, ceiling_z: f32, crouching: bool) {
+        self.swimming = false;
+        self.ease_eye_height(dt, ceiling_z, crouching);
+
+        // Walking off a small ledge steps down; anything taller is a fall
+        if self.on_ground && self.feet_z > floor_z && self.feet_z - floor_z <= MAX_STEP {
+            self.feet_z = floor_z;
+        }
+        if !self.on_ground || self.feet_z > floor_z {
+            self.vz -= GRAVITY * dt;
+            self.on_ground = false;
+        }
+        self.feet_z += self.vz * dt;
+
+        // Landing
+        if self.feet_z <= floor_z {
+            self.feet_z = floor_z;
+            self.vz = 0.0;
+            self.on_ground = true;
+        }
+
+        // Head bump: stop rising, keep the head under the ceiling
+        let head_z = self.eye_z() + HEAD_ABOVE_EYE;
+        if head_z > ceiling_z {
+            self.feet_z = (ceiling_z - self.eye_height - HEAD_ABOVE_EYE).max(floor_z);
+            self.vz = self.vz.min(0.0);
+        }
+    }
+
+    /// As `update`, in a sector filled with liquid up to `surface_z`. Deep enough in, the
+    /// body swims: `swim` is -1 (down) ..= 1 (up), and swimming up at the surface leaps out.
+    pub fn update_in_liquid(
+        &mut self,
+        dt: f32,
+        floor_z: f32,
+        ceiling_z: f32,
+        surface_z: f32,
+        swim: f32,
+        crouching: bool,
+    ) {
+        // Floating with the feet this deep, so the head stays above the surface
+        let float_z = surface_z - SWIM_DEPTH;
+        if self.feet_z > float_z + f32::EPSILON || float_z <= floor_z {
+            // Wading, or falling in from above
+            self.update(dt, floor_z, ceiling_z, crouching);
+            return;
+        }
+        self.swimming = true;
+        self.ease_eye_height(dt, ceiling_z, false);
+
+        let target = if swim != 0.0 {
+            swim.clamp(-1.0, 1.0) * SWIM_SPEED
+        } else {
+            -SINK_SPEED
+        };
+        self.vz += (target - self.vz) * (WATER_DRAG * dt).min(1.0);
+        self.feet_z += self.vz * dt;
+
+        if self.feet_z <= floor_z {
+            self.feet_z = floor_z;
+            self.vz = self.vz.max(0.0);
+            self.on_ground = true;
+        } else {
+            self.on_ground = false;
+        }
+        if self.feet_z >= float_z {
+            self.feet_z = float_z;
+            if swim > 0.0 {
+                // Kick up out of the water, e.g. onto a ledge
+                self.vz = JUMP_SPEED;
+                self.swimming = false;
+            } else {
+                self.vz = self.vz.min(0.0);
+            }
+        }
+    }
+
+    // Can't stand up under a ceiling that is too low
+    fn ease_eye_height(&mut self, dt: f32, ceiling_z: f32, crouching: bool) {
+        let room = ceiling_z - self.feet_z - HEAD_ABOVE_EYE;
+        let target = if crouching {
+            CROUCH_EYE_HEIGHT
+        } else {
+            STAND_EYE_HEIGHT.min(room.max(CROUCH_EYE_HEIGHT))
+        };
+        let step = CROUCH_SPEED * dt;
+        self.eye_height += (target - self.eye_height).clamp(-step, step);
+    }
+}
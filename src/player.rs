@@ -0,0 +1,185 @@
+// The player's health, armor and inventory: taking damage, picking things up, dying with a
+// fall to the floor, and getting back up at the map's player start
+
+use serde::{Deserialize, Serialize};
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::inventory::{Inventory, Pickup, START_AMMO};
+use crate::renderer::{mix, pack_rgb};
+
+pub const MAX_HEALTH: f32 = 100.0;
+// Share of each hit that armor soaks up while it lasts
+const ARMOR_ABSORB: f32 = 1.0 / 3.0;
+// Seconds between hurts while standing on a damaging floor
+const HAZARD_INTERVAL: f32 = 0.5;
+// Seconds to fall from standing to lying on the floor, and the eye height there
+const FALL_TIME: f32 = 0.8;
+const DEAD_EYE_HEIGHT: f32 = 0.25;
+// Seconds dead before Use gets back up, so the killing blow isn't skipped past
+pub const RESPAWN_DELAY: f32 = 1.0;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Player {
+    pub health: f32, // dead at 0
+    pub armor: f32,
+    #[serde(default)] // absent from saves made before there was anything to carry
+    pub inventory: Inventory,
+    dead_for: Option<f32>, // seconds since dying
+    hazard_timer: f32,     // toward the next hurt from the floor
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            armor: 0.0,
+            inventory: Inventory::new(),
+            dead_for: None,
+            hazard_timer: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead_for.is_some()
+    }
+
+    /// Dead long enough to get back up
+    pub fn can_respawn(&self) -> bool {
+        self.dead_for.is_some_and(|t| t >= RESPAWN_DELAY)
+    }
+
+    /// Take `amount` of damage, part of it soaked up by armor; true if it killed.
+    /// Nothing hurts the dead.
+    pub fn damage(&mut self, amount: f32) -> bool {
+        if self.is_dead() || amount <= 0.0 {
+            return false;
+        }
+        let absorbed = (amount * ARMOR_ABSORB).min(self.armor);
+        self.armor -= absorbed;
+        self.health = (self.health - (amount - absorbed)).max(0.0);
+        if self.health == 0.0 {
+            self.dead_for = Some(0.0);
+        }
+        self.is_dead()
+    }
+
+    /// Damage due this tick from standing `dt` seconds on a floor that hurts `per_second`,
+    /// dealt in pulses so each one can be felt; 0 between pulses
+    pub fn hazard(&mut self, dt: f32, per_second: f32) -> f32 {
+        self.hazard_timer += dt;
+        let pulses = (self.hazard_timer / HAZARD_INTERVAL).floor();
+        self.hazard_timer -= pulses * HAZARD_INTERVAL;
+        pulses * HAZARD_INTERVAL * per_second
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if let Some(t) = &mut self.dead_for {
+            *t += dt;
+        }
+    }
+
+    /// Eye height above the feet: `standing` while alive, easing down to the floor
+    /// after dying
+    pub fn eye_height(&self, standing: f32) -> f32 {
+        let Some(t) = self.dead_for else {
+            return standing;
+        };
+        let fall = (t / FALL_TIME).min(1.0);
+        // Slow to topple, fast to hit the floor
+        let fall = fall * fall;
+        standing + (DEAD_EYE_HEIGHT.min(standing) - standing) * fall
+    }
+
+    /// Take what `pickup` gives; false if it's no use, e.g. health at full health
+    pub fn pick_up(&mut self, pickup: Pickup) -> bool {
+        match pickup {
+            Pickup::Health { amount } => {
+                if self.health >= MAX_HEALTH {
+                    return false;
+                }
+                self.health = (self.health + amount).min(MAX_HEALTH);
+                true
+            }
+            Pickup::Ammo { amount } => self.inventory.give_ammo(amount),
+            Pickup::Key(key) => self.inventory.give_key(key),
+        }
+    }
+
+    /// Back to full health, without armor, for a fresh start at the player start. Keys are
+    /// kept, since the doors they open stay locked, and ammo is topped up to what a fresh
+    /// start has.
+    pub fn respawn(&mut self) {
+        let mut inventory = self.inventory;
+        inventory.ammo = inventory.ammo.max(START_AMMO);
+        *self = Self {
+            inventory,
+            ..Self::new()
+        };
+    }
+
+    /// Health and armor in the bottom-left corner and the inventory in the bottom-right:
+    /// the minimal HUD, for when the status bar is off
+    pub fn draw_hud(&self, buf: &mut [u32], width: usize, height: usize) {
+        // Same sizing as the profiler overlay, so the two read alike
+        let scale = (height / 240).max(1);
+        let margin = 2 * scale;
+        let y = height.saturating_sub(margin + GLYPH_HEIGHT * scale);
+        let health = self.health.ceil() as u32;
+        let health_color = if health <= 25 {
+            pack_rgb(255, 60, 60)
+        } else {
+            pack_rgb(255, 255, 255)
+        };
+        let label = format!("health {health}");
+        font::draw_text(buf, width, height, [margin, y], &label, health_color, scale);
+        if self.armor > 0.0 {
+            let x = margin + font::text_width(&label, scale) + 4 * margin;
+            let label = format!("armor {}", self.armor.ceil() as u32);
+            font::draw_text(
+                buf,
+                width,
+                height,
+                [x, y],
+                &label,
+                pack_rgb(120, 200, 255),
+                scale,
+            );
+        }
+
+        self.inventory.draw_hud(buf, width, height);
+    }
+
+    /// The view tinted red while dead, and how to get back up once that's possible; shown
+    /// whatever the HUD is set to. The flash when hurt is `ScreenFlash`'s.
+    pub fn draw_death(&self, buf: &mut [u32], width: usize, height: usize) {
+        if !self.is_dead() {
+            return;
+        }
+        let red = pack_rgb(160, 0, 0);
+        for pixel in buf.iter_mut() {
+            *pixel = mix(*pixel, red, 128);
+        }
+
+        if self.can_respawn() {
+            let scale = (height / 240).max(1);
+            let text = "press use to respawn";
+            let x = width.saturating_sub(font::text_width(text, scale)) / 2;
+            let y = height / 2;
+            font::draw_text(
+                buf,
+                width,
+                height,
+                [x, y],
+                text,
+                pack_rgb(255, 255, 255),
+                scale,
+            );
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// Random maps: a grid of rooms, each with its own floor and ceiling heights and light
+// level, joined by corridors along a random spanning tree plus a few extra loops. The same
+// seed always makes the same map.
+//
+// Each grid cell holds one rectangular room around the cell's center. Corridors run along
+// the center lines between neighboring cells, so a corridor always meets the middle of a
+// room's side, and each side has at most one.
+
+use crate::entity::Behavior;
+use crate::physics::STAND_EYE_HEIGHT;
+use crate::renderer::{WHITE, pack_rgb};
+use crate::rng::Rng;
+use crate::texture::Texture;
+use crate::world::{Blend, Material, PlayerStart, Sector, Special, Thing, Wall, World};
+
+/// Rooms across and down when none are asked for
+pub const DEFAULT_GRID: [usize; 2] = [4, 4];
+
+const CELL: f32 = 12.0;
+// Room half-extents from the cell center, in world units
+const ROOM_MIN: f32 = 2.0;
+const ROOM_MAX: f32 = 5.0;
+const CORRIDOR_HALF_WIDTH: f32 = 1.0;
+// Floors step by this between connected rooms, at most twice; the corridor sits halfway,
+// so no step is taller than `collision::MAX_STEP`
+const FLOOR_STEP: f32 = 0.2;
+const CORRIDOR_HEADROOM: f32 = 2.2;
+const ROOM_HEADROOM: [f32; 2] = [2.8, 5.0];
+// Chance of joining two neighbors the spanning tree left apart, making a loop
+const LOOP_CHANCE: f32 = 0.15;
+const ORB_CHANCE: f32 = 0.4;
+
+const WALL_COLORS: [[(u8, u8, u8); 2]; 4] = [
+    [(200, 200, 200), (150, 150, 150)],
+    [(180, 180, 250), (130, 130, 200)],
+    [(250, 180, 180), (200, 130, 130)],
+    [(180, 250, 180), (130, 200, 130)],
+];
+
+struct Room {
+    min: [f32; 2],
+    max: [f32; 2],
+    floor_z: f32,
+    ceiling_z: f32,
+    light: f32,
+    texture: usize,
+}
+
+// A corridor between cell `a` and the cell east (or north) of it
+struct Link {
+    a: usize,
+    b: usize,
+    north: bool,
+}
+
+/// A connected map of `grid[0] * grid[1]` rooms (at least one) made from `seed`
+pub fn generate(seed: u64, grid: [usize; 2]) -> World {
+    let [cols, rows] = [grid[0].max(1), grid[1].max(1)];
+    let mut rng = Rng::new(seed);
+    let cell_center = |c: usize| {
+        [
+            (c % cols) as f32 * CELL + 0.5 * CELL,
+            (c / cols) as f32 * CELL + 0.5 * CELL,
+        ]
+    };
+
+    // Spanning tree by randomized depth-first search from the first cell; floors drift by
+    // at most two steps from room to room along it
+    let count = cols * rows;
+    let mut floor = vec![None; count];
+    floor[0] = Some(0.0);
+    let mut links = Vec::new();
+    let mut stack = vec![0];
+    while let Some(&c) = stack.last() {
+        let open: Vec<usize> = neighbors(c, cols, rows)
+            .into_iter()
+            .filter(|&n| floor[n].is_none())
+            .collect();
+        if open.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let n = open[rng.below(open.len())];
+        let step = (rng.below(5) as f32 - 2.0) * FLOOR_STEP;
+        floor[n] = Some(floor[c].unwrap_or(0.0) + step);
+        links.push(link(c, n, cols));
+        stack.push(n);
+    }
+    let floor: Vec<f32> = floor.into_iter().map(|f| f.unwrap_or(0.0)).collect();
+
+    // Extra loops, only where the floors are close enough to walk between
+    for c in 0..count {
+        for n in neighbors(c, cols, rows) {
+            let joined = links.iter().any(|l| (l.a, l.b) == (c.min(n), c.max(n)));
+            if n > c
+                && !joined
+                && (floor[c] - floor[n]).abs() <= 2.0 * FLOOR_STEP + 1e-3
+                && rng.unit() < LOOP_CHANCE
+            {
+                links.push(link(c, n, cols));
+            }
+        }
+    }
+
+    let rooms: Vec<Room> = (0..count)
+        .map(|c| {
+            let center = cell_center(c);
+            let mut half = || ROOM_MIN + (ROOM_MAX - ROOM_MIN) * rng.unit();
+            let (w0, w1, h0, h1) = (half(), half(), half(), half());
+            let headroom = ROOM_HEADROOM[0] + (ROOM_HEADROOM[1] - ROOM_HEADROOM[0]) * rng.unit();
+            Room {
+                min: [center[0] - w0, center[1] - h0],
+                max: [center[0] + w1, center[1] + h1],
+                floor_z: floor[c],
+                ceiling_z: floor[c] + headroom,
+                light: 0.4 + 0.6 * rng.unit(),
+                texture: rng.below(WALL_COLORS.len()),
+            }
+        })
+        .collect();
+
+    let textures: Vec<Texture> = WALL_COLORS
+        .iter()
+        .map(|[a, b]| {
+            Texture::checkerboard(64, 8, pack_rgb(a.0, a.1, a.2), pack_rgb(b.0, b.1, b.2))
+        })
+        .chain([Texture::disc(
+            32,
+            pack_rgb(255, 240, 180),
+            pack_rgb(200, 120, 40),
+        )])
+        .collect();
+    let orb_texture = textures.len() - 1;
+
+    let mut sectors: Vec<Sector> = rooms
+        .iter()
+        .map(|r| {
+            let shade = (r.light * 255.0) as u8;
+            sector(r.floor_z, r.ceiling_z, r.light, shade)
+        })
+        .collect();
+
+    // Corridor sectors follow the rooms, one per link
+    let mut walls = Vec::new();
+    // Openings on each room side: (room, side) -> corridor sector; sides are E, N, W, S
+    let mut openings = vec![[None; 4]; count];
+    for l in &links {
+        let (a, b) = (&rooms[l.a], &rooms[l.b]);
+        let s = sectors.len();
+        let floor_z = 0.5 * (a.floor_z + b.floor_z);
+        let ceiling_z = a.floor_z.max(b.floor_z) + CORRIDOR_HEADROOM;
+        let light = 0.8 * a.light.min(b.light);
+        sectors.push(sector(floor_z, ceiling_z, light, (light * 200.0) as u8));
+
+        let c = cell_center(l.a);
+        let w = CORRIDOR_HALF_WIDTH;
+        let texture = a.texture;
+        if l.north {
+            // Runs from a's north side up to b's south side
+            let (y0, y1) = (a.max[1], b.min[1]);
+            let x = c[0];
+            walls.push(solid([x + w, y0], [x + w, y1], s, texture));
+            walls.push(solid([x - w, y1], [x - w, y0], s, texture));
+            openings[l.a][1] = Some(s);
+            openings[l.b][3] = Some(s);
+        } else {
+            let (x0, x1) = (a.max[0], b.min[0]);
+            let y = c[1];
+            walls.push(solid([x0, y - w], [x1, y - w], s, texture));
+            walls.push(solid([x1, y + w], [x0, y + w], s, texture));
+            openings[l.a][0] = Some(s);
+            openings[l.b][2] = Some(s);
+        }
+    }
+
+    // Room outlines counterclockwise, so each room is on the left of its walls, with each
+    // side split around its corridor opening
+    for (i, r) in rooms.iter().enumerate() {
+        let center = cell_center(i);
+        let w = CORRIDOR_HALF_WIDTH;
+        let corners = [
+            [r.max[0], r.max[1]],
+            [r.min[0], r.max[1]],
+            [r.min[0], r.min[1]],
+            [r.max[0], r.min[1]],
+        ];
+        // Side k runs from corner k-1 to corner k: E, N, W, S in order
+        for side in 0..4 {
+            let start = corners[(side + 3) % 4];
+            let end = corners[side];
+            let Some(corridor) = openings[i][side] else {
+                walls.push(solid(start, end, i, r.texture));
+                continue;
+            };
+            // The opening is centered on the cell's center line
+            let along = |t: f32| match side {
+                0 => [start[0], center[1] + t],
+                1 => [center[0] - t, start[1]],
+                2 => [start[0], center[1] - t],
+                _ => [center[0] + t, start[1]],
+            };
+            let (near, far) = (along(-w), along(w));
+            walls.push(solid(start, near, i, r.texture));
+            walls.push(Wall {
+                back_sector: Some(corridor),
+                ..solid(near, far, i, r.texture)
+            });
+            walls.push(solid(far, end, i, r.texture));
+        }
+    }
+
+    let things = rooms
+        .iter()
+        .enumerate()
+        .skip(1) // leave the start room empty
+        .filter(|_| rng.unit() < ORB_CHANCE)
+        .map(|(i, r)| Thing {
+            pos: cell_center(i),
+            z: r.floor_z + STAND_EYE_HEIGHT - 0.5,
+            height: 0.5,
+            texture: orb_texture,
+            scale: 1.0,
+            radius: 0.0,
+            behavior: Behavior::Bob {
+                amplitude: 0.15,
+                speed: 2.0,
+            },
+            prop: None,
+            pickup: None,
+            yaw: 0.0,
+            rotations: None,
+            model: None,
+            blend: Blend::Masked,
+        })
+        .collect();
+
+    let player_start = PlayerStart {
+        pos: cell_center(0),
+        yaw: 0.0,
+    };
+    // Play goes on with the same stream, so it's as repeatable as the map
+    let mut world = World::new(sectors, walls, textures, things, player_start, Vec::new());
+    world.rng = rng;
+    world
+}
+
+fn sector(floor_z: f32, ceiling_z: f32, light: f32, shade: u8) -> Sector {
+    Sector {
+        floor_z,
+        ceiling_z,
+        floor_color: pack_rgb(shade / 3, shade / 3, shade / 4),
+        ceiling_color: pack_rgb(shade / 2, shade / 2, shade / 2 + 20),
+        floor_texture: None,
+        ceiling_texture: None,
+        flat_offset: [0.0, 0.0],
+        flat_angle: 0.0,
+        flat_scroll: [0.0, 0.0],
+        slab: None,
+        light_level: light,
+        light_color: WHITE,
+        liquid: None,
+        material: Material::default(),
+        specials: Vec::new(),
+    }
+}
+
+fn solid(start: [f32; 2], end: [f32; 2], sector: usize, texture: usize) -> Wall {
+    Wall {
+        start,
+        end,
+        front_sector: sector,
+        back_sector: None,
+        texture,
+        tag: 0,
+        special: Special::None,
+        mid: None,
+        scroll: [0.0, 0.0],
+        offset: [0.0, 0.0],
+        peg_upper: false,
+        peg_lower: false,
+        teleport: None,
+        walkover: None,
+    }
+}
+
+// Cells east, north, west and south of `c` that are on the grid
+fn neighbors(c: usize, cols: usize, rows: usize) -> Vec<usize> {
+    let (x, y) = (c % cols, c / cols);
+    let mut out = Vec::with_capacity(4);
+    if x + 1 < cols {
+        out.push(c + 1);
+    }
+    if y + 1 < rows {
+        out.push(c + cols);
+    }
+    if x > 0 {
+        out.push(c - 1);
+    }
+    if y > 0 {
+        out.push(c - cols);
+    }
+    out
+}
+
+fn link(c: usize, n: usize, cols: usize) -> Link {
+    let (a, b) = (c.min(n), c.max(n));
+    Link {
+        a,
+        b,
+        north: b - a == cols,
+    }
+}
@@ -0,0 +1,171 @@
+// Frame profiler: per-stage CPU times, smoothed over frames and shown as an overlay (F3)
+
+use std::time::{Duration, Instant};
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::renderer::{RenderTimings, mix_half, pack_rgb};
+use crate::scaler::PresentTimings;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Tick,
+    Walls,
+    Flats,
+    Sprites,
+    Blit,
+    Sharpen,
+    Present,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::Tick,
+        Stage::Walls,
+        Stage::Flats,
+        Stage::Sprites,
+        Stage::Blit,
+        Stage::Sharpen,
+        Stage::Present,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Tick => "tick",
+            Stage::Walls => "walls",
+            Stage::Flats => "flats",
+            Stage::Sprites => "sprites",
+            Stage::Blit => "blit",
+            Stage::Sharpen => "sharpen",
+            Stage::Present => "present",
+        }
+    }
+
+    fn color(self) -> u32 {
+        match self {
+            Stage::Tick => pack_rgb(200, 200, 200),
+            Stage::Walls => pack_rgb(240, 90, 90),
+            Stage::Flats => pack_rgb(240, 180, 70),
+            Stage::Sprites => pack_rgb(220, 120, 240),
+            Stage::Blit => pack_rgb(90, 200, 240),
+            Stage::Sharpen => pack_rgb(90, 240, 140),
+            Stage::Present => pack_rgb(140, 140, 255),
+        }
+    }
+}
+
+// Weight of the newest frame in the running averages
+const SMOOTHING: f32 = 0.1;
+// Bar length per millisecond, in font pixels
+const BAR_PER_MS: f32 = 8.0;
+const BAR_MAX: usize = 96;
+
+#[derive(Default)]
+pub struct Profiler {
+    frame: [Duration; Stage::ALL.len()], // accumulating for the current frame
+    smoothed_ms: [f32; Stage::ALL.len()],
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `time` to `stage` for the current frame
+    pub fn record(&mut self, stage: Stage, time: Duration) {
+        self.frame[stage as usize] += time;
+    }
+
+    /// Time from now until the returned guard is dropped
+    pub fn scope(&mut self, stage: Stage) -> Scope<'_> {
+        Scope {
+            profiler: self,
+            stage,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn record_render(&mut self, timings: RenderTimings) {
+        self.record(Stage::Walls, timings.walls);
+        self.record(Stage::Flats, timings.flats);
+        self.record(Stage::Sprites, timings.sprites);
+    }
+
+    /// Blit and sharpen; the CRT filter counts as sharpening since both post-process the
+    /// scaled image
+    pub fn record_present(&mut self, timings: PresentTimings) {
+        self.record(Stage::Blit, timings.blit);
+        self.record(
+            Stage::Sharpen,
+            timings.sharpen + timings.crt + timings.color,
+        );
+    }
+
+    /// Fold the current frame into the averages and start a new one
+    pub fn end_frame(&mut self) {
+        for (avg, time) in self.smoothed_ms.iter_mut().zip(&mut self.frame) {
+            let ms = time.as_secs_f32() * 1000.0;
+            *avg += (ms - *avg) * SMOOTHING;
+            *time = Duration::ZERO;
+        }
+    }
+
+    /// Smoothed milliseconds spent in `stage` per frame
+    pub fn average_ms(&self, stage: Stage) -> f32 {
+        self.smoothed_ms[stage as usize]
+    }
+
+    /// Table of stage times with a bar each, in the top-left corner of `buf`
+    pub fn draw_overlay(&self, buf: &mut [u32], width: usize, height: usize) {
+        // Bigger text on bigger framebuffers so it stays legible once scaled
+        let scale = (height / 240).max(1);
+        let line = (GLYPH_HEIGHT + 2) * scale;
+        let margin = 2 * scale;
+        let label_w = font::text_width("sharpen 00.00", scale) + 2 * margin;
+        let panel_w = (label_w + BAR_MAX * scale + margin).min(width);
+        let panel_h = ((Stage::ALL.len() + 1) * line + margin).min(height);
+
+        // Dim what's behind the panel
+        let dim = pack_rgb(0, 0, 0);
+        for row in buf.chunks_exact_mut(width).take(panel_h) {
+            for pixel in &mut row[..panel_w] {
+                *pixel = mix_half(*pixel, dim);
+            }
+        }
+
+        let text = pack_rgb(255, 255, 255);
+        let mut total = 0.0;
+        for (i, stage) in Stage::ALL.into_iter().enumerate() {
+            let ms = self.average_ms(stage);
+            total += ms;
+            let y = margin + i * line;
+            let label = format!("{:<7} {ms:5.2}", stage.name());
+            font::draw_text(buf, width, height, [margin, y], &label, text, scale);
+
+            let bar = ((ms * BAR_PER_MS) as usize).min(BAR_MAX) * scale;
+            let x0 = label_w.min(panel_w);
+            let x1 = (label_w + bar).min(panel_w);
+            for row in buf
+                .chunks_exact_mut(width)
+                .skip(y)
+                .take(GLYPH_HEIGHT * scale)
+            {
+                row[x0..x1].fill(stage.color());
+            }
+        }
+        let y = margin + Stage::ALL.len() * line;
+        let label = format!("total   {total:5.2} ms");
+        font::draw_text(buf, width, height, [margin, y], &label, text, scale);
+    }
+}
+
+pub struct Scope<'a> {
+    profiler: &'a mut Profiler,
+    stage: Stage,
+    start: Instant,
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        self.profiler.record(self.stage, self.start.elapsed());
+    }
+}
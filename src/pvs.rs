@@ -0,0 +1,202 @@
+// Potentially visible sets: which sectors could be seen from anywhere in each sector,
+// worked out once at load by flooding through the portals. A sector behind a chain of
+// portals is visible only if some straight line passes through every portal in the chain.
+// Sector heights play no part, since doors and lifts change them as the map is played.
+
+use crate::world::Wall;
+
+// Portal chains longer than this stop being clipped and just flood on, which is
+// conservative and keeps maps with many small portals from taking long to load
+const MAX_DEPTH: usize = 64;
+// Shorter than this, what's left of a clipped portal counts as closed
+const MIN_LENGTH: f32 = 1e-4;
+const EPS: f32 = 1e-5;
+
+/// A portal out of a sector, oriented with that sector on its left
+#[derive(Clone, Copy)]
+struct Portal {
+    wall: usize,
+    start: [f32; 2],
+    end: [f32; 2],
+    to: usize, // the sector on the other side
+}
+
+#[derive(Default)]
+pub struct Pvs {
+    words: usize,   // per sector's row
+    bits: Vec<u64>, // bit `to` of row `from` set if `to` may be visible from `from`
+}
+
+impl Pvs {
+    pub fn build(walls: &[Wall], sector_count: usize) -> Self {
+        let mut portals = vec![Vec::new(); sector_count];
+        for (i, wall) in walls.iter().enumerate() {
+            // Walls naming missing sectors are left to `World::validate` to report
+            if let Some(back) = wall.back_sector.filter(|&b| b != wall.front_sector)
+                && wall.front_sector.max(back) < sector_count
+            {
+                portals[wall.front_sector].push(Portal {
+                    wall: i,
+                    start: wall.start,
+                    end: wall.end,
+                    to: back,
+                });
+                portals[back].push(Portal {
+                    wall: i,
+                    start: wall.end,
+                    end: wall.start,
+                    to: wall.front_sector,
+                });
+            }
+        }
+
+        let words = sector_count.div_ceil(64);
+        let mut pvs = Self {
+            words,
+            bits: vec![0; words * sector_count],
+        };
+        for from in 0..sector_count {
+            let mut flood = Flood {
+                portals: &portals,
+                row: &mut pvs.bits[from * words..(from + 1) * words],
+                path: Vec::new(),
+            };
+            flood.mark(from);
+            for &source in &portals[from] {
+                flood.path.push(source.wall);
+                flood.through(source, source, source.to);
+                flood.path.pop();
+            }
+        }
+        pvs
+    }
+
+    /// Whether anything in sector `to` might be seen from sector `from`
+    #[inline]
+    pub fn can_see(&self, from: usize, to: usize) -> bool {
+        self.bits
+            .get(from * self.words + to / 64)
+            .is_none_or(|word| word & (1 << (to % 64)) != 0)
+    }
+}
+
+// Flood out of one sector, marking its row
+struct Flood<'a> {
+    portals: &'a [Vec<Portal>],
+    row: &'a mut [u64],
+    path: Vec<usize>, // walls of the portals passed through so far
+}
+
+impl Flood<'_> {
+    fn mark(&mut self, sector: usize) {
+        self.row[sector / 64] |= 1 << (sector % 64);
+    }
+
+    // Entered `sector` through `pass`, seen through `source` out of the starting sector
+    fn through(&mut self, source: Portal, pass: Portal, sector: usize) {
+        self.mark(sector);
+        if self.path.len() >= MAX_DEPTH {
+            self.everything_from(sector);
+            return;
+        }
+        for &next in &self.portals[sector] {
+            if self.path.contains(&next.wall) {
+                continue;
+            }
+            let Some(next_clipped) = clip_to_view(source, pass, next) else {
+                continue;
+            };
+            self.path.push(next.wall);
+            self.through(source, next_clipped, next.to);
+            self.path.pop();
+        }
+    }
+
+    // Every sector reachable from `sector` at all
+    fn everything_from(&mut self, sector: usize) {
+        let mut stack = vec![sector];
+        let mut seen = vec![false; self.portals.len()];
+        seen[sector] = true;
+        while let Some(s) = stack.pop() {
+            self.mark(s);
+            for portal in &self.portals[s] {
+                if !seen[portal.to] {
+                    seen[portal.to] = true;
+                    stack.push(portal.to);
+                }
+            }
+        }
+    }
+}
+
+// The part of `next` some line through both `source` and `pass` could reach, if any
+fn clip_to_view(source: Portal, pass: Portal, next: Portal) -> Option<Portal> {
+    // Beyond the portal just passed through
+    let mut seg = keep_left(next, pass.end, pass.start)?;
+    if source.wall == pass.wall {
+        return Some(seg);
+    }
+    // Within the lines through an end of each that have the source and the pass on
+    // opposite sides, on the pass's side
+    for a in [source.start, source.end] {
+        for b in [pass.start, pass.end] {
+            if length([b[0] - a[0], b[1] - a[1]]) < MIN_LENGTH {
+                continue;
+            }
+            let other_a = if a == source.start {
+                source.end
+            } else {
+                source.start
+            };
+            let other_b = if b == pass.start {
+                pass.end
+            } else {
+                pass.start
+            };
+            let (side_a, side_b) = (side(a, b, other_a), side(a, b, other_b));
+            if side_a < -EPS && side_b > EPS {
+                seg = keep_left(seg, a, b)?;
+            } else if side_a > EPS && side_b < -EPS {
+                seg = keep_left(seg, b, a)?;
+            }
+        }
+    }
+    Some(seg)
+}
+
+// The part of `portal` left of the line from `a` through `b`
+fn keep_left(portal: Portal, a: [f32; 2], b: [f32; 2]) -> Option<Portal> {
+    let (d0, d1) = (side(a, b, portal.start), side(a, b, portal.end));
+    let mut clipped = portal;
+    if d0 < -EPS && d1 < -EPS {
+        return None;
+    }
+    if d0 < -EPS || d1 < -EPS {
+        let t = d0 / (d0 - d1);
+        let cut = [
+            portal.start[0] + t * (portal.end[0] - portal.start[0]),
+            portal.start[1] + t * (portal.end[1] - portal.start[1]),
+        ];
+        if d0 < -EPS {
+            clipped.start = cut;
+        } else {
+            clipped.end = cut;
+        }
+    }
+    let along = [
+        clipped.end[0] - clipped.start[0],
+        clipped.end[1] - clipped.start[1],
+    ];
+    (length(along) >= MIN_LENGTH).then_some(clipped)
+}
+
+// Positive left of the line from `a` through `b`
+#[inline]
+fn side(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+#[inline]
+fn length(v: [f32; 2]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1]).sqrt()
+}
@@ -0,0 +1,170 @@
+// Ray queries against walls and entities, for hitscan weapons, line of sight and the like
+
+use crate::camera::Camera;
+use crate::entity::{Behavior, EntityId};
+use crate::world::{World, ray_segment};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: [f32; 2],
+    pub dir: [f32; 2], // unit length
+    pub z: f32,        // height at the origin
+    pub slope: f32,    // change in height per unit of distance, 0 for level
+}
+
+impl Ray {
+    /// Level ray from the eye along the view direction
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            origin: camera.pos,
+            dir: [camera.yaw.sin(), camera.yaw.cos()],
+            z: camera.eye_z,
+            slope: 0.0,
+        }
+    }
+
+    /// Ray from `from` toward `to`, rising or falling to reach `to`'s height
+    pub fn between(from: [f32; 2], from_z: f32, to: [f32; 2], to_z: f32) -> (Self, f32) {
+        let d = [to[0] - from[0], to[1] - from[1]];
+        let dist = (d[0] * d[0] + d[1] * d[1]).sqrt().max(f32::EPSILON);
+        let ray = Self {
+            origin: from,
+            dir: [d[0] / dist, d[1] / dist],
+            z: from_z,
+            slope: (to_z - from_z) / dist,
+        };
+        (ray, dist)
+    }
+
+    #[inline]
+    pub fn point_at(&self, t: f32) -> [f32; 2] {
+        [
+            self.origin[0] + self.dir[0] * t,
+            self.origin[1] + self.dir[1] * t,
+        ]
+    }
+
+    #[inline]
+    pub fn z_at(&self, t: f32) -> f32 {
+        self.z + self.slope * t
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WallHit {
+    pub wall: usize,
+    pub point: [f32; 2],
+    pub distance: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EntityHit {
+    pub entity: EntityId,
+    pub distance: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct RayHits {
+    pub wall: Option<WallHit>,    // first wall that stops the ray
+    pub entities: Vec<EntityHit>, // nearest first, all in front of `wall`
+}
+
+/// Trace `ray` up to `max_dist`. Portals stop it only where its height is outside the
+/// opening, so it can pass over a step or under a lintel.
+pub fn cast(world: &World, ray: &Ray, max_dist: f32) -> RayHits {
+    let wall = first_blocking_wall(world, ray, max_dist);
+    let limit = wall.map_or(max_dist, |h| h.distance);
+
+    let mut entities: Vec<EntityHit> = world
+        .entities
+        .visible()
+        // Effects that expire on their own are just for show
+        .filter(|&(id, _, _)| !matches!(world.entities.behaviors[id], Behavior::Expire { .. }))
+        // So is what's left of a destroyed prop
+        .filter(|&(id, _, _)| !world.entities.props[id].is_some_and(|p| p.is_destroyed()))
+        .filter_map(|(id, transform, sprite)| {
+            let radius = world.entities.colliders[id].unwrap_or_else(|| {
+                let texture = &world.textures[sprite.texture_from(transform, ray.origin)];
+                0.5 * sprite.world_height() * texture.width as f32 / texture.height as f32
+            });
+            let t = ray_circle(ray, transform.pos, radius)?;
+            let z = ray.z_at(t);
+            let in_height = z >= transform.z && z <= transform.z + sprite.world_height();
+            (t <= limit && in_height).then_some(EntityHit {
+                entity: id,
+                distance: t,
+            })
+        })
+        .collect();
+    entities.sort_unstable_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    RayHits { wall, entities }
+}
+
+/// True if nothing solid lies between the two points (entities don't block)
+pub fn line_of_sight(world: &World, from: [f32; 2], from_z: f32, to: [f32; 2], to_z: f32) -> bool {
+    let (ray, dist) = Ray::between(from, from_z, to, to_z);
+    first_blocking_wall(world, &ray, dist).is_none()
+}
+
+/// How many one-sided walls, and portals shut like a closed door, a straight line between
+/// two points crosses; what a sound passes through on its way from one to the other
+pub fn walls_between(world: &World, from: [f32; 2], to: [f32; 2]) -> usize {
+    let (ray, dist) = Ray::between(from, 0.0, to, 0.0);
+    world
+        .grid
+        .walls_along(from, to)
+        .map(|i| &world.walls[i])
+        .filter(|wall| {
+            if ray_segment(ray.origin, ray.dir, wall.start, wall.end).is_none_or(|t| t > dist) {
+                return false;
+            }
+            wall.back_sector.is_none_or(|back| {
+                let (a, b) = (&world.sectors[wall.front_sector], &world.sectors[back]);
+                a.ceiling_z.min(b.ceiling_z) <= a.floor_z.max(b.floor_z)
+            })
+        })
+        .count()
+}
+
+fn first_blocking_wall(world: &World, ray: &Ray, max_dist: f32) -> Option<WallHit> {
+    world
+        .grid
+        .walls_along(ray.origin, ray.point_at(max_dist))
+        .filter_map(|i| {
+            let wall = &world.walls[i];
+            let t = ray_segment(ray.origin, ray.dir, wall.start, wall.end)?;
+            if t > max_dist {
+                return None;
+            }
+            if let Some(back) = wall.back_sector {
+                let (a, b) = (&world.sectors[wall.front_sector], &world.sectors[back]);
+                let z = ray.z_at(t);
+                if z >= a.floor_z.max(b.floor_z) && z <= a.ceiling_z.min(b.ceiling_z) {
+                    return None; // through the opening
+                }
+            }
+            Some(WallHit {
+                wall: i,
+                point: ray.point_at(t),
+                distance: t,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+/// Distance to where `ray` enters a circle, 0 if it starts inside
+pub fn ray_circle(ray: &Ray, center: [f32; 2], radius: f32) -> Option<f32> {
+    let to_c = [center[0] - ray.origin[0], center[1] - ray.origin[1]];
+    let along = to_c[0] * ray.dir[0] + to_c[1] * ray.dir[1];
+    let dist2 = to_c[0] * to_c[0] + to_c[1] * to_c[1];
+    let perp2 = dist2 - along * along;
+    if perp2 > radius * radius {
+        return None;
+    }
+    if dist2 <= radius * radius {
+        return Some(0.0);
+    }
+    let t = along - (radius * radius - perp2).sqrt();
+    (t >= 0.0).then_some(t)
+}
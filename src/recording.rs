@@ -0,0 +1,96 @@
+// Demo files: the input of every tick from a fresh map start, replayed tick for tick.
+// Ticks keep their own dt, so playback steps the world exactly as the recording did.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::MoveIntent;
+
+#[derive(Serialize, Deserialize)]
+pub struct Demo {
+    pub map: Option<String>, // None for the built-in demo map
+    pub ticks: Vec<DemoTick>,
+}
+
+/// Everything a tick reads from the player
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct DemoTick {
+    pub dt: f32,       // seconds since the previous tick, already capped
+    pub mouse_dx: f32, // raw mouse counts, turned into yaw by the sensitivity
+    pub intent: MoveIntent,
+}
+
+/// Feeds a demo's ticks back one per frame
+pub struct DemoPlayer {
+    ticks: Vec<DemoTick>,
+    next: usize,
+}
+
+impl DemoPlayer {
+    pub fn new(demo: Demo) -> Self {
+        Self {
+            ticks: demo.ticks,
+            next: 0,
+        }
+    }
+
+    /// None once every tick has been played
+    pub fn next_tick(&mut self) -> Option<DemoTick> {
+        let tick = self.ticks.get(self.next).copied()?;
+        self.next += 1;
+        Some(tick)
+    }
+
+    pub fn played(&self) -> usize {
+        self.next
+    }
+}
+
+#[derive(Debug)]
+pub enum DemoError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+}
+
+impl fmt::Display for DemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemoError::Io(err) => write!(f, "could not access demo: {err}"),
+            DemoError::Parse(err) => write!(f, "could not parse demo: {err}"),
+            DemoError::Write(err) => write!(f, "could not write demo: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DemoError {}
+
+impl From<std::io::Error> for DemoError {
+    fn from(err: std::io::Error) -> Self {
+        DemoError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for DemoError {
+    fn from(err: toml::de::Error) -> Self {
+        DemoError::Parse(err)
+    }
+}
+
+impl From<toml::ser::Error> for DemoError {
+    fn from(err: toml::ser::Error) -> Self {
+        DemoError::Write(err)
+    }
+}
+
+pub fn write_demo(path: impl AsRef<Path>, demo: &Demo) -> Result<(), DemoError> {
+    std::fs::write(path, toml::to_string(demo)?)?;
+    Ok(())
+}
+
+pub fn read_demo(path: impl AsRef<Path>) -> Result<Demo, DemoError> {
+    let source = std::fs::read_to_string(path)?;
+    Ok(toml::from_str(&source)?)
+}
@@ -1,173 +1,1445 @@
-use crate::{camera::Camera, world::World};
+use std::sync::atomic::AtomicU32;
+use std::time::{Duration, Instant};
+
+use rayon::{
+    iter::{IndexedParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bsp::{Aabb, BspVisitor, Seg},
+    camera::{Camera, DEFAULT_FOV},
+    decals::Decal,
+    palette::{Colormap, IndexedTexture, Palette},
+    texture::{Texture, TextureId},
+    viewport::Viewport,
+    world::{Blend, Fog, Liquid, MidTexture, Sector, Wall, World},
+};
+
+mod debug;
+mod flash;
+mod masked;
+mod planes;
+mod shader;
+mod sky;
+mod slabs;
+mod sprites;
+mod tilt;
+mod underwater;
+
+pub use debug::DebugView;
+pub use flash::Flash;
+pub use tilt::ViewOffset;
+
+use debug::Counting;
+use masked::MaskedColumn;
+use planes::{Flat, Visplanes};
+use shader::{Indexed, Shader, TrueColor};
+use slabs::{SlabLook, SlabPiece};
 
 const NEAR: f32 = 0.1;
+// Background where no sky texture is set
+const SKY_COLOR: u32 = pack_rgb(30, 30, 70);
+/// Light color that leaves what it lights as it is
+pub const WHITE: u32 = pack_rgb(255, 255, 255);
+// Depth at which distance shading has halved a surface's light
+const LIGHT_HALF_DEPTH: f32 = 12.0;
 
+/// A color in the engine's own pixel format, `PixelFormat::Xrgb8888`; frames for targets
+/// in other formats are converted by a `Framebuffer` when presented
 #[inline]
-fn pack_rgb(r: u8, g: u8, b: u8) -> u32 {
+pub const fn pack_rgb(r: u8, g: u8, b: u8) -> u32 {
     // BGRA8 in little-endian memory
     (b as u32) | ((g as u32) << 8) | ((r as u32) << 16)
     // Alpha at 0
 }
 
-fn wall_depth_cam_space(cam: &Camera, start: [f32; 2], end: [f32; 2]) -> f32 {
-    // Use midpoint depth in camera space for sorting
-    let mid = [(start[0] + end[0]) * 0.5, (start[1] + end[1]) * 0.5];
-    let m = cam.world_to_camera(mid);
-    m[1] // cy (forward depth)
+/// How to shade pixels at one depth: a color to multiply by, a light scale, then how far
+/// to blend into the fog, both 0..=256
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Light {
+    pub tint: u32, // `WHITE` for plain light
+    pub scale: u32,
+    pub fog: u32,
+}
+
+/// Light for a surface in a sector with `light_level` and `light_color`, seen at `depth`
+#[inline]
+fn light_at(light_level: f32, light_color: u32, depth: f32, fog: Option<Fog>) -> Light {
+    let atten = 1.0 / (1.0 + depth / LIGHT_HALF_DEPTH);
+    Light {
+        tint: light_color,
+        scale: (light_level * atten * 256.0).clamp(0.0, 256.0) as u32,
+        fog: fog_at(depth, fog),
+    }
+}
+
+/// Mip level for sampling a texture `texels_per_pixel` texels to a pixel, as `Shader::texel_lod`
+/// takes it
+#[inline]
+fn mip_lod(texels_per_pixel: f32) -> f32 {
+    texels_per_pixel.max(1.0).log2()
+}
+
+#[inline]
+fn fog_at(depth: f32, fog: Option<Fog>) -> u32 {
+    fog.map_or(0, |fog| (fog.amount(depth) * 256.0) as u32)
+}
+
+#[inline]
+pub(crate) fn shade(color: u32, light: u32) -> u32 {
+    shade_rounded(color, light, 0)
+}
+
+/// `color` multiplied channel by channel by `tint`, white leaving it unchanged
+#[inline]
+pub(crate) fn tint(color: u32, tint: u32) -> u32 {
+    let ch =
+        |shift: u32| ((((color >> shift) & 0xFF) * (((tint >> shift) & 0xFF) + 1)) >> 8) << shift;
+    ch(0) | ch(8) | ch(16)
+}
+
+/// `shade`, adding `bias` in 0..256 before the fraction is dropped
+#[inline]
+pub(crate) fn shade_rounded(color: u32, light: u32, bias: u32) -> u32 {
+    // Scale R and B together (00RR00BB), then G, like the scaler's lerp
+    let rb = (((color & 0x00FF00FF) * light + bias * 0x0001_0001) >> 8) & 0x00FF00FF;
+    let g = (((color & 0x0000FF00) * light + (bias << 8)) >> 8) & 0x0000FF00;
+    rb | g
+}
+
+/// `a` blended toward `b` by `t` in 0..=256
+#[inline]
+pub(crate) fn mix(a: u32, b: u32, t: u32) -> u32 {
+    mix_rounded(a, b, t, 0)
+}
+
+/// `mix`, adding `bias` in 0..256 before the fraction is dropped
+#[inline]
+pub(crate) fn mix_rounded(a: u32, b: u32, t: u32, bias: u32) -> u32 {
+    let inv = 256 - t;
+    let rb =
+        (((a & 0x00FF00FF) * inv + (b & 0x00FF00FF) * t + bias * 0x0001_0001) >> 8) & 0x00FF00FF;
+    let g = (((a & 0x0000FF00) * inv + (b & 0x0000FF00) * t + (bias << 8)) >> 8) & 0x0000FF00;
+    rb | g
+}
+
+#[inline]
+pub(crate) fn mix_half(a: u32, b: u32) -> u32 {
+    // Drop each channel's low bit so the halves can't carry into the next channel
+    ((a & 0x00FE_FEFE) >> 1) + ((b & 0x00FE_FEFE) >> 1)
+}
+
+#[inline]
+pub(crate) fn add_saturating(a: u32, b: u32) -> u32 {
+    let ch = |shift: u32| (((a >> shift) & 0xFF) + ((b >> shift) & 0xFF)).min(0xFF) << shift;
+    ch(0) | ch(8) | ch(16)
+}
+
+/// Draws a `World` from a `Camera` into a caller-owned framebuffer
+///
+/// Keeps its per-column scratch buffers between frames so steady-state rendering doesn't allocate.
+#[derive(Default)]
+pub struct Renderer {
+    scratch: Scratch,
+    indexed: Option<IndexedMode>,
+    timings: RenderTimings,
+    warp_row: Vec<u32>,
+    roll_frame: Vec<u32>, // a frame as drawn, before `ViewOffset::roll` turns it
+    viewport_frame: Vec<u32>, // a view drawn by `render_viewport`, before it's copied in
+    monitor_frames: u64,  // calls to `update_monitors`, counting off their intervals
+    dither: bool,
+    mipmaps: Mipmaps,
+    mips: MipCache,
+    debug_view: DebugView,
+    overdraw: Vec<AtomicU32>, // per pixel draws under `DebugView::Overdraw`
+}
+
+/// How walls and flats sample textures seen from far enough away that a pixel covers
+/// several texels. Point sampling makes them shimmer as the view moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mipmaps {
+    #[default]
+    Off, // always the full-size texture
+    Nearest, // the mip level closest to the texel density
+    Blended, // between the two levels around it
+}
+
+impl Mipmaps {
+    pub fn next(self) -> Self {
+        match self {
+            Mipmaps::Off => Mipmaps::Nearest,
+            Mipmaps::Nearest => Mipmaps::Blended,
+            Mipmaps::Blended => Mipmaps::Off,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+}
+
+// Mip chains of a world's textures, made once per world like the palette textures
+#[derive(Default)]
+struct MipCache {
+    chains: Vec<Vec<Texture>>,
+    of: Option<(u64, usize)>, // world id and texture count `chains` were made from
+}
+
+/// Time spent in each pass of the last frame drawn
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RenderTimings {
+    pub walls: Duration, // sky, BSP walk and wall columns
+    pub flats: Duration,
+    pub sprites: Duration, // sprites and portal mid textures
+}
+
+// Per-frame working storage, reused between frames
+#[derive(Default)]
+struct Scratch {
+    ceil_clip: Vec<i32>,
+    floor_clip: Vec<i32>,
+    planes: Visplanes,
+    clip_history: Vec<Vec<ClipSnapshot>>,
+    sky_columns: Vec<i32>,
+    masked: Vec<MaskedColumn>,
+    pieces: Pieces,
+    slabs: Vec<Vec<SlabPiece>>,
+    slab_entries: Vec<Option<(usize, f32)>>,
 }
 
-pub fn render_frame(buf: &mut [u32], width: usize, height: usize, world: &World, camera: &Camera) {
-    // Clear background
-    let sky = pack_rgb(30, 30, 70);
-    let ground = pack_rgb(40, 40, 40);
+// Palette rendering: the frame is drawn as indices, then expanded to BGRA8
+struct IndexedMode {
+    colormap: Colormap,
+    textures: Vec<IndexedTexture>,
+    textures_of: Option<(u64, usize)>, // world id and texture count `textures` were made from
+    frame: Vec<u8>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    let mid = height / 2;
-    for y in 0..mid {
-        let row = y * width;
-        for x in 0..width {
-            buf[row + x] = sky;
+    /// Draw in 8-bit palette indices, shaded through `palette`'s colormaps, for Doom-style
+    /// light banding; `None` goes back to true color. Building the tables takes a moment.
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.indexed = palette.map(|palette| IndexedMode {
+            colormap: Colormap::new(palette),
+            textures: Vec::new(),
+            textures_of: None,
+            frame: Vec::new(),
+        });
+    }
+
+    pub fn palette(&self) -> Option<&Palette> {
+        self.indexed.as_ref().map(|m| &m.colormap.palette)
+    }
+
+    /// Ordered dithering of shaded and fogged colors, trading banding in dark gradients
+    /// for a fine pattern; true color only, palette rendering bands by design
+    pub fn set_dither(&mut self, on: bool) {
+        self.dither = on;
+    }
+
+    pub fn dither(&self) -> bool {
+        self.dither
+    }
+
+    /// Mipmapped sampling of walls and flats; true color only, like dithering
+    pub fn set_mipmaps(&mut self, mipmaps: Mipmaps) {
+        self.mipmaps = mipmaps;
+        if mipmaps == Mipmaps::Off {
+            self.mips = MipCache::default();
         }
     }
-    for y in mid..height {
-        let row = y * width;
-        for x in 0..width {
-            buf[row + x] = ground;
+
+    pub fn mipmaps(&self) -> Mipmaps {
+        self.mipmaps
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view;
+        if view != DebugView::Overdraw {
+            self.overdraw = Vec::new();
+        }
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    pub fn timings(&self) -> RenderTimings {
+        self.timings
+    }
+
+    /// Post effect for a camera under the surface of `liquid`: sway and tint a finished
+    /// frame; `time` in seconds animates it
+    pub fn underwater(
+        &mut self,
+        buf: &mut [u32],
+        width: usize,
+        height: usize,
+        liquid: &Liquid,
+        time: f32,
+    ) {
+        underwater::warp(buf, width, height, liquid, time, &mut self.warp_row);
+    }
+
+    /// Post effect laying `flash` over a finished `width` x `height` frame, weapon and all.
+    /// In palette mode it comes in steps, like shifting the palette.
+    pub fn flash(&self, buf: &mut [u32], width: usize, height: usize, flash: Flash) {
+        flash::blend(&mut buf[..width * height], flash, self.indexed.is_some());
+    }
+
+    /// `render` from `camera` moved by `offset`, for bob, lean and the like that shouldn't
+    /// touch where the camera really is
+    pub fn render_offset(
+        &mut self,
+        buf: &mut [u32],
+        width: usize,
+        height: usize,
+        world: &World,
+        camera: &Camera,
+        offset: ViewOffset,
+    ) {
+        let camera = Camera {
+            eye_z: camera.eye_z + offset.z,
+            ..*camera
+        };
+        self.render(buf, width, height, world, &camera);
+        if offset.roll != 0.0 {
+            let frame = &mut buf[..width * height];
+            tilt::roll(frame, width, height, offset.roll, &mut self.roll_frame);
         }
     }
 
-    // Draw walls
-    if world.walls.is_empty() {
-        return;
+    /// Render one frame into a new `width` x `height` buffer, for tools and tests with no window
+    pub fn render_to_buffer(
+        &mut self,
+        world: &World,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+    ) -> Vec<u32> {
+        let mut buf = vec![0u32; width * height];
+        self.render(&mut buf, width, height, world, camera);
+        buf
+    }
+
+    /// Render one view into `viewport` of `buf`, a frame `width` pixels wide, leaving the
+    /// rest of it alone. `camera`'s focal factors should suit the viewport's size.
+    pub fn render_viewport(
+        &mut self,
+        buf: &mut [u32],
+        width: usize,
+        viewport: Viewport,
+        world: &World,
+        camera: &Camera,
+    ) {
+        let mut frame = std::mem::take(&mut self.viewport_frame);
+        frame.resize(viewport.width * viewport.height, 0);
+        self.render(&mut frame, viewport.width, viewport.height, world, camera);
+        viewport.copy_into(buf, width, &frame);
+        self.viewport_frame = frame;
     }
 
-    let mut order: Vec<usize> = (0..world.walls.len()).collect();
-    order.sort_by(|&ia, &ib| {
-        let wa = &world.walls[ia];
-        let wb = &world.walls[ib];
-        let da = wall_depth_cam_space(camera, wa.start, wa.end);
-        let db = wall_depth_cam_space(camera, wb.start, wb.end);
-        db.partial_cmp(&da).unwrap_or(std::cmp::Ordering::Equal) // farthest first
-    });
+    /// Redraw the world's monitor textures that are due, each from its own camera; call
+    /// once per frame before rendering. A monitor in view of its own camera shows its
+    /// previous picture.
+    pub fn update_monitors(&mut self, world: &mut World) {
+        let frame = self.monitor_frames;
+        self.monitor_frames += 1;
+        for i in 0..world.monitors.len() {
+            let monitor = world.monitors[i];
+            if !frame.is_multiple_of(u64::from(monitor.interval.max(1))) {
+                continue;
+            }
+            let texture = &world.textures[monitor.texture];
+            let (width, height) = (texture.width, texture.height);
+            let mut camera = Camera {
+                pos: monitor.pos,
+                yaw: monitor.yaw,
+                eye_z: monitor.eye_z,
+                fx: 0.0,
+                fy: 0.0,
+            };
+            camera.set_fov_hor_plus(width as f32, height as f32, DEFAULT_FOV);
 
-    let wall_colors = [
-        pack_rgb(200, 200, 200),
-        pack_rgb(180, 180, 250),
-        pack_rgb(250, 180, 180),
-        pack_rgb(180, 250, 180),
-    ];
+            let mut picture = std::mem::take(&mut self.viewport_frame);
+            picture.resize(width * height, 0);
+            self.render(&mut picture, width, height, world, &camera);
+            world.textures[monitor.texture]
+                .pixels
+                .copy_from_slice(&picture);
+            self.viewport_frame = picture;
 
-    for i in order {
-        let wall = &world.walls[i];
-        let sector = &world.sectors[wall.front_sector];
-        let color = wall_colors[i % wall_colors.len()];
-        draw_solid_wall(buf, width, height, camera, wall, sector, color);
+            // The mip chain and palette copy of the texture have to follow
+            if self.mips.of == Some((world.id, world.textures.len())) {
+                self.mips.chains[monitor.texture] = world.textures[monitor.texture].mip_chain();
+            }
+            if let Some(mode) = &mut self.indexed
+                && mode.textures_of == Some((world.id, world.textures.len()))
+            {
+                mode.textures[monitor.texture] = IndexedTexture::quantize(
+                    &world.textures[monitor.texture],
+                    &mode.colormap.palette,
+                );
+            }
+        }
+    }
+
+    /// Render one frame into `buf`, a row-major `width` x `height` packed BGRA8 framebuffer
+    pub fn render(
+        &mut self,
+        buf: &mut [u32],
+        width: usize,
+        height: usize,
+        world: &World,
+        camera: &Camera,
+    ) {
+        let debug_view = self.debug_view;
+        let Some(mode) = self
+            .indexed
+            .as_mut()
+            .filter(|_| debug_view == DebugView::Off)
+        else {
+            let mips_of = Some((world.id, world.textures.len()));
+            if self.mipmaps != Mipmaps::Off && self.mips.of != mips_of {
+                self.mips.chains = world.textures.iter().map(Texture::mip_chain).collect();
+                self.mips.of = mips_of;
+            }
+            let shader = TrueColor {
+                textures: &world.textures,
+                fog_color: world.fog.map_or(0, |fog| fog.color),
+                dither: self.dither,
+                mips: &self.mips.chains,
+                mipmaps: self.mipmaps,
+            };
+            if debug_view == DebugView::Overdraw {
+                self.overdraw.clear();
+                self.overdraw
+                    .resize_with(width * height, AtomicU32::default);
+                let counting = Counting {
+                    inner: &shader,
+                    counts: &self.overdraw,
+                    width,
+                };
+                self.timings = self
+                    .scratch
+                    .draw(buf, width, height, world, camera, &counting, debug_view);
+            } else {
+                self.timings = self
+                    .scratch
+                    .draw(buf, width, height, world, camera, &shader, debug_view);
+            }
+            let frame = &mut buf[..width * height];
+            match debug_view {
+                DebugView::Off | DebugView::SectorHeights => {}
+                DebugView::Wireframe => debug::draw_wireframe(frame, width, &self.scratch.pieces),
+                DebugView::Overdraw => debug::draw_overdraw(frame, &self.overdraw),
+                DebugView::ClipBuffer => {
+                    debug::draw_clip_buffer(frame, width, height, &self.scratch.clip_history)
+                }
+            }
+            return;
+        };
+
+        // Textures are converted once per world, not per frame
+        let textures_of = Some((world.id, world.textures.len()));
+        if mode.textures_of != textures_of {
+            mode.textures = world
+                .textures
+                .iter()
+                .map(|t| IndexedTexture::quantize(t, &mode.colormap.palette))
+                .collect();
+            mode.textures_of = textures_of;
+        }
+
+        if let Some(fog) = world.fog {
+            mode.colormap.set_fog_color(fog.color);
+        }
+        mode.colormap
+            .add_tints(world.sectors.iter().map(|s| s.light_color));
+        mode.frame.resize(width * height, 0);
+        let shader = Indexed {
+            textures: &mode.textures,
+            colormap: &mode.colormap,
+        };
+        self.timings = self.scratch.draw(
+            &mut mode.frame,
+            width,
+            height,
+            world,
+            camera,
+            &shader,
+            DebugView::Off,
+        );
+        mode.colormap
+            .expand(&mode.frame, &mut buf[..width * height]);
     }
 }
 
-fn draw_solid_wall(
-    buf: &mut [u32],
+impl Scratch {
+    #[allow(clippy::too_many_arguments)]
+    fn draw<S: Shader>(
+        &mut self,
+        buf: &mut [S::Pixel],
+        width: usize,
+        height: usize,
+        world: &World,
+        camera: &Camera,
+        shader: &S,
+        view: DebugView,
+    ) -> RenderTimings {
+        let start = Instant::now();
+        // Clear to sky; anything not covered by walls or flats is open sky
+        match world.sky {
+            Some(id) => sky::draw_sky(
+                buf,
+                width,
+                height,
+                camera,
+                shader,
+                id,
+                &world.textures[id],
+                &mut self.sky_columns,
+            ),
+            None => buf[..width * height].fill(shader.color(SKY_COLOR)),
+        }
+
+        self.ceil_clip.clear();
+        self.ceil_clip.resize(width, -1);
+        self.floor_clip.clear();
+        self.floor_clip.resize(width, height as i32);
+        self.planes.reset(width);
+        self.clip_history.resize_with(width, Vec::new);
+        for history in &mut self.clip_history {
+            history.clear();
+        }
+        self.slabs.resize_with(width, Vec::new);
+        for slabs in &mut self.slabs {
+            slabs.clear();
+        }
+        self.slab_entries.clear();
+        self.slab_entries.resize(width, None);
+        self.masked.clear();
+        self.pieces.clear();
+
+        // Draw walls nearest-first in BSP order until every column is closed
+        let mut pass = WallPass {
+            width,
+            height,
+            camera,
+            world,
+            pieces: &mut self.pieces,
+            ceil_clip: &mut self.ceil_clip,
+            floor_clip: &mut self.floor_clip,
+            open_columns: width,
+            planes: &mut self.planes,
+            clip_history: &mut self.clip_history,
+            masked: &mut self.masked,
+            slabs: &mut self.slabs,
+            slab_entries: &mut self.slab_entries,
+            camera_sector: world.sector_at(camera.pos),
+        };
+        world.bsp.walk_front_to_back(camera.pos, &mut pass);
+        self.pieces.cut_into_bands(height);
+        draw_wall_pieces(buf, width, shader, &self.pieces);
+        let walls_done = Instant::now();
+
+        if view == DebugView::SectorHeights {
+            self.planes.flats_mut().for_each(debug::color_by_height);
+            for piece in self.slabs.iter_mut().flatten() {
+                if let SlabLook::Surface { flat, .. } = &mut piece.look {
+                    debug::color_by_height(flat);
+                }
+            }
+        }
+
+        // Flats fill whatever the walls left visible above and below them
+        self.planes.draw(
+            buf,
+            width,
+            height,
+            camera,
+            shader,
+            &world.textures,
+            world.fog,
+        );
+        // Slabs go over the flats and walls beyond them
+        slabs::draw_slabs(
+            buf,
+            width,
+            height,
+            camera,
+            shader,
+            &world.textures,
+            world.fog,
+            &self.slabs,
+        );
+        let flats_done = Instant::now();
+
+        // Sprites and portal mid textures last, since both can be seen through
+        sprites::draw_sprites(
+            buf,
+            width,
+            height,
+            camera,
+            world,
+            shader,
+            &self.clip_history,
+            &self.slabs,
+            &mut self.masked,
+        );
+
+        RenderTimings {
+            walls: walls_done - start,
+            flats: flats_done - walls_done,
+            sprites: flats_done.elapsed(),
+        }
+    }
+}
+
+/// Clip window of a column right after a wall was drawn in it
+#[derive(Clone, Copy)]
+struct ClipSnapshot {
+    inv_cy: f32, // depth of that wall at the column
+    ceil_clip: i32,
+    floor_clip: i32,
+}
+
+/// Front-to-back wall pass state
+struct WallPass<'a> {
     width: usize,
     height: usize,
-    camera: &Camera,
-    wall: &crate::world::Wall,
-    sector: &crate::world::Sector,
-    color: u32,
-) {
-    let screen_width = width as f32;
-    let screen_height = height as f32;
-    let cy0 = camera.screen_center_y(screen_height);
+    camera: &'a Camera,
+    world: &'a World,
+    pieces: &'a mut Pieces, // what to draw, rasterized once the walk is done
+    // Per-column open window: rows ceil_clip[x]+1 ..= floor_clip[x]-1 are still visible
+    ceil_clip: &'a mut [i32],
+    floor_clip: &'a mut [i32],
+    open_columns: usize,
+    planes: &'a mut Visplanes,
+    // Per column, nearest-first, so sprites can be clipped against walls in front of them
+    clip_history: &'a mut [Vec<ClipSnapshot>],
+    masked: &'a mut Vec<MaskedColumn>, // portal mid textures, drawn after everything opaque
+    slabs: &'a mut [Vec<SlabPiece>],   // per column, nearest-first
+    // Per column, the last slab sector entered through a portal and the portal's depth
+    slab_entries: &'a mut [Option<(usize, f32)>],
+    camera_sector: Option<usize>, // None outside the map, where nothing is skipped
+}
 
-    // Transform wall endpoints to camera space
-    let mut p0 = camera.world_to_camera(wall.start);
-    let mut p1 = camera.world_to_camera(wall.end);
+impl WallPass<'_> {
+    #[inline]
+    fn is_closed(&self, x: usize) -> bool {
+        self.ceil_clip[x] + 1 > self.floor_clip[x] - 1
+    }
 
-    // Trivial reject: both behind near plane
-    if p0[1] <= NEAR && p1[1] <= NEAR {
-        return;
+    // Screen columns a box could cover, None if it's entirely behind or beside the view
+    fn column_range(&self, bbox: &Aabb) -> Option<(usize, usize)> {
+        let screen_width = self.width as f32;
+        let mut min_x = f32::INFINITY;
+        let mut max_x = f32::NEG_INFINITY;
+        let mut any_in_front = false;
+        for corner in bbox.corners() {
+            let c = self.camera.world_to_camera(corner);
+            if c[1] <= NEAR {
+                // A corner behind the near plane can project anywhere; don't cull sideways
+                min_x = f32::NEG_INFINITY;
+                max_x = f32::INFINITY;
+                continue;
+            }
+            any_in_front = true;
+            let sx = self.camera.project_x(c[0], c[1], screen_width);
+            min_x = min_x.min(sx);
+            max_x = max_x.max(sx);
+        }
+        if !any_in_front || max_x < 0.0 || min_x >= screen_width {
+            return None;
+        }
+        let x0 = min_x.floor().max(0.0) as usize;
+        let x1 = (max_x.ceil().min(screen_width - 1.0)) as usize;
+        Some((x0, x1))
+    }
+}
+
+impl BspVisitor for WallPass<'_> {
+    fn cull(&mut self, bbox: &Aabb) -> bool {
+        match self.column_range(bbox) {
+            Some((x0, x1)) => (x0..=x1).all(|x| self.is_closed(x)),
+            None => true,
+        }
     }
 
-    // Horizontal frustum reject (fully outside left/right)
-    let tan_half_fovx = 0.5 * screen_width / camera.fx;
+    fn visit(&mut self, seg: &Seg) -> bool {
+        // Walls between sectors that can't be seen from the camera's are hidden anyway
+        if let Some(from) = self.camera_sector {
+            let wall = &self.world.walls[seg.wall];
+            let pvs = &self.world.pvs;
+            if !pvs.can_see(from, wall.front_sector)
+                && wall.back_sector.is_none_or(|back| !pvs.can_see(from, back))
+            {
+                return true;
+            }
+        }
+        let Some(wall) = ProjectedWall::new(self.camera, self.world, seg, self.width) else {
+            return true;
+        };
+        for x in wall.x0..=wall.x1 {
+            if self.is_closed(x) {
+                continue;
+            }
+            let inv_cy = wall.inv_cy_at(x);
+            let window = (self.ceil_clip[x] + 1, self.floor_clip[x] - 1);
+            wall.draw_column(
+                self.pieces,
+                self.height,
+                self.camera,
+                x,
+                inv_cy,
+                &mut self.ceil_clip[x],
+                &mut self.floor_clip[x],
+                self.planes,
+            );
+            self.clip_history[x].push(ClipSnapshot {
+                inv_cy,
+                ceil_clip: self.ceil_clip[x],
+                floor_clip: self.floor_clip[x],
+            });
+            wall.slab_pieces(
+                self.world,
+                self.camera,
+                self.height,
+                x,
+                inv_cy,
+                window,
+                (self.ceil_clip[x] + 1, self.floor_clip[x] - 1),
+                &mut self.slab_entries[x],
+                &mut self.slabs[x],
+            );
+            if let Some(column) = wall.masked_column(
+                self.world,
+                self.camera,
+                self.height,
+                x,
+                inv_cy,
+                self.ceil_clip[x] + 1,
+                self.floor_clip[x] - 1,
+            ) {
+                self.masked.push(column);
+            }
+            if self.is_closed(x) {
+                self.open_columns -= 1;
+            }
+        }
+        self.open_columns > 0
+    }
+}
+
+/// A wall transformed, clipped and projected to screen space, ready to draw per column
+struct ProjectedWall<'a> {
+    x0: usize, // inclusive screen column range
+    x1: usize,
+    sx_left: f32,
+    sx_span: f32,
+    inv_cy0: f32,
+    inv_cy1: f32,
+    u_over_cy0: f32,
+    u_over_cy1: f32,
+    front: &'a Sector,        // sector on the camera's side
+    back: Option<&'a Sector>, // sector seen through the wall (portals only)
+    front_id: usize,
+    back_id: Option<usize>,
+    texture: TextureId,
+    texture_size: (usize, usize),
+    mid: Option<MidTexture>,
+    shift: [f32; 2], // world units the texture has scrolled by less its offset, within one repeat
+    peg_upper: bool,
+    peg_lower: bool,
+    decals: &'a [Decal],
+    fog: Option<Fog>,
+    time: f32,        // the world's, for scrolling flats
+    front_side: bool, // camera on the wall's front side, so its front decals show
+    textures: &'a [Texture],
+    ends: [bool; 2], // whether columns x0 and x1 show the wall's own ends, not cuts
+}
+
+impl<'a> ProjectedWall<'a> {
+    fn new(camera: &Camera, world: &'a World, seg: &Seg, width: usize) -> Option<Self> {
+        let screen_width = width as f32;
+        let wall = &world.walls[seg.wall];
+
+        // Transform seg endpoints to camera space
+        let start_cam = camera.world_to_camera(seg.start);
+        let mut p0 = start_cam;
+        let mut p1 = camera.world_to_camera(seg.end);
+
+        // Trivial reject: both behind near plane
+        if p0[1] <= NEAR && p1[1] <= NEAR {
+            return None;
+        }
+
+        // Horizontal frustum reject (fully outside left/right)
+        let tan_half_fovx = 0.5 * screen_width / camera.fx;
 
-    let left_plane = |cx: f32, cy: f32| cx < -cy * tan_half_fovx;
-    let right_plane = |cx: f32, cy: f32| cx > cy * tan_half_fovx;
+        let left_plane = |cx: f32, cy: f32| cx < -cy * tan_half_fovx;
+        let right_plane = |cx: f32, cy: f32| cx > cy * tan_half_fovx;
+
+        // Both endpoints are on the same outside side, cull
+        let p0_left = left_plane(p0[0], p0[1]);
+        let p1_left = left_plane(p1[0], p1[1]);
+        let p0_right = right_plane(p0[0], p0[1]);
+        let p1_right = right_plane(p1[0], p1[1]);
+
+        if (p0_left && p1_left) || (p0_right && p1_right) {
+            return None; // fully left
+        }
+
+        // Where the seg meets the wall's ends in front of the near plane
+        let mut ends = [
+            seg.start == wall.start && p0[1] > NEAR,
+            seg.end == wall.end && p1[1] > NEAR,
+        ];
+
+        // Clip against near plane (cy > NEAR)
+        if !clip_line_near(&mut p0, &mut p1) {
+            return None; // fully clipped
+        }
 
-    // Both endpoints are on the same outside side, cull
-    let p0_left = left_plane(p0[0], p0[1]);
-    let p1_left = left_plane(p1[0], p1[1]);
-    let p0_right = right_plane(p0[0], p0[1]);
-    let p1_right = right_plane(p1[0], p1[1]);
+        // Sector on the camera's side of the wall, and the one seen through it (portals only)
+        let front_side = camera_on_front_side(camera, wall);
+        let (front, back) = match wall.back_sector {
+            Some(back) if !front_side => (back, Some(wall.front_sector)),
+            back => (wall.front_sector, back),
+        };
 
-    if (p0_left && p1_left) || (p0_right && p1_right) {
-        return; // fully left
+        // Texture U (world units along the wall) at each endpoint after clipping
+        let mut u0 = seg.u_offset + dist(p0, start_cam);
+        let mut u1 = seg.u_offset + dist(p1, start_cam);
+
+        let mut sx_left = camera.project_x(p0[0], p0[1], screen_width);
+        let mut sx_right = camera.project_x(p1[0], p1[1], screen_width);
+        if sx_left > sx_right {
+            std::mem::swap(&mut sx_left, &mut sx_right);
+            std::mem::swap(&mut p0, &mut p1); // keep p0/p1 in sync with left/right
+            std::mem::swap(&mut u0, &mut u1);
+            ends.swap(0, 1);
+        }
+        let sx_span = sx_right - sx_left;
+        if sx_span < f32::EPSILON {
+            return None; // avoid div-by-zero
+        }
+
+        // Columns whose x lies in [sx_left, sx_right): walls sharing an endpoint tile
+        // without overlap, and depth is never extrapolated past the wall's ends
+        let x0 = (sx_left.ceil() as i32).max(0);
+        let x1 = (sx_right.ceil() as i32 - 1).min((width as i32) - 1);
+        if x0 > x1 {
+            return None; // off-screen or between columns
+        }
+
+        // Precompute 1/cy for endpoints
+        let inv_cy0 = 1.0 / p0[1];
+        let inv_cy1 = 1.0 / p1[1];
+        // An end off the side of the screen isn't in either column
+        let ends = [
+            ends[0] && sx_left >= 0.0,
+            ends[1] && sx_right <= screen_width,
+        ];
+
+        Some(Self {
+            x0: x0 as usize,
+            x1: x1 as usize,
+            sx_left,
+            sx_span,
+            inv_cy0,
+            inv_cy1,
+            // U/cy is linear in screen space, U itself is not
+            u_over_cy0: u0 * inv_cy0,
+            u_over_cy1: u1 * inv_cy1,
+            front: &world.sectors[front],
+            back: back.map(|b| &world.sectors[b]),
+            front_id: front,
+            back_id: back,
+            texture: wall.texture,
+            texture_size: {
+                let texture = &world.textures[wall.texture];
+                (texture.width, texture.height)
+            },
+            mid: wall.mid.filter(|_| back.is_some()),
+            shift: [
+                (wall.scroll[0] * world.time - wall.offset[0]).rem_euclid(1.0),
+                (wall.scroll[1] * world.time - wall.offset[1]).rem_euclid(1.0),
+            ],
+            peg_upper: wall.peg_upper,
+            peg_lower: wall.peg_lower,
+            decals: world.decals.on_wall(seg.wall),
+            fog: world.fog,
+            time: world.time,
+            front_side,
+            textures: &world.textures,
+            ends,
+        })
     }
 
-    // Clip against near plane (cy > NEAR)
-    if !clip_line_near(&mut p0, &mut p1) {
-        return; // fully clipped
+    // 0..1 across the wall at screen column x
+    #[inline]
+    fn alpha(&self, x: usize) -> f32 {
+        ((x as f32) - self.sx_left) / self.sx_span
     }
 
-    let sx0 = camera.project_x(p0[0], p0[1], screen_width);
-    let sx1 = camera.project_x(p1[0], p1[1], screen_width);
+    // Interpolated 1/cy at screen column x
+    #[inline]
+    fn inv_cy_at(&self, x: usize) -> f32 {
+        inv_lerp(self.inv_cy0, self.inv_cy1, self.alpha(x))
+    }
 
-    // If projected to a single column or entirely off-screen, skip
-    if (sx0 - sx1).abs() < 0.5 {
-        return;
+    // Texture repeats along the wall between screen column x and its neighbor, for
+    // picking a mip level
+    #[inline]
+    fn u_step_at(&self, x: usize) -> f32 {
+        let u_at = |x: usize| {
+            inv_lerp(self.u_over_cy0, self.u_over_cy1, self.alpha(x)) / self.inv_cy_at(x)
+        };
+        // The last column looks back, so nothing is sampled past the wall's end
+        let (a, b) = if x < self.x1 {
+            (x, x + 1)
+        } else {
+            (x.saturating_sub(1), x)
+        };
+        (u_at(b) - u_at(a)).abs()
     }
 
-    // Compute integer screen x range and clamp
-    let mut x0 = sx0.floor() as i32;
-    let mut x1 = sx1.floor() as i32;
-    if x0 > x1 {
-        std::mem::swap(&mut x0, &mut x1);
-        std::mem::swap(&mut p0, &mut p1); // keep p0/p1 in sync with x0/x1
+    // Mid texture column hanging from the top of the opening, clipped to rows clip_top..=clip_bottom
+    #[allow(clippy::too_many_arguments)]
+    fn masked_column(
+        &self,
+        world: &World,
+        camera: &Camera,
+        height: usize,
+        x: usize,
+        inv_cy: f32,
+        clip_top: i32,
+        clip_bottom: i32,
+    ) -> Option<MaskedColumn> {
+        let mid = self.mid?;
+        let back = self.back?;
+        if clip_top > clip_bottom {
+            return None;
+        }
+        let texture = &world.textures[mid.texture];
+        let u = inv_lerp(self.u_over_cy0, self.u_over_cy1, self.alpha(x)) / inv_cy;
+        let y_to_screen = camera.fy * inv_cy;
+        let open_top = self.front.ceiling_z.min(back.ceiling_z);
+        Some(MaskedColumn {
+            x,
+            inv_cy,
+            texture: mid.texture,
+            blend: mid.blend,
+            tx: ((u - self.shift[0]) * texture.width as f32).floor() as i32,
+            top: camera.screen_center_y(height as f32) - y_to_screen * (open_top - camera.eye_z),
+            v_step: texture.height as f32 / y_to_screen,
+            v_shift: (self.shift[1] * texture.height as f32).floor() as i32,
+            light: light_at(
+                self.front.light_level,
+                self.front.light_color,
+                1.0 / inv_cy,
+                self.fog,
+            ),
+            clip_top,
+            clip_bottom,
+        })
     }
-    let (x0, x1) = (x0.max(0), x1.min((width as i32) - 1));
-    if x0 >= x1 {
-        return; // off-screen
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_column(
+        &self,
+        pieces: &mut Pieces,
+        height: usize,
+        camera: &Camera,
+        x: usize,
+        inv_cy: f32,
+        ceil_clip: &mut i32,
+        floor_clip: &mut i32,
+        planes: &mut Visplanes,
+    ) {
+        let clip_top = *ceil_clip + 1;
+        let clip_bottom = *floor_clip - 1;
+        let front = self.front;
+        let (texture_width, texture_height) = self.texture_size;
+
+        let u = inv_lerp(self.u_over_cy0, self.u_over_cy1, self.alpha(x)) / inv_cy;
+        // One texture repeat per world unit in both directions
+        let tx = ((u - self.shift[0]) * texture_width as f32).floor() as i32;
+
+        let cy0 = camera.screen_center_y(height as f32);
+        let y_to_screen = camera.fy * inv_cy;
+        let z_to_screen = |z: f32| cy0 - y_to_screen * (z - camera.eye_z);
+        let top = z_to_screen(front.ceiling_z);
+        let bottom = z_to_screen(front.floor_z);
+
+        // Ceiling above the wall's top edge and floor below its bottom edge
+        let flat_offset = [
+            front.flat_offset[0] - front.flat_scroll[0] * self.time,
+            front.flat_offset[1] - front.flat_scroll[1] * self.time,
+        ];
+        let ceiling = Flat {
+            height: front.ceiling_z,
+            color: front.ceiling_color,
+            light_level: front.light_level,
+            light_color: front.light_color,
+            texture: front.ceiling_texture,
+            offset: flat_offset,
+            angle: front.flat_angle,
+            blend: Blend::Masked,
+        };
+        let floor = Flat {
+            height: front.floor_z,
+            color: front.floor_color,
+            light_level: front.light_level,
+            light_color: front.light_color,
+            texture: front.floor_texture,
+            offset: flat_offset,
+            angle: front.flat_angle,
+            blend: Blend::Masked,
+        };
+        planes.mark(
+            ceiling,
+            x,
+            clip_top,
+            (top.floor() as i32 - 1).min(clip_bottom),
+        );
+        planes.mark(
+            floor,
+            x,
+            (bottom.floor() as i32 + 1).max(clip_top),
+            clip_bottom,
+        );
+        // Liquid surface seen from above, over the floor and the walls beneath it
+        if let Some(liquid) = front.liquid
+            && camera.eye_z > liquid.surface_z
+        {
+            let surface = Flat {
+                height: liquid.surface_z,
+                color: liquid.color,
+                light_level: front.light_level,
+                light_color: front.light_color,
+                texture: None,
+                offset: [0.0, 0.0],
+                angle: 0.0,
+                blend: Blend::Translucent,
+            };
+            planes.mark_liquid(
+                surface,
+                x,
+                (z_to_screen(liquid.surface_z).floor() as i32 + 1).max(clip_top),
+                clip_bottom,
+            );
+        }
+
+        let v_step = texture_height as f32 / y_to_screen;
+        let column = WallColumn {
+            x,
+            texture: self.texture,
+            tx,
+            v_step,
+            lod: mip_lod((self.u_step_at(x) * texture_width as f32).max(v_step)),
+            v_shift: self.shift[1] * texture_height as f32,
+            light: light_at(front.light_level, front.light_color, 1.0 / inv_cy, self.fog),
+            clip_top,
+            clip_bottom,
+            wall_end: (x == self.x0 && self.ends[0]) || (x == self.x1 && self.ends[1]),
+        };
+
+        // Decals go over whichever wall pieces they overlap
+        let mut push_piece = |top: f32, bottom: f32, origin: f32| {
+            if let Some(rows) = column.push_piece(&mut pieces.walls, top, bottom, origin) {
+                self.push_decals(
+                    &mut pieces.decals,
+                    &column,
+                    u,
+                    y_to_screen,
+                    z_to_screen,
+                    rows,
+                );
+            }
+        };
+        match self.back {
+            None => {
+                // Solid wall: fill the open window and close the column, the texture
+                // hanging from the ceiling unless pegged to the floor
+                push_piece(top, bottom, if self.peg_lower { bottom } else { top });
+                *ceil_clip = height as i32;
+                *floor_clip = -1;
+            }
+            Some(back) => {
+                // Upper step where the back ceiling is lower than ours, hanging from our
+                // ceiling or pegged to the back one so it moves with a door
+                let back_top = z_to_screen(back.ceiling_z);
+                if back_top > top {
+                    push_piece(top, back_top, if self.peg_upper { back_top } else { top });
+                }
+                // Lower step where the back floor is higher than ours, from the back floor
+                // down or pegged to our floor so it stays put as a lift moves
+                let back_bottom = z_to_screen(back.floor_z);
+                if back_bottom < bottom {
+                    let origin = if self.peg_lower { bottom } else { back_bottom };
+                    push_piece(back_bottom, bottom, origin);
+                }
+
+                // Narrow the window to the opening so farther walls only draw through it
+                let open_top = top.max(back_top).floor() as i32;
+                let open_bottom = bottom.min(back_bottom).floor() as i32;
+                *ceil_clip = (*ceil_clip).max(open_top - 1);
+                *floor_clip = (*floor_clip).min(open_bottom + 1);
+            }
+        }
     }
 
-    // Precompute 1/cy for endpoints
-    let inv_cy0 = 1.0 / p0[1];
-    let inv_cy1 = 1.0 / p1[1];
+    // Slab pieces in column x: the top or underside of the front sector's slab from where
+    // the column entered the sector out to this wall, within the window it had here, then
+    // the edge of the back sector's slab within the opening
+    #[allow(clippy::too_many_arguments)]
+    fn slab_pieces(
+        &self,
+        world: &World,
+        camera: &Camera,
+        height: usize,
+        x: usize,
+        inv_cy: f32,
+        (clip_top, clip_bottom): (i32, i32),
+        (open_top, open_bottom): (i32, i32),
+        entry: &mut Option<(usize, f32)>,
+        slabs: &mut Vec<SlabPiece>,
+    ) {
+        let cy0 = camera.screen_center_y(height as f32);
+        let y_to_screen = camera.fy * inv_cy;
+        let z_to_screen = |z: f32| cy0 - y_to_screen * (z - camera.eye_z);
+
+        if let Some(slab) = self.front.slab {
+            let control = &world.sectors[slab.control];
+            // Without an entry the camera is in the sector, and the surface runs off screen
+            let near_inv_cy = match *entry {
+                Some((s, near)) if s == self.front_id => near,
+                _ => f32::INFINITY,
+            };
+            let near_row =
+                |z: f32| (cy0 - camera.fy * near_inv_cy * (z - camera.eye_z)).floor() as i32;
+            let surface = |height: f32, color: u32, texture: Option<TextureId>| Flat {
+                height,
+                color,
+                light_level: self.front.light_level,
+                light_color: self.front.light_color,
+                texture,
+                offset: [
+                    control.flat_offset[0] - control.flat_scroll[0] * self.time,
+                    control.flat_offset[1] - control.flat_scroll[1] * self.time,
+                ],
+                angle: control.flat_angle,
+                blend: Blend::Masked,
+            };
+            // The rows next to the near end are the slab's edge, pushed where it was entered
+            let seen = if camera.eye_z > control.ceiling_z {
+                let top = control.ceiling_z;
+                Some((
+                    surface(top, control.ceiling_color, control.ceiling_texture),
+                    true,
+                    z_to_screen(top).floor() as i32 + 1,
+                    near_row(top).saturating_sub(1),
+                ))
+            } else if camera.eye_z < control.floor_z {
+                let bottom = control.floor_z;
+                Some((
+                    surface(bottom, control.floor_color, control.floor_texture),
+                    false,
+                    near_row(bottom).saturating_add(1),
+                    z_to_screen(bottom).floor() as i32 - 1,
+                ))
+            } else {
+                None
+            };
+            if let Some((flat, from_above, y0, y1)) = seen {
+                let (y0, y1) = (y0.max(clip_top), y1.min(clip_bottom));
+                if y0 <= y1 {
+                    slabs.push(SlabPiece {
+                        y0,
+                        y1,
+                        near_inv_cy,
+                        far_inv_cy: inv_cy,
+                        look: SlabLook::Surface { flat, from_above },
+                    });
+                }
+            }
+        }
 
-    // Left/right screen x after potential swap
-    let sx_left = camera.project_x(p0[0], p0[1], screen_width);
-    let sx_right = camera.project_x(p1[0], p1[1], screen_width);
-    let sx_span = sx_right - sx_left;
-    if sx_span.abs() < f32::EPSILON {
-        return; // avoid div-by-zero
+        if let (Some(back), Some(back_id)) = (self.back, self.back_id)
+            && let Some(slab) = back.slab
+        {
+            *entry = Some((back_id, inv_cy));
+            let control = &world.sectors[slab.control];
+            let texture = &world.textures[slab.texture];
+            let u = inv_lerp(self.u_over_cy0, self.u_over_cy1, self.alpha(x)) / inv_cy;
+            let v_step = texture.height as f32 / y_to_screen;
+            let u_step = self.u_step_at(x) * texture.width as f32;
+            let column = WallColumn {
+                x,
+                texture: slab.texture,
+                tx: (u * texture.width as f32).floor() as i32,
+                v_step,
+                lod: mip_lod(u_step.max(v_step)),
+                v_shift: 0.0,
+                light: light_at(back.light_level, back.light_color, 1.0 / inv_cy, self.fog),
+                clip_top: open_top,
+                clip_bottom: open_bottom,
+                wall_end: false,
+            };
+            let top = z_to_screen(control.ceiling_z);
+            if let Some(piece) = column.piece(top, z_to_screen(control.floor_z), top) {
+                slabs.push(SlabPiece {
+                    y0: piece.y0,
+                    y1: piece.y1,
+                    near_inv_cy: inv_cy,
+                    far_inv_cy: inv_cy,
+                    look: SlabLook::Edge(piece),
+                });
+            }
+        }
     }
 
-    // Draw per column
-    for xi in x0..=x1 {
-        let x = xi as usize;
-        let alpha = ((xi as f32) - sx_left) / sx_span; // 0..1 across the wall
-        // Interpolate 1/cy at this column
-        let inv_cy = inv_lerp(inv_cy0, inv_cy1, alpha);
+    // Queue the parts of this wall's decals crossing column `column` at wall position `u`,
+    // within the wall piece's rows
+    fn push_decals(
+        &self,
+        decals: &mut Vec<DecalPiece>,
+        column: &WallColumn,
+        u: f32,
+        y_to_screen: f32,
+        z_to_screen: impl Fn(f32) -> f32,
+        (y0, y1): (i32, i32),
+    ) {
+        for decal in self.decals {
+            let half = 0.5 * decal.size;
+            if decal.front != self.front_side || (u - decal.u).abs() >= half {
+                continue;
+            }
+            let texture = &self.textures[decal.texture];
+            let top = z_to_screen(decal.z + half);
+            let bottom = z_to_screen(decal.z - half);
+            let d0 = (top.floor() as i32).max(y0);
+            let d1 = (bottom.floor() as i32).min(y1);
+            if d0 > d1 {
+                continue;
+            }
+            let v_step = texture.height as f32 / (decal.size * y_to_screen);
+            decals.push(DecalPiece {
+                x: column.x,
+                y0: d0,
+                y1: d1,
+                texture: decal.texture,
+                tx: ((u - decal.u + half) / decal.size * texture.width as f32) as i32,
+                v0: ((d0 as f32) + 0.5 - top) * v_step,
+                v_step,
+                light: column.light,
+                blend: decal.blend,
+            });
+        }
+    }
+}
 
-        let y_to_screen = camera.fy * inv_cy;
-        let top = cy0 - y_to_screen * (sector.ceiling_z - camera.eye_z);
-        let bottom = cy0 - y_to_screen * (sector.floor_z - camera.eye_z);
+/// One screen column of a wall, with the texture column and clip window already resolved
+#[derive(Clone, Copy)]
+struct WallColumn {
+    x: usize,
+    texture: TextureId,
+    tx: i32,
+    v_step: f32,  // texels per screen pixel
+    lod: f32,     // mip level, from the texels per pixel down and across
+    v_shift: f32, // texels the texture has scrolled down
+    light: Light, // constant down a column since depth is
+    clip_top: i32,
+    clip_bottom: i32,
+    wall_end: bool, // at either end of the wall, for `DebugView::Wireframe`
+}
 
-        // Clamp to screen
-        let mut y0 = top.floor() as i32;
-        let mut y1 = bottom.floor() as i32;
+impl WallColumn {
+    // The piece spanning screen rows top..bottom, V starting at 0 on screen row `origin`,
+    // if any of it is left once clipped
+    fn piece(&self, top: f32, bottom: f32, origin: f32) -> Option<WallPiece> {
+        let y0 = (top.floor() as i32).max(self.clip_top);
+        let y1 = (bottom.floor() as i32).min(self.clip_bottom);
         if y0 > y1 {
-            std::mem::swap(&mut y0, &mut y1);
+            return None;
         }
-        y0 = y0.max(0);
-        y1 = y1.min((height as i32) - 1);
+        Some(WallPiece {
+            column: *self,
+            y0,
+            y1,
+            v0: ((y0 as f32) + 0.5 - origin) * self.v_step - self.v_shift,
+        })
+    }
 
-        // Vertical draw
-        let mut idx = (y0 as usize) * width + x;
-        for _y in y0..=y1 {
-            buf[idx] = color;
-            idx += width;
+    // Queue that piece; the rows it covers, if any
+    fn push_piece(
+        &self,
+        pieces: &mut Vec<WallPiece>,
+        top: f32,
+        bottom: f32,
+        origin: f32,
+    ) -> Option<(i32, i32)> {
+        let piece = self.piece(top, bottom, origin)?;
+        pieces.push(piece);
+        Some((piece.y0, piece.y1))
+    }
+}
+
+/// Rows y0..=y1 of a wall column, already clipped, with the texture V at row y0
+#[derive(Clone, Copy)]
+struct WallPiece {
+    column: WallColumn,
+    y0: i32,
+    y1: i32,
+    v0: f32,
+}
+
+/// Rows y0..=y1 of a decal over one wall column, already clipped to the wall piece under it
+#[derive(Clone, Copy)]
+struct DecalPiece {
+    x: usize,
+    y0: i32,
+    y1: i32,
+    texture: TextureId,
+    tx: i32,
+    v0: f32,
+    v_step: f32,
+    light: Light,
+    blend: Blend,
+}
+
+#[derive(Default)]
+struct Pieces {
+    walls: Vec<WallPiece>,
+    decals: Vec<DecalPiece>, // drawn over the walls
+    bands: Vec<Band>,        // the same pieces cut at band edges, for `draw_wall_pieces`
+}
+
+/// The wall pieces cut to one band of rows, and the decals reaching into it
+#[derive(Default)]
+struct Band {
+    walls: Vec<WallPiece>,
+    decals: Vec<DecalPiece>,
+}
+
+impl Pieces {
+    fn clear(&mut self) {
+        self.walls.clear();
+        self.decals.clear();
+    }
+
+    // Sort every piece into the bands of a frame `height` rows tall, cutting it where it
+    // crosses from one band into the next
+    fn cut_into_bands(&mut self, height: usize) {
+        self.bands
+            .resize_with(height.div_ceil(WALL_BAND_ROWS), Band::default);
+        for band in &mut self.bands {
+            band.walls.clear();
+            band.decals.clear();
+        }
+        for piece in &self.walls {
+            // V adds up a row at a time down the whole piece, so each cut starts on the
+            // same texel drawing the piece in one go would have reached
+            let mut v = piece.v0;
+            for (band, y0, y1) in band_cuts(piece.y0, piece.y1) {
+                self.bands[band].walls.push(WallPiece {
+                    y0,
+                    y1,
+                    v0: v,
+                    ..*piece
+                });
+                for _ in y0..=y1 {
+                    v += piece.column.v_step;
+                }
+            }
+        }
+        // Decals go whole into each band they reach, and are clipped to it when drawn
+        for decal in &self.decals {
+            for (band, _, _) in band_cuts(decal.y0, decal.y1) {
+                self.bands[band].decals.push(*decal);
+            }
         }
     }
 }
 
+// Rows per band when rasterizing wall pieces in parallel
+const WALL_BAND_ROWS: usize = 16;
+
+// Rows y0..=y1 split at band edges, as (band, first row, last row)
+fn band_cuts(y0: i32, y1: i32) -> impl Iterator<Item = (usize, i32, i32)> {
+    let rows = WALL_BAND_ROWS as i32;
+    (y0 / rows..=y1 / rows).map(move |band| {
+        let top = band * rows;
+        (band as usize, y0.max(top), y1.min(top + rows - 1))
+    })
+}
+
+// Wall pieces never overlap, so they can be drawn in any order: split the frame into bands
+// of whole rows and let each thread draw the pieces cut to its band, then the decals over
+// them
+fn draw_wall_pieces<S: Shader>(buf: &mut [S::Pixel], width: usize, shader: &S, pieces: &Pieces) {
+    buf.par_chunks_mut(width * WALL_BAND_ROWS)
+        .zip(&pieces.bands)
+        .enumerate()
+        .for_each(|(band, (rows, cut))| {
+            let band_top = (band * WALL_BAND_ROWS) as i32;
+            let band_bottom = band_top + (rows.len() / width) as i32 - 1;
+            for piece in &cut.walls {
+                let column = &piece.column;
+                let mut idx = (piece.y0 - band_top) as usize * width + column.x;
+                let mut v = piece.v0;
+                for y in piece.y0..=piece.y1 {
+                    let texel =
+                        shader.texel_lod(column.texture, column.tx, v.floor() as i32, column.lod);
+                    rows[idx] = shader.shade_at(texel, column.light, column.x, y as usize);
+                    v += column.v_step;
+                    idx += width;
+                }
+            }
+            for decal in &cut.decals {
+                let y0 = decal.y0.max(band_top);
+                let y1 = decal.y1.min(band_bottom);
+                let mut idx = (y0 - band_top) as usize * width + decal.x;
+                for y in y0..=y1 {
+                    let v = decal.v0 + (y - decal.y0) as f32 * decal.v_step;
+                    let texel = shader.texel(decal.texture, decal.tx, v.floor() as i32);
+                    if !shader.is_transparent(texel) {
+                        let lit = shader.shade_at(texel, decal.light, decal.x, y as usize);
+                        rows[idx] = shader.blend(rows[idx], lit, decal.blend);
+                    }
+                    idx += width;
+                }
+            }
+        });
+}
+
+// Front sector is on the left of start->end
+#[inline]
+fn camera_on_front_side(camera: &Camera, wall: &Wall) -> bool {
+    let ex = wall.end[0] - wall.start[0];
+    let ey = wall.end[1] - wall.start[1];
+    let px = camera.pos[0] - wall.start[0];
+    let py = camera.pos[1] - wall.start[1];
+    ex * py - ey * px >= 0.0
+}
+
+#[inline]
+fn dist(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    (dx * dx + dy * dy).sqrt()
+}
+
 #[inline]
 fn inv_lerp(a: f32, b: f32, t: f32) -> f32 {
     a + t * (b - a)
@@ -0,0 +1,181 @@
+// Debug views: the frame redrawn to show how it was put together, for tracking down
+// occlusion and portal bugs
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::planes::Flat;
+use super::{ClipSnapshot, Light, Pieces, Shader, WHITE, pack_rgb, shade};
+use crate::{texture::TextureId, world::Blend};
+
+// World units of height per turn around the color wheel in `SectorHeights`
+const HEIGHT_PERIOD: f32 = 8.0;
+// Light scale the frame is darkened to under the clip windows in `ClipBuffer`
+const CLIP_BACKDROP: u32 = 80;
+
+// Overdraw counts 0, 1, 2, ... and clip windows nearest first, the last for anything more
+const RAMP: [u32; 7] = [
+    pack_rgb(0, 0, 0),
+    pack_rgb(0, 0, 170),
+    pack_rgb(0, 170, 200),
+    pack_rgb(0, 200, 0),
+    pack_rgb(230, 230, 0),
+    pack_rgb(230, 0, 0),
+    WHITE,
+];
+
+/// What the renderer draws in place of the finished frame; true color only, so palette
+/// rendering is set aside while one is on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off,
+    Wireframe,     // wall edges on black: tops and bottoms left unclipped, and wall ends
+    SectorHeights, // flats colored around the color wheel by height, walls as usual
+    Overdraw,      // how many times each pixel was drawn, from blue for once up to white
+    ClipBuffer,    // each column's clip window after every wall drawn in it
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Off => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::SectorHeights,
+            DebugView::SectorHeights => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::ClipBuffer,
+            DebugView::ClipBuffer => DebugView::Off,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next().next().next()
+    }
+}
+
+/// Untextured and evenly lit, in a color picked by height so flats at the same height match
+pub(super) fn color_by_height(flat: &mut Flat) {
+    flat.texture = None;
+    flat.color = hue((flat.height / HEIGHT_PERIOD).rem_euclid(1.0));
+    flat.light_level = 1.0;
+    flat.light_color = WHITE;
+}
+
+// Fully saturated color `h` of the way around the color wheel from red
+fn hue(h: f32) -> u32 {
+    let h6 = h * 6.0;
+    let f = h6.fract();
+    let (r, g, b) = match h6 as u32 {
+        0 => (1.0, f, 0.0),
+        1 => (1.0 - f, 1.0, 0.0),
+        2 => (0.0, 1.0, f),
+        3 => (0.0, 1.0 - f, 1.0),
+        4 => (f, 0.0, 1.0),
+        _ => (1.0, 0.0, 1.0 - f),
+    };
+    let channel = |c: f32| (c * 255.0) as u8;
+    pack_rgb(channel(r), channel(g), channel(b))
+}
+
+/// A shader drawing as `inner` does while counting the draws to each pixel. Everything
+/// drawn over the sky is shaded by position, flats too once dithering is claimed, so
+/// `shade_at` sees every draw but the particles'.
+pub(super) struct Counting<'a, S> {
+    pub inner: &'a S,
+    pub counts: &'a [AtomicU32],
+    pub width: usize,
+}
+
+impl<S: Shader> Shader for Counting<'_, S> {
+    type Pixel = S::Pixel;
+
+    #[inline]
+    fn texel(&self, texture: TextureId, tx: i32, ty: i32) -> S::Pixel {
+        self.inner.texel(texture, tx, ty)
+    }
+
+    #[inline]
+    fn texel_lod(&self, texture: TextureId, tx: i32, ty: i32, lod: f32) -> S::Pixel {
+        self.inner.texel_lod(texture, tx, ty, lod)
+    }
+
+    #[inline]
+    fn is_transparent(&self, pixel: S::Pixel) -> bool {
+        self.inner.is_transparent(pixel)
+    }
+
+    #[inline]
+    fn shade(&self, pixel: S::Pixel, light: Light) -> S::Pixel {
+        self.inner.shade(pixel, light)
+    }
+
+    #[inline]
+    fn shade_at(&self, pixel: S::Pixel, light: Light, x: usize, y: usize) -> S::Pixel {
+        self.counts[y * self.width + x].fetch_add(1, Ordering::Relaxed);
+        self.inner.shade_at(pixel, light, x, y)
+    }
+
+    fn dithers(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn color(&self, color: u32) -> S::Pixel {
+        self.inner.color(color)
+    }
+
+    #[inline]
+    fn blend(&self, dst: S::Pixel, src: S::Pixel, blend: Blend) -> S::Pixel {
+        self.inner.blend(dst, src, blend)
+    }
+}
+
+/// The frame replaced by `counts` along the ramp
+pub(super) fn draw_overdraw(buf: &mut [u32], counts: &[AtomicU32]) {
+    for (pixel, count) in buf.iter_mut().zip(counts) {
+        let count = count.load(Ordering::Relaxed) as usize;
+        *pixel = RAMP[count.min(RAMP.len() - 1)];
+    }
+}
+
+/// The frame replaced by the edges of the wall pieces drawn: each piece's top and bottom
+/// rows where the clip window didn't cut them off, and the whole piece at a wall's end
+pub(super) fn draw_wireframe(buf: &mut [u32], width: usize, pieces: &Pieces) {
+    buf.fill(0);
+    for piece in &pieces.walls {
+        let column = &piece.column;
+        let mut plot = |y: i32| buf[y as usize * width + column.x] = WHITE;
+        if column.wall_end {
+            (piece.y0..=piece.y1).for_each(&mut plot);
+            continue;
+        }
+        if piece.y0 > column.clip_top {
+            plot(piece.y0);
+        }
+        if piece.y1 < column.clip_bottom {
+            plot(piece.y1);
+        }
+    }
+}
+
+/// The frame darkened under each column's clip history: the rows just outside its window
+/// after every wall drawn in it, colored along the ramp nearest first
+pub(super) fn draw_clip_buffer(
+    buf: &mut [u32],
+    width: usize,
+    height: usize,
+    history: &[Vec<ClipSnapshot>],
+) {
+    for pixel in buf.iter_mut() {
+        *pixel = shade(*pixel, CLIP_BACKDROP);
+    }
+    for (x, snapshots) in history.iter().enumerate() {
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            let color = RAMP[(i + 1).min(RAMP.len() - 1)];
+            // A solid wall closes the column, putting both off screen
+            for y in [snapshot.ceil_clip, snapshot.floor_clip] {
+                if (0..height as i32).contains(&y) {
+                    buf[y as usize * width + x] = color;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Screen flash: the finished frame blended toward one color, for feedback on damage,
+// pickups and the like
+
+use super::mix;
+
+// Palette mode steps a flash through this many strengths, as a game palette's shifted
+// copies would, rather than fading smoothly
+const PALETTE_SHIFTS: f32 = 8.0;
+
+/// A color laid over the whole view, `amount` 0 (none) ..= 1 (solid)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flash {
+    pub color: u32,
+    pub amount: f32,
+}
+
+/// Blend `buf` toward `flash.color`, stepping the amount like a palette shift when `stepped`
+pub(super) fn blend(buf: &mut [u32], flash: Flash, stepped: bool) {
+    let mut amount = flash.amount.clamp(0.0, 1.0);
+    if stepped {
+        amount = (amount * PALETTE_SHIFTS).ceil() / PALETTE_SHIFTS;
+    }
+    let t = (amount * 256.0) as u32;
+    if t == 0 {
+        return;
+    }
+    for pixel in buf {
+        *pixel = mix(*pixel, flash.color, t);
+    }
+}
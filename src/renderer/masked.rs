@@ -0,0 +1,59 @@
+// Mid textures on portals, recorded per column during the wall pass and drawn after
+// the flats, back to front with the sprites
+
+use super::{Light, Shader};
+use crate::{
+    texture::{Texture, TextureId},
+    world::Blend,
+};
+
+/// One screen column of a portal's mid texture
+#[derive(Clone, Copy)]
+pub(super) struct MaskedColumn {
+    pub x: usize,
+    pub inv_cy: f32,
+    pub texture: TextureId,
+    pub blend: Blend,
+    pub tx: i32,
+    pub top: f32,     // screen y of the texture's top edge
+    pub v_step: f32,  // texels per screen pixel
+    pub v_shift: i32, // texels the texture has scrolled down
+    pub light: Light,
+    // Rows still open once the portal was drawn, i.e. its opening as seen through nearer walls
+    pub clip_top: i32,
+    pub clip_bottom: i32,
+}
+
+impl MaskedColumn {
+    /// `texture` is the one `self.texture` names, for its size; rows `hidden` says are
+    /// covered by something nearer are left alone
+    pub fn draw<S: Shader>(
+        &self,
+        buf: &mut [S::Pixel],
+        width: usize,
+        shader: &S,
+        texture: &Texture,
+        hidden: impl Fn(i32) -> bool,
+    ) {
+        // Not repeated vertically: stop at the texture's bottom edge
+        let bottom = self.top + texture.height as f32 / self.v_step;
+        let y0 = (self.top.floor() as i32).max(self.clip_top);
+        let y1 = (bottom.ceil() as i32 - 1).min(self.clip_bottom);
+        if y0 > y1 {
+            return;
+        }
+
+        let mut v = ((y0 as f32) + 0.5 - self.top) * self.v_step;
+        let mut idx = (y0 as usize) * width + self.x;
+        for y in y0..=y1 {
+            let ty = (v as i32).min(texture.height as i32 - 1) - self.v_shift;
+            let texel = shader.texel(self.texture, self.tx, ty);
+            if !shader.is_transparent(texel) && !hidden(y) {
+                let lit = shader.shade_at(texel, self.light, self.x, y as usize);
+                buf[idx] = shader.blend(buf[idx], lit, self.blend);
+            }
+            v += self.v_step;
+            idx += width;
+        }
+    }
+}
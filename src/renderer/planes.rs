@@ -0,0 +1,381 @@
+// Visplanes: floor/ceiling regions collected per column during the wall pass,
+// then filled afterwards as horizontal spans
+
+use super::{Light, Shader, light_at, mip_lod};
+use crate::{
+    camera::Camera,
+    texture::{Texture, TextureId},
+    world::{Blend, Fog},
+};
+
+// Marks an unused column (top > bottom for any real row)
+const EMPTY_TOP: i32 = i32::MAX;
+const EMPTY_BOTTOM: i32 = -1;
+
+/// What a plane looks like; columns only merge into a plane with an identical flat
+#[derive(Clone, Copy, PartialEq)]
+pub struct Flat {
+    pub height: f32, // world z of the flat
+    pub color: u32,  // used when there's no texture
+    pub light_level: f32,
+    pub light_color: u32,
+    pub texture: Option<TextureId>,
+    pub offset: [f32; 2], // texture shift in world units, applied after rotating
+    pub angle: f32,       // texture rotation about the world origin, radians
+    pub blend: Blend,     // anything but Masked is drawn over the opaque planes, e.g. liquid
+}
+
+pub struct Visplane {
+    pub flat: Flat,
+    minx: i32,
+    maxx: i32,
+    // Rows top..=bottom covered in each column, offset by one so x - 1 and x + 1 are always valid
+    top: Vec<i32>,
+    bottom: Vec<i32>,
+}
+
+impl Visplane {
+    fn new(flat: Flat, width: usize) -> Self {
+        Self {
+            flat,
+            minx: i32::MAX,
+            maxx: i32::MIN,
+            top: vec![EMPTY_TOP; width + 2],
+            bottom: vec![EMPTY_BOTTOM; width + 2],
+        }
+    }
+
+    fn clear(&mut self, flat: Flat) {
+        self.flat = flat;
+        self.minx = i32::MAX;
+        self.maxx = i32::MIN;
+        self.top.fill(EMPTY_TOP);
+        self.bottom.fill(EMPTY_BOTTOM);
+    }
+
+    #[inline]
+    fn is_free(&self, x: usize) -> bool {
+        self.top[x + 1] == EMPTY_TOP
+    }
+}
+
+#[derive(Default)]
+pub struct Visplanes {
+    planes: Vec<Visplane>, // planes[..used] are live, the rest are kept for reuse
+    used: usize,
+    width: usize,
+    // Per column, the top row of the nearest liquid surface marked so far
+    liquid_clip: Vec<i32>,
+}
+
+impl Visplanes {
+    /// Empty every plane for a new frame, keeping their storage if the width is unchanged
+    pub fn reset(&mut self, width: usize) {
+        if width != self.width {
+            self.planes.clear();
+            self.width = width;
+        }
+        self.used = 0;
+        self.liquid_clip.clear();
+        self.liquid_clip.resize(width, i32::MAX);
+    }
+
+    /// Like `mark`, for a liquid surface: only rows above any nearer surface in the column,
+    /// so surfaces seen through one another aren't blended twice
+    pub fn mark_liquid(&mut self, flat: Flat, x: usize, y0: i32, y1: i32) {
+        let y1 = y1.min(self.liquid_clip[x].saturating_sub(1));
+        if y0 > y1 {
+            return;
+        }
+        self.mark(flat, x, y0, y1);
+        self.liquid_clip[x] = y0;
+    }
+
+    /// The flats of the planes marked so far, to restyle them before drawing
+    pub fn flats_mut(&mut self) -> impl Iterator<Item = &mut Flat> {
+        self.planes[..self.used].iter_mut().map(|p| &mut p.flat)
+    }
+
+    /// Record rows y0..=y1 of column x as showing `flat`
+    pub fn mark(&mut self, flat: Flat, x: usize, y0: i32, y1: i32) {
+        if y0 > y1 {
+            return;
+        }
+
+        // A plane holds one span per column, so a second visit to the same column
+        // (e.g. seen again through another portal) needs its own plane
+        let idx = match self.planes[..self.used]
+            .iter()
+            .position(|p| p.flat == flat && p.is_free(x))
+        {
+            Some(i) => i,
+            None => {
+                if self.used < self.planes.len() {
+                    self.planes[self.used].clear(flat);
+                } else {
+                    self.planes.push(Visplane::new(flat, self.width));
+                }
+                self.used += 1;
+                self.used - 1
+            }
+        };
+
+        let plane = &mut self.planes[idx];
+        plane.top[x + 1] = y0;
+        plane.bottom[x + 1] = y1;
+        plane.minx = plane.minx.min(x as i32);
+        plane.maxx = plane.maxx.max(x as i32);
+    }
+
+    /// Fill every plane into the framebuffer, row by row, blended planes last
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<S: Shader>(
+        &self,
+        buf: &mut [S::Pixel],
+        width: usize,
+        height: usize,
+        camera: &Camera,
+        shader: &S,
+        textures: &[Texture],
+        fog: Option<Fog>,
+    ) {
+        let cy0 = camera.screen_center_y(height as f32);
+        let cx0 = 0.5 * width as f32;
+        let (sin_yaw, cos_yaw) = camera.yaw.sin_cos();
+        let mut span_start = vec![0i32; height];
+        let live = &self.planes[..self.used];
+        let opaque = |p: &&Visplane| p.flat.blend == Blend::Masked;
+        let blended = live.iter().filter(|p| !opaque(p));
+        for plane in live.iter().filter(opaque).chain(blended) {
+            if plane.minx > plane.maxx {
+                continue;
+            }
+            let flat = plane.flat;
+            let base = shader.color(flat.color);
+            let eye_height = (camera.eye_z - flat.height).abs();
+            let mapping = flat
+                .texture
+                .map(|id| TextureMapping::new(&flat, id, &textures[id]));
+            make_spans(plane, &mut span_start, |y, x0, x1| {
+                // Every pixel of a row on a horizontal plane sits at the same depth
+                let dy = ((y as f32) + 0.5 - cy0).abs().max(0.5);
+                let depth = eye_height * camera.fy / dy;
+                let light = light_at(flat.light_level, flat.light_color, depth, fog);
+                let Some(mapping) = &mapping else {
+                    if shader.dithers() {
+                        let row = y as usize * width;
+                        for x in x0 as usize..=x1 as usize {
+                            let color = shader.shade_at(base, light, x, y as usize);
+                            buf[row + x] = shader.blend(buf[row + x], color, flat.blend);
+                        }
+                    } else {
+                        let color = shader.shade(base, light);
+                        draw_span(buf, width, y, x0, x1, shader, color, flat.blend);
+                    }
+                    return;
+                };
+
+                // Constant depth also makes the row a straight line in world space, stepped
+                // evenly per pixel: linear stepping is already perspective-correct here
+                let cx = ((x0 as f32) + 0.5 - cx0) * depth / camera.fx;
+                let world = [
+                    camera.pos[0] + cx * cos_yaw + depth * sin_yaw,
+                    camera.pos[1] - cx * sin_yaw + depth * cos_yaw,
+                ];
+                let per_pixel = depth / camera.fx;
+                let step = [per_pixel * cos_yaw, -per_pixel * sin_yaw];
+                let span = TexturedSpan {
+                    texture: mapping.texture,
+                    uv: mapping.texel_at(world),
+                    step: mapping.texel_step(step),
+                    // Down the screen, the depth changes by depth / dy per row
+                    lod: mapping.lod(per_pixel.max(depth / dy)),
+                    light,
+                    blend: flat.blend,
+                };
+                span.draw(buf, width, y, x0, x1, shader);
+            });
+        }
+    }
+}
+
+/// Rows y0..=y1 of column x of `flat` on their own, for a piece of a plane that has to be
+/// drawn in depth order rather than with the rest, like a slab's top
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_flat_column<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    shader: &S,
+    textures: &[Texture],
+    fog: Option<Fog>,
+    flat: &Flat,
+    x: usize,
+    (y0, y1): (i32, i32),
+) {
+    let cy0 = camera.screen_center_y(height as f32);
+    let cx = (x as f32) + 0.5 - 0.5 * width as f32;
+    let (sin_yaw, cos_yaw) = camera.yaw.sin_cos();
+    let eye_height = (camera.eye_z - flat.height).abs();
+    let base = shader.color(flat.color);
+    let mapping = flat
+        .texture
+        .map(|id| TextureMapping::new(flat, id, &textures[id]));
+    for y in y0..=y1 {
+        // As in `Visplanes::draw`, one pixel of a row at a time
+        let dy = ((y as f32) + 0.5 - cy0).abs().max(0.5);
+        let depth = eye_height * camera.fy / dy;
+        let light = light_at(flat.light_level, flat.light_color, depth, fog);
+        let texel = match &mapping {
+            Some(mapping) => {
+                let side = cx * depth / camera.fx;
+                let [u, v] = mapping.texel_at([
+                    camera.pos[0] + side * cos_yaw + depth * sin_yaw,
+                    camera.pos[1] - side * sin_yaw + depth * cos_yaw,
+                ]);
+                let lod = mapping.lod((depth / camera.fx).max(depth / dy));
+                shader.texel_lod(mapping.texture, u.floor() as i32, v.floor() as i32, lod)
+            }
+            None => base,
+        };
+        let idx = y as usize * width + x;
+        let lit = shader.shade_at(texel, light, x, y as usize);
+        buf[idx] = shader.blend(buf[idx], lit, flat.blend);
+    }
+}
+
+// World x/y to texel coordinates for one textured flat: one repeat per world unit, like walls
+struct TextureMapping {
+    texture: TextureId,
+    offset: [f32; 2],
+    // Rotation by -angle, then scaled to texels, per axis
+    u_axis: [f32; 2],
+    v_axis: [f32; 2],
+    scale: [f32; 2],
+}
+
+impl TextureMapping {
+    fn new(flat: &Flat, id: TextureId, texture: &Texture) -> Self {
+        let (sin, cos) = flat.angle.sin_cos();
+        Self {
+            texture: id,
+            offset: flat.offset,
+            u_axis: [cos, sin],
+            v_axis: [-sin, cos],
+            scale: [texture.width as f32, texture.height as f32],
+        }
+    }
+
+    #[inline]
+    fn texel_at(&self, p: [f32; 2]) -> [f32; 2] {
+        let [u, v] = self.texel_step(p);
+        [
+            u + self.offset[0] * self.scale[0],
+            v + self.offset[1] * self.scale[1],
+        ]
+    }
+
+    // Mip level where a pixel spans `per_pixel` world units
+    #[inline]
+    fn lod(&self, per_pixel: f32) -> f32 {
+        mip_lod(per_pixel * self.scale[0].max(self.scale[1]))
+    }
+
+    // A world-space direction in texels, without the offset
+    #[inline]
+    fn texel_step(&self, d: [f32; 2]) -> [f32; 2] {
+        [
+            (d[0] * self.u_axis[0] + d[1] * self.u_axis[1]) * self.scale[0],
+            (d[0] * self.v_axis[0] + d[1] * self.v_axis[1]) * self.scale[1],
+        ]
+    }
+}
+
+struct TexturedSpan {
+    texture: TextureId,
+    uv: [f32; 2],   // texel coordinates at the center of the span's first pixel
+    step: [f32; 2], // texels per pixel to the right
+    lod: f32,
+    light: Light,
+    blend: Blend,
+}
+
+impl TexturedSpan {
+    #[inline]
+    fn draw<S: Shader>(
+        &self,
+        buf: &mut [S::Pixel],
+        width: usize,
+        y: i32,
+        x0: i32,
+        x1: i32,
+        shader: &S,
+    ) {
+        let row = y as usize * width;
+        let [mut u, mut v] = self.uv;
+        let span = &mut buf[row + x0 as usize..=row + x1 as usize];
+        for (x, pixel) in (x0 as usize..).zip(span) {
+            let texel =
+                shader.texel_lod(self.texture, u.floor() as i32, v.floor() as i32, self.lod);
+            let lit = shader.shade_at(texel, self.light, x, y as usize);
+            *pixel = shader.blend(*pixel, lit, self.blend);
+            u += self.step[0];
+            v += self.step[1];
+        }
+    }
+}
+
+// Convert per-column runs into horizontal spans: walking left to right, a row's span
+// opens when the row enters the plane's column and closes when it leaves
+fn make_spans(plane: &Visplane, span_start: &mut [i32], mut emit: impl FnMut(i32, i32, i32)) {
+    for x in plane.minx..=plane.maxx + 1 {
+        let i = x as usize; // previous column is i, current is i + 1 (offset storage)
+        let (mut t1, mut b1) = (plane.top[i], plane.bottom[i]);
+        let (mut t2, mut b2) = (plane.top[i + 1], plane.bottom[i + 1]);
+
+        // Close rows that were in the previous column but not this one
+        while t1 < t2 && t1 <= b1 {
+            emit(t1, span_start[t1 as usize], x - 1);
+            t1 += 1;
+        }
+        while b1 > b2 && b1 >= t1 {
+            emit(b1, span_start[b1 as usize], x - 1);
+            b1 -= 1;
+        }
+
+        // Open rows that start in this column
+        while t2 < t1 && t2 <= b2 {
+            span_start[t2 as usize] = x;
+            t2 += 1;
+        }
+        while b2 > b1 && b2 >= t2 {
+            span_start[b2 as usize] = x;
+            b2 -= 1;
+        }
+    }
+}
+
+#[inline]
+#[allow(clippy::too_many_arguments)]
+fn draw_span<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    y: i32,
+    x0: i32,
+    x1: i32,
+    shader: &S,
+    color: S::Pixel,
+    blend: Blend,
+) {
+    let row = y as usize * width;
+    let span = &mut buf[row + x0 as usize..=row + x1 as usize];
+    match blend {
+        Blend::Masked => span.fill(color),
+        _ => {
+            for pixel in span {
+                *pixel = shader.blend(*pixel, color, blend);
+            }
+        }
+    }
+}
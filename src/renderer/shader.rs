@@ -0,0 +1,188 @@
+// Pixel formats the passes can draw in: packed BGRA8 shaded by arithmetic, or palette
+// indices shaded through colormap tables
+
+use super::{
+    Light, Mipmaps, WHITE, add_saturating, mix, mix_half, mix_rounded, shade, shade_rounded, tint,
+};
+use crate::{
+    palette::{Colormap, IndexedTexture, TRANSPARENT_INDEX},
+    texture::{TRANSPARENT, Texture, TextureId},
+    world::Blend,
+};
+
+/// Texel fetch, shading and blending for one pixel format
+pub(super) trait Shader: Sync {
+    type Pixel: Copy + PartialEq + Send;
+
+    fn texel(&self, texture: TextureId, tx: i32, ty: i32) -> Self::Pixel;
+    /// As `texel`, from the mip level for `lod` (log2 of texels per pixel) if the shader
+    /// has mipmaps; `tx`/`ty` stay in full-size texels
+    #[inline]
+    fn texel_lod(&self, texture: TextureId, tx: i32, ty: i32, _lod: f32) -> Self::Pixel {
+        self.texel(texture, tx, ty)
+    }
+    fn is_transparent(&self, pixel: Self::Pixel) -> bool;
+    /// Tint by `light`'s color, darken by its scale, then blend into the fog by its fog
+    /// amount
+    fn shade(&self, pixel: Self::Pixel, light: Light) -> Self::Pixel;
+    /// As `shade`, for the pixel at (`x`, `y`) on screen, which a dithering shader rounds
+    /// by its place in the pattern
+    #[inline]
+    fn shade_at(&self, pixel: Self::Pixel, light: Light, _x: usize, _y: usize) -> Self::Pixel {
+        self.shade(pixel, light)
+    }
+    /// Whether `shade_at` depends on the position, so a flat color can't be shaded once
+    /// for a whole span
+    fn dithers(&self) -> bool {
+        false
+    }
+    /// A packed BGRA8 color, e.g. a flat's, in this format
+    fn color(&self, color: u32) -> Self::Pixel;
+    fn blend(&self, dst: Self::Pixel, src: Self::Pixel, blend: Blend) -> Self::Pixel;
+}
+
+pub(super) struct TrueColor<'a> {
+    pub textures: &'a [Texture],
+    pub fog_color: u32,
+    pub dither: bool,
+    pub mips: &'a [Vec<Texture>], // each texture's `mip_chain`, empty with mipmaps off
+    pub mipmaps: Mipmaps,
+}
+
+// 4x4 ordered dither thresholds, 0..16
+const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl Shader for TrueColor<'_> {
+    type Pixel = u32;
+
+    #[inline]
+    fn texel(&self, texture: TextureId, tx: i32, ty: i32) -> u32 {
+        self.textures[texture].texel(tx, ty)
+    }
+
+    #[inline]
+    fn texel_lod(&self, texture: TextureId, tx: i32, ty: i32, lod: f32) -> u32 {
+        let Some(chain) = self.mips.get(texture).filter(|_| lod > 0.0) else {
+            return self.texel(texture, tx, ty);
+        };
+        let level = |level: usize| match level.min(chain.len()) {
+            0 => self.texel(texture, tx, ty),
+            l => chain[l - 1].texel(tx >> l, ty >> l),
+        };
+        match self.mipmaps {
+            Mipmaps::Off => self.texel(texture, tx, ty),
+            Mipmaps::Nearest => level((lod + 0.5) as usize),
+            Mipmaps::Blended => {
+                let near = lod as usize;
+                let t = ((lod - near as f32) * 256.0) as u32;
+                mix(level(near), level(near + 1), t)
+            }
+        }
+    }
+
+    #[inline]
+    fn is_transparent(&self, pixel: u32) -> bool {
+        pixel == TRANSPARENT
+    }
+
+    #[inline]
+    fn shade(&self, pixel: u32, light: Light) -> u32 {
+        let pixel = if light.tint == WHITE {
+            pixel
+        } else {
+            tint(pixel, light.tint)
+        };
+        let lit = shade(pixel, light.scale);
+        if light.fog == 0 {
+            lit
+        } else {
+            mix(lit, self.fog_color, light.fog)
+        }
+    }
+
+    #[inline]
+    fn shade_at(&self, pixel: u32, light: Light, x: usize, y: usize) -> u32 {
+        if !self.dither {
+            return self.shade(pixel, light);
+        }
+        // Round the lost fraction up or down by the pattern instead of always down, so
+        // smooth gradients come out as fine texture rather than bands
+        let bias = BAYER[y & 3][x & 3] * 16 + 8;
+        let pixel = if light.tint == WHITE {
+            pixel
+        } else {
+            tint(pixel, light.tint)
+        };
+        let lit = shade_rounded(pixel, light.scale, bias);
+        if light.fog == 0 {
+            lit
+        } else {
+            mix_rounded(lit, self.fog_color, light.fog, bias)
+        }
+    }
+
+    fn dithers(&self) -> bool {
+        self.dither
+    }
+
+    #[inline]
+    fn color(&self, color: u32) -> u32 {
+        color
+    }
+
+    #[inline]
+    fn blend(&self, dst: u32, src: u32, blend: Blend) -> u32 {
+        match blend {
+            Blend::Masked => src,
+            Blend::Translucent => mix_half(dst, src),
+            Blend::Additive => add_saturating(dst, src),
+        }
+    }
+}
+
+pub(super) struct Indexed<'a> {
+    pub textures: &'a [IndexedTexture],
+    pub colormap: &'a Colormap,
+}
+
+impl Shader for Indexed<'_> {
+    type Pixel = u8;
+
+    #[inline]
+    fn texel(&self, texture: TextureId, tx: i32, ty: i32) -> u8 {
+        self.textures[texture].texel(tx, ty)
+    }
+
+    #[inline]
+    fn is_transparent(&self, pixel: u8) -> bool {
+        pixel == TRANSPARENT_INDEX
+    }
+
+    #[inline]
+    fn shade(&self, pixel: u8, light: Light) -> u8 {
+        let pixel = if light.tint == WHITE {
+            pixel
+        } else {
+            self.colormap.tint(pixel, light.tint)
+        };
+        let lit = self.colormap.shade(pixel, light.scale);
+        if light.fog == 0 {
+            lit
+        } else {
+            self.colormap.fog(lit, light.fog)
+        }
+    }
+
+    fn color(&self, color: u32) -> u8 {
+        self.colormap.palette.nearest(color)
+    }
+
+    #[inline]
+    fn blend(&self, dst: u8, src: u8, blend: Blend) -> u8 {
+        match blend {
+            Blend::Masked => src,
+            Blend::Translucent => self.colormap.translucent(dst, src),
+            Blend::Additive => self.colormap.additive(dst, src),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Cylindrical sky behind everything: one texture wrap per full turn, so it stays put
+// in the world as the camera turns
+
+use super::Shader;
+use crate::{
+    camera::Camera,
+    texture::{Texture, TextureId},
+};
+
+/// Fill `buf` with the sky as seen from `camera`; `columns` is scratch for per-column texel x
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_sky<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    shader: &S,
+    id: TextureId,
+    texture: &Texture,
+    columns: &mut Vec<i32>,
+) {
+    let cx0 = 0.5 * width as f32;
+    let cy0 = camera.screen_center_y(height as f32);
+    let texels_per_radian = texture.width as f32 / std::f32::consts::TAU;
+
+    columns.clear();
+    columns.extend((0..width).map(|x| {
+        let angle = camera.yaw + ((x as f32 + 0.5 - cx0) / camera.fx).atan();
+        ((angle * texels_per_radian).floor() as i32).rem_euclid(texture.width as i32)
+    }));
+
+    // Horizon at the middle of the texture, its full height spanning one screen height
+    let max_ty = texture.height as i32 - 1;
+    for (y, row) in buf[..width * height].chunks_exact_mut(width).enumerate() {
+        let v = (y as f32 + 0.5 - cy0) / height as f32 + 0.5;
+        let ty = ((v * texture.height as f32) as i32).clamp(0, max_ty);
+        for (dst, &tx) in row.iter_mut().zip(columns.iter()) {
+            *dst = shader.texel(id, tx, ty);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Slabs across sectors (3D floors such as bridges): recorded per column during the wall
+// pass and drawn once the flats are down, farthest first in each column. Whatever the
+// sprite pass draws afterwards leaves out the rows a slab piece hides from it.
+
+use super::{Shader, WallPiece, planes::Flat, planes::draw_flat_column};
+use crate::{camera::Camera, texture::Texture, world::Fog};
+
+/// Rows y0..=y1 of a column covered by a slab, already clipped
+#[derive(Clone, Copy)]
+pub(super) struct SlabPiece {
+    pub y0: i32,
+    pub y1: i32,
+    // Depth range the piece spans in its column, as 1/cy
+    pub near_inv_cy: f32,
+    pub far_inv_cy: f32,
+    pub look: SlabLook,
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum SlabLook {
+    /// The slab's edge where it meets a portal, seen from outside its sector
+    Edge(WallPiece),
+    /// Its top seen from above, or its underside from below
+    Surface { flat: Flat, from_above: bool },
+}
+
+impl SlabPiece {
+    /// Whether this covers row `y` of something at depth `inv_cy` whose height there is `z`
+    #[inline]
+    pub fn hides(&self, y: i32, inv_cy: f32, z: f32) -> bool {
+        if y < self.y0 || y > self.y1 || inv_cy >= self.near_inv_cy {
+            return false;
+        }
+        // Beyond the whole piece, or within its depth but on the far side of the surface
+        inv_cy < self.far_inv_cy
+            || match self.look {
+                SlabLook::Edge(_) => false,
+                SlabLook::Surface { flat, from_above } => (z < flat.height) == from_above,
+            }
+    }
+}
+
+/// Whether any of a column's slab pieces covers row `y` of something at depth `inv_cy`,
+/// `z_at(y)` being its height at that row
+#[inline]
+pub(super) fn hidden(pieces: &[SlabPiece], y: i32, inv_cy: f32, z_at: impl Fn(i32) -> f32) -> bool {
+    !pieces.is_empty() && {
+        let z = z_at(y);
+        pieces.iter().any(|piece| piece.hides(y, inv_cy, z))
+    }
+}
+
+/// Draw each column's slab pieces, farthest first so nearer slabs cover farther ones
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_slabs<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    shader: &S,
+    textures: &[Texture],
+    fog: Option<Fog>,
+    slabs: &[Vec<SlabPiece>],
+) {
+    for (x, pieces) in slabs.iter().enumerate() {
+        // Recorded nearest first, as the wall pass walks the BSP
+        for piece in pieces.iter().rev() {
+            match piece.look {
+                SlabLook::Edge(edge) => {
+                    let column = &edge.column;
+                    for y in piece.y0..=piece.y1 {
+                        let v = edge.v0 + (y - edge.y0) as f32 * column.v_step;
+                        let texel = shader.texel_lod(
+                            column.texture,
+                            column.tx,
+                            v.floor() as i32,
+                            column.lod,
+                        );
+                        buf[y as usize * width + x] =
+                            shader.shade_at(texel, column.light, x, y as usize);
+                    }
+                }
+                SlabLook::Surface { flat, .. } => draw_flat_column(
+                    buf,
+                    width,
+                    height,
+                    camera,
+                    shader,
+                    textures,
+                    fog,
+                    &flat,
+                    x,
+                    (piece.y0, piece.y1),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use super::{
+    ClipSnapshot, Light, MaskedColumn, NEAR, Shader, WHITE, fog_at, light_at,
+    slabs::{SlabPiece, hidden},
+};
+use crate::{
+    camera::Camera,
+    entity::Transform,
+    particles::Particle,
+    texture::TextureId,
+    voxel::{ModelId, VoxelModel},
+    world::{Blend, Fog, World},
+};
+
+// An entity's sprite projected to screen space
+struct VisSprite {
+    texture: TextureId,
+    inv_cy: f32,
+    sx_left: f32,
+    sx_right: f32,
+    top: f32,
+    bottom: f32,
+    light: Light,
+    blend: Blend,
+}
+
+// A particle projected to a screen-space square, inclusive pixel bounds
+struct VisParticle {
+    inv_cy: f32,
+    x0: i32,
+    x1: i32,
+    y0: i32,
+    y1: i32,
+    color: u32,
+    light: Light,
+    blend: Blend,
+}
+
+// An entity's voxel model, sorted among the sprites by its center and drawn a column at a
+// time
+struct VisModel {
+    model: ModelId,
+    transform: Transform,
+    voxel_size: f32, // world units per voxel
+    inv_cy: f32,
+    light: Light,
+    blend: Blend,
+}
+
+enum Vis {
+    Sprite(VisSprite),
+    Particle(VisParticle),
+    Model(VisModel),
+}
+
+impl Vis {
+    #[inline]
+    fn inv_cy(&self) -> f32 {
+        match self {
+            Vis::Sprite(s) => s.inv_cy,
+            Vis::Particle(p) => p.inv_cy,
+            Vis::Model(m) => m.inv_cy,
+        }
+    }
+}
+
+/// Draw entity sprites as camera-facing billboards or voxel models and particles as small
+/// squares, clipped per column against nearer walls and slabs, interleaved with the portal mid textures in `masked` so
+/// each covers whatever is behind it
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_sprites<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    world: &World,
+    shader: &S,
+    clip_history: &[Vec<ClipSnapshot>],
+    slabs: &[Vec<SlabPiece>],
+    masked: &mut [MaskedColumn],
+) {
+    let screen_width = width as f32;
+    let cy0 = camera.screen_center_y(height as f32);
+
+    let mut items: Vec<Vis> = world
+        .entities
+        .visible()
+        .filter_map(|(_, transform, sprite)| {
+            let c = camera.world_to_camera(transform.pos);
+            if c[1] <= NEAR {
+                return None; // behind the camera
+            }
+            let inv_cy = 1.0 / c[1];
+            let light = || {
+                let (level, color) = world.sector_at(transform.pos).map_or((1.0, WHITE), |s| {
+                    (world.sectors[s].light_level, world.sectors[s].light_color)
+                });
+                light_at(level, color, c[1], world.fog)
+            };
+
+            let world_h = sprite.world_height();
+            if let Some(model) = sprite.model {
+                return Some(Vis::Model(VisModel {
+                    model,
+                    transform: *transform,
+                    voxel_size: world_h / world.models[model].size[2] as f32,
+                    inv_cy,
+                    light: light(),
+                    blend: sprite.blend,
+                }));
+            }
+            let texture_id = sprite.texture_from(transform, camera.pos);
+            let texture = &world.textures[texture_id];
+            let world_w = world_h * texture.width as f32 / texture.height as f32;
+            let sx = camera.project_x(c[0], c[1], screen_width);
+            let half_w = 0.5 * world_w * camera.fx * inv_cy;
+            if sx + half_w < 0.0 || sx - half_w >= screen_width {
+                return None; // off to the side
+            }
+
+            let y_to_screen = camera.fy * inv_cy;
+            Some(Vis::Sprite(VisSprite {
+                texture: texture_id,
+                inv_cy,
+                sx_left: sx - half_w,
+                sx_right: sx + half_w,
+                top: cy0 - y_to_screen * (transform.z + world_h - camera.eye_z),
+                bottom: cy0 - y_to_screen * (transform.z - camera.eye_z),
+                light: light(),
+                blend: sprite.blend,
+            }))
+        })
+        .collect();
+    items.extend(
+        world
+            .particles
+            .iter()
+            .filter_map(|p| project_particle(p, camera, width, height, world.fog))
+            .map(Vis::Particle),
+    );
+
+    // Farthest first so nearer sprites overdraw farther ones and see-through ones blend over
+    // what's behind them. Stable, so things at the same depth don't swap from frame to frame.
+    items.sort_by(|a, b| a.inv_cy().total_cmp(&b.inv_cy()));
+    masked.sort_by(|a, b| a.inv_cy.total_cmp(&b.inv_cy));
+    let mut masked = masked.iter().peekable();
+
+    for item in &items {
+        // Mid texture columns behind this sprite go first
+        while let Some(column) = masked.next_if(|c| c.inv_cy < item.inv_cy()) {
+            draw_masked(buf, width, camera, cy0, shader, world, slabs, column);
+        }
+
+        let sprite = match item {
+            Vis::Sprite(sprite) => sprite,
+            Vis::Particle(particle) => {
+                draw_particle(
+                    buf,
+                    width,
+                    height,
+                    camera,
+                    shader,
+                    clip_history,
+                    slabs,
+                    particle,
+                );
+                continue;
+            }
+            Vis::Model(model) => {
+                let voxels = &world.models[model.model];
+                draw_model(
+                    buf,
+                    width,
+                    height,
+                    camera,
+                    shader,
+                    clip_history,
+                    slabs,
+                    voxels,
+                    model,
+                );
+                continue;
+            }
+        };
+        let texture = &world.textures[sprite.texture];
+        let u_step = texture.width as f32 / (sprite.sx_right - sprite.sx_left);
+        let v_step = texture.height as f32 / (sprite.bottom - sprite.top);
+
+        let x0 = (sprite.sx_left.ceil() as i32).max(0);
+        let x1 = (sprite.sx_right.ceil() as i32 - 1).min(width as i32 - 1);
+        for xi in x0..=x1 {
+            let x = xi as usize;
+            let (clip_top, clip_bottom) = window_at(&clip_history[x], sprite.inv_cy, height);
+            let z_at = upright(camera, cy0, sprite.inv_cy);
+            let y0 = (sprite.top.ceil() as i32).max(clip_top);
+            let y1 = (sprite.bottom.ceil() as i32 - 1).min(clip_bottom);
+            if y0 > y1 {
+                continue;
+            }
+
+            let tx = (((xi as f32) - sprite.sx_left) * u_step) as i32;
+            let mut v = ((y0 as f32) + 0.5 - sprite.top) * v_step;
+            let mut idx = (y0 as usize) * width + x;
+            for y in y0..=y1 {
+                let texel = shader.texel(sprite.texture, tx, v as i32);
+                if !shader.is_transparent(texel) && !hidden(&slabs[x], y, sprite.inv_cy, z_at) {
+                    let lit = shader.shade_at(texel, sprite.light, x, y as usize);
+                    buf[idx] = shader.blend(buf[idx], lit, sprite.blend);
+                }
+                v += v_step;
+                idx += width;
+            }
+        }
+    }
+
+    for column in masked {
+        draw_masked(buf, width, camera, cy0, shader, world, slabs, column);
+    }
+}
+
+// A portal's mid texture column, less the rows slabs in front of it hide
+#[allow(clippy::too_many_arguments)]
+fn draw_masked<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    camera: &Camera,
+    cy0: f32,
+    shader: &S,
+    world: &World,
+    slabs: &[Vec<SlabPiece>],
+    column: &MaskedColumn,
+) {
+    let z_at = upright(camera, cy0, column.inv_cy);
+    column.draw(buf, width, shader, &world.textures[column.texture], |y| {
+        hidden(&slabs[column.x], y, column.inv_cy, z_at)
+    });
+}
+
+// World height at each screen row of something standing upright at depth `inv_cy`
+fn upright(camera: &Camera, cy0: f32, inv_cy: f32) -> impl Fn(i32) -> f32 + Copy {
+    let y_to_screen = camera.fy * inv_cy;
+    let eye_z = camera.eye_z;
+    move |y| eye_z + (cy0 - (y as f32 + 0.5)) / y_to_screen
+}
+
+fn project_particle(
+    p: &Particle,
+    camera: &Camera,
+    width: usize,
+    height: usize,
+    fog: Option<Fog>,
+) -> Option<VisParticle> {
+    let c = camera.world_to_camera(p.pos);
+    if c[1] <= NEAR {
+        return None;
+    }
+    let inv_cy = 1.0 / c[1];
+    let sx = camera.project_x(c[0], c[1], width as f32);
+    let sy = camera.screen_center_y(height as f32) - camera.fy * inv_cy * (p.z - camera.eye_z);
+    // At least one pixel however far away
+    let half = (0.5 * p.size * camera.fx * inv_cy).max(0.5);
+    let x0 = (sx - half).round() as i32;
+    let y0 = (sy - half).round() as i32;
+    let x1 = ((sx + half).round() as i32 - 1).max(x0);
+    let y1 = ((sy + half).round() as i32 - 1).max(y0);
+    if x1 < 0 || y1 < 0 || x0 >= width as i32 || y0 >= height as i32 {
+        return None;
+    }
+    let light = if p.glow {
+        Light {
+            tint: WHITE,
+            scale: ((1.0 - p.progress()) * 256.0) as u32,
+            fog: fog_at(c[1], fog),
+        }
+    } else {
+        light_at(p.light, WHITE, c[1], fog)
+    };
+    Some(VisParticle {
+        inv_cy,
+        x0: x0.max(0),
+        x1: x1.min(width as i32 - 1),
+        y0,
+        y1,
+        color: p.color,
+        light,
+        blend: p.blend,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_particle<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    shader: &S,
+    clip_history: &[Vec<ClipSnapshot>],
+    slabs: &[Vec<SlabPiece>],
+    particle: &VisParticle,
+) {
+    let pixel = shader.shade(shader.color(particle.color), particle.light);
+    let z_at = upright(
+        camera,
+        camera.screen_center_y(height as f32),
+        particle.inv_cy,
+    );
+    for x in particle.x0..=particle.x1 {
+        let x = x as usize;
+        let (clip_top, clip_bottom) = window_at(&clip_history[x], particle.inv_cy, height);
+        for y in particle.y0.max(clip_top)..=particle.y1.min(clip_bottom) {
+            if hidden(&slabs[x], y, particle.inv_cy, z_at) {
+                continue;
+            }
+            let idx = y as usize * width + x;
+            buf[idx] = shader.blend(buf[idx], pixel, particle.blend);
+        }
+    }
+}
+
+// Each voxel column of a model as a stack of screen-aligned squares, farthest column first
+// so nearer ones cover it, clipped at the column's own depth so a model standing in a
+// doorway is cut by the frame only where it's behind it
+#[allow(clippy::too_many_arguments)]
+fn draw_model<S: Shader>(
+    buf: &mut [S::Pixel],
+    width: usize,
+    height: usize,
+    camera: &Camera,
+    shader: &S,
+    clip_history: &[Vec<ClipSnapshot>],
+    slabs: &[Vec<SlabPiece>],
+    model: &VoxelModel,
+    vis: &VisModel,
+) {
+    let [size_x, size_y, _] = model.size;
+    let s = vis.voxel_size;
+    let (sin, cos) = vis.transform.yaw.sin_cos();
+    let (forward, right) = ([sin, cos], [cos, -sin]);
+    let pos = vis.transform.pos;
+
+    // (depth, camera x, column x, column y) for the columns in front of the camera
+    let mut columns: Vec<(f32, f32, usize, usize)> = Vec::new();
+    for y in 0..size_y {
+        for x in 0..size_x {
+            if model.column(x, y).is_empty() {
+                continue;
+            }
+            let lx = (x as f32 + 0.5 - 0.5 * size_x as f32) * s;
+            let ly = (y as f32 + 0.5 - 0.5 * size_y as f32) * s;
+            let c = camera.world_to_camera([
+                pos[0] + lx * right[0] + ly * forward[0],
+                pos[1] + lx * right[1] + ly * forward[1],
+            ]);
+            if c[1] > NEAR {
+                columns.push((c[1], c[0], x, y));
+            }
+        }
+    }
+    columns.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+
+    let cy0 = camera.screen_center_y(height as f32);
+    let mut windows = Vec::new();
+    for (cy, cx, x, y) in columns {
+        let inv_cy = 1.0 / cy;
+        let sx = camera.project_x(cx, cy, width as f32);
+        let half = 0.5 * s * camera.fx * inv_cy;
+        let x0 = ((sx - half).floor() as i32).max(0);
+        let x1 = ((sx + half).ceil() as i32 - 1).min(width as i32 - 1);
+        if x0 > x1 {
+            continue;
+        }
+        windows.clear();
+        windows.extend((x0..=x1).map(|xi| window_at(&clip_history[xi as usize], inv_cy, height)));
+
+        let y_to_screen = camera.fy * inv_cy;
+        for voxel in model.column(x, y) {
+            let z = vis.transform.z + voxel.z as f32 * s;
+            let top = cy0 - y_to_screen * (z + s - camera.eye_z);
+            let bottom = cy0 - y_to_screen * (z - camera.eye_z);
+            let pixel = shader.color(voxel.color);
+            let middle = z + 0.5 * s;
+            for (xi, &(clip_top, clip_bottom)) in (x0..).zip(&windows) {
+                let y0 = (top.floor() as i32).max(clip_top);
+                let y1 = (bottom.ceil() as i32 - 1).min(clip_bottom);
+                for yi in y0..=y1 {
+                    let (px, py) = (xi as usize, yi as usize);
+                    if hidden(&slabs[px], yi, inv_cy, |_| middle) {
+                        continue;
+                    }
+                    let lit = shader.shade_at(pixel, vis.light, px, py);
+                    let idx = py * width + px;
+                    buf[idx] = shader.blend(buf[idx], lit, vis.blend);
+                }
+            }
+        }
+    }
+}
+
+// Rows still visible at a sprite's depth: only walls nearer than it narrow the window
+fn window_at(history: &[ClipSnapshot], inv_cy: f32, height: usize) -> (i32, i32) {
+    let mut window = (0, height as i32 - 1);
+    for snap in history {
+        if snap.inv_cy <= inv_cy {
+            break; // history is nearest-first, the rest is behind the sprite
+        }
+        window = (snap.ceil_clip + 1, snap.floor_clip - 1);
+    }
+    window
+}
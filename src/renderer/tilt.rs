@@ -0,0 +1,33 @@
+// View roll: the finished frame turned about its center, zoomed just enough that no
+// corner turns in from outside it
+
+/// Changes to a view made only while drawing it, leaving the camera as it is: `z` is added
+/// to the eye height, and `roll` tilts the head that many radians to the right, turning the
+/// frame the other way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ViewOffset {
+    pub z: f32,
+    pub roll: f32,
+}
+
+/// Turn `buf` in place `angle` radians counterclockwise, nearest-neighbor; `frame` is scratch
+pub(super) fn roll(buf: &mut [u32], width: usize, height: usize, angle: f32, frame: &mut Vec<u32>) {
+    frame.clear();
+    frame.extend_from_slice(buf);
+    let (w, h) = (width as f32, height as f32);
+    // Each pixel samples the source turned back the other way, zoomed in enough that the
+    // source covers the whole frame
+    let (s, c) = (-angle).sin_cos();
+    let zoom = c.abs() + s.abs() * (w / h).max(h / w);
+    let (s, c) = (s / zoom, c / zoom);
+    let (cx, cy) = (0.5 * w, 0.5 * h);
+    for (y, pixels) in buf.chunks_exact_mut(width).take(height).enumerate() {
+        let dy = y as f32 + 0.5 - cy;
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            let dx = x as f32 + 0.5 - cx;
+            let sx = (cx + dx * c + dy * s) as usize;
+            let sy = (cy - dx * s + dy * c) as usize;
+            *pixel = frame[sy.min(height - 1) * width + sx.min(width - 1)];
+        }
+    }
+}
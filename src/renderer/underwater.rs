@@ -0,0 +1,34 @@
+// Underwater view: each row of the finished frame swayed sideways on a sine wave and
+// tinted toward the liquid's color
+
+use super::mix;
+use crate::world::Liquid;
+
+// Sway in pixels at 240 rows tall, scaled with the frame
+const AMPLITUDE: f32 = 2.5;
+// Radians per row and per second
+const ROW_FREQUENCY: f32 = 0.08;
+const SPEED: f32 = 3.0;
+// How far toward the liquid's color, 0..=256
+const TINT: u32 = 96;
+
+/// Warp and tint `buf` in place; `time` in seconds animates the sway, `row` is scratch
+pub(super) fn warp(
+    buf: &mut [u32],
+    width: usize,
+    height: usize,
+    liquid: &Liquid,
+    time: f32,
+    row: &mut Vec<u32>,
+) {
+    let amplitude = AMPLITUDE * height as f32 / 240.0;
+    for (y, pixels) in buf.chunks_exact_mut(width).take(height).enumerate() {
+        let shift = (amplitude * (y as f32 * ROW_FREQUENCY + time * SPEED).sin()).round() as isize;
+        row.clear();
+        row.extend_from_slice(pixels);
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            let src = (x as isize + shift).clamp(0, width as isize - 1) as usize;
+            *pixel = mix(row[src], liquid.color, TINT);
+        }
+    }
+}
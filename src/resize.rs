@@ -0,0 +1,48 @@
+// Window resize debouncing: dragging a window edge sends a stream of sizes, and rebuilding
+// the framebuffer and scaler for each one hitches, so a new size is only taken up once it
+// has stopped changing for a moment. Frames in between keep being drawn at the old size.
+
+use std::time::{Duration, Instant};
+
+// How long the size must hold still before it's used
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+// A side growing or shrinking by this factor at once (maximizing, restoring) is used
+// straight away, since nothing more is likely to follow
+const JUMP_FACTOR: f32 = 1.5;
+
+#[derive(Default)]
+pub struct ResizeDebouncer {
+    pending: Option<([usize; 2], Instant)>, // latest size and when it arrived
+}
+
+impl ResizeDebouncer {
+    /// The window is now `size` while drawing at `current`: returns the size to switch to
+    /// right away, if any, otherwise holds it until `poll` says it settled
+    pub fn resized(
+        &mut self,
+        current: [usize; 2],
+        size: [usize; 2],
+        now: Instant,
+    ) -> Option<[usize; 2]> {
+        let jump = |a: usize, b: usize| {
+            let (lo, hi) = (a.min(b) as f32, a.max(b) as f32);
+            lo == 0.0 || hi / lo >= JUMP_FACTOR
+        };
+        if jump(current[0], size[0]) || jump(current[1], size[1]) {
+            self.pending = None;
+            return Some(size);
+        }
+        self.pending = (size != current).then_some((size, now));
+        None
+    }
+
+    /// The held size once it has settled
+    pub fn poll(&mut self, now: Instant) -> Option<[usize; 2]> {
+        let (size, at) = self.pending?;
+        if now.duration_since(at) < SETTLE_TIME {
+            return None;
+        }
+        self.pending = None;
+        Some(size)
+    }
+}
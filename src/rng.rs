@@ -0,0 +1,112 @@
+// Seeded random numbers for everything that has to come out the same on every run from the
+// same start: the world's own stream drives wandering things and flickering lights and is
+// saved with the world, and particles draw from a stream of their own so that effects only
+// some machines show never shift the rest. Nothing in the engine reads the OS for entropy.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// SplitMix64: small, fast, and its whole state is one number that saves as text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in 0..1
+    pub fn unit(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `lo..hi`
+    pub fn range(&mut self, lo: f32, hi: f32) -> f32 {
+        lo + (hi - lo) * self.unit()
+    }
+
+    /// Uniform in 0..n; `n` must not be 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A stream of its own, seeded from this one, for a subsystem that shouldn't take
+    /// numbers from anyone else's
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+}
+
+// Saved as 16 hex digits, since TOML integers stop at `i64::MAX`
+impl From<Rng> for String {
+    fn from(rng: Rng) -> Self {
+        format!("{:016x}", rng.state)
+    }
+}
+
+impl TryFrom<String> for Rng {
+    type Error = RngParseError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        u64::from_str_radix(&text, 16)
+            .map(Self::new)
+            .map_err(|_| RngParseError(text))
+    }
+}
+
+#[derive(Debug)]
+pub struct RngParseError(String);
+
+impl fmt::Display for RngParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "random state {:?} isn't 16 hex digits", self.0)
+    }
+}
+
+impl std::error::Error for RngParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut rng = Rng::new(0xDEAD_BEEF_0123_4567);
+        rng.next_u64();
+        let text = String::from(rng);
+        assert_eq!(text.len(), 16);
+        assert_eq!(Rng::try_from(text).unwrap(), rng);
+
+        // The top bit set is past what TOML integers hold
+        let high = Rng::new(u64::MAX);
+        assert_eq!(Rng::try_from(String::from(high)).unwrap(), high);
+    }
+
+    #[test]
+    fn refuses_text_that_isnt_hex() {
+        assert!(Rng::try_from("not a seed".to_string()).is_err());
+    }
+
+    #[test]
+    fn forks_the_same_from_the_same_state() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        let (mut fa, mut fb) = (a.fork(), b.fork());
+        for _ in 0..8 {
+            assert_eq!(fa.next_u64(), fb.next_u64());
+        }
+        // The parents move on in step too
+        assert_eq!(a.next_u64(), b.next_u64());
+    }
+}
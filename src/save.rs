@@ -0,0 +1,192 @@
+// Save games: the map a world was built from plus everything that changes while playing.
+// Loading rebuilds the map, then lays the saved state over it.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::entity::{Entities, EntityRecord};
+use crate::physics::VerticalBody;
+use crate::player::Player;
+use crate::rng::Rng;
+use crate::sector_effects::SectorEffect;
+use crate::texture::TextureId;
+use crate::world::loader::{self, LoadError};
+use crate::world::{Special, SwitchReset, Walkover, World};
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub map: Option<String>, // path the world was loaded from, None for the built-in demo
+    pub camera: Camera,
+    pub body: VerticalBody,
+    #[serde(default)] // absent from saves made before the player could be hurt
+    pub player: Player,
+    pub world: WorldState,
+}
+
+impl SaveGame {
+    /// The saved world: its map rebuilt from the source, with the saved state laid over it
+    pub fn world(&self) -> Result<World, SaveError> {
+        let mut world = match &self.map {
+            Some(path) => loader::load_map(path)?,
+            None => World::demo(),
+        };
+        self.world.apply(&mut world)?;
+        Ok(world)
+    }
+}
+
+/// The parts of a `World` that change at runtime
+#[derive(Serialize, Deserialize)]
+pub struct WorldState {
+    sectors: Vec<SectorState>,
+    walls: Vec<WallState>,
+    effects: Vec<SectorEffect>,
+    #[serde(default)] // absent from saves made before switches flipped back
+    switch_resets: Vec<SwitchReset>,
+    entities: Vec<EntityRecord>,
+    #[serde(default)] // absent from saves made before the world had its own random numbers
+    rng: Option<Rng>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SectorState {
+    floor_z: f32,
+    ceiling_z: f32,
+}
+
+// Switches swap textures when used, and walk-over lines can be spent
+#[derive(Serialize, Deserialize)]
+struct WallState {
+    texture: TextureId,
+    special: Special,
+    #[serde(default)] // absent from saves made before lines could be walked over
+    walkover: Option<Walkover>,
+}
+
+impl WorldState {
+    pub fn capture(world: &World) -> Self {
+        Self {
+            sectors: world
+                .sectors
+                .iter()
+                .map(|s| SectorState {
+                    floor_z: s.floor_z,
+                    ceiling_z: s.ceiling_z,
+                })
+                .collect(),
+            walls: world
+                .walls
+                .iter()
+                .map(|w| WallState {
+                    texture: w.texture,
+                    special: w.special,
+                    walkover: w.walkover,
+                })
+                .collect(),
+            effects: world.effects.clone(),
+            switch_resets: world.switch_resets.clone(),
+            entities: world.entities.records(),
+            rng: Some(world.rng),
+        }
+    }
+
+    /// Overwrite `world`'s runtime state; it must be built from the same map the state came from
+    pub fn apply(&self, world: &mut World) -> Result<(), SaveError> {
+        if self.sectors.len() != world.sectors.len() || self.walls.len() != world.walls.len() {
+            return Err(SaveError::Mismatch);
+        }
+        let sector_count = world.sectors.len();
+        let texture_count = world.textures.len();
+        let in_range = |w: &WallState| {
+            let alt_ok = match w.special {
+                Special::Switch { alt_texture, .. } => alt_texture < texture_count,
+                _ => true,
+            };
+            w.texture < texture_count && alt_ok
+        };
+        let wall_count = world.walls.len();
+        if !self.walls.iter().all(in_range)
+            || self.effects.iter().any(|e| e.sector >= sector_count)
+            || self.switch_resets.iter().any(|r| r.wall >= wall_count)
+        {
+            return Err(SaveError::Mismatch);
+        }
+
+        for (sector, state) in world.sectors.iter_mut().zip(&self.sectors) {
+            sector.floor_z = state.floor_z;
+            sector.ceiling_z = state.ceiling_z;
+        }
+        for (wall, state) in world.walls.iter_mut().zip(&self.walls) {
+            wall.texture = state.texture;
+            wall.special = state.special;
+            wall.walkover = state.walkover;
+        }
+        world.effects = self.effects.clone();
+        world.switch_resets = self.switch_resets.clone();
+        world.entities = Entities::from_records(self.entities.clone());
+        if let Some(rng) = self.rng {
+            world.rng = rng;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+    Map(LoadError), // the map the save was made on
+    Mismatch,       // the save doesn't fit the world it was applied to
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access save: {err}"),
+            SaveError::Parse(err) => write!(f, "could not parse save: {err}"),
+            SaveError::Write(err) => write!(f, "could not write save: {err}"),
+            SaveError::Map(err) => write!(f, "could not load the saved map: {err}"),
+            SaveError::Mismatch => write!(f, "save was made on a different map"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SaveError {
+    fn from(err: toml::de::Error) -> Self {
+        SaveError::Parse(err)
+    }
+}
+
+impl From<LoadError> for SaveError {
+    fn from(err: LoadError) -> Self {
+        SaveError::Map(err)
+    }
+}
+
+impl From<toml::ser::Error> for SaveError {
+    fn from(err: toml::ser::Error) -> Self {
+        SaveError::Write(err)
+    }
+}
+
+pub fn write_save(path: impl AsRef<Path>, save: &SaveGame) -> Result<(), SaveError> {
+    std::fs::write(path, toml::to_string(save)?)?;
+    Ok(())
+}
+
+pub fn read_save(path: impl AsRef<Path>) -> Result<SaveGame, SaveError> {
+    let source = std::fs::read_to_string(path)?;
+    Ok(toml::from_str(&source)?)
+}
@@ -1,7 +1,13 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
 use rayon::{
     iter::{IndexedParallelIterator, ParallelIterator},
     slice::ParallelSliceMut,
 };
+use serde::{Deserialize, Serialize};
+
+use crate::framebuffer::Framebuffer;
 
 /// Precomputed mapping from dest pixels to src neighbors + weights
 pub struct ScaleLut {
@@ -26,6 +32,365 @@ impl ScaleLut {
     }
 }
 
+/// Source pixel per dest column and row for the unfiltered modes; `LETTERBOX` is outside the image
+pub struct NearestLut {
+    xs: Vec<usize>,
+    ys: Vec<usize>,
+}
+
+const LETTERBOX: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleMode {
+    Bilinear, // smooth stretch plus sharpen, fills the window
+    Nearest,  // blocky stretch, fills the window but pixels may differ in size by one
+    Integer,  // largest whole-number scale that fits, centered with black bars
+}
+
+impl ScaleMode {
+    pub fn next(self) -> Self {
+        match self {
+            ScaleMode::Bilinear => ScaleMode::Nearest,
+            ScaleMode::Nearest => ScaleMode::Integer,
+            ScaleMode::Integer => ScaleMode::Bilinear,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+}
+
+/// Height of the internal framebuffer: a fixed line count for a consistent pixel size,
+/// or a percentage of the window height; written as in `parse`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RenderScale {
+    Lines(usize),
+    Percent(u32),
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        RenderScale::Lines(480)
+    }
+}
+
+impl RenderScale {
+    /// The steps `next` and `prev` move through
+    pub const PRESETS: [RenderScale; 5] = [
+        RenderScale::Lines(240),
+        RenderScale::Lines(360),
+        RenderScale::Lines(480),
+        RenderScale::Lines(720),
+        RenderScale::Percent(100),
+    ];
+
+    /// Internal height for a window `window_h` pixels tall, never below 120 lines
+    pub fn height_for(self, window_h: usize) -> usize {
+        let h = match self {
+            RenderScale::Lines(lines) => lines,
+            RenderScale::Percent(pct) => window_h * pct as usize / 100,
+        };
+        h.max(120)
+    }
+
+    pub fn next(self) -> Self {
+        let i = self.preset_index();
+        Self::PRESETS[(i + 1).min(Self::PRESETS.len() - 1)]
+    }
+
+    pub fn prev(self) -> Self {
+        Self::PRESETS[self.preset_index().saturating_sub(1)]
+    }
+
+    /// "360" for a line count or "50%" for a share of the window
+    pub fn parse(s: &str) -> Option<Self> {
+        let scale = match s.strip_suffix('%') {
+            Some(pct) => RenderScale::Percent(pct.parse().ok()?),
+            None => RenderScale::Lines(s.parse().ok()?),
+        };
+        let valid = match scale {
+            RenderScale::Lines(lines) => lines > 0,
+            RenderScale::Percent(pct) => (1..=100).contains(&pct),
+        };
+        valid.then_some(scale)
+    }
+
+    // Custom scales step from the default preset
+    fn preset_index(self) -> usize {
+        let find = |scale| Self::PRESETS.iter().position(|&p| p == scale);
+        find(self).or_else(|| find(Self::default())).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for RenderScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderScale::Lines(lines) => write!(f, "{lines}"),
+            RenderScale::Percent(pct) => write!(f, "{pct}%"),
+        }
+    }
+}
+
+impl TryFrom<String> for RenderScale {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::parse(&s).ok_or_else(|| format!("render scale {s:?} is not lines or a percentage"))
+    }
+}
+
+impl From<RenderScale> for String {
+    fn from(scale: RenderScale) -> Self {
+        scale.to_string()
+    }
+}
+
+enum Lut {
+    Bilinear(ScaleLut),
+    Nearest(NearestLut),
+}
+
+impl Lut {
+    fn build(mode: ScaleMode, dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) -> Self {
+        match mode {
+            ScaleMode::Bilinear => Lut::Bilinear(build_scale_lut(dst_w, dst_h, src_w, src_h)),
+            ScaleMode::Nearest => Lut::Nearest(build_nearest_lut(dst_w, dst_h, src_w, src_h)),
+            ScaleMode::Integer => Lut::Nearest(build_integer_lut(dst_w, dst_h, src_w, src_h)),
+        }
+    }
+}
+
+/// Strengths of the CRT post filter, each 0 (off) ..= 1 (full)
+#[derive(Clone, Copy, Debug)]
+pub struct CrtParams {
+    pub scanlines: f32, // darkening between source rows
+    pub vignette: f32,  // darkening toward the corners
+    pub mask: f32,      // aperture-grille RGB stripes
+}
+
+impl Default for CrtParams {
+    fn default() -> Self {
+        Self {
+            scanlines: 0.5,
+            vignette: 0.35,
+            mask: 0.25,
+        }
+    }
+}
+
+/// Display correction for screens that show dark areas too dark or too flat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorAdjust {
+    pub gamma: f32,      // above 1 lifts the shadows
+    pub brightness: f32, // added, -1..=1
+    pub contrast: f32,   // spread around mid gray, 1 unchanged
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+impl ColorAdjust {
+    pub const GAMMA_RANGE: (f32, f32) = (0.5, 3.0);
+
+    /// Limited to values that leave a picture
+    pub fn clamped(self) -> Self {
+        Self {
+            gamma: self.gamma.clamp(Self::GAMMA_RANGE.0, Self::GAMMA_RANGE.1),
+            brightness: self.brightness.clamp(-1.0, 1.0),
+            contrast: self.contrast.clamp(0.0, 4.0),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `value` 0..=1 corrected; what the lookup table and the GPU shader both compute
+    pub fn apply(&self, value: f32) -> f32 {
+        let lifted = value.powf(1.0 / self.gamma);
+        ((lifted - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0)
+    }
+
+    /// Corrected value of each 8-bit channel value
+    pub fn lut(&self) -> [u8; 256] {
+        std::array::from_fn(|i| (self.apply(i as f32 / 255.0) * 255.0).round() as u8)
+    }
+}
+
+/// Upscales the internal framebuffer to the window in one of the `ScaleMode`s
+pub struct Scaler {
+    mode: ScaleMode,
+    pub crt: Option<CrtParams>, // applied after scaling when set
+    color: ColorAdjust,
+    color_lut: Option<Box<[u8; 256]>>, // None when `color` changes nothing
+    lut: Lut,
+    src_w: usize,
+    src_h: usize,
+    dst_w: usize,
+    dst_h: usize,
+    sharpen: SharpenScratch,
+    staging: Vec<u32>, // the scaled frame on its way to a target in another format
+}
+
+impl Scaler {
+    pub fn new(dst_w: usize, dst_h: usize, src_w: usize, src_h: usize, mode: ScaleMode) -> Self {
+        Self {
+            mode,
+            crt: None,
+            color: ColorAdjust::default(),
+            color_lut: None,
+            lut: Lut::build(mode, dst_w, dst_h, src_w, src_h),
+            src_w,
+            src_h,
+            dst_w,
+            dst_h,
+            sharpen: SharpenScratch::default(),
+            staging: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ScaleMode) {
+        self.mode = mode;
+        self.lut = Lut::build(mode, self.dst_w, self.dst_h, self.src_w, self.src_h);
+    }
+
+    pub fn color_adjust(&self) -> ColorAdjust {
+        self.color
+    }
+
+    /// Correct every presented frame by `color`, last of all
+    pub fn set_color_adjust(&mut self, color: ColorAdjust) {
+        self.color = color.clamped();
+        self.color_lut = (!self.color.is_identity()).then(|| Box::new(self.color.lut()));
+    }
+
+    /// Rebuild for new sizes, keeping the mode and filter settings
+    pub fn resize(&mut self, dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) {
+        if (dst_w, dst_h, src_w, src_h) == (self.dst_w, self.dst_h, self.src_w, self.src_h) {
+            return;
+        }
+        (self.dst_w, self.dst_h, self.src_w, self.src_h) = (dst_w, dst_h, src_w, src_h);
+        self.lut = Lut::build(self.mode, dst_w, dst_h, src_w, src_h);
+    }
+
+    /// Scale `src` into `dst`; both sizes must match the ones given to `new` or `resize`.
+    /// Returns how long each stage took.
+    pub fn present(&mut self, dst: &mut [u32], src: &[u32]) -> PresentTimings {
+        let mut timings = PresentTimings::default();
+        let start = Instant::now();
+        match &self.lut {
+            Lut::Bilinear(lut) => {
+                blit_bilinear_stretch(dst, self.dst_w, src, self.src_w, lut);
+                let blitted = Instant::now();
+                timings.blit = blitted - start;
+                sharpen3x3_cross_inplace(dst, self.dst_w, self.dst_h, &mut self.sharpen);
+                timings.sharpen = blitted.elapsed();
+            }
+            Lut::Nearest(lut) => {
+                blit_nearest(dst, self.dst_w, src, self.src_w, lut);
+                timings.blit = start.elapsed();
+            }
+        }
+        if let Some(params) = &self.crt {
+            let start = Instant::now();
+            crt_filter_inplace(dst, self.dst_w, self.dst_h, self.src_h, params);
+            timings.crt = start.elapsed();
+        }
+        if let Some(lut) = &self.color_lut {
+            let start = Instant::now();
+            apply_color_lut(dst, lut);
+            timings.color = start.elapsed();
+        }
+        timings
+    }
+
+    /// As `present`, into a target in any pixel format; converting counts as blitting
+    pub fn present_to(&mut self, dst: &mut Framebuffer, src: &[u32]) -> PresentTimings {
+        let mut staging = std::mem::take(&mut self.staging);
+        staging.resize(self.dst_w * self.dst_h, 0);
+        let mut timings = self.present(&mut staging, src);
+        let start = Instant::now();
+        dst.store(&staging);
+        timings.blit += start.elapsed();
+        self.staging = staging;
+        timings
+    }
+}
+
+/// Time spent in each stage of `Scaler::present`; zero for stages the mode skips
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PresentTimings {
+    pub blit: Duration,
+    pub sharpen: Duration,
+    pub crt: Duration,
+    pub color: Duration,
+}
+
+pub fn build_nearest_lut(dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) -> NearestLut {
+    // Sample at dest pixel centers
+    let map = |dst: usize, src: usize| -> Vec<usize> {
+        let step = src as f32 / dst as f32;
+        (0..dst)
+            .map(|i| (((i as f32 + 0.5) * step) as usize).min(src - 1))
+            .collect()
+    };
+    NearestLut {
+        xs: map(dst_w, src_w),
+        ys: map(dst_h, src_h),
+    }
+}
+
+pub fn build_integer_lut(dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) -> NearestLut {
+    // A window smaller than the source still gets 1x, cropped around the center
+    let k = (dst_w / src_w.max(1)).min(dst_h / src_h.max(1)).max(1);
+    let map = |dst: usize, src: usize| -> Vec<usize> {
+        let offset = (dst as isize - (src * k) as isize) / 2;
+        (0..dst)
+            .map(|i| {
+                let s = i as isize - offset;
+                if s >= 0 && (s as usize) < src * k {
+                    s as usize / k
+                } else {
+                    LETTERBOX
+                }
+            })
+            .collect()
+    };
+    NearestLut {
+        xs: map(dst_w, src_w),
+        ys: map(dst_h, src_h),
+    }
+}
+
+/// Parallel unfiltered blit; letterboxed pixels are cleared to black
+pub fn blit_nearest(dst: &mut [u32], dw: usize, src: &[u32], sw: usize, lut: &NearestLut) {
+    dst.par_chunks_mut(dw).enumerate().for_each(|(y, dst_row)| {
+        let sy = lut.ys[y];
+        if sy == LETTERBOX {
+            dst_row.fill(0);
+            return;
+        }
+        let src_row = &src[sy * sw..(sy + 1) * sw];
+        for (d, &sx) in dst_row.iter_mut().zip(&lut.xs) {
+            *d = if sx == LETTERBOX { 0 } else { src_row[sx] };
+        }
+    });
+}
+
 pub fn build_scale_lut(dst_w: usize, dst_h: usize, src_w: usize, src_h: usize) -> ScaleLut {
     let mut x0 = vec![0; dst_w];
     let mut x1 = vec![0; dst_w];
@@ -86,7 +451,7 @@ pub fn blit_bilinear_stretch(dst: &mut [u32], dw: usize, src: &[u32], sw: usize,
         let row0 = y0 * sw;
         let row1 = y1 * sw;
 
-        for x in 0..dw {
+        for (x, out) in dst_row.iter_mut().enumerate().take(dw) {
             let x0 = lut.x0[x];
             let x1 = lut.x1[x];
             let wx = lut.wx[x] as u32;
@@ -101,58 +466,150 @@ pub fn blit_bilinear_stretch(dst: &mut [u32], dw: usize, src: &[u32], sw: usize,
             let top = lerp_color_u32(c00, c10, wx);
             let bot = lerp_color_u32(c01, c11, wx);
             // vertical lerp
-            dst_row[x] = lerp_color_u32(top, bot, wy);
+            *out = lerp_color_u32(top, bot, wy);
         }
     });
 }
 
+/// Rows kept between frames by `sharpen3x3_cross_inplace`, so it doesn't allocate
+#[derive(Default)]
+pub struct SharpenScratch {
+    rows: Vec<u32>, // per band: row above, row below, then two rolling rows
+}
+
+// Rows per band when sharpening in parallel
+const SHARPEN_BAND_ROWS: usize = 32;
+
 /// Cross-shaped 3x3 sharpen
-pub fn sharpen3x3_cross_inplace(dst: &mut [u32], w: usize, h: usize) {
+pub fn sharpen3x3_cross_inplace(dst: &mut [u32], w: usize, h: usize, scratch: &mut SharpenScratch) {
     if w < 3 || h < 3 {
         return;
     }
-    let src = dst.to_vec();
+    let band_len = SHARPEN_BAND_ROWS * w;
+    scratch
+        .rows
+        .resize(h.div_ceil(SHARPEN_BAND_ROWS) * 4 * w, 0);
 
-    // top/bottom rows unchanged
-    // use parallel rows for y = 1..h-2
-    dst.par_chunks_mut(w).enumerate().for_each(|(y, row)| {
-        if y == 0 || y == h - 1 {
-            row.copy_from_slice(&src[y * w..(y + 1) * w]);
-            return;
+    // Bands overwrite their own rows, so save the rows just outside each band first
+    for (band, saved) in scratch.rows.chunks_exact_mut(4 * w).enumerate() {
+        let top = band * SHARPEN_BAND_ROWS;
+        let below = (top + SHARPEN_BAND_ROWS).min(h);
+        if top > 0 {
+            saved[..w].copy_from_slice(&dst[(top - 1) * w..top * w]);
         }
+        if below < h {
+            saved[w..2 * w].copy_from_slice(&dst[below * w..(below + 1) * w]);
+        }
+    }
 
-        // left/right borders unchanged
-        row[0] = src[y * w];
-        row[w - 1] = src[y * w + (w - 1)];
+    // top/bottom rows and left/right borders unchanged
+    dst[..h * w]
+        .par_chunks_mut(band_len)
+        .zip(scratch.rows.par_chunks_mut(4 * w))
+        .enumerate()
+        .for_each(|(band, (rows, saved))| {
+            let (edges, rolling) = saved.split_at_mut(2 * w);
+            let (above, below) = edges.split_at(w);
+            let (mut prev, mut cur) = rolling.split_at_mut(w);
+            prev.copy_from_slice(above);
 
-        for x in 1..(w - 1) {
-            let c = src[y * w + x];
-            let n = src[(y - 1) * w + x];
-            let s = src[(y + 1) * w + x];
-            let e = src[y * w + (x + 1)];
-            let wv = src[y * w + (x - 1)];
+            let top = band * SHARPEN_BAND_ROWS;
+            let count = rows.len() / w;
+            for i in 0..count {
+                let y = top + i;
+                let (head, tail) = rows.split_at_mut((i + 1) * w);
+                let row = &mut head[i * w..];
+                // Keep the original row: the next one needs it as its north neighbor
+                cur.copy_from_slice(row);
+                if y != 0 && y != h - 1 {
+                    let next = if i + 1 < count { &tail[..w] } else { below };
+                    sharpen_row(row, prev, cur, next);
+                }
+                std::mem::swap(&mut prev, &mut cur);
+            }
+        });
+}
 
-            // per channel integer math
-            let (cb, cg, cr) = (c & 0xFF, (c >> 8) & 0xFF, (c >> 16) & 0xFF);
-            let (nb, ng, nr) = (n & 0xFF, (n >> 8) & 0xFF, (n >> 16) & 0xFF);
-            let (sb, sg, sr) = (s & 0xFF, (s >> 8) & 0xFF, (s >> 16) & 0xFF);
-            let (eb, eg, er) = (e & 0xFF, (e >> 8) & 0xFF, (e >> 16) & 0xFF);
-            let (wb, wg, wr) = (wv & 0xFF, (wv >> 8) & 0xFF, (wv >> 16) & 0xFF);
+// Sharpen the interior of one row from the original rows above, at and below it
+fn sharpen_row(row: &mut [u32], north: &[u32], center: &[u32], south: &[u32]) {
+    #[inline]
+    fn sat8(v: i32) -> u32 {
+        v.clamp(0, 255) as u32
+    }
 
-            #[inline]
-            fn sat8(v: i32) -> u32 {
-                v.clamp(0, 255) as u32
-            }
+    for x in 1..(row.len() - 1) {
+        let c = center[x];
+        let n = north[x];
+        let s = south[x];
+        let e = center[x + 1];
+        let wv = center[x - 1];
+
+        // per channel integer math
+        let (cb, cg, cr) = (c & 0xFF, (c >> 8) & 0xFF, (c >> 16) & 0xFF);
+        let (nb, ng, nr) = (n & 0xFF, (n >> 8) & 0xFF, (n >> 16) & 0xFF);
+        let (sb, sg, sr) = (s & 0xFF, (s >> 8) & 0xFF, (s >> 16) & 0xFF);
+        let (eb, eg, er) = (e & 0xFF, (e >> 8) & 0xFF, (e >> 16) & 0xFF);
+        let (wb, wg, wr) = (wv & 0xFF, (wv >> 8) & 0xFF, (wv >> 16) & 0xFF);
 
-            let rb = 5 * (cr as i32) - (nr as i32 + sr as i32 + er as i32 + wr as i32);
-            let gb = 5 * (cg as i32) - (ng as i32 + sg as i32 + eg as i32 + wg as i32);
-            let bb = 5 * (cb as i32) - (nb as i32 + sb as i32 + eb as i32 + wb as i32);
+        let rb = 5 * (cr as i32) - (nr as i32 + sr as i32 + er as i32 + wr as i32);
+        let gb = 5 * (cg as i32) - (ng as i32 + sg as i32 + eg as i32 + wg as i32);
+        let bb = 5 * (cb as i32) - (nb as i32 + sb as i32 + eb as i32 + wb as i32);
 
-            let r = sat8(rb);
-            let g = sat8(gb);
-            let b = sat8(bb);
+        let r = sat8(rb);
+        let g = sat8(gb);
+        let b = sat8(bb);
+
+        row[x] = (r << 16) | (g << 8) | b;
+    }
+}
+
+/// CRT look: scanlines following the `rows` source rows, corner vignette and an RGB stripe mask
+pub fn crt_filter_inplace(dst: &mut [u32], w: usize, h: usize, rows: usize, params: &CrtParams) {
+    if w == 0 || h == 0 {
+        return;
+    }
+
+    // Vignette is separable, 1 - v * n^4 per axis with n in -1..1, so it folds into
+    // per-column and per-row weights
+    let edge = |i: usize, n: usize| {
+        let t = (i as f32 + 0.5) / n as f32 * 2.0 - 1.0;
+        1.0 - params.vignette * t * t * t * t
+    };
+
+    // Per column: R, G, B weights in 8.8 fixed point, one stripe color every three columns
+    let dim = 1.0 - params.mask;
+    let cols: Vec<[u32; 3]> = (0..w)
+        .map(|x| {
+            let v = edge(x, w);
+            let mut rgb = [dim; 3];
+            rgb[x % 3] = 1.0;
+            rgb.map(|c| (c * v * 256.0) as u32)
+        })
+        .collect();
+
+    let rows_per_px = rows as f32 / h as f32;
+    dst.par_chunks_mut(w).enumerate().for_each(|(y, row)| {
+        // Darkest at the bottom of each source row, so even a 2x scale shows a gap line
+        let phase = ((y as f32 + 0.5) * rows_per_px).fract();
+        let scan = 1.0 - params.scanlines * phase * phase;
+        let wy = (scan * edge(y, h) * 256.0) as u32;
+
+        for (px, c) in row.iter_mut().zip(&cols) {
+            let (r, g, b) = ((*px >> 16) & 0xFF, (*px >> 8) & 0xFF, *px & 0xFF);
+            let r = (r * c[0] * wy) >> 16;
+            let g = (g * c[1] * wy) >> 16;
+            let b = (b * c[2] * wy) >> 16;
+            *px = (r << 16) | (g << 8) | b;
+        }
+    });
+}
 
-            row[x] = (r << 16) | (g << 8) | b;
+/// Map each channel of every pixel through `lut`
+pub fn apply_color_lut(dst: &mut [u32], lut: &[u8; 256]) {
+    dst.par_chunks_mut(4096).for_each(|chunk| {
+        for p in chunk {
+            let ch = |shift: u32| u32::from(lut[((*p >> shift) & 0xFF) as usize]) << shift;
+            *p = ch(0) | ch(8) | ch(16);
         }
     });
 }
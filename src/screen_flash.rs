@@ -0,0 +1,91 @@
+// Full-screen feedback: red on being hurt, yellow on picking something up and a steady
+// green while the eye is under a liquid's surface. Handed to `Renderer::flash` over the
+// finished view; the strongest of the three at the moment is the one shown.
+
+use crate::renderer::{Flash, pack_rgb};
+
+const DAMAGE_COLOR: u32 = pack_rgb(255, 0, 0);
+const PICKUP_COLOR: u32 = pack_rgb(255, 230, 60);
+const UNDERWATER_COLOR: u32 = pack_rgb(40, 160, 60);
+// Damage taken at once that flashes at full strength; less flashes less, and more hits
+// while a flash is showing build on it
+const DAMAGE_FULL: f32 = 40.0;
+// Strength of a pickup flash and of the underwater tint, before `intensity`
+const PICKUP: f32 = 0.25;
+const UNDERWATER: f32 = 0.2;
+// How fast the underwater tint comes and goes on diving and surfacing, per second
+const UNDERWATER_EASE: f32 = 4.0;
+// How much of a frame a flash at full strength covers
+const MAX_AMOUNT: f32 = 0.6;
+
+/// Default `decay`
+pub const DEFAULT_DECAY: f32 = 1.5;
+
+#[derive(Debug)]
+pub struct ScreenFlash {
+    pub intensity: f32, // 0 (off) ..= 1, scaling every flash
+    pub decay: f32,     // of full strength worn off per second
+    damage: f32,        // 0 ..= 1
+    pickup: f32,
+    underwater: f32,
+}
+
+impl Default for ScreenFlash {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            decay: DEFAULT_DECAY,
+            damage: 0.0,
+            pickup: 0.0,
+            underwater: 0.0,
+        }
+    }
+}
+
+impl ScreenFlash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flash for `damage` just taken
+    pub fn damage(&mut self, damage: f32) {
+        self.damage = (self.damage + damage.max(0.0) / DAMAGE_FULL).min(1.0);
+    }
+
+    /// Flash for something picked up
+    pub fn pickup(&mut self) {
+        self.pickup = self.pickup.max(PICKUP);
+    }
+
+    /// Advance by `dt` seconds, easing the underwater tint toward `submerged`
+    pub fn update(&mut self, dt: f32, submerged: bool) {
+        let fade = self.decay.max(0.0) * dt;
+        self.damage = (self.damage - fade).max(0.0);
+        self.pickup = (self.pickup - fade).max(0.0);
+        let target = if submerged { UNDERWATER } else { 0.0 };
+        let ease = (UNDERWATER_EASE * dt).min(1.0);
+        self.underwater += (target - self.underwater) * ease;
+    }
+
+    /// Clear every flash at once, as on respawning
+    pub fn reset(&mut self) {
+        *self = Self {
+            intensity: self.intensity,
+            decay: self.decay,
+            ..Self::default()
+        };
+    }
+
+    /// What to lay over the view this instant, if anything
+    pub fn flash(&self) -> Option<Flash> {
+        let (strength, color) = [
+            (self.damage, DAMAGE_COLOR),
+            (self.pickup, PICKUP_COLOR),
+            (self.underwater, UNDERWATER_COLOR),
+        ]
+        .into_iter()
+        .max_by(|a, b| a.0.total_cmp(&b.0))?;
+        let amount = strength * MAX_AMOUNT * self.intensity.clamp(0.0, 1.0);
+        (amount > 0.0).then_some(Flash { color, amount })
+    }
+}
@@ -0,0 +1,306 @@
+// Map scripts in rhai. A map names a script, whose top-level statements run once when the
+// map starts, and whose functions are called on triggers:
+//
+//     fn on_use(wall, tag) { ... }       // a door, switch or exit wall was used
+//     fn on_cross(wall, tag) { ... }     // the player walked over a walk-over line
+//     fn on_enter_sector(sector) { ... } // the player walked into another sector
+//     fn on_tick(dt) { ... }             // every tick, dt in seconds
+//
+// Any of them can be left out. They act on the world through these functions:
+//
+//     floor(sector), ceiling(sector), light(sector)  // current heights and light level
+//     set_floor(sector, z), set_ceiling(sector, z), set_light(sector, level)
+//     trigger(tag)         // start the doors and lifts tagged `tag`, as using a wall would
+//     spawn(thing, x, y)   // another of the map's thing number `thing`, standing at (x, y)
+//     message(text)        // shown on the HUD for a few seconds
+//     time()               // seconds since the map started
+//
+// Functions can't see the script's top-level variables, so state lives in the world.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{
+    AST, CallFnOptions, Engine, EvalAltResult, FLOAT, FuncArgs, INT, ImmutableString, Scope,
+};
+use tracing::{error, warn};
+
+use crate::events::Event;
+use crate::sector_effects;
+use crate::world::World;
+
+const EXTENSION: &str = "rhai";
+// A script running this long in one call is assumed stuck in a loop and stopped
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    Parse(rhai::ParseError),
+    Run(Box<EvalAltResult>),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "could not read script: {err}"),
+            ScriptError::Parse(err) => write!(f, "could not parse script: {err}"),
+            ScriptError::Run(err) => write!(f, "script failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<std::io::Error> for ScriptError {
+    fn from(err: std::io::Error) -> Self {
+        ScriptError::Io(err)
+    }
+}
+
+impl From<rhai::ParseError> for ScriptError {
+    fn from(err: rhai::ParseError) -> Self {
+        ScriptError::Parse(err)
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(err: Box<EvalAltResult>) -> Self {
+        ScriptError::Run(err)
+    }
+}
+
+// Sector state as the script sees it during a call, written back to the world after
+#[derive(Clone, Copy)]
+struct SectorView {
+    floor_z: f32,
+    ceiling_z: f32,
+    light_level: f32,
+}
+
+// What the registered functions read and queue up while a script function runs
+#[derive(Default)]
+struct Shared {
+    sectors: Vec<SectorView>,
+    things: usize,
+    time: f32,
+    triggers: Vec<u32>,
+    spawns: Vec<(usize, [f32; 2])>,
+    messages: Vec<String>,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    shared: Rc<RefCell<Shared>>,
+    failed: bool, // stops calling in after a runtime error rather than repeat it
+}
+
+impl Script {
+    /// Script `name`, from `<root>/scripts/<name>.rhai`
+    pub fn load(root: impl AsRef<Path>, name: &str) -> Result<Self, ScriptError> {
+        let path = root
+            .as_ref()
+            .join("scripts")
+            .join(name)
+            .with_extension(EXTENSION);
+        Self::new(&std::fs::read_to_string(path)?)
+    }
+
+    /// Compile `source`; nothing runs until `start`
+    pub fn new(source: &str) -> Result<Self, ScriptError> {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        register(&mut engine, &shared);
+        let ast = engine.compile(source)?;
+        Ok(Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            shared,
+            failed: false,
+        })
+    }
+
+    /// Run the top-level statements against `world`; returns HUD messages
+    pub fn start(&mut self, world: &mut World) -> Vec<String> {
+        self.with_world(world, |script| {
+            script
+                .engine
+                .run_ast_with_scope(&mut script.scope, &script.ast)
+        })
+    }
+
+    /// Call `on_use`, `on_cross` and `on_enter_sector` for the walls used, lines crossed and
+    /// sectors entered in `events`; returns HUD messages
+    pub fn handle(&mut self, world: &mut World, events: &[Event]) -> Vec<String> {
+        let mut messages = Vec::new();
+        for event in events {
+            messages.extend(match *event {
+                Event::WallUsed { wall, tag, .. } => {
+                    self.call(world, "on_use", 2, (wall as INT, tag as INT))
+                }
+                Event::LineCrossed { wall, tag, .. } => {
+                    self.call(world, "on_cross", 2, (wall as INT, tag as INT))
+                }
+                Event::SectorEntered { sector } => {
+                    self.call(world, "on_enter_sector", 1, (sector as INT,))
+                }
+                _ => Vec::new(),
+            });
+        }
+        messages
+    }
+
+    /// Advance by `dt`, calling `on_tick`; returns HUD messages
+    pub fn update(&mut self, world: &mut World, dt: f32) -> Vec<String> {
+        self.shared.borrow_mut().time += dt;
+        self.call(world, "on_tick", 1, (dt as FLOAT,))
+    }
+
+    // Call script function `name` if the script has one taking `arity` arguments
+    fn call(
+        &mut self,
+        world: &mut World,
+        name: &str,
+        arity: usize,
+        args: impl FuncArgs,
+    ) -> Vec<String> {
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == arity);
+        if !defined {
+            return Vec::new();
+        }
+        self.with_world(world, |script| {
+            // Top-level statements already ran in `start`
+            let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+            script.engine.call_fn_with_options::<()>(
+                options,
+                &mut script.scope,
+                &script.ast,
+                name,
+                args,
+            )
+        })
+    }
+
+    // Show the script `world`, run `f`, then apply what it did
+    fn with_world(
+        &mut self,
+        world: &mut World,
+        f: impl FnOnce(&mut Self) -> Result<(), Box<EvalAltResult>>,
+    ) -> Vec<String> {
+        if self.failed {
+            return Vec::new();
+        }
+        {
+            let mut shared = self.shared.borrow_mut();
+            shared.sectors = world
+                .sectors
+                .iter()
+                .map(|s| SectorView {
+                    floor_z: s.floor_z,
+                    ceiling_z: s.ceiling_z,
+                    light_level: s.light_level,
+                })
+                .collect();
+            shared.things = world.things.len();
+        }
+
+        if let Err(err) = f(self) {
+            error!(target: "world", "{}; map script stopped", ScriptError::Run(err));
+            self.failed = true;
+        }
+
+        let mut shared = self.shared.borrow_mut();
+        for (sector, view) in world.sectors.iter_mut().zip(&shared.sectors) {
+            sector.floor_z = view.floor_z;
+            sector.ceiling_z = view.ceiling_z;
+            sector.light_level = view.light_level;
+        }
+        for tag in shared.triggers.drain(..) {
+            sector_effects::trigger(world, tag);
+        }
+        for (thing, pos) in shared.spawns.drain(..) {
+            if world.spawn_thing(thing, pos).is_none() {
+                warn!(
+                    target: "world",
+                    "map script: can't spawn thing {thing} outside the map at {pos:?}"
+                );
+            }
+        }
+        std::mem::take(&mut shared.messages)
+    }
+}
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+// The functions scripts call into the engine with, all working on `shared`
+fn register(engine: &mut Engine, shared: &Rc<RefCell<Shared>>) {
+    let get = |field: fn(&SectorView) -> f32| {
+        let shared = Rc::clone(shared);
+        move |sector: INT| -> Fallible<FLOAT> {
+            let shared = shared.borrow();
+            let s = sector_index(&shared, sector)?;
+            Ok(field(&shared.sectors[s]) as FLOAT)
+        }
+    };
+    engine.register_fn("floor", get(|s| s.floor_z));
+    engine.register_fn("ceiling", get(|s| s.ceiling_z));
+    engine.register_fn("light", get(|s| s.light_level));
+
+    let set = |field: fn(&mut SectorView, f32)| {
+        let shared = Rc::clone(shared);
+        move |sector: INT, value: FLOAT| -> Fallible<()> {
+            let mut shared = shared.borrow_mut();
+            let s = sector_index(&shared, sector)?;
+            field(&mut shared.sectors[s], value as f32);
+            Ok(())
+        }
+    };
+    engine.register_fn("set_floor", set(|s, z| s.floor_z = z));
+    engine.register_fn("set_ceiling", set(|s, z| s.ceiling_z = z));
+    engine.register_fn("set_light", set(|s, l| s.light_level = l.clamp(0.0, 1.0)));
+
+    let queue = Rc::clone(shared);
+    engine.register_fn("trigger", move |tag: INT| -> Fallible<()> {
+        let tag = u32::try_from(tag).map_err(|_| format!("no tag {tag}"))?;
+        queue.borrow_mut().triggers.push(tag);
+        Ok(())
+    });
+
+    let queue = Rc::clone(shared);
+    engine.register_fn(
+        "spawn",
+        move |thing: INT, x: FLOAT, y: FLOAT| -> Fallible<()> {
+            let mut shared = queue.borrow_mut();
+            let thing = usize::try_from(thing)
+                .ok()
+                .filter(|&t| t < shared.things)
+                .ok_or_else(|| format!("no thing {thing}"))?;
+            shared.spawns.push((thing, [x as f32, y as f32]));
+            Ok(())
+        },
+    );
+
+    let queue = Rc::clone(shared);
+    engine.register_fn("message", move |text: ImmutableString| {
+        queue.borrow_mut().messages.push(text.to_string());
+    });
+
+    let clock = Rc::clone(shared);
+    engine.register_fn("time", move || clock.borrow().time as FLOAT);
+}
+
+fn sector_index(shared: &Shared, sector: INT) -> Fallible<usize> {
+    usize::try_from(sector)
+        .ok()
+        .filter(|&s| s < shared.sectors.len())
+        .ok_or_else(|| format!("no sector {sector}").into())
+}
@@ -0,0 +1,223 @@
+// Sector movers: doors raise their ceiling when used, lifts lower their floor on a timer.
+// Both wait at the far end and then return to where they started. Floors move once when
+// used and stay put, e.g. to raise a bridge or sink a wall. Crushers bring their ceiling down
+// toward the floor and back up over and over, grinding up whatever they catch.
+
+use serde::{Deserialize, Serialize};
+
+use crate::collision;
+use crate::entity;
+use crate::events::Event;
+use crate::world::World;
+
+/// Damage per second to whatever a running crusher brings its ceiling down on
+pub const CRUSH_DAMAGE: f32 = 40.0;
+// A thing with a ceiling this far down its height is squashed outright
+const SQUASH: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum EffectKind {
+    Door,    // moves the ceiling, starts when a wall with its tag is used
+    Lift,    // moves the floor, cycles by itself
+    Floor,   // moves the floor once, starts when a wall with its tag is used
+    Crusher, // moves the ceiling, cycles by itself
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Phase {
+    AtRest,
+    Leaving,
+    Away,
+    Returning,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SectorEffect {
+    pub sector: usize,
+    pub kind: EffectKind,
+    pub tag: u32,    // walls with this tag trigger it; 0 means none
+    pub away_z: f32, // open ceiling for doors, low floor for lifts
+    pub speed: f32,  // world units per second
+    pub wait: f32,   // seconds spent at each end before moving again
+    rest_z: f32,
+    phase: Phase,
+    timer: f32,
+    #[serde(default)] // absent from saves made before there were crushers
+    crush: f32, // damage built up and not yet dealt, as props only take whole points
+}
+
+impl SectorEffect {
+    /// `rest_z` is the sector's starting ceiling (doors, crushers) or floor (lifts)
+    pub fn new(kind: EffectKind, sector: usize, tag: u32, rest_z: f32, away_z: f32) -> Self {
+        let (speed, wait) = match kind {
+            EffectKind::Door => (2.0, 3.0),
+            EffectKind::Lift | EffectKind::Floor => (1.0, 2.0),
+            EffectKind::Crusher => (0.5, 0.5),
+        };
+        Self {
+            sector,
+            kind,
+            tag,
+            away_z,
+            speed,
+            wait,
+            rest_z,
+            phase: Phase::AtRest,
+            timer: 0.0,
+            crush: 0.0,
+        }
+    }
+
+    // Lifts and crushers start off again by themselves
+    fn cycles(&self) -> bool {
+        matches!(self.kind, EffectKind::Lift | EffectKind::Crusher)
+    }
+
+    fn activate(&mut self) {
+        if self.kind == EffectKind::Floor {
+            if self.phase == Phase::AtRest {
+                self.phase = Phase::Leaving;
+            }
+            return;
+        }
+        match self.phase {
+            Phase::AtRest | Phase::Returning => self.phase = Phase::Leaving,
+            Phase::Away => self.timer = 0.0, // used again while open: stay open longer
+            Phase::Leaving => {}
+        }
+    }
+
+    // `blocked` stops a door from closing on the player
+    fn update(&mut self, z: &mut f32, dt: f32, blocked: bool) {
+        match self.phase {
+            Phase::AtRest => {
+                if self.cycles() {
+                    self.timer += dt;
+                    if self.timer >= self.wait {
+                        self.phase = Phase::Leaving;
+                    }
+                }
+            }
+            Phase::Leaving => {
+                if approach(z, self.away_z, self.speed * dt) {
+                    self.phase = Phase::Away;
+                    self.timer = 0.0;
+                }
+            }
+            Phase::Away => {
+                self.timer += dt;
+                if self.kind != EffectKind::Floor && self.timer >= self.wait {
+                    self.phase = Phase::Returning;
+                }
+            }
+            Phase::Returning => {
+                if blocked {
+                    self.phase = Phase::Leaving;
+                } else if approach(z, self.rest_z, self.speed * dt) {
+                    self.phase = Phase::AtRest;
+                    self.timer = 0.0;
+                }
+            }
+        }
+    }
+}
+
+/// Start every effect tagged `tag`, e.g. from a used door or switch
+pub fn trigger(world: &mut World, tag: u32) {
+    if tag == 0 {
+        return;
+    }
+    for effect in world.effects.iter_mut().filter(|e| e.tag == tag) {
+        effect.activate();
+    }
+}
+
+/// Start the movers tagged like the walls used and lines walked over in `events`
+pub fn handle(world: &mut World, events: &[Event]) {
+    for event in events {
+        if let Event::WallUsed { tag, .. } | Event::LineCrossed { tag, .. } = *event {
+            trigger(world, tag);
+        }
+    }
+}
+
+/// Advance every mover by `dt`; `occupied` are the sectors players stand in. Crushers
+/// hurt the props they catch and squash them and anything else solid once low enough; the
+/// player's share is theirs to take, see `crusher_over`.
+pub fn update(world: &mut World, dt: f32, occupied: &[usize]) {
+    let World {
+        sectors, effects, ..
+    } = world;
+    // Whole points of damage each crusher deals this tick, by effect
+    let mut hits = vec![0; effects.len()];
+    for (effect, hit) in effects.iter_mut().zip(&mut hits) {
+        let sector = &mut sectors[effect.sector];
+        let blocked = effect.kind == EffectKind::Door && occupied.contains(&effect.sector);
+        let z = match effect.kind {
+            EffectKind::Door | EffectKind::Crusher => &mut sector.ceiling_z,
+            EffectKind::Lift | EffectKind::Floor => &mut sector.floor_z,
+        };
+        effect.update(z, dt, blocked);
+        if effect.kind == EffectKind::Crusher {
+            effect.crush += CRUSH_DAMAGE * dt;
+            *hit = effect.crush.floor() as u32;
+            effect.crush -= *hit as f32;
+        }
+    }
+    if world.effects.iter().any(|e| e.kind == EffectKind::Crusher) {
+        crush(world, &hits);
+    }
+}
+
+/// The running crusher, if any, with its ceiling lowest among those come down below `top_z`
+/// over any of `sectors`: what's crushing something standing in them that far up. An index
+/// into `World::effects`.
+pub fn crusher_over(world: &World, sectors: &[usize], top_z: f32) -> Option<usize> {
+    world
+        .effects
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.kind == EffectKind::Crusher && e.phase != Phase::AtRest)
+        .filter(|(_, e)| sectors.contains(&e.sector))
+        .map(|(i, e)| (i, world.sectors[e.sector].ceiling_z))
+        .filter(|&(_, ceiling_z)| ceiling_z < top_z)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+// Deal each crusher's share of `hits` to every solid thing it's come down on, and squash
+// the ones it's come far enough down
+fn crush(world: &mut World, hits: &[u32]) {
+    let caught: Vec<_> = world
+        .entities
+        .ids()
+        .filter_map(|id| {
+            let radius = world.entities.colliders[id]?;
+            let t = world.entities.transforms[id];
+            let height = world.entities.sprites[id]?.world_height();
+            let sectors = collision::sectors_under(world, t.pos, radius);
+            let crusher = crusher_over(world, &sectors, t.z + height)?;
+            let ceiling_z = world.sectors[world.effects[crusher].sector].ceiling_z;
+            let squeeze = (t.z + height - ceiling_z) / height;
+            Some((id, hits[crusher], squeeze >= SQUASH))
+        })
+        .collect();
+    for (id, hit, squashed) in caught {
+        match world.entities.props[id] {
+            Some(prop) if squashed => {
+                entity::damage(world, id, prop.health);
+            }
+            Some(_) => {
+                entity::damage(world, id, hit);
+            }
+            None if squashed => entity::squash(world, id),
+            None => {}
+        }
+    }
+}
+
+// Move `z` toward `target` by at most `step`; true once it's there
+fn approach(z: &mut f32, target: f32, step: f32) -> bool {
+    *z += (target - *z).clamp(-step, step);
+    *z == target
+}
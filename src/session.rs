@@ -0,0 +1,98 @@
+// Campaigns: maps played one after another, each ended by using an exit wall. Only the
+// world is swapped between maps; everything the player carries lives on `App` and comes
+// along unchanged.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// `maps = ["e1m1.toml", "e1m2.toml"]`, paths relative to the campaign file
+#[derive(Deserialize)]
+struct CampaignFile {
+    maps: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum CampaignError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Empty,
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CampaignError::Io(err) => write!(f, "could not read campaign: {err}"),
+            CampaignError::Parse(err) => write!(f, "could not parse campaign: {err}"),
+            CampaignError::Empty => write!(f, "campaign has no maps"),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError {}
+
+impl From<std::io::Error> for CampaignError {
+    fn from(err: std::io::Error) -> Self {
+        CampaignError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for CampaignError {
+    fn from(err: toml::de::Error) -> Self {
+        CampaignError::Parse(err)
+    }
+}
+
+/// Where the player is in a campaign
+pub struct GameSession {
+    maps: Vec<String>, // as paths loadable from the working directory
+    current: usize,
+}
+
+impl GameSession {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, CampaignError> {
+        let path = path.as_ref();
+        let file: CampaignFile = toml::from_str(&std::fs::read_to_string(path)?)?;
+        if file.maps.is_empty() {
+            return Err(CampaignError::Empty);
+        }
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let maps = file
+            .maps
+            .iter()
+            .map(|m| dir.join(m).to_string_lossy().into_owned())
+            .collect();
+        Ok(Self { maps, current: 0 })
+    }
+
+    pub fn current_map(&self) -> &str {
+        &self.maps[self.current]
+    }
+
+    /// 1-based number of the current map, and how many there are
+    pub fn progress(&self) -> (usize, usize) {
+        (self.current + 1, self.maps.len())
+    }
+
+    /// Move on to the next map and return it; None after the last one
+    pub fn advance(&mut self) -> Option<&str> {
+        if self.current + 1 >= self.maps.len() {
+            return None;
+        }
+        self.current += 1;
+        Some(self.current_map())
+    }
+
+    /// Pick up from `map`, e.g. after loading a save made on it; false if it isn't part of
+    /// the campaign
+    pub fn resume_at(&mut self, map: &str) -> bool {
+        match self.maps.iter().position(|m| m == map) {
+            Some(i) => {
+                self.current = i;
+                true
+            }
+            None => false,
+        }
+    }
+}
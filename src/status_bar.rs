@@ -0,0 +1,345 @@
+// Classic status bar across the bottom of the frame: ammo, health, the player's face, armor
+// and keys over a panel. Drawn from HUD graphics in the assets directory where they exist
+// and from built-in ones where they don't, at the frame's height like everything else drawn
+// into the internal framebuffer.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::assets;
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::inventory::Key;
+use crate::player::{MAX_HEALTH, Player};
+use crate::renderer::{mix, pack_rgb};
+use crate::texture::{TRANSPARENT, Texture};
+
+// Where in the assets directory HUD graphics are looked for
+const HUD_DIR: &str = "hud";
+
+// The panel's size in texels, drawn `BAR_HEIGHT` pixels tall per 240 lines of frame
+const BAR_WIDTH: usize = 320;
+const BAR_HEIGHT: usize = 32;
+// Sections of the panel, left and right edges in texels
+const AMMO_BOX: (usize, usize) = (4, 62);
+const HEALTH_BOX: (usize, usize) = (66, 132);
+const FACE_BOX: (usize, usize) = (136, 184);
+const ARMOR_BOX: (usize, usize) = (188, 254);
+const KEYS_BOX: (usize, usize) = (258, 316);
+// Right edges of the numbers, their top, and the top of the face and the key icons
+const AMMO_RIGHT: usize = 58;
+const HEALTH_RIGHT: usize = 116;
+const ARMOR_RIGHT: usize = 238;
+const NUMBER_TOP: usize = 4;
+const FACE_TOP: usize = 4;
+const KEY_TOP: usize = 7;
+// Top of the labels under each section
+const LABEL_TOP: usize = 23;
+
+// Built-in numbers are the HUD font at this many pixels to a font pixel
+const DIGIT_SCALE: usize = 3;
+// Faces from unhurt to nearly dead, by health lost
+const HEALTH_FACES: usize = 5;
+// Seconds the pained face shows after a hit
+const PAIN_TIME: f32 = 0.5;
+
+/// How much of the HUD to draw
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HudStyle {
+    #[default]
+    Full, // the status bar, with the view above it
+    Minimal, // health, armor and the inventory in the corners of the view
+    None,
+}
+
+impl HudStyle {
+    pub fn next(self) -> Self {
+        match self {
+            HudStyle::Full => HudStyle::Minimal,
+            HudStyle::Minimal => HudStyle::None,
+            HudStyle::None => HudStyle::Full,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+}
+
+pub struct StatusBar {
+    panel: Texture,                  // "stbar", `BAR_WIDTH` x `BAR_HEIGHT`
+    side: Texture,                   // "stbarside", tiled out to the frame's edges
+    digits: [Texture; 10],           // "stnum0" ..= "stnum9"
+    percent: Texture,                // "stpercnt"
+    faces: [Texture; HEALTH_FACES],  // "stface0" unhurt ..= "stface4"
+    pain_face: Texture,              // "stfpain"
+    dead_face: Texture,              // "stfdead"
+    keys: [Texture; Key::ALL.len()], // "stkeyred", "stkeyyellow", "stkeyblue"
+    pain: f32,                       // seconds of the pained face left
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusBar {
+    /// With the built-in graphics only
+    pub fn new() -> Self {
+        Self {
+            panel: panel_texture(),
+            side: side_texture(),
+            digits: std::array::from_fn(|i| glyph_texture(char::from(b'0' + i as u8))),
+            percent: glyph_texture('%'),
+            faces: std::array::from_fn(|hurt| face_texture(hurt, Look::Normal)),
+            pain_face: face_texture(0, Look::Pain),
+            dead_face: face_texture(HEALTH_FACES - 1, Look::Dead),
+            keys: Key::ALL.map(key_texture),
+            pain: 0.0,
+        }
+    }
+
+    /// With each graphic found in `<root>/hud/` (as `.png` or `.tga`) in place of the
+    /// built-in one
+    pub fn load(root: impl AsRef<Path>) -> Self {
+        let dir = root.as_ref().join(HUD_DIR);
+        let mut bar = Self::new();
+        let load = |slot: &mut Texture, name: &str| match assets::load_image(&dir, name) {
+            Ok(texture) => *slot = texture,
+            Err(err) => debug!(target: "renderer", "HUD graphic {name:?}: {err}; built-in"),
+        };
+        load(&mut bar.panel, "stbar");
+        load(&mut bar.side, "stbarside");
+        for (i, digit) in bar.digits.iter_mut().enumerate() {
+            load(digit, &format!("stnum{i}"));
+        }
+        load(&mut bar.percent, "stpercnt");
+        for (i, face) in bar.faces.iter_mut().enumerate() {
+            load(face, &format!("stface{i}"));
+        }
+        load(&mut bar.pain_face, "stfpain");
+        load(&mut bar.dead_face, "stfdead");
+        for (key, texture) in Key::ALL.into_iter().zip(&mut bar.keys) {
+            load(texture, &format!("stkey{}", key.name()));
+        }
+        bar
+    }
+
+    /// Rows the bar takes at the bottom of a frame `frame_height` tall
+    pub fn height(frame_height: usize) -> usize {
+        (BAR_HEIGHT * frame_height + 120) / 240
+    }
+
+    /// Grimace for a hit just taken
+    pub fn pain(&mut self) {
+        self.pain = PAIN_TIME;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.pain = (self.pain - dt).max(0.0);
+    }
+
+    /// Across the bottom `height` rows of `buf`, a `width` x `height` frame, showing
+    /// `player`
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize, player: &Player) {
+        let bar_height = Self::height(height);
+        if bar_height == 0 || width == 0 {
+            return;
+        }
+        let top = (height - bar_height) as f32;
+        let scale = bar_height as f32 / BAR_HEIGHT as f32;
+        let side_width = self.side.width as f32 * scale;
+        let mut x = 0.0;
+        while x < width as f32 {
+            self.side
+                .blit_scaled(buf, width, height, [x, top], scale, 256);
+            x += side_width.max(1.0);
+        }
+
+        // Narrower frames shrink the panel to fit, keeping it on the bottom edge
+        let scale = scale.min(width as f32 / BAR_WIDTH as f32);
+        let left = (0.5 * (width as f32 - BAR_WIDTH as f32 * scale)).round();
+        let top = height as f32 - BAR_HEIGHT as f32 * scale;
+        let mut blit = |texture: &Texture, tx: usize, ty: usize| {
+            let at = [left + tx as f32 * scale, top + ty as f32 * scale];
+            texture.blit_scaled(buf, width, height, at, scale, 256);
+        };
+        blit(&self.panel, 0, 0);
+
+        let health = player.health.ceil() as u32;
+        let armor = player.armor.ceil() as u32;
+        for (value, right, percent) in [
+            (player.inventory.ammo, AMMO_RIGHT, false),
+            (health, HEALTH_RIGHT, true),
+            (armor, ARMOR_RIGHT, true),
+        ] {
+            if percent {
+                blit(&self.percent, right + 1, NUMBER_TOP);
+            }
+            let mut x = right;
+            for digit in value.to_string().bytes().rev() {
+                let texture = &self.digits[(digit - b'0') as usize];
+                x = x.saturating_sub(texture.width + 1);
+                blit(texture, x + 1, NUMBER_TOP);
+            }
+        }
+
+        let face = self.face(player);
+        let middle = (FACE_BOX.0 + FACE_BOX.1) / 2;
+        blit(face, middle.saturating_sub(face.width / 2), FACE_TOP);
+
+        let slot = (KEYS_BOX.1 - KEYS_BOX.0) / Key::ALL.len();
+        for (i, key) in Key::ALL.into_iter().enumerate() {
+            if player.inventory.has_key(key) {
+                let texture = &self.keys[i];
+                let x = KEYS_BOX.0 + slot * i + slot.saturating_sub(texture.width) / 2;
+                blit(texture, x, KEY_TOP);
+            }
+        }
+    }
+
+    // Bloodier the more health is lost, pained for a moment after each hit
+    fn face(&self, player: &Player) -> &Texture {
+        if player.is_dead() {
+            return &self.dead_face;
+        }
+        if self.pain > 0.0 {
+            return &self.pain_face;
+        }
+        let lost = (1.0 - player.health / MAX_HEALTH).clamp(0.0, 1.0);
+        &self.faces[((lost * HEALTH_FACES as f32) as usize).min(HEALTH_FACES - 1)]
+    }
+}
+
+// A stone-gray panel with a bevelled edge and a sunken, labeled box for each section
+fn panel_texture() -> Texture {
+    let base = pack_rgb(96, 90, 84);
+    let mut pixels = bevelled(BAR_WIDTH, base);
+    let sunken = pack_rgb(52, 48, 44);
+    for (left, right) in [AMMO_BOX, HEALTH_BOX, FACE_BOX, ARMOR_BOX, KEYS_BOX] {
+        for y in 2..BAR_HEIGHT - 2 {
+            pixels[y * BAR_WIDTH + left..y * BAR_WIDTH + right].fill(sunken);
+        }
+    }
+    let label_color = pack_rgb(200, 196, 180);
+    for (label, (left, right)) in [
+        ("ammo", AMMO_BOX),
+        ("health", HEALTH_BOX),
+        ("armor", ARMOR_BOX),
+        ("keys", KEYS_BOX),
+    ] {
+        let x = left + (right - left).saturating_sub(font::text_width(label, 1)) / 2;
+        font::draw_text(
+            &mut pixels,
+            BAR_WIDTH,
+            BAR_HEIGHT,
+            [x, LABEL_TOP],
+            label,
+            label_color,
+            1,
+        );
+    }
+    Texture::from_pixels(BAR_WIDTH, BAR_HEIGHT, pixels)
+}
+
+// Plain panel, with the same bevel as the panel's edges, for either side of it
+fn side_texture() -> Texture {
+    const W: usize = 16;
+    Texture::from_pixels(W, BAR_HEIGHT, bevelled(W, pack_rgb(96, 90, 84)))
+}
+
+// `width` texels of `base`, lit along the top edge and shadowed along the bottom
+fn bevelled(width: usize, base: u32) -> Vec<u32> {
+    let mut pixels = vec![base; width * BAR_HEIGHT];
+    pixels[..width].fill(mix(base, pack_rgb(255, 255, 255), 96));
+    pixels[(BAR_HEIGHT - 1) * width..].fill(mix(base, 0, 96));
+    pixels
+}
+
+// One character of the HUD font, enlarged, in the red of a classic status bar's numbers
+fn glyph_texture(c: char) -> Texture {
+    let (w, h) = (GLYPH_WIDTH * DIGIT_SCALE, GLYPH_HEIGHT * DIGIT_SCALE);
+    let mut pixels = vec![TRANSPARENT; w * h];
+    let text = c.to_string();
+    font::draw_text(
+        &mut pixels,
+        w,
+        h,
+        [0, 0],
+        &text,
+        pack_rgb(210, 40, 30),
+        DIGIT_SCALE,
+    );
+    Texture::from_pixels(w, h, pixels)
+}
+
+// A key card in its color, lighter along the top
+fn key_texture(key: Key) -> Texture {
+    const W: usize = 10;
+    const H: usize = 12;
+    let color = key.color();
+    let mut pixels = vec![color; W * H];
+    pixels[..W].fill(mix(color, pack_rgb(255, 255, 255), 128));
+    // A hole to hang it by
+    pixels[2 * W + W / 2 - 1..2 * W + W / 2 + 1].fill(pack_rgb(52, 48, 44));
+    Texture::from_pixels(W, H, pixels)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Look {
+    Normal,
+    Pain,
+    Dead,
+}
+
+// A round face, bloodied by `hurt` of `HEALTH_FACES` steps, grimacing or gray and
+// cross-eyed as `look` says
+fn face_texture(hurt: usize, look: Look) -> Texture {
+    const SIZE: usize = 24;
+    let (mut center, mut edge) = (pack_rgb(225, 175, 135), pack_rgb(165, 115, 85));
+    if look == Look::Dead {
+        let gray = pack_rgb(120, 120, 120);
+        (center, edge) = (mix(center, gray, 160), mix(edge, gray, 160));
+    }
+    let mut face = Texture::disc(SIZE, center, edge);
+    let dark = pack_rgb(40, 25, 20);
+    let blood = pack_rgb(170, 20, 15);
+    let mut fill = |x0: usize, x1: usize, y0: usize, y1: usize, color: u32| {
+        for y in y0..y1 {
+            face.pixels[y * SIZE + x0..y * SIZE + x1].fill(color);
+        }
+    };
+
+    // Streaks of blood, one more for each step hurt
+    for &(x, y) in [(5, 12), (17, 6), (9, 18), (15, 14)].iter().take(hurt) {
+        fill(x, x + 2, y, y + 3, blood);
+    }
+    match look {
+        Look::Normal => {
+            fill(6, 9, 8, 10, dark);
+            fill(15, 18, 8, 10, dark);
+            fill(8, 16, 16, 17, dark);
+        }
+        Look::Pain => {
+            // Brows drawn down and the mouth wide open
+            fill(5, 9, 6, 7, dark);
+            fill(15, 19, 6, 7, dark);
+            fill(6, 9, 8, 10, dark);
+            fill(15, 18, 8, 10, dark);
+            fill(9, 15, 14, 19, dark);
+        }
+        Look::Dead => {
+            for (x, y) in [(6, 7), (15, 7)] {
+                for i in 0..3 {
+                    fill(x + i, x + i + 1, y + i, y + i + 1, dark);
+                    fill(x + 2 - i, x + 3 - i, y + i, y + i + 1, dark);
+                }
+            }
+            fill(8, 16, 17, 18, dark);
+        }
+    }
+    face
+}
@@ -0,0 +1,155 @@
+use crate::renderer::shade;
+
+pub type TextureId = usize;
+
+/// Eight views of one sprite 45 degrees apart: from the front, then around to its left
+pub type Rotations = [TextureId; 8];
+
+/// Color key for see-through texels (magenta), skipped when drawing sprites
+pub const TRANSPARENT: u32 = 0x00FF_00FF;
+
+/// RGBA texture stored row-major in the same packed BGRA8 layout as the framebuffer
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Texture {
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        assert_eq!(pixels.len(), width * height, "texture size mismatch");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Two-color checkerboard, `cell` texels per square
+    pub fn checkerboard(size: usize, cell: usize, a: u32, b: u32) -> Self {
+        let cell = cell.max(1);
+        let mut pixels = vec![0u32; size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let odd = ((x / cell) + (y / cell)) % 2 == 1;
+                pixels[y * size + x] = if odd { b } else { a };
+            }
+        }
+        Self::from_pixels(size, size, pixels)
+    }
+
+    /// Filled disc fading from `center` to `edge`, transparent outside the circle
+    pub fn disc(size: usize, center: u32, edge: u32) -> Self {
+        let r = size as f32 * 0.5;
+        let mut pixels = vec![TRANSPARENT; size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let dx = x as f32 + 0.5 - r;
+                let dy = y as f32 + 0.5 - r;
+                let t = (dx * dx + dy * dy).sqrt() / r;
+                if t <= 1.0 {
+                    pixels[y * size + x] = lerp_rgb(center, edge, t);
+                }
+            }
+        }
+        Self::from_pixels(size, size, pixels)
+    }
+
+    /// Mean color of the texels that aren't `TRANSPARENT`, e.g. for debris matching a sprite
+    pub fn average(&self) -> u32 {
+        let mut sum = [0u64; 3];
+        let mut count = 0u64;
+        for &p in self.pixels.iter().filter(|&&p| p != TRANSPARENT) {
+            sum[0] += u64::from((p >> 16) & 0xFF);
+            sum[1] += u64::from((p >> 8) & 0xFF);
+            sum[2] += u64::from(p & 0xFF);
+            count += 1;
+        }
+        let count = count.max(1);
+        ((sum[0] / count) << 16 | (sum[1] / count) << 8 | (sum[2] / count)) as u32
+    }
+
+    /// Smaller copies for sampling from far away, each half the size of the one before with
+    /// every texel the average of the four it covers. The chain stops before a side would
+    /// be odd, so texel `tx` of this texture is texel `tx >> level` of every level.
+    pub fn mip_chain(&self) -> Vec<Texture> {
+        let mut chain: Vec<Texture> = Vec::new();
+        loop {
+            let above = chain.last().unwrap_or(self);
+            if !above.width.is_multiple_of(2) || !above.height.is_multiple_of(2) {
+                return chain;
+            }
+            let (width, height) = (above.width / 2, above.height / 2);
+            let mut pixels = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let at = |dx: usize, dy: usize| {
+                        above.pixels[(2 * y + dy) * above.width + 2 * x + dx]
+                    };
+                    pixels.push(average4([at(0, 0), at(1, 0), at(0, 1), at(1, 1)]));
+                }
+            }
+            chain.push(Self::from_pixels(width, height, pixels));
+        }
+    }
+
+    /// Fetch a texel with wrap-around addressing, `tx`/`ty` in texels
+    #[inline]
+    pub fn texel(&self, tx: i32, ty: i32) -> u32 {
+        let x = tx.rem_euclid(self.width as i32) as usize;
+        let y = ty.rem_euclid(self.height as i32) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    /// Nearest-neighbor draw into `buf`, a `width` x `height` frame, with the top-left at
+    /// `at`, `scale` pixels to a texel, shaded by `light` 0..=256 and skipping transparent
+    /// texels
+    pub fn blit_scaled(
+        &self,
+        buf: &mut [u32],
+        width: usize,
+        height: usize,
+        at: [f32; 2],
+        scale: f32,
+        light: u32,
+    ) {
+        let x0 = at[0].max(0.0) as usize;
+        let y0 = at[1].max(0.0) as usize;
+        let x1 = ((at[0] + self.width as f32 * scale).ceil() as usize).min(width);
+        let y1 = ((at[1] + self.height as f32 * scale).ceil() as usize).min(height);
+        for y in y0..y1 {
+            let ty = ((y as f32 + 0.5 - at[1]) / scale) as usize;
+            if ty >= self.height {
+                continue;
+            }
+            for x in x0..x1 {
+                let tx = ((x as f32 + 0.5 - at[0]) / scale) as usize;
+                if tx >= self.width {
+                    continue;
+                }
+                let texel = self.pixels[ty * self.width + tx];
+                if texel != TRANSPARENT {
+                    buf[y * width + x] = shade(texel, light);
+                }
+            }
+        }
+    }
+}
+
+fn average4(texels: [u32; 4]) -> u32 {
+    let ch = |shift: u32| {
+        let sum: u32 = texels.iter().map(|t| (t >> shift) & 0xFF).sum();
+        ((sum + 2) / 4) << shift
+    };
+    ch(0) | ch(8) | ch(16)
+}
+
+#[inline]
+fn lerp_rgb(a: u32, b: u32, t: f32) -> u32 {
+    let ch = |shift: u32| {
+        let ca = ((a >> shift) & 0xFF) as f32;
+        let cb = ((b >> shift) & 0xFF) as f32;
+        ((ca + (cb - ca) * t).round() as u32) << shift
+    };
+    ch(0) | ch(8) | ch(16)
+}
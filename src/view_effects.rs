@@ -0,0 +1,69 @@
+// Camera motion that's felt rather than steered: the head bobbing while walking, leaning
+// into strafes and flinching when hurt. Only ever handed to the renderer as a
+// `ViewOffset`, so movement, aim and what gets saved or sent stay where the camera is.
+
+use crate::renderer::ViewOffset;
+use crate::weapon::Bob;
+
+// Eye drop at full walking speed, world units
+const BOB_HEIGHT: f32 = 0.05;
+// Lean at full strafing speed, radians, and how fast it follows the strafe, per second
+const LEAN: f32 = 0.03;
+const LEAN_EASE: f32 = 8.0;
+// A flinch at full strength drops the eye this far and tilts the head this many radians,
+// and it recovers this much of full strength per second
+const FLINCH_DROP: f32 = 0.1;
+const FLINCH_TILT: f32 = 0.06;
+const FLINCH_RECOVERY: f32 = 4.0;
+// Damage taken at once that flinches at full strength; less flinches less
+const FLINCH_DAMAGE: f32 = 20.0;
+
+#[derive(Debug, Default)]
+pub struct ViewEffects {
+    bob: Bob,         // as the weapon's, so the view model and the eye dip together
+    lean: f32,        // radians, following the strafe
+    flinch: f32,      // 0 ..= 1, strength of the last flinch as it wears off
+    flinch_side: f32, // 1 to tilt right, -1 left; alternates hit to hit
+}
+
+impl ViewEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advance by `dt` seconds after walking `distance` at `speed`, 0..=1 of full speed,
+    /// and strafing by `strafe`, -1 left ..= 1 right
+    pub fn update(&mut self, dt: f32, distance: f32, speed: f32, strafe: f32) {
+        self.bob.update(dt, distance, speed);
+        let ease = (LEAN_EASE * dt).min(1.0);
+        self.lean += (strafe.clamp(-1.0, 1.0) * LEAN - self.lean) * ease;
+        self.flinch = (self.flinch - FLINCH_RECOVERY * dt).max(0.0);
+    }
+
+    /// Jolt the view for `damage` just taken
+    pub fn flinch(&mut self, damage: f32) {
+        let strength = (damage / FLINCH_DAMAGE).clamp(0.0, 1.0);
+        if strength > self.flinch {
+            self.flinch = strength;
+            self.flinch_side = if self.flinch_side > 0.0 { -1.0 } else { 1.0 };
+        }
+    }
+
+    /// Settle at once, as after a teleport or respawn
+    pub fn reset(&mut self) {
+        *self = Self {
+            flinch_side: self.flinch_side,
+            ..Self::default()
+        };
+    }
+
+    /// Where the view is moved to this instant
+    pub fn offset(&self) -> ViewOffset {
+        // The drop eases in and out over the recovery rather than snapping back
+        let flinch = self.flinch * self.flinch * (3.0 - 2.0 * self.flinch);
+        ViewOffset {
+            z: -self.bob.down() * BOB_HEIGHT - flinch * FLINCH_DROP,
+            roll: self.lean + flinch * FLINCH_TILT * self.flinch_side,
+        }
+    }
+}
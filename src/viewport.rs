@@ -0,0 +1,95 @@
+// Views sharing one framebuffer: each camera draws into its own rectangle of the frame,
+// for two views side by side or a small picture-in-picture inset
+
+/// A rectangle of a framebuffer, in pixels from the top-left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    /// The whole of a `width` x `height` frame
+    pub fn full(width: usize, height: usize) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_full(&self, width: usize, height: usize) -> bool {
+        *self == Self::full(width, height)
+    }
+
+    /// Copy `src`, a `self.width` x `self.height` image, into this rectangle of `dst`, a
+    /// frame `dst_width` pixels wide
+    pub fn copy_into(&self, dst: &mut [u32], dst_width: usize, src: &[u32]) {
+        if self.width == 0 {
+            return;
+        }
+        for (row, src_row) in src.chunks_exact(self.width).take(self.height).enumerate() {
+            let start = (self.y + row) * dst_width + self.x;
+            dst[start..start + self.width].copy_from_slice(src_row);
+        }
+    }
+}
+
+// Inset size as a fraction of the frame, and its gap from the frame edge in pixels
+const INSET_SCALE: usize = 3;
+const INSET_MARGIN: usize = 4;
+
+/// How the frame is shared between the player's view and a second camera
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitLayout {
+    #[default]
+    Single, // the player's view only
+    Horizontal,       // player's view on top, second camera below
+    PictureInPicture, // second camera in a small inset at the top right
+}
+
+impl SplitLayout {
+    pub fn next(self) -> Self {
+        match self {
+            SplitLayout::Single => SplitLayout::Horizontal,
+            SplitLayout::Horizontal => SplitLayout::PictureInPicture,
+            SplitLayout::PictureInPicture => SplitLayout::Single,
+        }
+    }
+
+    /// The views of a `width` x `height` frame in drawing order, the player's first; later
+    /// ones may cover earlier ones
+    pub fn viewports(self, width: usize, height: usize) -> Vec<Viewport> {
+        let full = Viewport::full(width, height);
+        match self {
+            SplitLayout::Single => vec![full],
+            SplitLayout::Horizontal => {
+                let top = height / 2;
+                vec![
+                    Viewport {
+                        height: top,
+                        ..full
+                    },
+                    Viewport {
+                        y: top,
+                        height: height - top,
+                        ..full
+                    },
+                ]
+            }
+            SplitLayout::PictureInPicture => {
+                let (w, h) = (width / INSET_SCALE, height / INSET_SCALE);
+                let inset = Viewport {
+                    x: width.saturating_sub(w + INSET_MARGIN),
+                    y: INSET_MARGIN.min(height - h),
+                    width: w,
+                    height: h,
+                };
+                vec![full, inset]
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+// Voxel models for small props like trees, barrels and pickups, drawn in the sprite pass
+// instead of a billboard. Only the voxels with an open face are kept, by column, since
+// the ones inside can never be seen.
+
+use crate::renderer::pack_rgb;
+
+pub type ModelId = usize;
+
+const PLACEHOLDER_SIZE: usize = 4;
+
+/// One solid voxel: its layer up from the bottom and its color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Voxel {
+    pub z: u16,
+    pub color: u32,
+}
+
+/// `size[0]` voxels wide, `size[1]` deep front to back and `size[2]` tall. The model
+/// faces +y, the way its entity faces, with +x on its right.
+pub struct VoxelModel {
+    pub size: [usize; 3],
+    columns: Vec<Vec<Voxel>>, // by x + y * size[0], bottom up
+}
+
+impl VoxelModel {
+    /// Keep the voxels of `solid` (indexed x + size[0] * (y + size[1] * z), None where
+    /// empty) that could be seen
+    pub fn from_voxels(size: [usize; 3], solid: &[Option<u32>]) -> Self {
+        assert_eq!(
+            solid.len(),
+            size[0] * size[1] * size[2],
+            "voxel model size mismatch"
+        );
+        let at = |x: isize, y: isize, z: isize| {
+            let inside = (0..size[0] as isize).contains(&x)
+                && (0..size[1] as isize).contains(&y)
+                && (0..size[2] as isize).contains(&z);
+            inside && solid[x as usize + size[0] * (y as usize + size[1] * z as usize)].is_some()
+        };
+        let mut columns = vec![Vec::new(); size[0] * size[1]];
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    let Some(color) = solid[x + size[0] * (y + size[1] * z)] else {
+                        continue;
+                    };
+                    let (xi, yi, zi) = (x as isize, y as isize, z as isize);
+                    let buried = at(xi - 1, yi, zi)
+                        && at(xi + 1, yi, zi)
+                        && at(xi, yi - 1, zi)
+                        && at(xi, yi + 1, zi)
+                        && at(xi, yi, zi - 1)
+                        && at(xi, yi, zi + 1);
+                    if !buried {
+                        columns[x + size[0] * y].push(Voxel { z: z as u16, color });
+                    }
+                }
+            }
+        }
+        Self { size, columns }
+    }
+
+    /// Magenta and black cube standing in for a model that couldn't be loaded
+    pub fn placeholder() -> Self {
+        let n = PLACEHOLDER_SIZE;
+        let solid: Vec<Option<u32>> = (0..n * n * n)
+            .map(|i| {
+                let odd = (i % n + i / n % n + i / (n * n)) % 2 == 1;
+                Some(if odd {
+                    pack_rgb(0, 0, 0)
+                } else {
+                    pack_rgb(255, 0, 255)
+                })
+            })
+            .collect();
+        Self::from_voxels([n; 3], &solid)
+    }
+
+    /// The visible voxels of column (`x`, `y`), bottom up
+    #[inline]
+    pub fn column(&self, x: usize, y: usize) -> &[Voxel] {
+        &self.columns[x + self.size[0] * y]
+    }
+}
@@ -0,0 +1,44 @@
+// File hot-reload: a file's modification time is polled a couple of times a second, so
+// saving the loaded map or the config from an editor puts it into effect in place
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>, // None while the file can't be read
+    next_check: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            modified: modified(&path),
+            path,
+            next_check: Instant::now() + POLL_INTERVAL,
+        }
+    }
+
+    /// True once each time the file has been written since the last change seen. A file
+    /// that's briefly missing, as when an editor saves by replacing it, is not a change.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + POLL_INTERVAL;
+        let Some(current) = modified(&self.path) else {
+            return false;
+        };
+        let changed = self.modified != Some(current);
+        self.modified = Some(current);
+        changed
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,234 @@
+// First-person weapon: a view model drawn over the 3D view, bobbing as the player walks,
+// that fires hitscan shots through the raycast module
+
+use crate::camera::Camera;
+use crate::decals::{self, Decal};
+use crate::entity::{self, Behavior, EntityId, Sprite, Transform};
+use crate::raycast::{self, Ray};
+use crate::renderer::pack_rgb;
+use crate::texture::{TRANSPARENT, Texture, TextureId};
+use crate::world::{Blend, World};
+
+pub const RANGE: f32 = 64.0;
+pub const DAMAGE: u32 = 10;
+// Seconds between shots
+pub const COOLDOWN: f32 = 0.35;
+// Seconds the muzzle flash shows
+const FLASH_TIME: f32 = 0.06;
+
+// Wall puffs: sprite height, seconds they last, and how far they sit off the wall
+const PUFF_HEIGHT: f32 = 0.2;
+const PUFF_TIME: f32 = 0.3;
+const PUFF_OFFSET: f32 = 0.05;
+// Bullet holes, world units across
+const HOLE_SIZE: f32 = 0.08;
+
+// View model size as a fraction of the screen height
+const VIEW_HEIGHT: f32 = 0.35;
+// Bob in view model texels at full walking speed, and radians of bob per world unit walked
+const BOB_SIDE: f32 = 6.0;
+const BOB_DOWN: f32 = 4.0;
+const BOB_RATE: f32 = 4.0;
+// How fast the bob eases in and out when starting and stopping, per second
+const BOB_EASE: f32 = 6.0;
+// Texels the model kicks down when fired, recovering over the cooldown
+const RECOIL: f32 = 6.0;
+
+/// What a shot did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shot {
+    Missed,
+    Wall {
+        wall: usize,
+        point: [f32; 2],
+        z: f32, // height the shot struck at
+    },
+    Entity {
+        entity: EntityId,
+        point: [f32; 2],
+        damage: u32,
+        destroyed: bool, // a prop the shot broke
+    },
+}
+
+pub struct Weapon {
+    view: Texture,
+    flash: Texture,
+    puff_texture: Texture,
+    hole_texture: Texture,
+    puff: Option<TextureId>, // ids in the attached world
+    hole: Option<TextureId>,
+    cooldown: f32,
+    flash_time: f32,
+    bob: Bob,
+}
+
+/// The sway of walking, kept by the view model and by the camera alike so the two move
+/// in step
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bob {
+    phase: f32,
+    amount: f32, // 0 standing still ..= 1 walking at full speed
+}
+
+impl Bob {
+    /// Advance after walking `distance` at `speed`, 0..=1 of full speed, over `dt` seconds
+    pub fn update(&mut self, dt: f32, distance: f32, speed: f32) {
+        self.phase = (self.phase + distance * BOB_RATE) % std::f32::consts::TAU;
+        let ease = (BOB_EASE * dt).min(1.0);
+        self.amount += (speed.clamp(0.0, 1.0) - self.amount) * ease;
+    }
+
+    /// Sideways sway, -1 left ..= 1 right at full speed
+    pub fn side(&self) -> f32 {
+        self.phase.sin() * self.amount
+    }
+
+    /// Downward dip, 0 ..= 1 at full speed
+    pub fn down(&self) -> f32 {
+        self.phase.cos().abs() * self.amount
+    }
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Weapon {
+    pub fn new() -> Self {
+        Self {
+            view: pistol_texture(),
+            flash: Texture::disc(16, pack_rgb(255, 250, 200), pack_rgb(255, 150, 40)),
+            puff_texture: Texture::disc(16, pack_rgb(220, 220, 200), pack_rgb(120, 120, 110)),
+            hole_texture: Texture::disc(8, pack_rgb(15, 15, 15), pack_rgb(60, 55, 50)),
+            puff: None,
+            hole: None,
+            cooldown: 0.0,
+            flash_time: 0.0,
+            bob: Bob::default(),
+        }
+    }
+
+    /// Give `world` the textures the weapon spawns sprites with; call whenever the world
+    /// is replaced
+    pub fn attach(&mut self, world: &mut World) {
+        let mut add = |texture: &Texture| {
+            world.textures.push(Texture::from_pixels(
+                texture.width,
+                texture.height,
+                texture.pixels.clone(),
+            ));
+            Some(world.textures.len() - 1)
+        };
+        self.puff = add(&self.puff_texture);
+        self.hole = add(&self.hole_texture);
+    }
+
+    /// Advance timers and the bob; `speed` is how fast the player walked this tick,
+    /// 0..=1 of full speed
+    pub fn update(&mut self, dt: f32, distance: f32, speed: f32) {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        self.flash_time = (self.flash_time - dt).max(0.0);
+        self.bob.update(dt, distance, speed);
+    }
+
+    pub fn ready(&self) -> bool {
+        self.cooldown == 0.0
+    }
+
+    /// Shoot along the view direction; None while still cooling down from the last shot
+    pub fn fire(&mut self, world: &mut World, camera: &Camera) -> Option<Shot> {
+        if !self.ready() {
+            return None;
+        }
+        self.cooldown = COOLDOWN;
+        self.flash_time = FLASH_TIME;
+
+        let ray = Ray::from_camera(camera);
+        let hits = raycast::cast(world, &ray, RANGE);
+        if let Some(hit) = hits.entities.first() {
+            return Some(Shot::Entity {
+                entity: hit.entity,
+                point: ray.point_at(hit.distance),
+                damage: DAMAGE,
+                destroyed: entity::damage(world, hit.entity, DAMAGE),
+            });
+        }
+        let Some(hit) = hits.wall else {
+            return Some(Shot::Missed);
+        };
+
+        // Puff just in front of the wall, centered on the height the shot struck
+        if let Some(texture) = self.puff {
+            let pos = ray.point_at(hit.distance - PUFF_OFFSET);
+            let z = ray.z_at(hit.distance) - 0.5 * PUFF_HEIGHT;
+            let id = world.entities.spawn(Transform { pos, z, yaw: 0.0 });
+            world.entities.sprites[id] = Some(Sprite {
+                texture,
+                height: PUFF_HEIGHT,
+                scale: 1.0,
+                rotations: None,
+                model: None,
+                blend: Blend::Masked,
+            });
+            world.entities.behaviors[id] = Behavior::Expire { seconds: PUFF_TIME };
+        }
+        if let Some(texture) = self.hole {
+            let (u, front) = decals::wall_position(&world.walls[hit.wall], ray.origin, hit.point);
+            let decal = Decal {
+                u,
+                z: ray.z_at(hit.distance),
+                size: HOLE_SIZE,
+                texture,
+                blend: Blend::Masked,
+                front,
+            };
+            world.decals.place(hit.wall, decal);
+        }
+        Some(Shot::Wall {
+            wall: hit.wall,
+            point: hit.point,
+            z: ray.z_at(hit.distance),
+        })
+    }
+
+    /// Draw the view model at the bottom center of the frame, lit by `light_level`
+    pub fn draw(&self, buf: &mut [u32], width: usize, height: usize, light_level: f32) {
+        let scale = height as f32 * VIEW_HEIGHT / self.view.height as f32;
+        let light = (light_level.clamp(0.0, 1.0) * 256.0) as u32;
+        let bob_x = self.bob.side() * BOB_SIDE;
+        let bob_y = self.bob.down() * BOB_DOWN + RECOIL * self.cooldown / COOLDOWN;
+
+        let left = 0.5 * width as f32 - 0.5 * self.view.width as f32 * scale + bob_x * scale;
+        let top = height as f32 - self.view.height as f32 * scale + bob_y * scale;
+        if self.flash_time > 0.0 {
+            // Centered on the muzzle at the top of the barrel
+            let muzzle_x = left + 0.5 * self.view.width as f32 * scale;
+            let size = self.flash.width as f32 * scale;
+            let at = [muzzle_x - 0.5 * size, top - 0.5 * size];
+            self.flash.blit_scaled(buf, width, height, at, scale, 256);
+        }
+        self.view
+            .blit_scaled(buf, width, height, [left, top], scale, light);
+    }
+}
+
+// A pistol seen from behind: barrel pointing up the middle, slide and grip below
+fn pistol_texture() -> Texture {
+    const W: usize = 48;
+    const H: usize = 40;
+    let mut pixels = vec![TRANSPARENT; W * H];
+    let mut fill = |x0: usize, x1: usize, y0: usize, y1: usize, color: u32| {
+        for y in y0..y1 {
+            pixels[y * W + x0..y * W + x1].fill(color);
+        }
+    };
+    fill(20, 28, 0, 18, pack_rgb(70, 70, 80)); // barrel
+    fill(22, 24, 1, 17, pack_rgb(130, 130, 145)); // highlight along it
+    fill(14, 34, 16, 26, pack_rgb(90, 90, 100)); // slide
+    fill(16, 32, 26, 40, pack_rgb(95, 70, 50)); // grip
+    fill(18, 20, 28, 40, pack_rgb(125, 95, 70));
+    Texture::from_pixels(W, H, pixels)
+}
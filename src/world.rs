@@ -1,16 +1,682 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bsp::Bsp;
+use crate::decals::Decals;
+use crate::entity::{Behavior, Entities, EntityId, Prop, Sprite, Transform};
+use crate::events::{Event, EventQueue};
+use crate::grid::WallGrid;
+use crate::inventory::{Inventory, Key, Pickup};
+use crate::light_effects::LightEffect;
+use crate::particles::Particles;
+use crate::pvs::Pvs;
+use crate::rng::Rng;
+use crate::sector_effects::SectorEffect;
+use crate::texture::{Rotations, Texture, TextureId};
+use crate::voxel::{ModelId, VoxelModel};
+
+mod demo;
+pub mod loader;
+mod validate;
+
+pub use validate::{Diagnostic, Usage};
+
+// How far from the eye a wall can be used
+pub const USE_RANGE: f32 = 1.2;
+
+static NEXT_WORLD_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct Sector {
     pub floor_z: f32,
     pub ceiling_z: f32,
+    pub floor_color: u32, // drawn when the flat has no texture
+    pub ceiling_color: u32,
+    pub floor_texture: Option<TextureId>,
+    pub ceiling_texture: Option<TextureId>,
+    pub flat_offset: [f32; 2], // floor and ceiling texture shift in world units
+    pub flat_angle: f32,       // floor and ceiling texture rotation, radians
+    pub flat_scroll: [f32; 2], // world units per second the flat textures move, e.g. a conveyor
+    pub light_level: f32,      // 0.0 (black) ..= 1.0 (full bright)
+    pub light_color: u32,      // multiplied into everything lit in the sector; white for none
+    pub liquid: Option<Liquid>, // water filling the sector up to a surface
+    pub slab: Option<Slab>,    // a bridge or ledge across it, stood on and walked under
+    pub material: Material,    // what the floor sounds like underfoot
+    pub specials: Vec<SectorSpecial>, // ongoing effects on whoever stands in the sector
+}
+
+impl Sector {
+    /// Health lost per second standing on the floor, or in the liquid if there is one
+    pub fn damage_per_second(&self) -> f32 {
+        self.specials
+            .iter()
+            .map(|special| match *special {
+                SectorSpecial::Damage { per_second } => per_second,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// Velocity the sector carries the player along with, on top of their own: conveyors
+    /// only underfoot, wind anywhere in the sector
+    pub fn push(&self, on_floor: bool) -> [f32; 2] {
+        self.specials
+            .iter()
+            .fold([0.0, 0.0], |[x, y], special| match *special {
+                SectorSpecial::Conveyor { velocity } if on_floor => {
+                    [x + velocity[0], y + velocity[1]]
+                }
+                SectorSpecial::Wind { velocity } => [x + velocity[0], y + velocity[1]],
+                _ => [x, y],
+            })
+    }
+
+    /// Grip of the floor, 1 for normal footing and less for slippery ones
+    pub fn friction(&self) -> f32 {
+        self.specials
+            .iter()
+            .map(|special| match *special {
+                SectorSpecial::Friction { factor } => factor,
+                _ => 1.0,
+            })
+            .fold(1.0, f32::min)
+    }
+
+    /// Teleporter catching whoever enters the sector, if it has one
+    pub fn teleport(&self) -> Option<Teleport> {
+        self.specials.iter().find_map(|special| match *special {
+            SectorSpecial::Teleport(teleport) => Some(teleport),
+            _ => None,
+        })
+    }
+}
+
+/// What a sector's floor is made of, for the sound of walking and landing on it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Material {
+    #[default]
+    Stone,
+    Metal,
+    Water, // shallow enough to walk through; deep liquid sounds like this anyway
+}
+
+impl Material {
+    pub fn footstep_sound(self) -> &'static str {
+        match self {
+            Material::Stone => "footstep",
+            Material::Metal => "footstep_metal",
+            Material::Water => "footstep_water",
+        }
+    }
+
+    pub fn landing_sound(self) -> &'static str {
+        match self {
+            Material::Stone => "land",
+            Material::Metal => "land_metal",
+            Material::Water => "splash",
+        }
+    }
+}
+
+/// What a sector does to the player standing in it, every tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SectorSpecial {
+    Damage { per_second: f32 },      // lava, slime and the like
+    Conveyor { velocity: [f32; 2] }, // world units per second, moving what stands on the floor
+    Wind { velocity: [f32; 2] },     // as Conveyor, in the air too
+    Friction { factor: f32 },        // below 1 slides like ice, speeding up and stopping slowly
+    Teleport(Teleport),              // on entering rather than every tick
+}
+
+/// A line that acts when walked across instead of (or as well as) when used: opening doors
+/// ahead, springing ambushes or ending the map
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Walkover {
+    Once,   // then it's spent
+    Repeat, // every crossing
+}
+
+/// Sends whoever it catches to the destination tagged `tag`, facing its way
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Teleport {
+    pub tag: u32,
+    pub keep_velocity: bool, // carry on as fast, turned along with them; else arrive standing
+    pub flash: bool,         // a burst of light and a sound at both ends
+}
+
+impl Teleport {
+    /// Velocity on arriving facing `to_yaw` of something that left facing `from_yaw`
+    pub fn arrival_velocity(&self, velocity: [f32; 2], from_yaw: f32, to_yaw: f32) -> [f32; 2] {
+        if !self.keep_velocity {
+            return [0.0, 0.0];
+        }
+        // Yaw turns clockwise, so turning by it takes (sin y, cos y) to (sin, cos) of y + turn
+        let (sin, cos) = (to_yaw - from_yaw).sin_cos();
+        [
+            velocity[0] * cos + velocity[1] * sin,
+            velocity[1] * cos - velocity[0] * sin,
+        ]
+    }
+}
+
+/// Where teleporters tagged `tag` send things
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Destination {
+    pub pos: [f32; 2],
+    pub yaw: f32, // radians, which way arrivals face
+    pub tag: u32,
+}
+
+/// A solid slab across a whole sector between the floor and ceiling of sector `control`,
+/// e.g. a bridge. Its top looks like the control sector's ceiling, its underside like its
+/// floor, and its edges show `texture`. Moving the control sector moves the slab.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Slab {
+    pub control: usize,
+    pub texture: TextureId,
+}
+
+/// A pool of liquid in a sector: swum through below `surface_z`, which is drawn as a
+/// translucent `color` plane from above and tints the view from below
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Liquid {
+    pub surface_z: f32,
+    pub color: u32,
 }
 
 pub struct Wall {
-    pub start: [f32; 2], // (x, y) start point in world space
-    pub end: [f32; 2],   // (x, y) end point in world space
-    pub front_sector: usize,
+    pub start: [f32; 2],            // (x, y) start point in world space
+    pub end: [f32; 2],              // (x, y) end point in world space
+    pub front_sector: usize,        // sector on the left of start -> end
     pub back_sector: Option<usize>, // None if one-sided wall
+    pub texture: TextureId,
+    pub tag: u32, // sector effects this wall's special acts on; 0 for none
+    pub special: Special,
+    pub mid: Option<MidTexture>,    // two-sided walls only
+    pub scroll: [f32; 2], // world units per second the texture moves from start to end and down
+    pub offset: [f32; 2], // world units the texture is shifted by, as scroll
+    pub peg_upper: bool, // upper step texture sits on the back ceiling instead of hanging from ours
+    pub peg_lower: bool, // lower step and solid wall textures sit on our floor
+    pub teleport: Option<Teleport>, // catches whoever crosses from the front to the back
+    pub walkover: Option<Walkover>, // fires special and tag when the player crosses either way
+}
+
+/// See-through texture hung in a portal's opening, drawn after the solid walls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MidTexture {
+    pub texture: TextureId, // hangs from the top of the opening, not repeated vertically
+    pub blend: Blend,
+}
+
+/// How a mid texture or sprite covers what's behind it; `TRANSPARENT` texels are always
+/// skipped
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Blend {
+    #[default]
+    Masked, // opaque texels replace the background, e.g. grates and fences
+    Translucent, // 50% mix with the background
+    Additive,    // added to the background, e.g. force fields
+}
+
+/// Distance fog: surfaces blend toward `color` the farther they are from the camera
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fog {
+    pub color: u32,
+    pub falloff: FogFalloff,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FogFalloff {
+    Linear { start: f32, end: f32 }, // clear up to `start`, solid fog from `end` on
+    Exponential { density: f32 },    // 1 - e^(-density * depth)
+}
+
+impl Fog {
+    /// 0 (clear) ..= 1 (only fog) at camera-space `depth`
+    pub fn amount(&self, depth: f32) -> f32 {
+        match self.falloff {
+            FogFalloff::Linear { start, end } => {
+                ((depth - start) / (end - start).max(f32::EPSILON)).clamp(0.0, 1.0)
+            }
+            FogFalloff::Exponential { density } => 1.0 - (-density * depth.max(0.0)).exp(),
+        }
+    }
+}
+
+/// What happens when the player uses a wall
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Special {
+    #[default]
+    None,
+    Door, // triggers the effects tagged like the wall
+    LockedDoor {
+        key: Key,
+    }, // as Door, for a player holding `key`
+    Switch {
+        alt_texture: TextureId,
+        #[serde(default)] // absent from saves made before switches could be used up
+        once: bool, // keeps alt_texture and does nothing more after the first use
+        #[serde(default)]
+        cooldown: f32, // seconds before a repeatable switch flips back and can be used again
+    }, // as Door, and swaps texture with alt_texture
+    Exit, // ends the map, moving on to the next one in the campaign
+}
+
+/// A repeatable switch showing its used texture until it flips back
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SwitchReset {
+    pub wall: usize,
+    pub left: f32, // seconds
+}
+
+/// A used wall, returned by `World::use_line` for the game to act on
+#[derive(Clone, Copy, Debug)]
+pub struct UseEvent {
+    pub wall: usize,
+    pub tag: u32,
+    pub special: Special,
+}
+
+/// Map placement of an entity, spawned when the world is built
+pub struct Thing {
+    pub pos: [f32; 2], // (x, y) position in world space
+    pub z: f32,        // world z of the sprite's bottom edge
+    pub height: f32,   // world units tall at scale 1.0, width follows the texture aspect
+    pub texture: TextureId,
+    pub scale: f32,
+    pub radius: f32, // collision radius against walls; 0 for none
+    pub behavior: Behavior,
+    pub prop: Option<Prop>, // destructible, and solid within `radius` if so marked
+    pub pickup: Option<Pickup>, // given to the player who walks over it
+    pub yaw: f32,           // radians, which way it faces
+    pub rotations: Option<Rotations>, // views from around it, see `Sprite`
+    pub model: Option<ModelId>, // voxel model drawn instead of the texture
+    pub blend: Blend,
+}
+
+/// A texture showing the world from a fixed camera, e.g. a security monitor's screen; the
+/// renderer redraws it every `interval` frames
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    pub texture: TextureId, // drawn over, at the texture's own size
+    pub pos: [f32; 2],
+    pub yaw: f32, // radians
+    pub eye_z: f32,
+    pub interval: u32,
+}
+
+pub struct PlayerStart {
+    pub pos: [f32; 2],
+    pub yaw: f32, // radians
 }
 
 pub struct World {
     pub sectors: Vec<Sector>,
     pub walls: Vec<Wall>,
+    pub textures: Vec<Texture>,
+    pub things: Vec<Thing>,
+    pub entities: Entities, // live game objects, starting with one per thing
+    pub particles: Particles,
+    pub decals: Decals, // bullet holes and the like, per wall
+    pub events: EventQueue,
+    pub player_start: PlayerStart,
+    pub effects: Vec<SectorEffect>, // doors, lifts and crushers, moving sector heights over time
+    pub switch_resets: Vec<SwitchReset>, // switches cooling down after use
+    pub lights: Vec<LightEffect>,   // flickering and pulsing sectors
+    pub monitors: Vec<Monitor>,
+    pub models: Vec<VoxelModel>, // for things drawn as voxels, by `ModelId`
+    pub destinations: Vec<Destination>, // teleporter targets
+    pub sky: Option<TextureId>,  // panorama behind open space, flat color if None
+    pub music: Option<String>,   // track name looked up by the audio module
+    pub script: Option<String>,  // map logic, looked up by name like the music
+    pub fog: Option<Fog>,        // the sky is left clear so maps can pick
+    pub time: f32,               // seconds the map has been played, animating scrolling textures
+    pub rng: Rng,                // for what plays out by chance, saved with the rest
+    pub bsp: Bsp,                // built from `walls`, rebuild if wall geometry changes
+    pub grid: WallGrid,          // likewise, for finding the walls near a point or line
+    pub pvs: Pvs,                // likewise, for sectors the renderer can skip
+    pub(crate) id: u64,          // unique per world built, for caches derived from it
+}
+
+impl World {
+    pub fn new(
+        sectors: Vec<Sector>,
+        walls: Vec<Wall>,
+        textures: Vec<Texture>,
+        things: Vec<Thing>,
+        player_start: PlayerStart,
+        effects: Vec<SectorEffect>,
+    ) -> Self {
+        let bsp = Bsp::build(&walls);
+        let grid = WallGrid::build(&walls);
+        let pvs = Pvs::build(&walls, sectors.len());
+        let decals = Decals::new(walls.len());
+        let mut rng = Rng::new(map_seed(&sectors, &walls));
+        let particles = Particles::new(rng.fork());
+
+        let mut entities = Entities::default();
+        for thing in &things {
+            spawn_thing(&mut entities, thing, thing.pos, thing.z);
+        }
+
+        Self {
+            sectors,
+            walls,
+            textures,
+            things,
+            entities,
+            particles,
+            decals,
+            events: EventQueue::default(),
+            player_start,
+            effects,
+            switch_resets: Vec::new(),
+            lights: Vec::new(),
+            monitors: Vec::new(),
+            models: Vec::new(),
+            destinations: Vec::new(),
+            sky: None,
+            music: None,
+            script: None,
+            fog: None,
+            time: 0.0,
+            rng,
+            bsp,
+            grid,
+            pvs,
+            id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Sector containing `p`, by ray-crossing parity over the walls bounding each sector
+    pub fn sector_at(&self, p: [f32; 2]) -> Option<usize> {
+        // Walls the ray from p toward +x crosses; only those in p's grid row can
+        let crossed = || {
+            self.grid
+                .walls_right_of(p)
+                .map(|i| &self.walls[i])
+                .filter(move |wall| {
+                    let (a, b) = (wall.start, wall.end);
+                    if (a[1] > p[1]) == (b[1] > p[1]) {
+                        return false;
+                    }
+                    let t = (p[1] - a[1]) / (b[1] - a[1]);
+                    p[0] < a[0] + t * (b[0] - a[0])
+                })
+        };
+        let sides = |wall: &Wall| {
+            let back = wall.back_sector.filter(|&b| b != wall.front_sector);
+            std::iter::once(wall.front_sector).chain(back)
+        };
+        // Inside the lowest-numbered sector crossed an odd number of times; counting each
+        // one's crossings again keeps these lookups, made every tick, from allocating
+        crossed()
+            .flat_map(sides)
+            .filter(|&s| {
+                let crossings = crossed().filter(|wall| sides(wall).any(|t| t == s)).count();
+                !crossings.is_multiple_of(2)
+            })
+            .min()
+    }
+
+    /// Teleporter line crossed from its front side by moving from `from` to `to`, if any
+    pub fn crossed_teleport(&self, from: [f32; 2], to: [f32; 2]) -> Option<Teleport> {
+        self.grid.walls_along(from, to).find_map(|i| {
+            let wall = &self.walls[i];
+            let teleport = wall.teleport?;
+            (crossing(wall, from, to) == Some(true)).then_some(teleport)
+        })
+    }
+
+    /// Fire the walk-over lines the player crossed, either way, by moving from `from` to
+    /// `to`, publishing `Event::LineCrossed` for each; lines that work once are spent
+    pub fn cross_lines(&mut self, from: [f32; 2], to: [f32; 2]) {
+        if from == to {
+            return;
+        }
+        for i in self.grid.walls_along(from, to) {
+            let wall = &mut self.walls[i];
+            let Some(walkover) = wall.walkover else {
+                continue;
+            };
+            if crossing(wall, from, to).is_none() {
+                continue;
+            }
+            if walkover == Walkover::Once {
+                wall.walkover = None;
+            }
+            self.events.publish(Event::LineCrossed {
+                wall: i,
+                tag: wall.tag,
+                special: wall.special,
+            });
+        }
+    }
+
+    /// Destination teleporters tagged `tag` send things to, the first if there are several
+    pub fn destination(&self, tag: u32) -> Option<Destination> {
+        self.destinations.iter().find(|d| d.tag == tag).copied()
+    }
+
+    /// Where `teleport` sends the player (`entity` None) or an entity leaving from `from`,
+    /// flashing and publishing `Event::Teleported` on the way; None, leaving it be, if the
+    /// map has no destination with its tag
+    pub fn teleport(
+        &mut self,
+        teleport: Teleport,
+        from: [f32; 2],
+        entity: Option<EntityId>,
+    ) -> Option<Destination> {
+        let destination = self.destination(teleport.tag)?;
+        if teleport.flash {
+            for pos in [from, destination.pos] {
+                let floor_z = self.sector_at(pos).map_or(0.0, |s| self.sectors[s].floor_z);
+                self.particles.flash(pos, floor_z);
+            }
+        }
+        self.events.publish(Event::Teleported {
+            entity,
+            from,
+            to: destination.pos,
+            flash: teleport.flash,
+        });
+        Some(destination)
+    }
+
+    /// World z of sector `s`'s slab bottom and top, if it has one
+    pub fn slab_span(&self, s: usize) -> Option<(f32, f32)> {
+        let control = &self.sectors[self.sectors[s].slab?.control];
+        Some((control.floor_z, control.ceiling_z))
+    }
+
+    /// Floor and ceiling of sector `s` around feet at height `z`: from above a slab's middle
+    /// its top is the floor, from below it its underside is the ceiling
+    pub fn floor_ceiling_at(&self, s: usize, z: f32) -> (f32, f32) {
+        let sector = &self.sectors[s];
+        match self.slab_span(s) {
+            Some((bottom, top)) if z >= 0.5 * (bottom + top) => (top, sector.ceiling_z),
+            Some((bottom, _)) => (sector.floor_z, bottom),
+            None => (sector.floor_z, sector.ceiling_z),
+        }
+    }
+
+    /// Another of map thing `thing` at `pos`, as high above the floor there as the thing is
+    /// above its own; None if there's no such thing or `pos` is outside the map
+    pub fn spawn_thing(&mut self, thing: usize, pos: [f32; 2]) -> Option<EntityId> {
+        let thing = self.things.get(thing)?;
+        let floor_at = |p| self.sector_at(p).map(|s| self.sectors[s].floor_z);
+        let z = floor_at(pos)? + thing.z - floor_at(thing.pos).unwrap_or(thing.z);
+        Some(spawn_thing(&mut self.entities, thing, pos, z))
+    }
+
+    /// Nearest wall crossed by the ray from `origin` along unit `dir` within `max_dist`,
+    /// skipping walls `stops` rejects; returns the wall index and the distance
+    pub fn first_wall_hit(
+        &self,
+        origin: [f32; 2],
+        dir: [f32; 2],
+        max_dist: f32,
+        stops: impl Fn(&Wall) -> bool,
+    ) -> Option<(usize, f32)> {
+        let end = [origin[0] + dir[0] * max_dist, origin[1] + dir[1] * max_dist];
+        self.grid
+            .walls_along(origin, end)
+            .filter(|&i| stops(&self.walls[i]))
+            .filter_map(|i| {
+                let w = &self.walls[i];
+                ray_segment(origin, dir, w.start, w.end).map(|t| (i, t))
+            })
+            .filter(|&(_, t)| t <= max_dist)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Use whatever the player at `pos` facing `yaw` is pointing at, publishing
+    /// `Event::WallUsed` if it was a special wall. Open portals let the ray through, so a
+    /// door or switch has to be the first thing in reach. A locked door without its key in
+    /// `inventory` publishes `Event::DoorLocked` instead, and a switch still cooling down
+    /// does nothing.
+    pub fn use_line(&mut self, pos: [f32; 2], yaw: f32, inventory: &Inventory) -> Option<UseEvent> {
+        let dir = [yaw.sin(), yaw.cos()];
+        let stops = |w: &Wall| w.back_sector.is_none() || w.special != Special::None;
+        let (i, _) = self.first_wall_hit(pos, dir, USE_RANGE, stops)?;
+
+        let wall = &mut self.walls[i];
+        if let Special::LockedDoor { key } = wall.special
+            && !inventory.has_key(key)
+        {
+            self.events.publish(Event::DoorLocked { wall: i, key });
+            return None;
+        }
+        if wall.special == Special::None || self.switch_resets.iter().any(|r| r.wall == i) {
+            return None;
+        }
+        let (tag, special) = (wall.tag, wall.special);
+        if let Special::Switch {
+            alt_texture,
+            once,
+            cooldown,
+        } = &mut wall.special
+        {
+            std::mem::swap(&mut wall.texture, alt_texture);
+            if *once {
+                wall.special = Special::None;
+            } else if *cooldown > 0.0 {
+                self.switch_resets.push(SwitchReset {
+                    wall: i,
+                    left: *cooldown,
+                });
+            }
+        }
+        self.events.publish(Event::WallUsed {
+            wall: i,
+            tag,
+            special,
+        });
+        Some(UseEvent {
+            wall: i,
+            tag,
+            special,
+        })
+    }
+
+    /// Count down the switches used recently, flipping each back to its first texture
+    /// once its cooldown is over
+    pub fn update_switches(&mut self, dt: f32) {
+        let walls = &mut self.walls;
+        self.switch_resets.retain_mut(|reset| {
+            reset.left -= dt;
+            if reset.left > 0.0 {
+                return true;
+            }
+            let wall = &mut walls[reset.wall];
+            if let Special::Switch { alt_texture, .. } = &mut wall.special {
+                std::mem::swap(&mut wall.texture, alt_texture);
+            }
+            false
+        });
+    }
+}
+
+fn spawn_thing(entities: &mut Entities, thing: &Thing, pos: [f32; 2], z: f32) -> EntityId {
+    let id = entities.spawn(Transform {
+        pos,
+        z,
+        yaw: thing.yaw,
+    });
+    entities.sprites[id] = Some(Sprite {
+        texture: thing.texture,
+        height: thing.height,
+        scale: thing.scale,
+        rotations: thing.rotations,
+        model: thing.model,
+        blend: thing.blend,
+    });
+    entities.colliders[id] = (thing.radius > 0.0).then_some(thing.radius);
+    entities.behaviors[id] = thing.behavior;
+    entities.props[id] = thing.prop;
+    entities.pickups[id] = thing.pickup;
+    id
+}
+
+/// As `World::sector_at`, for walls not yet built into a world, e.g. while importing one
+pub fn sector_containing(walls: &[Wall], sector_count: usize, p: [f32; 2]) -> Option<usize> {
+    (0..sector_count).find(|&s| {
+        let mut inside = false;
+        for wall in walls {
+            if wall.front_sector != s && wall.back_sector != Some(s) {
+                continue;
+            }
+            let (a, b) = (wall.start, wall.end);
+            // Does a ray from p toward +x cross this wall?
+            if (a[1] > p[1]) != (b[1] > p[1]) {
+                let t = (p[1] - a[1]) / (b[1] - a[1]);
+                if p[0] < a[0] + t * (b[0] - a[0]) {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    })
+}
+
+/// Distance along unit `dir` from `origin` to segment `a`-`b`, if the ray crosses it
+pub fn ray_segment(origin: [f32; 2], dir: [f32; 2], a: [f32; 2], b: [f32; 2]) -> Option<f32> {
+    let e = [b[0] - a[0], b[1] - a[1]];
+    let denom = cross(dir, e);
+    if denom.abs() < f32::EPSILON {
+        return None; // parallel
+    }
+    let w = [a[0] - origin[0], a[1] - origin[1]];
+    let t = cross(w, e) / denom; // along the ray
+    let u = cross(w, dir) / denom; // along the segment
+    (t >= 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+// Seed for a new world's random stream, from its geometry, so every run of the same map
+// plays out alike and different maps differ; saves carry the stream on from there
+fn map_seed(sectors: &[Sector], walls: &[Wall]) -> u64 {
+    let heights = sectors.iter().flat_map(|s| [s.floor_z, s.ceiling_z]);
+    let ends = walls.iter().flat_map(|w| [w.start, w.end]).flatten();
+    // FNV-1a over the bits of every height and wall end
+    heights.chain(ends).fold(0xCBF2_9CE4_8422_2325, |h, v| {
+        (h ^ u64::from(v.to_bits())).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+// Whether moving from `from` to `to` crosses `wall` between its ends: Some(true) from the
+// front (left) to the back, Some(false) the other way, None if it doesn't
+#[inline]
+fn crossing(wall: &Wall, from: [f32; 2], to: [f32; 2]) -> Option<bool> {
+    let step = [to[0] - from[0], to[1] - from[1]];
+    let along = [wall.end[0] - wall.start[0], wall.end[1] - wall.start[1]];
+    let side = |p: [f32; 2]| cross(along, [p[0] - wall.start[0], p[1] - wall.start[1]]) >= 0.0;
+    let across = |p: [f32; 2]| cross(step, [p[0] - from[0], p[1] - from[1]]);
+    let between = across(wall.start) * across(wall.end) <= 0.0;
+    (between && side(from) != side(to)).then_some(side(from))
+}
+
+#[inline]
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
 }
@@ -0,0 +1,221 @@
+use crate::entity::Behavior;
+use crate::renderer::{WHITE, pack_rgb};
+use crate::sector_effects::{EffectKind, SectorEffect};
+use crate::texture::Texture;
+use crate::world::{Blend, Material, PlayerStart, Sector, Special, Thing, Wall, World};
+
+impl World {
+    /// Built-in test map, the same layout as `maps/demo.toml`
+    pub fn demo() -> Self {
+        // Two rooms joined by a short corridor (a lift), with a pillar in the far room
+        // and a closet behind a door south of the start
+        let sectors = vec![
+            Sector {
+                floor_z: 0.0,
+                ceiling_z: 3.0,
+                floor_color: pack_rgb(70, 70, 70),
+                ceiling_color: pack_rgb(110, 110, 130),
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: 0.9,
+                light_color: WHITE,
+                liquid: None,
+                material: Material::default(),
+                specials: Vec::new(),
+            },
+            Sector {
+                floor_z: 0.3,
+                ceiling_z: 2.4,
+                floor_color: pack_rgb(90, 70, 50),
+                ceiling_color: pack_rgb(60, 60, 90),
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: 0.6,
+                light_color: WHITE,
+                liquid: None,
+                material: Material::Metal,
+                specials: Vec::new(),
+            },
+            Sector {
+                floor_z: 0.0,
+                ceiling_z: 4.0,
+                floor_color: pack_rgb(50, 80, 50),
+                ceiling_color: pack_rgb(120, 100, 100),
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: 0.75,
+                light_color: WHITE,
+                liquid: None,
+                material: Material::default(),
+                specials: Vec::new(),
+            },
+            // Door, closed: ceiling down on the floor
+            Sector {
+                floor_z: 0.0,
+                ceiling_z: 0.0,
+                floor_color: pack_rgb(70, 70, 70),
+                ceiling_color: pack_rgb(90, 90, 110),
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: 0.8,
+                light_color: WHITE,
+                liquid: None,
+                material: Material::default(),
+                specials: Vec::new(),
+            },
+            // Closet
+            Sector {
+                floor_z: 0.0,
+                ceiling_z: 2.5,
+                floor_color: pack_rgb(60, 50, 40),
+                ceiling_color: pack_rgb(80, 80, 80),
+                floor_texture: None,
+                ceiling_texture: None,
+                flat_offset: [0.0, 0.0],
+                flat_angle: 0.0,
+                flat_scroll: [0.0, 0.0],
+                slab: None,
+                light_level: 0.5,
+                light_color: WHITE,
+                liquid: None,
+                material: Material::default(),
+                specials: Vec::new(),
+            },
+        ];
+        let textures = [
+            (pack_rgb(200, 200, 200), pack_rgb(150, 150, 150)),
+            (pack_rgb(180, 180, 250), pack_rgb(130, 130, 200)),
+            (pack_rgb(250, 180, 180), pack_rgb(200, 130, 130)),
+            (pack_rgb(180, 250, 180), pack_rgb(130, 200, 130)),
+        ]
+        .into_iter()
+        .map(|(a, b)| Texture::checkerboard(64, 16, a, b))
+        .chain([Texture::disc(
+            32,
+            pack_rgb(255, 240, 180),
+            pack_rgb(200, 120, 40),
+        )])
+        .collect();
+
+        let wall = |start, end, front_sector, back_sector, texture| Wall {
+            start,
+            end,
+            front_sector,
+            back_sector,
+            texture,
+            tag: 0,
+            special: Special::None,
+            mid: None,
+            scroll: [0.0, 0.0],
+            offset: [0.0, 0.0],
+            peg_upper: false,
+            peg_lower: false,
+            teleport: None,
+            walkover: None,
+        };
+        let walls = vec![
+            // Room 0
+            wall([-3.0, -3.0], [-0.5, -3.0], 0, None, 0),
+            Wall {
+                tag: 1,
+                special: Special::Door,
+                ..wall([-0.5, -3.0], [0.5, -3.0], 0, Some(3), 1)
+            },
+            wall([0.5, -3.0], [3.0, -3.0], 0, None, 0),
+            wall([3.0, -3.0], [3.0, 6.0], 0, None, 0),
+            wall([3.0, 6.0], [1.0, 6.0], 0, None, 0),
+            wall([1.0, 6.0], [-1.0, 6.0], 0, Some(1), 1),
+            wall([-1.0, 6.0], [-3.0, 6.0], 0, None, 0),
+            wall([-3.0, 6.0], [-3.0, -3.0], 0, None, 0),
+            // Corridor
+            wall([1.0, 6.0], [1.0, 9.0], 1, None, 1),
+            wall([1.0, 9.0], [-1.0, 9.0], 1, Some(2), 1),
+            wall([-1.0, 9.0], [-1.0, 6.0], 1, None, 1),
+            // Room 2
+            wall([-4.0, 9.0], [-1.0, 9.0], 2, None, 2),
+            wall([1.0, 9.0], [4.0, 9.0], 2, None, 2),
+            wall([4.0, 9.0], [4.0, 16.0], 2, None, 2),
+            wall([4.0, 16.0], [-4.0, 16.0], 2, None, 2),
+            wall([-4.0, 16.0], [-4.0, 9.0], 2, None, 2),
+            // Pillar in room 2
+            wall([-1.0, 12.0], [-1.0, 14.0], 2, None, 3),
+            wall([-1.0, 14.0], [1.0, 14.0], 2, None, 3),
+            wall([1.0, 14.0], [1.0, 12.0], 2, None, 3),
+            wall([1.0, 12.0], [-1.0, 12.0], 2, None, 3),
+            // Door jambs
+            wall([-0.5, -3.0], [-0.5, -3.25], 3, None, 1),
+            wall([0.5, -3.25], [0.5, -3.0], 3, None, 1),
+            // Closet
+            wall([1.5, -3.25], [0.5, -3.25], 4, None, 0),
+            Wall {
+                tag: 1,
+                special: Special::Door,
+                ..wall([0.5, -3.25], [-0.5, -3.25], 4, Some(3), 1)
+            },
+            wall([-0.5, -3.25], [-1.5, -3.25], 4, None, 0),
+            wall([-1.5, -3.25], [-1.5, -5.25], 4, None, 0),
+            wall([-1.5, -5.25], [1.5, -5.25], 4, None, 0),
+            wall([1.5, -5.25], [1.5, -3.25], 4, None, 0),
+        ];
+
+        let effects = vec![
+            SectorEffect::new(EffectKind::Door, 3, 1, 0.0, 2.5),
+            SectorEffect::new(EffectKind::Lift, 1, 0, 0.3, 0.0),
+        ];
+
+        // Glowing orbs: one wandering the first room, a few bobbing around the pillar
+        let orb = |pos, radius, behavior| Thing {
+            pos,
+            z: 1.0,
+            height: 0.5,
+            texture: 4,
+            scale: 1.0,
+            radius,
+            behavior,
+            prop: None,
+            pickup: None,
+            yaw: 0.0,
+            rotations: None,
+            model: None,
+            blend: Blend::Masked,
+        };
+        let bob = Behavior::Bob {
+            amplitude: 0.15,
+            speed: 2.0,
+        };
+        let things = vec![
+            orb([1.5, 3.0], 0.25, Behavior::Wander { speed: 0.5 }),
+            orb([-2.5, 11.0], 0.0, bob),
+            orb([2.5, 11.0], 0.0, bob),
+            orb([0.0, 15.0], 0.0, bob),
+        ];
+
+        World::new(
+            sectors,
+            walls,
+            textures,
+            things,
+            PlayerStart {
+                pos: [0.0, 0.0],
+                yaw: 0.0,
+            },
+            effects,
+        )
+    }
+}
@@ -0,0 +1,1149 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+use toml::Spanned;
+use tracing::{info_span, warn};
+
+use crate::assets::{DEFAULT_ASSET_DIR, TextureManager};
+use crate::entity::{Behavior, Prop};
+use crate::formats::build::{self, BuildError};
+use crate::formats::udmf::{self, UdmfError};
+use crate::formats::vox;
+use crate::inventory::{Key, Pickup};
+use crate::light_effects::{LightEffect, LightKind};
+use crate::renderer::pack_rgb;
+use crate::sector_effects::{EffectKind, SectorEffect};
+use crate::texture::{Texture, TextureId};
+use crate::voxel::VoxelModel;
+use crate::world::{
+    Blend, Destination, Diagnostic, Fog, FogFalloff, Liquid, Material, MidTexture, Monitor,
+    PlayerStart, Sector, SectorSpecial, Slab, Special, Teleport, Thing, Walkover, Wall, World,
+};
+
+// On-disk layout of a TOML map file. Sectors and walls keep their source spans
+// so validation errors can point at the offending line.
+#[derive(Deserialize)]
+struct MapFile {
+    player: PlayerDef,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    sectors: Vec<Spanned<SectorDef>>,
+    walls: Vec<Spanned<WallDef>>,
+    #[serde(default)]
+    models: Vec<String>, // e.g. "barrel" for assets/barrel.vox
+    #[serde(default)]
+    things: Vec<Spanned<ThingDef>>,
+    #[serde(default)]
+    destinations: Vec<DestinationDef>,
+    #[serde(default)]
+    effects: Vec<Spanned<EffectDef>>,
+    sky: Option<Spanned<usize>>, // texture index
+    music: Option<String>,       // e.g. "e1m1" for assets/music/e1m1.ogg
+    script: Option<String>,      // e.g. "e1m1" for assets/scripts/e1m1.rhai
+    fog: Option<Spanned<FogDef>>,
+}
+
+#[derive(Deserialize)]
+struct PlayerDef {
+    pos: [f32; 2],
+    #[serde(default)]
+    yaw_deg: f32,
+}
+
+// `{ pos = [2.0, 9.0], yaw_deg = 180.0, tag = 3 }`: where teleporters tagged 3 send things
+#[derive(Deserialize)]
+struct DestinationDef {
+    pos: [f32; 2],
+    #[serde(default)]
+    yaw_deg: f32,
+    tag: u32,
+}
+
+// `{ image = "brick" }` for assets/brick.png (or .tga), `{ camera = { ... } }` for a
+// monitor screen, otherwise a procedural texture
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureDef {
+    Image { image: String },
+    Camera { camera: CameraDef },
+    Procedural(ProceduralDef),
+}
+
+// What a monitor texture shows: `camera = { pos = [2.0, 9.0], yaw_deg = 180.0 }`, redrawn
+// every `interval` frames at `size` texels, which span a wall metre like any texture
+#[derive(Deserialize)]
+struct CameraDef {
+    pos: [f32; 2],
+    #[serde(default)]
+    yaw_deg: f32,
+    #[serde(default = "default_camera_eye_z")]
+    eye_z: f32,
+    #[serde(default = "default_camera_size")]
+    size: [usize; 2],
+    #[serde(default = "default_camera_interval")]
+    interval: u32,
+}
+
+fn default_camera_eye_z() -> f32 {
+    2.0
+}
+
+fn default_camera_size() -> [usize; 2] {
+    [64, 48]
+}
+
+fn default_camera_interval() -> u32 {
+    4
+}
+
+#[derive(Deserialize)]
+struct ProceduralDef {
+    #[serde(default)]
+    kind: TextureKind,
+    a: [u8; 3],
+    b: [u8; 3],
+    #[serde(default = "default_texture_size")]
+    size: usize,
+    #[serde(default = "default_texture_cell")]
+    cell: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum TextureKind {
+    #[default]
+    Checker, // squares alternating a/b
+    Disc, // a at the center fading to b at the rim, transparent outside
+}
+
+fn default_texture_size() -> usize {
+    64
+}
+
+fn default_texture_cell() -> usize {
+    16
+}
+
+#[derive(Deserialize)]
+struct SectorDef {
+    floor_z: f32,
+    ceiling_z: f32,
+    floor_color: [u8; 3],
+    ceiling_color: [u8; 3],
+    floor_texture: Option<usize>, // texture index; the color is used without one
+    ceiling_texture: Option<usize>,
+    #[serde(default)]
+    flat_offset: [f32; 2],
+    #[serde(default)]
+    flat_angle_deg: f32,
+    #[serde(default)]
+    flat_scroll: [f32; 2], // world units per second
+    #[serde(default = "default_light_level")]
+    light_level: f32,
+    #[serde(default = "default_light_color")]
+    light_color: [u8; 3],
+    liquid: Option<LiquidDef>,
+    slab: Option<SlabDef>,
+    #[serde(default)]
+    material: Material, // "stone", "metal" or "water"
+    light_effect: Option<LightEffectDef>,
+    #[serde(default)]
+    specials: Vec<SectorSpecialDef>,
+}
+
+// `liquid = { surface_z = -0.3, color = [40, 90, 160] }`
+#[derive(Deserialize, Clone, Copy)]
+struct LiquidDef {
+    surface_z: f32,
+    color: [u8; 3],
+}
+
+// `slab = { control = 6, texture = 2 }`: a bridge across the sector from sector 6's floor
+// up to its ceiling, edged with texture 2
+#[derive(Deserialize, Clone, Copy)]
+struct SlabDef {
+    control: usize,
+    texture: usize,
+}
+
+// `specials = [{ damage = { per_second = 20.0 } }]`, `{ conveyor = { velocity = [1.0, 0.0] } }`,
+// `{ wind = { velocity = [0.0, -2.0] } }`, `{ friction = { factor = 0.1 } }` or
+// `{ teleport = { tag = 3 } }`
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum SectorSpecialDef {
+    Damage { per_second: f32 },
+    Conveyor { velocity: [f32; 2] },
+    Wind { velocity: [f32; 2] },
+    Friction { factor: f32 },
+    Teleport(TeleportDef),
+}
+
+// `teleport = { tag = 3 }` sends whoever crosses the wall (from the front) or enters the
+// sector to the destination tagged 3, stopped unless `keep_velocity = true`, and with a
+// flash and a sound unless `flash = false`
+#[derive(Deserialize, Clone, Copy)]
+struct TeleportDef {
+    tag: u32,
+    #[serde(default)]
+    keep_velocity: bool,
+    #[serde(default = "default_flash")]
+    flash: bool,
+}
+
+fn default_flash() -> bool {
+    true
+}
+
+// `light_effect = { flicker = { min = 0.2 } }`, `{ glow = { min = 0.3, max = 1.0, period = 2.0 } }`
+// or `{ strobe = { min = 0.1, period = 1.0, on = 0.1 } }`
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum LightEffectDef {
+    Flicker { min: f32 },
+    Glow { min: f32, max: f32, period: f32 },
+    Strobe { min: f32, period: f32, on: f32 },
+}
+
+fn default_light_level() -> f32 {
+    1.0
+}
+
+fn default_light_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+#[derive(Deserialize)]
+struct WallDef {
+    start: [f32; 2],
+    end: [f32; 2],
+    front: usize,
+    back: Option<usize>,
+    #[serde(default)]
+    texture: usize,
+    #[serde(default)]
+    tag: u32,
+    #[serde(default)]
+    special: SpecialDef,
+    mid: Option<MidDef>,
+    #[serde(default)]
+    scroll: [f32; 2], // world units per second along the wall and down it
+    #[serde(default)]
+    offset: [f32; 2], // world units along the wall and down it
+    #[serde(default)]
+    peg_upper: bool,
+    #[serde(default)]
+    peg_lower: bool,
+    teleport: Option<TeleportDef>,
+    walkover: Option<WalkoverDef>,
+}
+
+// `walkover = "once"` or `"repeat"`: the wall's special and tag fire when the player walks
+// across it, portals only
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WalkoverDef {
+    Once,
+    Repeat,
+}
+
+// `mid = { texture = 4, blend = "translucent" }`, portals only
+#[derive(Deserialize, Clone, Copy)]
+struct MidDef {
+    texture: usize,
+    #[serde(default)]
+    blend: BlendDef,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BlendDef {
+    #[default]
+    Masked,
+    Translucent,
+    Additive,
+}
+
+// `special = "door"`, `special = "exit"`, `special = { locked_door = { key = "red" } }` or
+// `special = { switch = { alt_texture = 5 } }`, which can be `once = true` or flip back
+// after `cooldown = 1.0` seconds
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SpecialDef {
+    #[default]
+    None,
+    Door,
+    LockedDoor {
+        key: Key,
+    },
+    Switch {
+        alt_texture: usize,
+        #[serde(default)]
+        once: bool,
+        #[serde(default)]
+        cooldown: f32,
+    },
+    Exit,
+}
+
+#[derive(Deserialize)]
+struct ThingDef {
+    pos: [f32; 2],
+    #[serde(default)]
+    z: f32,
+    height: f32,
+    texture: usize,
+    #[serde(default = "default_thing_scale")]
+    scale: f32,
+    #[serde(default)]
+    radius: f32,
+    #[serde(default)]
+    behavior: BehaviorDef,
+    prop: Option<PropDef>,
+    pickup: Option<PickupDef>,
+    #[serde(default)]
+    yaw_deg: f32,
+    rotations: Option<String>, // image name, see `TextureManager::load_rotations`
+    model: Option<usize>,      // index into `models`
+    #[serde(default)]
+    blend: BlendDef,
+}
+
+// `pickup = { health = 25.0 }`, `{ ammo = 10 }` or `{ key = "red" }`
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum PickupDef {
+    Health(f32),
+    Ammo(u32),
+    Key(Key),
+}
+
+// `prop = { health = 30, broken = 5 }`: shot to pieces, leaving texture 5 behind (or
+// nothing); `solid = false` lets things walk through it
+#[derive(Deserialize, Clone, Copy)]
+struct PropDef {
+    health: u32,
+    #[serde(default = "default_solid")]
+    solid: bool,
+    broken: Option<usize>,
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+// `behavior = { bob = { amplitude = 0.15, speed = 2.0 } }`, `{ wander = { speed = 0.5 } }`,
+// `{ hunt = { speed = 1.5, sight_range = 20.0, attack_range = 1.5 } }` or
+// `{ platform = { to = [4.0, 0.0], speed = 1.0, wait = 2.0 } }`
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BehaviorDef {
+    #[default]
+    None,
+    Bob {
+        amplitude: f32,
+        speed: f32,
+    },
+    Wander {
+        speed: f32,
+    },
+    Hunt {
+        speed: f32,
+        #[serde(default = "default_sight_range")]
+        sight_range: f32,
+        #[serde(default = "default_attack_range")]
+        attack_range: f32,
+    },
+    Platform {
+        to: [f32; 2],
+        speed: f32,
+        #[serde(default = "default_platform_wait")]
+        wait: f32,
+    },
+}
+
+fn default_sight_range() -> f32 {
+    20.0
+}
+
+fn default_attack_range() -> f32 {
+    1.5
+}
+
+fn default_platform_wait() -> f32 {
+    2.0
+}
+
+fn default_thing_scale() -> f32 {
+    1.0
+}
+
+// `fog = { color = [40, 40, 48], falloff = { exponential = { density = 0.08 } } }`
+// or `falloff = { linear = { start = 4.0, end = 30.0 } }`
+#[derive(Deserialize)]
+struct FogDef {
+    color: [u8; 3],
+    falloff: FalloffDef,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FalloffDef {
+    Linear { start: f32, end: f32 },
+    Exponential { density: f32 },
+}
+
+#[derive(Deserialize)]
+struct EffectDef {
+    kind: EffectKindDef,
+    sector: usize,
+    #[serde(default)]
+    tag: u32,
+    to: f32, // open ceiling for doors, low floor for lifts, where floors end up, crushers' lowest
+    speed: Option<f32>,
+    wait: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EffectKindDef {
+    Door,
+    Lift,
+    Floor,
+    Crusher,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(toml::de::Error), // carries its own line/column and source snippet
+    Invalid { line: usize, message: String },
+    Build(BuildError),          // from a Build engine .map
+    Udmf(UdmfError),            // from a UDMF TEXTMAP
+    Malformed(Vec<Diagnostic>), // built fine but fails `World::validate`; errors only
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read map: {err}"),
+            LoadError::Parse(err) => write!(f, "could not parse map: {err}"),
+            LoadError::Invalid { line, message } => write!(f, "line {line}: {message}"),
+            LoadError::Build(err) => write!(f, "{err}"),
+            LoadError::Udmf(err) => write!(f, "{err}"),
+            LoadError::Malformed(errors) => {
+                write!(f, "malformed map:")?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(err: std::io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<BuildError> for LoadError {
+    fn from(err: BuildError) -> Self {
+        LoadError::Build(err)
+    }
+}
+
+impl From<UdmfError> for LoadError {
+    fn from(err: UdmfError) -> Self {
+        LoadError::Udmf(err)
+    }
+}
+
+impl From<toml::de::Error> for LoadError {
+    fn from(err: toml::de::Error) -> Self {
+        LoadError::Parse(err)
+    }
+}
+
+/// Load a map, looking up its images in `DEFAULT_ASSET_DIR`. Files ending in `.map` are
+/// read as Build engine maps, and `TEXTMAP` files (or ones ending in `.udmf`) as UDMF.
+pub fn load_map(path: impl AsRef<Path>) -> Result<World, LoadError> {
+    let path = path.as_ref();
+    let _load = info_span!(target: "world", "load_map", path = %path.display()).entered();
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let textmap = path
+        .file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("textmap"));
+    if extension.eq_ignore_ascii_case("map") {
+        return checked(build::load_map(path)?);
+    }
+    if textmap || extension.eq_ignore_ascii_case("udmf") {
+        return checked(udmf::load_map(path)?);
+    }
+    let source = std::fs::read_to_string(path)?;
+    parse_map(&source)
+}
+
+pub fn parse_map(source: &str) -> Result<World, LoadError> {
+    parse_map_with(source, TextureManager::new(DEFAULT_ASSET_DIR))
+}
+
+/// Parse a map whose images come from `assets`; textures already in it are kept, and the
+/// map's own are numbered after them
+pub fn parse_map_with(source: &str, mut assets: TextureManager) -> Result<World, LoadError> {
+    let map: MapFile = toml::from_str(source)?;
+    let invalid = |span: Range<usize>, message: String| LoadError::Invalid {
+        line: line_of(source, span.start),
+        message,
+    };
+
+    if map.sectors.is_empty() {
+        return Err(LoadError::Invalid {
+            line: 1,
+            message: "map has no sectors".to_string(),
+        });
+    }
+
+    let has_destination = |tag: u32| map.destinations.iter().any(|d| d.tag == tag);
+    for (i, sector) in map.sectors.iter().enumerate() {
+        let def = sector.get_ref();
+        // Equal heights are allowed: that's a closed door
+        if def.floor_z > def.ceiling_z {
+            return Err(invalid(
+                sector.span(),
+                format!(
+                    "sector {i} floor_z {} is above ceiling_z {}",
+                    def.floor_z, def.ceiling_z
+                ),
+            ));
+        }
+        if let Some(liquid) = def.liquid
+            && !(def.floor_z..=def.ceiling_z).contains(&liquid.surface_z)
+        {
+            return Err(invalid(
+                sector.span(),
+                format!(
+                    "sector {i} liquid surface_z {} is outside its floor and ceiling",
+                    liquid.surface_z
+                ),
+            ));
+        }
+        let light_problem = match def.light_effect {
+            Some(LightEffectDef::Glow { period, .. } | LightEffectDef::Strobe { period, .. })
+                if period <= 0.0 =>
+            {
+                Some("must have a positive period")
+            }
+            Some(LightEffectDef::Strobe { period, on, .. }) if !(0.0..=period).contains(&on) => {
+                Some("must strobe on for no longer than its period")
+            }
+            _ => None,
+        };
+        if let Some(problem) = light_problem {
+            return Err(invalid(
+                sector.span(),
+                format!("sector {i} light effect {problem}"),
+            ));
+        }
+        for special in &def.specials {
+            let problem = match *special {
+                SectorSpecialDef::Damage { per_second } if per_second < 0.0 => {
+                    Some(format!("damage per_second {per_second} is negative"))
+                }
+                SectorSpecialDef::Friction { factor } if factor <= 0.0 => {
+                    Some(format!("friction factor {factor} must be above 0"))
+                }
+                SectorSpecialDef::Teleport(teleport) if !has_destination(teleport.tag) => {
+                    Some(format!(
+                        "teleports to tag {}, but no destination has it",
+                        teleport.tag
+                    ))
+                }
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                return Err(invalid(sector.span(), format!("sector {i} {problem}")));
+            }
+        }
+        if let Some(slab) = def.slab
+            && (slab.control >= map.sectors.len() || slab.control == i)
+        {
+            return Err(invalid(
+                sector.span(),
+                format!(
+                    "sector {i} slab needs another of the {} sectors as its control, not {}",
+                    map.sectors.len(),
+                    slab.control
+                ),
+            ));
+        }
+        for texture in [def.floor_texture, def.ceiling_texture]
+            .into_iter()
+            .chain([def.slab.map(|slab| slab.texture)])
+            .flatten()
+        {
+            if texture >= map.textures.len() {
+                return Err(invalid(
+                    sector.span(),
+                    format!(
+                        "sector {i} uses texture {texture}, but the map defines {}",
+                        map.textures.len()
+                    ),
+                ));
+            }
+        }
+    }
+
+    let sector_count = map.sectors.len();
+    for (i, wall) in map.walls.iter().enumerate() {
+        let def = wall.get_ref();
+        for sector in std::iter::once(def.front).chain(def.back) {
+            if sector >= sector_count {
+                return Err(invalid(
+                    wall.span(),
+                    format!("wall {i} references sector {sector}, but the map has {sector_count}"),
+                ));
+            }
+        }
+        if def.back == Some(def.front) {
+            return Err(invalid(
+                wall.span(),
+                format!("wall {i} has the same front and back sector"),
+            ));
+        }
+        if def.texture >= map.textures.len() {
+            return Err(invalid(
+                wall.span(),
+                format!(
+                    "wall {i} uses texture {}, but the map defines {}",
+                    def.texture,
+                    map.textures.len()
+                ),
+            ));
+        }
+        if def.start == def.end {
+            return Err(invalid(wall.span(), format!("wall {i} has zero length")));
+        }
+        if let SpecialDef::Switch { cooldown, .. } = def.special
+            && cooldown < 0.0
+        {
+            return Err(invalid(
+                wall.span(),
+                format!("wall {i} is a switch with a negative cooldown"),
+            ));
+        }
+        if let SpecialDef::Switch { alt_texture, .. } = def.special
+            && alt_texture >= map.textures.len()
+        {
+            return Err(invalid(
+                wall.span(),
+                format!(
+                    "wall {i} switches to texture {alt_texture}, but the map defines {}",
+                    map.textures.len()
+                ),
+            ));
+        }
+        if let Some(teleport) = def.teleport
+            && !has_destination(teleport.tag)
+        {
+            return Err(invalid(
+                wall.span(),
+                format!(
+                    "wall {i} teleports to tag {}, but no destination has it",
+                    teleport.tag
+                ),
+            ));
+        }
+        if def.walkover.is_some() {
+            if def.back.is_none() {
+                return Err(invalid(
+                    wall.span(),
+                    format!("wall {i} is walked over but has no back sector"),
+                ));
+            }
+            if matches!(
+                def.special,
+                SpecialDef::LockedDoor { .. } | SpecialDef::Switch { .. }
+            ) {
+                return Err(invalid(
+                    wall.span(),
+                    format!(
+                        "wall {i} is walked over, which works for doors and exits, not locked doors or switches"
+                    ),
+                ));
+            }
+        }
+        if let Some(mid) = def.mid {
+            if def.back.is_none() {
+                return Err(invalid(
+                    wall.span(),
+                    format!("wall {i} has a mid texture but no back sector"),
+                ));
+            }
+            if mid.texture >= map.textures.len() {
+                return Err(invalid(
+                    wall.span(),
+                    format!(
+                        "wall {i} has mid texture {}, but the map defines {}",
+                        mid.texture,
+                        map.textures.len()
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (i, thing) in map.things.iter().enumerate() {
+        let def = thing.get_ref();
+        if def.texture >= map.textures.len() {
+            return Err(invalid(
+                thing.span(),
+                format!(
+                    "thing {i} uses texture {}, but the map defines {}",
+                    def.texture,
+                    map.textures.len()
+                ),
+            ));
+        }
+        if let Some(model) = def.model
+            && model >= map.models.len()
+        {
+            return Err(invalid(
+                thing.span(),
+                format!(
+                    "thing {i} uses model {model}, but the map defines {}",
+                    map.models.len()
+                ),
+            ));
+        }
+        if def.height <= 0.0 || def.scale <= 0.0 {
+            return Err(invalid(
+                thing.span(),
+                format!("thing {i} must have a positive height and scale"),
+            ));
+        }
+        if def.radius < 0.0 {
+            return Err(invalid(
+                thing.span(),
+                format!("thing {i} has a negative radius"),
+            ));
+        }
+        if let BehaviorDef::Hunt {
+            speed,
+            sight_range,
+            attack_range,
+        } = def.behavior
+            && (speed <= 0.0 || sight_range < 0.0 || attack_range < 0.0)
+        {
+            return Err(invalid(
+                thing.span(),
+                format!("thing {i} must hunt with a positive speed and non-negative ranges"),
+            ));
+        }
+        if let BehaviorDef::Platform { speed, wait, .. } = def.behavior
+            && (speed <= 0.0 || wait < 0.0 || def.radius <= 0.0)
+        {
+            return Err(invalid(
+                thing.span(),
+                format!(
+                    "thing {i} is a platform without a radius, a positive speed or a non-negative wait"
+                ),
+            ));
+        }
+        if let Some(prop) = def.prop {
+            if prop.health == 0 {
+                return Err(invalid(
+                    thing.span(),
+                    format!("thing {i} is a prop with no health"),
+                ));
+            }
+            if let Some(broken) = prop.broken
+                && broken >= map.textures.len()
+            {
+                return Err(invalid(
+                    thing.span(),
+                    format!(
+                        "thing {i} breaks into texture {broken}, but the map defines {}",
+                        map.textures.len()
+                    ),
+                ));
+            }
+        }
+
+        let empty = match def.pickup {
+            Some(PickupDef::Health(amount)) => amount <= 0.0,
+            Some(PickupDef::Ammo(amount)) => amount == 0,
+            _ => false,
+        };
+        if empty {
+            return Err(invalid(
+                thing.span(),
+                format!("thing {i} is a pickup that gives nothing"),
+            ));
+        }
+    }
+
+    for (i, effect) in map.effects.iter().enumerate() {
+        let def = effect.get_ref();
+        if def.sector >= sector_count {
+            return Err(invalid(
+                effect.span(),
+                format!(
+                    "effect {i} references sector {}, but the map has {sector_count}",
+                    def.sector
+                ),
+            ));
+        }
+        if def.speed.is_some_and(|v| v <= 0.0) || def.wait.is_some_and(|v| v < 0.0) {
+            return Err(invalid(
+                effect.span(),
+                format!("effect {i} must have a positive speed and a non-negative wait"),
+            ));
+        }
+        if matches!(def.kind, EffectKindDef::Crusher)
+            && def.to < map.sectors[def.sector].get_ref().floor_z
+        {
+            return Err(invalid(
+                effect.span(),
+                format!("effect {i} is a crusher that comes down through its floor"),
+            ));
+        }
+    }
+
+    if let Some(sky) = &map.sky
+        && *sky.get_ref() >= map.textures.len()
+    {
+        return Err(invalid(
+            sky.span(),
+            format!(
+                "sky uses texture {}, but the map defines {}",
+                sky.get_ref(),
+                map.textures.len()
+            ),
+        ));
+    }
+
+    if let Some(fog) = &map.fog {
+        let valid = match fog.get_ref().falloff {
+            FalloffDef::Linear { start, end } => start >= 0.0 && start < end,
+            FalloffDef::Exponential { density } => density > 0.0,
+        };
+        if !valid {
+            return Err(invalid(
+                fog.span(),
+                "fog needs 0 <= start < end, or a positive density".to_string(),
+            ));
+        }
+    }
+
+    // Map texture index -> id, with images named more than once sharing one texture
+    let mut monitors = Vec::new();
+    let texture_ids: Vec<TextureId> = map
+        .textures
+        .iter()
+        .map(|t| match t {
+            TextureDef::Image { image } => assets.load(image),
+            TextureDef::Camera { camera } => {
+                let [w, h] = camera.size.map(|n| n.max(1));
+                let texture = assets.add(None, Texture::from_pixels(w, h, vec![0; w * h]));
+                monitors.push(Monitor {
+                    texture,
+                    pos: camera.pos,
+                    yaw: camera.yaw_deg.to_radians(),
+                    eye_z: camera.eye_z,
+                    interval: camera.interval.max(1),
+                });
+                texture
+            }
+            TextureDef::Procedural(t) => assets.add(
+                None,
+                match t.kind {
+                    TextureKind::Checker => {
+                        Texture::checkerboard(t.size, t.cell, rgb(t.a), rgb(t.b))
+                    }
+                    TextureKind::Disc => Texture::disc(t.size, rgb(t.a), rgb(t.b)),
+                },
+            ),
+        })
+        .collect();
+    let texture = |index: usize| texture_ids[index];
+
+    // Clamped like the light levels they stand in for
+    let lights: Vec<LightEffect> = map
+        .sectors
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| {
+            let s = s.get_ref();
+            let kind = match s.light_effect? {
+                LightEffectDef::Flicker { min } => LightKind::Flicker {
+                    min: min.clamp(0.0, 1.0),
+                },
+                LightEffectDef::Glow { min, max, period } => LightKind::Glow {
+                    min: min.clamp(0.0, 1.0),
+                    max: max.clamp(0.0, 1.0),
+                    period,
+                },
+                LightEffectDef::Strobe { min, period, on } => LightKind::Strobe {
+                    min: min.clamp(0.0, 1.0),
+                    period,
+                    on,
+                },
+            };
+            Some(LightEffect::new(kind, i, s.light_level.clamp(0.0, 1.0)))
+        })
+        .collect();
+
+    let sectors: Vec<Sector> = map
+        .sectors
+        .into_iter()
+        .map(|s| {
+            let s = s.into_inner();
+            Sector {
+                floor_z: s.floor_z,
+                ceiling_z: s.ceiling_z,
+                floor_color: rgb(s.floor_color),
+                ceiling_color: rgb(s.ceiling_color),
+                floor_texture: s.floor_texture.map(texture),
+                ceiling_texture: s.ceiling_texture.map(texture),
+                flat_offset: s.flat_offset,
+                flat_angle: s.flat_angle_deg.to_radians(),
+                flat_scroll: s.flat_scroll,
+                light_level: s.light_level.clamp(0.0, 1.0),
+                light_color: rgb(s.light_color),
+                liquid: s.liquid.map(|l| Liquid {
+                    surface_z: l.surface_z,
+                    color: rgb(l.color),
+                }),
+                slab: s.slab.map(|slab| Slab {
+                    control: slab.control,
+                    texture: texture(slab.texture),
+                }),
+                material: s.material,
+                specials: s
+                    .specials
+                    .iter()
+                    .map(|special| match *special {
+                        SectorSpecialDef::Damage { per_second } => {
+                            SectorSpecial::Damage { per_second }
+                        }
+                        SectorSpecialDef::Conveyor { velocity } => {
+                            SectorSpecial::Conveyor { velocity }
+                        }
+                        SectorSpecialDef::Wind { velocity } => SectorSpecial::Wind { velocity },
+                        SectorSpecialDef::Friction { factor } => SectorSpecial::Friction { factor },
+                        SectorSpecialDef::Teleport(def) => SectorSpecial::Teleport(teleport(def)),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let walls = map
+        .walls
+        .into_iter()
+        .map(|w| {
+            let w = w.into_inner();
+            Wall {
+                start: w.start,
+                end: w.end,
+                front_sector: w.front,
+                back_sector: w.back,
+                texture: texture(w.texture),
+                tag: w.tag,
+                special: match w.special {
+                    SpecialDef::None => Special::None,
+                    SpecialDef::Door => Special::Door,
+                    SpecialDef::LockedDoor { key } => Special::LockedDoor { key },
+                    SpecialDef::Exit => Special::Exit,
+                    SpecialDef::Switch {
+                        alt_texture,
+                        once,
+                        cooldown,
+                    } => Special::Switch {
+                        alt_texture: texture(alt_texture),
+                        once,
+                        cooldown,
+                    },
+                },
+                mid: w.mid.map(|m| MidTexture {
+                    texture: texture(m.texture),
+                    blend: blend(m.blend),
+                }),
+                scroll: w.scroll,
+                offset: w.offset,
+                peg_upper: w.peg_upper,
+                peg_lower: w.peg_lower,
+                teleport: w.teleport.map(teleport),
+                walkover: w.walkover.map(|walkover| match walkover {
+                    WalkoverDef::Once => Walkover::Once,
+                    WalkoverDef::Repeat => Walkover::Repeat,
+                }),
+            }
+        })
+        .collect();
+
+    let things = map
+        .things
+        .into_iter()
+        .map(|t| {
+            let t = t.into_inner();
+            Thing {
+                pos: t.pos,
+                z: t.z,
+                height: t.height,
+                texture: texture(t.texture),
+                scale: t.scale,
+                radius: t.radius,
+                behavior: match t.behavior {
+                    BehaviorDef::None => Behavior::None,
+                    BehaviorDef::Bob { amplitude, speed } => Behavior::Bob { amplitude, speed },
+                    BehaviorDef::Wander { speed } => Behavior::Wander { speed },
+                    BehaviorDef::Hunt {
+                        speed,
+                        sight_range,
+                        attack_range,
+                    } => Behavior::Hunt {
+                        speed,
+                        sight_range,
+                        attack_range,
+                    },
+                    BehaviorDef::Platform { to, speed, wait } => {
+                        Behavior::Platform { to, speed, wait }
+                    }
+                },
+                prop: t.prop.map(|p| Prop {
+                    health: p.health,
+                    solid: p.solid,
+                    broken: p.broken.map(texture),
+                }),
+                pickup: t.pickup.map(|p| match p {
+                    PickupDef::Health(amount) => Pickup::Health { amount },
+                    PickupDef::Ammo(amount) => Pickup::Ammo { amount },
+                    PickupDef::Key(key) => Pickup::Key(key),
+                }),
+                yaw: t.yaw_deg.to_radians(),
+                rotations: t.rotations.map(|name| assets.load_rotations(&name)),
+                model: t.model,
+                blend: blend(t.blend),
+            }
+        })
+        .collect();
+
+    let player_start = PlayerStart {
+        pos: map.player.pos,
+        yaw: map.player.yaw_deg.to_radians(),
+    };
+
+    let effects = map
+        .effects
+        .into_iter()
+        .map(|e| {
+            let e = e.into_inner();
+            let sector = &sectors[e.sector];
+            let (kind, rest_z) = match e.kind {
+                EffectKindDef::Door => (EffectKind::Door, sector.ceiling_z),
+                EffectKindDef::Lift => (EffectKind::Lift, sector.floor_z),
+                EffectKindDef::Floor => (EffectKind::Floor, sector.floor_z),
+                EffectKindDef::Crusher => (EffectKind::Crusher, sector.ceiling_z),
+            };
+            let mut effect = SectorEffect::new(kind, e.sector, e.tag, rest_z, e.to);
+            effect.speed = e.speed.unwrap_or(effect.speed);
+            effect.wait = e.wait.unwrap_or(effect.wait);
+            effect
+        })
+        .collect();
+
+    // A model that can't be loaded stands out like a missing texture
+    let models: Vec<VoxelModel> = map
+        .models
+        .iter()
+        .map(|name| {
+            let file = if Path::new(name).extension().is_some() {
+                name.clone()
+            } else {
+                format!("{name}.vox")
+            };
+            vox::load_model(assets.root().join(file)).unwrap_or_else(|err| {
+                warn!(target: "world", "model {name:?}: {err}; using a placeholder");
+                VoxelModel::placeholder()
+            })
+        })
+        .collect();
+
+    let sky = map.sky.map(|s| texture(s.into_inner()));
+    let textures = assets.into_textures();
+    let mut world = World::new(sectors, walls, textures, things, player_start, effects);
+    world.sky = sky;
+    world.music = map.music;
+    world.script = map.script;
+    world.monitors = monitors;
+    world.lights = lights;
+    world.models = models;
+    world.destinations = map
+        .destinations
+        .iter()
+        .map(|d| Destination {
+            pos: d.pos,
+            yaw: d.yaw_deg.to_radians(),
+            tag: d.tag,
+        })
+        .collect();
+    world.fog = map.fog.map(|fog| {
+        let fog = fog.into_inner();
+        Fog {
+            color: rgb(fog.color),
+            falloff: match fog.falloff {
+                FalloffDef::Linear { start, end } => FogFalloff::Linear { start, end },
+                FalloffDef::Exponential { density } => FogFalloff::Exponential { density },
+            },
+        }
+    });
+    checked(world)
+}
+
+// Refuse a world `validate` finds errors in, rather than have it panic or draw garbage
+// later; warnings are reported and the world kept
+fn checked(world: World) -> Result<World, LoadError> {
+    let (errors, warnings): (Vec<_>, Vec<_>) =
+        world.validate().into_iter().partition(Diagnostic::is_error);
+    for warning in &warnings {
+        warn!(target: "world", "{warning}");
+    }
+    if errors.is_empty() {
+        Ok(world)
+    } else {
+        Err(LoadError::Malformed(errors))
+    }
+}
+
+#[inline]
+fn rgb(c: [u8; 3]) -> u32 {
+    pack_rgb(c[0], c[1], c[2])
+}
+
+fn blend(def: BlendDef) -> Blend {
+    match def {
+        BlendDef::Masked => Blend::Masked,
+        BlendDef::Translucent => Blend::Translucent,
+        BlendDef::Additive => Blend::Additive,
+    }
+}
+
+fn teleport(def: TeleportDef) -> Teleport {
+    Teleport {
+        tag: def.tag,
+        keep_velocity: def.keep_velocity,
+        flash: def.flash,
+    }
+}
+
+// 1-based line number of a byte offset
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
@@ -0,0 +1,287 @@
+// Checks a built world for what would crash or draw wrongly at render time, whichever
+// format it came from
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::world::World;
+
+// Wall ends closer than this count as the same vertex when following sector outlines
+const VERTEX_SNAP: f32 = 1e-3;
+
+/// Something `World::validate` found wrong
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    SectorOutOfRange {
+        wall: usize,
+        sector: usize,
+    },
+    SameFrontAndBack {
+        wall: usize,
+    },
+    ZeroLength {
+        wall: usize,
+    },
+    TextureOutOfRange {
+        texture: usize,
+        used_by: Usage,
+    },
+    SlabControlInvalid {
+        sector: usize,
+        control: usize,
+    },
+    FloorAboveCeiling {
+        sector: usize,
+    },
+    /// No wall has the sector on either side
+    NoWalls {
+        sector: usize,
+    },
+    /// Its walls don't join up into closed outlines; `at` is one loose end
+    OpenSector {
+        sector: usize,
+        at: [f32; 2],
+    },
+}
+
+/// What a texture index belongs to, for `Diagnostic::TextureOutOfRange`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    Wall(usize),
+    Sector(usize), // a flat or its slab
+}
+
+impl Diagnostic {
+    /// Errors index out of bounds or draw garbage, so a map with any is refused; the rest
+    /// are warnings, drawn and played as well as they can be
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            Diagnostic::NoWalls { .. } | Diagnostic::OpenSector { .. }
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::SectorOutOfRange { wall, sector } => {
+                write!(
+                    f,
+                    "wall {wall} references sector {sector}, which doesn't exist"
+                )
+            }
+            Diagnostic::SameFrontAndBack { wall } => {
+                write!(f, "wall {wall} has the same front and back sector")
+            }
+            Diagnostic::ZeroLength { wall } => write!(f, "wall {wall} has zero length"),
+            Diagnostic::TextureOutOfRange { texture, used_by } => {
+                let user = match used_by {
+                    Usage::Wall(i) => format!("wall {i}"),
+                    Usage::Sector(i) => format!("sector {i}"),
+                };
+                write!(f, "{user} uses texture {texture}, which doesn't exist")
+            }
+            Diagnostic::SlabControlInvalid { sector, control } => {
+                write!(
+                    f,
+                    "sector {sector} has sector {control} as its slab control"
+                )
+            }
+            Diagnostic::FloorAboveCeiling { sector } => {
+                write!(f, "sector {sector} has its floor above its ceiling")
+            }
+            Diagnostic::NoWalls { sector } => write!(f, "sector {sector} has no walls"),
+            Diagnostic::OpenSector { sector, at } => write!(
+                f,
+                "sector {sector} isn't closed; its outline breaks off at ({}, {})",
+                at[0], at[1]
+            ),
+        }
+    }
+}
+
+impl World {
+    /// Everything wrong with the map's geometry and references, errors and warnings alike
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut found = Vec::new();
+        let sector_count = self.sectors.len();
+        let texture_count = self.textures.len();
+
+        for (i, wall) in self.walls.iter().enumerate() {
+            for sector in std::iter::once(wall.front_sector).chain(wall.back_sector) {
+                if sector >= sector_count {
+                    found.push(Diagnostic::SectorOutOfRange { wall: i, sector });
+                }
+            }
+            if wall.back_sector == Some(wall.front_sector) {
+                found.push(Diagnostic::SameFrontAndBack { wall: i });
+            }
+            if wall.start == wall.end {
+                found.push(Diagnostic::ZeroLength { wall: i });
+            }
+            for texture in std::iter::once(wall.texture).chain(wall.mid.map(|m| m.texture)) {
+                if texture >= texture_count {
+                    found.push(Diagnostic::TextureOutOfRange {
+                        texture,
+                        used_by: Usage::Wall(i),
+                    });
+                }
+            }
+        }
+
+        for (i, sector) in self.sectors.iter().enumerate() {
+            // Equal heights are allowed: that's a closed door
+            if sector.floor_z > sector.ceiling_z {
+                found.push(Diagnostic::FloorAboveCeiling { sector: i });
+            }
+            if let Some(slab) = sector.slab
+                && (slab.control >= sector_count || slab.control == i)
+            {
+                found.push(Diagnostic::SlabControlInvalid {
+                    sector: i,
+                    control: slab.control,
+                });
+            }
+            for texture in [sector.floor_texture, sector.ceiling_texture]
+                .into_iter()
+                .chain([sector.slab.map(|slab| slab.texture)])
+                .flatten()
+            {
+                if texture >= texture_count {
+                    found.push(Diagnostic::TextureOutOfRange {
+                        texture,
+                        used_by: Usage::Sector(i),
+                    });
+                }
+            }
+        }
+
+        found.extend(self.outline_problems());
+        found
+    }
+
+    // Sectors whose walls, each taken in the direction that keeps the sector on its left,
+    // don't leave every vertex with as many walls in as out
+    fn outline_problems(&self) -> Vec<Diagnostic> {
+        let vertex = |p: [f32; 2]| {
+            (
+                (p[0] / VERTEX_SNAP).round() as i64,
+                (p[1] / VERTEX_SNAP).round() as i64,
+            )
+        };
+        // Per sector, each vertex's position and walls in minus walls out
+        let mut balance: Vec<HashMap<_, ([f32; 2], i32)>> =
+            vec![HashMap::new(); self.sectors.len()];
+        for wall in &self.walls {
+            let sides = [
+                (wall.front_sector, wall.start, wall.end),
+                (wall.back_sector.unwrap_or(usize::MAX), wall.end, wall.start),
+            ];
+            for (sector, from, to) in sides {
+                let Some(balance) = balance.get_mut(sector) else {
+                    continue;
+                };
+                balance.entry(vertex(from)).or_insert((from, 0)).1 -= 1;
+                balance.entry(vertex(to)).or_insert((to, 0)).1 += 1;
+            }
+        }
+
+        let mut found = Vec::new();
+        for (sector, balance) in balance.iter().enumerate() {
+            if balance.is_empty() {
+                found.push(Diagnostic::NoWalls { sector });
+            } else if let Some(&(at, _)) = balance.values().find(|(_, n)| *n != 0) {
+                found.push(Diagnostic::OpenSector { sector, at });
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The demo map, which validates clean, broken in one place per test
+    fn demo() -> World {
+        let world = World::demo();
+        assert_eq!(world.validate(), Vec::new());
+        world
+    }
+
+    #[test]
+    fn sector_out_of_range() {
+        let mut world = demo();
+        let sector = world.sectors.len();
+        world.walls[0].front_sector = sector;
+        assert!(
+            world
+                .validate()
+                .contains(&Diagnostic::SectorOutOfRange { wall: 0, sector })
+        );
+    }
+
+    #[test]
+    fn unclosed_loop() {
+        let mut world = demo();
+        let wall = world
+            .walls
+            .iter()
+            .position(|w| w.back_sector.is_none())
+            .unwrap();
+        let sector = world.walls[wall].front_sector;
+        let end = world.walls[wall].end;
+        world.walls[wall].end = [end[0] + 0.5, end[1] + 0.5];
+        let found = world.validate();
+        // Either loose end may be reported
+        assert!(
+            found
+                .iter()
+                .any(|d| matches!(d, Diagnostic::OpenSector { sector: s, .. } if *s == sector)),
+            "{found:?}"
+        );
+        assert!(found.iter().all(|d| !d.is_error()), "{found:?}");
+    }
+
+    #[test]
+    fn floor_above_ceiling() {
+        let mut world = demo();
+        world.sectors[1].floor_z = world.sectors[1].ceiling_z + 0.5;
+        assert_eq!(
+            world.validate(),
+            vec![Diagnostic::FloorAboveCeiling { sector: 1 }]
+        );
+        // Level with it is a closed door
+        world.sectors[1].floor_z = world.sectors[1].ceiling_z;
+        assert_eq!(world.validate(), Vec::new());
+    }
+
+    #[test]
+    fn zero_length_wall() {
+        let mut world = demo();
+        let start = world.walls[2].start;
+        world.walls[2].end = start;
+        assert!(
+            world
+                .validate()
+                .contains(&Diagnostic::ZeroLength { wall: 2 })
+        );
+    }
+
+    #[test]
+    fn back_sector_same_as_front() {
+        let mut world = demo();
+        let wall = world
+            .walls
+            .iter()
+            .position(|w| w.back_sector.is_some())
+            .unwrap();
+        world.walls[wall].back_sector = Some(world.walls[wall].front_sector);
+        assert!(
+            world
+                .validate()
+                .contains(&Diagnostic::SameFrontAndBack { wall })
+        );
+    }
+}
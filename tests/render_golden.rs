@@ -0,0 +1,218 @@
+// Golden-image regression tests for the headless renderer.
+//
+// Each view of the demo map, and of a few other fixed scenes, is compared against a PPM
+// in tests/golden/. After an intentional rendering change, regenerate them with
+//   UPDATE_GOLDEN=1 cargo test --test render_golden
+// and look over the new images before committing.
+
+use std::path::PathBuf;
+
+use two_halfD_engine::camera::DEFAULT_FOV;
+use two_halfD_engine::procgen;
+use two_halfD_engine::renderer::Mipmaps;
+use two_halfD_engine::world::Monitor;
+use two_halfD_engine::world::loader;
+use two_halfD_engine::{Camera, Renderer, Texture, Viewport, World};
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 120;
+
+// Per-channel difference still counted as equal, and how many pixels may exceed it,
+// so float differences between platforms don't fail the suite
+const CHANNEL_TOLERANCE: u8 = 2;
+const MAX_DIFFERING_PIXELS: usize = 8;
+
+fn camera(pos: [f32; 2], yaw_deg: f32) -> Camera {
+    let mut camera = Camera {
+        pos,
+        yaw: yaw_deg.to_radians(),
+        eye_z: 1.7,
+        fx: 0.0,
+        fy: 0.0,
+    };
+    camera.set_fov_from_horizontal(WIDTH as f32, HEIGHT as f32, 90.0);
+    camera
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"))
+}
+
+fn to_rgb(buf: &[u32]) -> Vec<u8> {
+    buf.iter()
+        .flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8])
+        .collect()
+}
+
+fn write_ppm(path: &PathBuf, rgb: &[u8]) {
+    let mut data = format!("P6\n{WIDTH} {HEIGHT}\n255\n").into_bytes();
+    data.extend_from_slice(rgb);
+    std::fs::write(path, data).expect("write golden image");
+}
+
+// Only what write_ppm produces: binary P6, single whitespace separators, 8-bit
+fn read_ppm(path: &PathBuf) -> Vec<u8> {
+    let data = std::fs::read(path).unwrap_or_else(|err| {
+        panic!(
+            "{}: {err}; run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    let header = format!("P6\n{WIDTH} {HEIGHT}\n255\n");
+    assert!(
+        data.starts_with(header.as_bytes()),
+        "{}: unexpected header or size",
+        path.display()
+    );
+    data[header.len()..].to_vec()
+}
+
+fn assert_matches_golden(name: &str, world: &World, camera: &Camera) {
+    assert_renders_golden(name, &mut Renderer::new(), world, camera);
+}
+
+// As `assert_matches_golden`, drawn by a renderer with its own settings
+fn assert_renders_golden(name: &str, renderer: &mut Renderer, world: &World, camera: &Camera) {
+    let buf = renderer.render_to_buffer(world, camera, WIDTH, HEIGHT);
+    let rgb = to_rgb(&buf);
+    let path = golden_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_ppm(&path, &rgb);
+        return;
+    }
+
+    let expected = read_ppm(&path);
+    let differing = rgb
+        .chunks(3)
+        .zip(expected.chunks(3))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(b.iter())
+                .any(|(x, y)| x.abs_diff(*y) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    if differing > MAX_DIFFERING_PIXELS {
+        let actual = path.with_extension("actual.ppm");
+        write_ppm(&actual, &rgb);
+        panic!(
+            "{name}: {differing} pixels differ from {}; actual frame written to {}",
+            path.display(),
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn start_view() {
+    assert_matches_golden("start_view", &World::demo(), &camera([0.0, 0.0], 0.0));
+}
+
+#[test]
+fn through_corridor() {
+    // Looks down the raised corridor into the far room: portal steps and sprites
+    assert_matches_golden("through_corridor", &World::demo(), &camera([0.5, 4.0], 0.0));
+}
+
+#[test]
+fn behind_pillar() {
+    // The far orb is cut off by the pillar's edge, exercising sprite clipping
+    assert_matches_golden("behind_pillar", &World::demo(), &camera([-3.4, 11.5], 44.0));
+}
+
+#[test]
+fn procgen_rooms() {
+    // Generated rooms at several floor heights, seen through a chain of portals
+    let world = procgen::generate(1, [4, 4]);
+    let mut view = camera(world.player_start.pos, 0.0);
+    let start = world.sector_at(view.pos).expect("start room");
+    view.eye_z += world.sectors[start].floor_z;
+    assert_matches_golden("procgen_rooms", &world, &view);
+}
+
+#[test]
+fn mipmapped_corridor() {
+    // The corridor view again, its far walls and floor sampled from blended mip levels
+    let mut renderer = Renderer::new();
+    renderer.set_mipmaps(Mipmaps::Blended);
+    assert_renders_golden(
+        "mipmapped_corridor",
+        &mut renderer,
+        &World::demo(),
+        &camera([0.5, 4.0], 0.0),
+    );
+}
+
+#[test]
+fn demo_map_matches_builtin_demo() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("maps/demo.toml");
+    let loaded = loader::load_map(path).expect("load demo map");
+    let camera = camera([0.5, 4.0], 0.0);
+
+    let mut renderer = Renderer::new();
+    let from_file = renderer.render_to_buffer(&loaded, &camera, WIDTH, HEIGHT);
+    let builtin = renderer.render_to_buffer(&World::demo(), &camera, WIDTH, HEIGHT);
+    assert!(
+        from_file == builtin,
+        "maps/demo.toml renders differently from World::demo()"
+    );
+}
+
+#[test]
+fn viewport_matches_full_render_of_its_size() {
+    // The golden start view, drawn into the middle of a larger frame
+    let viewport = Viewport {
+        x: 20,
+        y: 10,
+        width: WIDTH,
+        height: HEIGHT,
+    };
+    let (frame_w, frame_h) = (WIDTH + 40, HEIGHT + 30);
+    let mut frame = vec![1u32; frame_w * frame_h];
+    let camera = camera([0.0, 0.0], 0.0);
+    let mut renderer = Renderer::new();
+    renderer.render_viewport(&mut frame, frame_w, viewport, &World::demo(), &camera);
+    let alone = renderer.render_to_buffer(&World::demo(), &camera, WIDTH, HEIGHT);
+
+    for (y, row) in frame.chunks_exact(frame_w).enumerate() {
+        for (x, &pixel) in row.iter().enumerate() {
+            let inside = (viewport.x..viewport.x + WIDTH).contains(&x)
+                && (viewport.y..viewport.y + HEIGHT).contains(&y);
+            let expected = if inside {
+                alone[(y - viewport.y) * WIDTH + x - viewport.x]
+            } else {
+                1
+            };
+            assert_eq!(pixel, expected, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn monitor_shows_its_camera_view() {
+    let mut world = World::demo();
+    world
+        .textures
+        .push(Texture::from_pixels(WIDTH, HEIGHT, vec![0; WIDTH * HEIGHT]));
+    let view = camera([0.0, 0.0], 0.0);
+    world.monitors.push(Monitor {
+        texture: world.textures.len() - 1,
+        pos: view.pos,
+        yaw: view.yaw,
+        eye_z: view.eye_z,
+        interval: 2,
+    });
+
+    let mut renderer = Renderer::new();
+    renderer.update_monitors(&mut world);
+    // Monitors see at the default field of view for their size
+    let mut view = view;
+    view.set_fov_hor_plus(WIDTH as f32, HEIGHT as f32, DEFAULT_FOV);
+    let expected = renderer.render_to_buffer(&World::demo(), &view, WIDTH, HEIGHT);
+    assert!(
+        world.textures.last().unwrap().pixels == expected,
+        "monitor texture differs from its camera's view"
+    );
+}